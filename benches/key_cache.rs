@@ -0,0 +1,74 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compares opening boxes sharing the password and salt with and without `KeyCache`.
+//!
+//! Run with `cargo bench --bench key_cache --features testing`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rand::{thread_rng, RngCore};
+
+use pwbox::{
+    cache::KeyCache,
+    sodium::{Scrypt, Sodium},
+    Eraser, RestoredPwBox, Suite,
+};
+
+const PASSWORD: &str = "correct horse battery staple";
+const BOX_COUNT: usize = 50;
+
+fn bench_key_cache(c: &mut Criterion) {
+    let mut rng = thread_rng();
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+
+    let mut salt = [0_u8; 32];
+    rng.fill_bytes(&mut salt);
+    let boxes: Vec<RestoredPwBox> = (0..BOX_COUNT)
+        .map(|i| {
+            let pwbox = Sodium::build_box(&mut rng)
+                .kdf(Scrypt::interactive())
+                .salt(salt)
+                .seal(PASSWORD, i.to_le_bytes())
+                .unwrap();
+            eraser.restore(&eraser.erase(&pwbox).unwrap()).unwrap()
+        })
+        .collect();
+
+    let mut group = c.benchmark_group(format!("open_{}_boxes_sharing_salt", BOX_COUNT));
+    // Without the cache, each iteration derives a key for every box, which takes a while.
+    group.sample_size(10);
+    group.bench_function("without_cache", |b| {
+        b.iter(|| {
+            for pwbox in &boxes {
+                pwbox.open(PASSWORD).unwrap();
+            }
+        });
+    });
+    // The cache is created anew on each iteration, so that the key is derived once
+    // per iteration.
+    group.bench_function("with_cache", |b| {
+        b.iter(|| {
+            let mut cache = KeyCache::new();
+            for pwbox in &boxes {
+                pwbox.open_with_cache(PASSWORD, &mut cache).unwrap();
+            }
+            assert_eq!(cache.len(), 1);
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_key_cache);
+criterion_main!(benches);
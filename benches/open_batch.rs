@@ -0,0 +1,74 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compares opening boxes one by one with opening them in parallel via `open_any()`.
+//!
+//! Run with `cargo bench --bench open_batch`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rand::thread_rng;
+
+use pwbox::{
+    open_any,
+    sodium::{Scrypt, Sodium},
+    Eraser, RestoredPwBox, Suite,
+};
+
+const PASSWORD: &str = "correct horse battery staple";
+const BOX_COUNT: usize = 16;
+
+fn bench_open_batch(c: &mut Criterion) {
+    let mut rng = thread_rng();
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+
+    let boxes: Vec<RestoredPwBox> = (0..BOX_COUNT)
+        .map(|_| {
+            let pwbox = Sodium::build_box(&mut rng)
+                .kdf(Scrypt::interactive())
+                .seal(PASSWORD, b"battery staple")
+                .unwrap();
+            eraser.restore(&eraser.erase(&pwbox).unwrap()).unwrap()
+        })
+        .collect();
+
+    let mut group = c.benchmark_group(format!("open_{}_boxes", BOX_COUNT));
+    // Each iteration derives a key for every box, which takes a while.
+    group.sample_size(10);
+    group.bench_function("serial", |b| {
+        b.iter(|| {
+            for pwbox in &boxes {
+                pwbox.open(PASSWORD).unwrap();
+            }
+        });
+    });
+    group.bench_function("parallel", |b| {
+        b.iter(|| {
+            for res in open_any(&boxes, PASSWORD.as_bytes()) {
+                res.unwrap();
+            }
+        });
+    });
+    group.bench_function("serial_password_check", |b| {
+        b.iter(|| {
+            for pwbox in &boxes {
+                assert!(pwbox.check_password(PASSWORD).unwrap());
+            }
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_open_batch);
+criterion_main!(benches);
@@ -0,0 +1,143 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Benchmarks sealing and opening boxes with payloads of various sizes.
+//!
+//! Run with `cargo bench --bench seal_open`. Before running the benchmarks, the number
+//! of heap allocations per operation is printed for each payload size.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::{thread_rng, RngCore};
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    io,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use pwbox::{
+    sodium::{Scrypt, Sodium},
+    Suite,
+};
+
+const PASSWORD: &str = "correct horse battery staple";
+const SIZES: &[usize] = &[32, 1_024, 1 << 20];
+const STREAM_CHUNK_SIZE: u32 = 1_024;
+
+/// Allocator counting the number of allocations.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn count_allocations<T>(action: impl FnOnce() -> T) -> usize {
+    let start = ALLOCATIONS.load(Ordering::Relaxed);
+    let output = action();
+    let count = ALLOCATIONS.load(Ordering::Relaxed) - start;
+    drop(output);
+    count
+}
+
+fn random_message(len: usize) -> Vec<u8> {
+    let mut message = vec![0_u8; len];
+    thread_rng().fill_bytes(&mut message);
+    message
+}
+
+fn report_allocations(_: &mut Criterion) {
+    let mut rng = thread_rng();
+    for &size in SIZES {
+        let message = random_message(size);
+        let mut builder = Sodium::build_box(&mut rng);
+        builder.kdf(Scrypt::light());
+        let seal_count = count_allocations(|| builder.seal(PASSWORD, &message).unwrap());
+        let pwbox = builder.seal(PASSWORD, &message).unwrap();
+        let open_count = count_allocations(|| pwbox.open(PASSWORD).unwrap());
+        println!(
+            "{} B: {} allocations per seal, {} allocations per open",
+            size, seal_count, open_count
+        );
+    }
+
+    // Streaming boxes encrypt each chunk separately, so allocations in the cipher
+    // are multiplied by the number of chunks.
+    let message = random_message(1 << 20);
+    let mut encrypted = Vec::with_capacity(2 << 20);
+    let mut builder = Sodium::build_box(&mut rng);
+    builder.kdf(Scrypt::light()).chunk_size(STREAM_CHUNK_SIZE);
+    let mut pwbox = None;
+    let seal_count = count_allocations(|| {
+        pwbox = Some(
+            builder
+                .seal_from_reader(PASSWORD, &*message, &mut encrypted)
+                .unwrap(),
+        );
+    });
+    let pwbox = pwbox.unwrap();
+    let open_count = count_allocations(|| {
+        pwbox
+            .open_to_writer(PASSWORD, &*encrypted, io::sink())
+            .unwrap();
+    });
+    println!(
+        "1048576 B in {} B chunks: {} allocations per streaming seal, {} per streaming open",
+        STREAM_CHUNK_SIZE, seal_count, open_count
+    );
+}
+
+fn bench_seal(c: &mut Criterion) {
+    let mut group = c.benchmark_group("seal");
+    for &size in SIZES {
+        let message = random_message(size);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &message, |b, message| {
+            let mut rng = thread_rng();
+            let mut builder = Sodium::build_box(&mut rng);
+            builder.kdf(Scrypt::light());
+            b.iter(|| builder.seal(PASSWORD, message).unwrap());
+        });
+    }
+    group.finish();
+}
+
+fn bench_open(c: &mut Criterion) {
+    let mut group = c.benchmark_group("open");
+    for &size in SIZES {
+        let pwbox = Sodium::build_box(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .seal(PASSWORD, random_message(size))
+            .unwrap();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &pwbox, |b, pwbox| {
+            b.iter(|| pwbox.open(PASSWORD).unwrap());
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, report_allocations, bench_seal, bench_open);
+criterion_main!(benches);
@@ -0,0 +1,157 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compares cryptographic backends: key derivation, sealing / opening boxes with the suite
+//! cipher, and the overhead of erasing / restoring boxes.
+//!
+//! Run with `cargo bench --bench suites --all-features`; backends whose crate features
+//! are disabled are skipped. Benchmarks for the sensitive KDF preset require ~1 GiB of RAM.
+//!
+//! To guard against regressions, save a baseline before a change with
+//! `cargo bench --bench suites --all-features -- --save-baseline before`
+//! and compare to it after the change with `-- --baseline before`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::{thread_rng, RngCore};
+use serde::Serialize;
+
+use std::iter;
+
+use pwbox::{Cipher, DeriveKey, Eraser, KdfDifficulty, KdfPreset, NoKdf, PwBox, Suite};
+
+const PASSWORD: &str = "correct horse battery staple";
+const SIZES: &[usize] = &[32, 1 << 20];
+const PRESETS: &[(&str, KdfPreset)] = &[
+    ("interactive", KdfPreset::Interactive),
+    ("sensitive", KdfPreset::Sensitive),
+];
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0_u8; len];
+    thread_rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+/// Benchmarks a suite. `light_kdf` is the recommended KDF of the suite with "light" params,
+/// which are not covered by `KdfPreset`s.
+fn bench_suite<S>(c: &mut Criterion, name: &str, light_kdf: S::DeriveKey)
+where
+    S: Suite,
+    S::DeriveKey: KdfDifficulty + Serialize,
+{
+    bench_kdf::<S>(c, name, light_kdf.clone());
+    bench_cipher::<S::Cipher>(c, name);
+    bench_erasure::<S>(c, name, light_kdf);
+}
+
+fn bench_kdf<S>(c: &mut Criterion, name: &str, light_kdf: S::DeriveKey)
+where
+    S: Suite,
+    S::DeriveKey: KdfDifficulty,
+{
+    let kdfs = PRESETS
+        .iter()
+        .map(|&(level, preset)| (level, S::DeriveKey::preset(preset)));
+    let kdfs = iter::once(("light", light_kdf)).chain(kdfs);
+
+    let mut group = c.benchmark_group(format!("{}/kdf", name));
+    // Sensitive params take seconds per derivation.
+    group.sample_size(10);
+    for (level, kdf) in kdfs {
+        let salt = random_bytes(kdf.salt_len());
+        let mut key = vec![0_u8; <S::Cipher as Cipher>::KEY_LEN];
+        group.bench_function(level, |b| {
+            b.iter(|| {
+                kdf.derive_key(&mut key, PASSWORD.as_bytes(), &salt)
+                    .unwrap()
+            });
+        });
+    }
+    group.finish();
+}
+
+/// Benchmarks the cipher in isolation from the KDF, which would dominate the measurements
+/// for small messages.
+fn bench_cipher<C: Cipher>(c: &mut Criterion, name: &str) {
+    let key = random_bytes(C::KEY_LEN);
+
+    let mut group = c.benchmark_group(format!("{}/seal", name));
+    for &size in SIZES {
+        let message = random_bytes(size);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &message, |b, message| {
+            let mut rng = thread_rng();
+            b.iter(|| PwBox::<NoKdf, C>::seal_with_key(&mut rng, &key, message).unwrap());
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group(format!("{}/open", name));
+    for &size in SIZES {
+        let pwbox =
+            PwBox::<NoKdf, C>::seal_with_key(&mut thread_rng(), &key, random_bytes(size)).unwrap();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &pwbox, |b, pwbox| {
+            b.iter(|| pwbox.open_with_key(&key).unwrap());
+        });
+    }
+    group.finish();
+}
+
+fn bench_erasure<S>(c: &mut Criterion, name: &str, kdf: S::DeriveKey)
+where
+    S: Suite,
+    S::DeriveKey: Serialize,
+{
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<S>().unwrap();
+    let pwbox = S::build_box(&mut thread_rng())
+        .kdf(kdf)
+        .seal(PASSWORD, random_bytes(32))
+        .unwrap();
+    let erased = eraser.erase(&pwbox).unwrap();
+
+    let mut group = c.benchmark_group(format!("{}/eraser", name));
+    group.bench_function("erase", |b| b.iter(|| eraser.erase(&pwbox).unwrap()));
+    group.bench_function("restore", |b| b.iter(|| eraser.restore(&erased).unwrap()));
+    group.finish();
+}
+
+/// Benchmarks all backends enabled via crate features. To bench a new backend,
+/// add a `bench_suite()` call for it here.
+fn bench_suites(c: &mut Criterion) {
+    use pwbox::sodium::{Scrypt, Sodium};
+    bench_suite::<Sodium>(c, "sodium", Scrypt::light());
+
+    #[cfg(feature = "rust-crypto")]
+    {
+        use pwbox::{
+            rcrypto::{RustCrypto, Scrypt},
+            ScryptParams,
+        };
+        bench_suite::<RustCrypto>(c, "rcrypto", Scrypt(ScryptParams::light()));
+    }
+
+    #[cfg(feature = "pure")]
+    {
+        use pwbox::{
+            pure::{PureCrypto, Scrypt},
+            ScryptParams,
+        };
+        bench_suite::<PureCrypto>(c, "pure", Scrypt(ScryptParams::light()));
+    }
+}
+
+criterion_group!(benches, bench_suites);
+criterion_main!(benches);
@@ -185,7 +185,7 @@ impl Args {
     fn execute(self) -> Result<(), Error> {
         // Initialize an `Eraser` instance together with a `sodium` cryptosuite.
         let mut eraser = Eraser::new();
-        eraser.add_suite::<Sodium>();
+        eraser.add_suite::<Sodium>()?;
 
         match self {
             Args::Encrypt {
@@ -104,7 +104,7 @@ impl Keypair<SecretKey> {
 fn main() {
     // Initialize an `Eraser` instance together with a `sodium` cryptosuite.
     let mut eraser = Eraser::new();
-    eraser.add_suite::<Sodium>();
+    eraser.add_suite::<Sodium>().unwrap();
 
     // Create a random keypair.
     let keypair = Keypair::new();
@@ -0,0 +1,48 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checks that restoring, opening and re-erasing arbitrary boxes never panics.
+//!
+//! Run with `cargo fuzz run restore` from the crate root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pwbox::{pure::PureCrypto, rcrypto::RustCrypto, sodium::Sodium, ErasedPwBox, Eraser, Suite};
+
+fn eraser<S: Suite>() -> Eraser {
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<S>().unwrap();
+    eraser
+}
+
+fuzz_target!(|data: &[u8]| {
+    let erased: ErasedPwBox = match serde_json::from_slice(data) {
+        Ok(erased) => erased,
+        Err(_) => return,
+    };
+
+    // Suites register some KDFs under the same names, so they are checked separately.
+    let erasers = [
+        eraser::<Sodium>(),
+        eraser::<RustCrypto>(),
+        eraser::<PureCrypto>(),
+    ];
+    for eraser in &erasers {
+        if let Ok(restored) = eraser.restore(&erased) {
+            restored.open("correct horse battery staple").ok();
+            eraser.erase_restored(&restored).ok();
+        }
+    }
+});
@@ -0,0 +1,718 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Export and import of password-encrypted [age] files.
+//!
+//! An age file consists of a textual header and a binary payload. Files encrypted
+//! with a passphrase have a single `scrypt` recipient stanza in the header; the stanza
+//! records the salt and work factor (`log_n`) of the KDF, and the random file key
+//! encrypted with the derived key. The payload is encrypted in 64 KiB chunks
+//! with ChaCha20-Poly1305 under a key derived from the file key.
+//!
+//! This module only supports the `scrypt` recipient type. Files with other recipients
+//! (e.g., X25519 public keys) cannot be decrypted, and are rejected
+//! with [`AgeError::UnsupportedStanza`].
+//!
+//! [age]: https://age-encryption.org/v1
+//!
+//! # Examples
+//!
+//! ```
+//! use rand::thread_rng;
+//! use pwbox::{age_compat, pure::{PureCrypto, Scrypt}, ScryptParams, Suite};
+//!
+//! # fn main() -> Result<(), age_compat::AgeError> {
+//! let pwbox = PureCrypto::build_box(&mut thread_rng())
+//!     .kdf(Scrypt(ScryptParams::custom(10, 1)))
+//!     .seal(b"correct horse", b"battery staple")
+//!     .unwrap();
+//! let file = age_compat::to_age(&mut thread_rng(), &pwbox, b"correct horse")?;
+//! assert!(file.starts_with(b"age-encryption.org/v1\n-> scrypt "));
+//!
+//! let plaintext = age_compat::from_age(&file, b"correct horse")?;
+//! assert_eq!(&*plaintext, b"battery staple");
+//! # Ok(())
+//! # }
+//! ```
+
+use chacha20poly1305::{
+    aead::{generic_array::GenericArray, AeadInPlace, NewAead},
+    ChaCha20Poly1305, Nonce,
+};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac as _, NewMac};
+use rand_core::{CryptoRng, RngCore};
+use sha2::Sha256;
+
+use std::fmt;
+
+use crate::{
+    pure::Scrypt, utils::decode_base64, DeriveKey, Error, PwBox, ScryptParams, SensitiveData,
+};
+
+/// Version line starting the header of age v1 files.
+const VERSION_LINE: &[u8] = b"age-encryption.org/v1";
+/// Label prepended to the salt of the `scrypt` stanza.
+const SCRYPT_LABEL: &[u8] = b"age-encryption.org/v1/scrypt";
+/// Byte size of the file key.
+const FILE_KEY_LEN: usize = 16;
+/// Byte size of the `scrypt` salt.
+const SALT_LEN: usize = 16;
+/// Byte size of the nonce prepended to the payload.
+const PAYLOAD_NONCE_LEN: usize = 16;
+/// Byte size of a plaintext chunk of the payload.
+const CHUNK_SIZE: usize = 65_536;
+/// Byte size of the ChaCha20-Poly1305 authentication tag.
+const TAG_LEN: usize = 16;
+/// Number of base64 chars in a full line of the stanza body.
+const BODY_LINE_LEN: usize = 64;
+
+/// Maximum `scrypt` work factor (`log_n`) accepted by [`from_age()`]. The limit is the same
+/// as in the reference age implementation; deriving a key with it takes about a second
+/// and consumes 4 GiB of memory.
+pub const MAX_WORK_FACTOR: u8 = 22;
+
+/// Errors occurring during age file import / export.
+#[derive(Debug)]
+pub enum AgeError {
+    /// The file does not start with the age v1 version line.
+    UnsupportedVersion,
+
+    /// The header is malformed, e.g., contains non-canonical base64 or an invalid
+    /// `scrypt` stanza.
+    MalformedHeader(&'static str),
+
+    /// The header does not contain a `scrypt` stanza. The type of the first stanza
+    /// in the header is attached to the error.
+    UnsupportedStanza(String),
+
+    /// The `scrypt` work factor exceeds [`MAX_WORK_FACTOR`].
+    WorkFactorTooLarge(u8),
+
+    /// Failed to decrypt the file key. This error means that either the supplied password
+    /// is incorrect, or the file is corrupted.
+    IncorrectPassword,
+
+    /// The MAC of the header does not verify. This error means that the header is corrupted.
+    HeaderMac,
+
+    /// The payload is truncated or corrupted.
+    Payload(&'static str),
+
+    /// Box was sealed with associated data, which is not supported by age files.
+    AssociatedData,
+
+    /// Error opening the exported box or deriving the key.
+    Open(Error),
+}
+
+impl fmt::Display for AgeError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AgeError::UnsupportedVersion => formatter.write_str("unsupported age file version"),
+            AgeError::MalformedHeader(reason) => {
+                write!(formatter, "malformed age header: {}", reason)
+            }
+            AgeError::UnsupportedStanza(kind) => {
+                write!(formatter, "unsupported age recipient stanza: {}", kind)
+            }
+            AgeError::WorkFactorTooLarge(log_n) => write!(
+                formatter,
+                "scrypt work factor is too large: {} (max {})",
+                log_n, MAX_WORK_FACTOR
+            ),
+            AgeError::IncorrectPassword => {
+                formatter.write_str("incorrect password or corrupted age file")
+            }
+            AgeError::HeaderMac => formatter.write_str("age header MAC mismatch"),
+            AgeError::Payload(reason) => write!(formatter, "invalid age payload: {}", reason),
+            AgeError::AssociatedData => {
+                formatter.write_str("boxes sealed with associated data cannot be exported")
+            }
+            AgeError::Open(e) => write!(formatter, "cannot open box: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AgeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AgeError::Open(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Stanza in the age header.
+#[derive(Debug)]
+struct Stanza<'a> {
+    kind: &'a str,
+    args: Vec<&'a str>,
+    body: Vec<u8>,
+}
+
+/// Parsed age header.
+#[derive(Debug)]
+struct Header<'a> {
+    stanzas: Vec<Stanza<'a>>,
+    /// Header bytes authenticated by the MAC (up to and including `---`).
+    authenticated: &'a [u8],
+    mac: Vec<u8>,
+}
+
+/// Splits off the next `\n`-terminated line from the `input`.
+fn next_line<'a>(input: &mut &'a [u8]) -> Result<&'a [u8], AgeError> {
+    let pos = input
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .ok_or(AgeError::MalformedHeader("unexpected end of header"))?;
+    let line = &input[..pos];
+    *input = &input[(pos + 1)..];
+    Ok(line)
+}
+
+fn parse_stanza<'a>(line: &'a [u8], input: &mut &'a [u8]) -> Result<Stanza<'a>, AgeError> {
+    let is_valid_arg = |arg: &[u8]| !arg.is_empty() && arg.iter().all(|&ch| ch > b' ' && ch < 127);
+    if !line.split(|&ch| ch == b' ').all(is_valid_arg) {
+        return Err(AgeError::MalformedHeader("invalid stanza arguments"));
+    }
+    // All arguments are ASCII, so the conversion cannot fail.
+    let line = std::str::from_utf8(line).expect("stanza arguments are ASCII");
+    let mut args = line.split(' ');
+    let kind = args
+        .next()
+        .expect("`split` always yields at least one item");
+
+    let mut encoded_body = String::new();
+    loop {
+        let body_line = std::str::from_utf8(next_line(input)?)
+            .map_err(|_| AgeError::MalformedHeader("invalid stanza body"))?;
+        if body_line.len() > BODY_LINE_LEN {
+            return Err(AgeError::MalformedHeader("stanza body line is too long"));
+        }
+        encoded_body.push_str(body_line);
+        if body_line.len() < BODY_LINE_LEN {
+            break;
+        }
+    }
+    let body = decode_base64(&encoded_body)
+        .ok_or(AgeError::MalformedHeader("invalid base64 in stanza body"))?;
+
+    Ok(Stanza {
+        kind,
+        args: args.collect(),
+        body,
+    })
+}
+
+/// Parses the header, returning it together with the remaining (payload) bytes.
+fn parse_header(file: &[u8]) -> Result<(Header<'_>, &[u8]), AgeError> {
+    let mut input = file;
+    if next_line(&mut input).ok() != Some(VERSION_LINE) {
+        return Err(AgeError::UnsupportedVersion);
+    }
+
+    let mut stanzas = vec![];
+    loop {
+        let line_start = file.len() - input.len();
+        let line = next_line(&mut input)?;
+        if line.starts_with(b"-> ") {
+            stanzas.push(parse_stanza(&line[3..], &mut input)?);
+        } else if line.starts_with(b"--- ") {
+            let mac = std::str::from_utf8(&line[4..])
+                .ok()
+                .and_then(decode_base64)
+                .filter(|mac| mac.len() == 32)
+                .ok_or(AgeError::MalformedHeader("invalid header MAC"))?;
+            let header = Header {
+                stanzas,
+                authenticated: &file[..(line_start + 3)],
+                mac,
+            };
+            return Ok((header, input));
+        } else {
+            return Err(AgeError::MalformedHeader("unexpected header line"));
+        }
+    }
+}
+
+/// Parses the work factor of the `scrypt` stanza. The work factor must be a decimal number
+/// without leading zeros or sign.
+fn parse_work_factor(arg: &str) -> Result<u8, AgeError> {
+    const ERROR: AgeError = AgeError::MalformedHeader("invalid scrypt work factor");
+
+    if arg.starts_with('0') || !arg.bytes().all(|ch| ch.is_ascii_digit()) {
+        return Err(ERROR);
+    }
+    let log_n: u8 = arg.parse().map_err(|_| ERROR)?;
+    if log_n > MAX_WORK_FACTOR {
+        Err(AgeError::WorkFactorTooLarge(log_n))
+    } else {
+        Ok(log_n)
+    }
+}
+
+/// Derives the key wrapping the file key from the password.
+fn derive_wrap_key(password: &[u8], salt: &[u8], log_n: u8) -> Result<SensitiveData, AgeError> {
+    let mut labeled_salt = Vec::with_capacity(SCRYPT_LABEL.len() + salt.len());
+    labeled_salt.extend_from_slice(SCRYPT_LABEL);
+    labeled_salt.extend_from_slice(salt);
+
+    let mut key = SensitiveData::zeros(32);
+    Scrypt(ScryptParams::custom(log_n, 1))
+        .derive_key(&mut key, password, &labeled_salt)
+        .map_err(|e| AgeError::Open(Error::DeriveKey(e)))?;
+    Ok(key)
+}
+
+/// Extracts the file key from the sole `scrypt` stanza in the header.
+fn unwrap_file_key(stanzas: &[Stanza<'_>], password: &[u8]) -> Result<SensitiveData, AgeError> {
+    let stanza = match stanzas {
+        [] => return Err(AgeError::MalformedHeader("no recipient stanzas")),
+        [stanza] if stanza.kind == "scrypt" => stanza,
+        _ if stanzas.iter().any(|stanza| stanza.kind == "scrypt") => {
+            return Err(AgeError::MalformedHeader(
+                "scrypt stanza must be the only stanza in the header",
+            ));
+        }
+        [stanza, ..] => return Err(AgeError::UnsupportedStanza(stanza.kind.to_owned())),
+    };
+
+    let (salt, log_n) = match stanza.args[..] {
+        [salt, log_n] => (salt, log_n),
+        _ => return Err(AgeError::MalformedHeader("invalid scrypt stanza arguments")),
+    };
+    let salt = decode_base64(salt)
+        .filter(|salt| salt.len() == SALT_LEN)
+        .ok_or(AgeError::MalformedHeader("invalid scrypt salt"))?;
+    let log_n = parse_work_factor(log_n)?;
+    if stanza.body.len() != FILE_KEY_LEN + TAG_LEN {
+        return Err(AgeError::MalformedHeader(
+            "invalid scrypt stanza body length",
+        ));
+    }
+
+    let wrap_key = derive_wrap_key(password, &salt, log_n)?;
+    let (encrypted_key, tag) = stanza.body.split_at(FILE_KEY_LEN);
+    let mut file_key = SensitiveData::zeros(FILE_KEY_LEN);
+    file_key.copy_from_slice(encrypted_key);
+    ChaCha20Poly1305::new(GenericArray::from_slice(&wrap_key))
+        .decrypt_in_place_detached(
+            &GenericArray::default(),
+            b"",
+            &mut file_key,
+            GenericArray::from_slice(tag),
+        )
+        .map_err(|_| AgeError::IncorrectPassword)?;
+    Ok(file_key)
+}
+
+/// Expands the file key into a 32-byte key with HKDF-SHA-256.
+fn expand_key(file_key: &[u8], salt: &[u8], info: &[u8]) -> SensitiveData {
+    let mut key = SensitiveData::zeros(32);
+    Hkdf::<Sha256>::new(Some(salt), file_key)
+        .expand(info, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA-256 output length");
+    key
+}
+
+fn header_mac(file_key: &[u8], authenticated: &[u8]) -> Hmac<Sha256> {
+    let mac_key = expand_key(file_key, b"", b"header");
+    let mut mac = Hmac::<Sha256>::new_varkey(&mac_key).expect("HMAC accepts keys of any size");
+    mac.update(authenticated);
+    mac
+}
+
+/// Computes the ChaCha20-Poly1305 nonce for the payload chunk: an 11-byte big-endian
+/// chunk counter followed by the flag set for the last chunk.
+fn chunk_nonce(index: usize, is_last: bool) -> Nonce {
+    let mut nonce = GenericArray::default();
+    nonce[3..11].copy_from_slice(&(index as u64).to_be_bytes());
+    nonce[11] = u8::from(is_last);
+    nonce
+}
+
+fn decrypt_payload(file_key: &[u8], payload: &[u8]) -> Result<SensitiveData, AgeError> {
+    const ENCRYPTED_CHUNK_SIZE: usize = CHUNK_SIZE + TAG_LEN;
+
+    if payload.len() < PAYLOAD_NONCE_LEN {
+        return Err(AgeError::Payload("payload nonce is truncated"));
+    }
+    let (nonce, ciphertext) = payload.split_at(PAYLOAD_NONCE_LEN);
+    if ciphertext.is_empty() {
+        return Err(AgeError::Payload("payload has no chunks"));
+    }
+    let chunk_count = (ciphertext.len() + ENCRYPTED_CHUNK_SIZE - 1) / ENCRYPTED_CHUNK_SIZE;
+    let last_chunk_len = ciphertext.len() - (chunk_count - 1) * ENCRYPTED_CHUNK_SIZE;
+    if last_chunk_len < TAG_LEN {
+        return Err(AgeError::Payload("payload chunk is truncated"));
+    }
+    if last_chunk_len == TAG_LEN && chunk_count > 1 {
+        return Err(AgeError::Payload("last payload chunk is empty"));
+    }
+
+    let payload_key = expand_key(file_key, nonce, b"payload");
+    let cipher = ChaCha20Poly1305::new(GenericArray::from_slice(&payload_key));
+    let mut output = SensitiveData::zeros(ciphertext.len() - chunk_count * TAG_LEN);
+    let chunks = ciphertext
+        .chunks(ENCRYPTED_CHUNK_SIZE)
+        .zip(output.chunks_mut(CHUNK_SIZE).chain(Some(&mut [][..])));
+    for (index, (chunk, output_chunk)) in chunks.enumerate() {
+        let (chunk, tag) = chunk.split_at(chunk.len() - TAG_LEN);
+        output_chunk.copy_from_slice(chunk);
+        let nonce = chunk_nonce(index, index + 1 == chunk_count);
+        cipher
+            .decrypt_in_place_detached(&nonce, b"", output_chunk, GenericArray::from_slice(tag))
+            .map_err(|_| AgeError::Payload("payload chunk authentication failed"))?;
+    }
+    Ok(output)
+}
+
+/// Decrypts a password-encrypted age file.
+///
+/// # Errors
+///
+/// Returns an error if the file is malformed, does not have a `scrypt` stanza, has the work
+/// factor greater than [`MAX_WORK_FACTOR`], or if the password is incorrect.
+pub fn from_age(file: &[u8], password: impl AsRef<[u8]>) -> Result<SensitiveData, AgeError> {
+    let (header, payload) = parse_header(file)?;
+    let file_key = unwrap_file_key(&header.stanzas, password.as_ref())?;
+    header_mac(&file_key, header.authenticated)
+        .verify(&header.mac)
+        .map_err(|_| AgeError::HeaderMac)?;
+    decrypt_payload(&file_key, payload)
+}
+
+fn encrypt<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    password: &[u8],
+    message: &[u8],
+    log_n: u8,
+) -> Result<Vec<u8>, AgeError> {
+    let mut file_key = SensitiveData::zeros(FILE_KEY_LEN);
+    rng.fill_bytes(&mut file_key);
+    let mut salt = [0_u8; SALT_LEN];
+    rng.fill_bytes(&mut salt);
+
+    let wrap_key = derive_wrap_key(password, &salt, log_n)?;
+    // The body is encrypted in place, so it is zeroized on drop like the file key.
+    let mut body = SensitiveData::zeros(FILE_KEY_LEN + TAG_LEN);
+    let (encrypted_key, tag) = body.split_at_mut(FILE_KEY_LEN);
+    encrypted_key.copy_from_slice(&file_key);
+    let computed_tag = ChaCha20Poly1305::new(GenericArray::from_slice(&wrap_key))
+        .encrypt_in_place_detached(&GenericArray::default(), b"", encrypted_key)
+        .expect("cannot encrypt file key");
+    tag.copy_from_slice(&computed_tag);
+
+    let mut file = VERSION_LINE.to_vec();
+    let stanza = format!(
+        "\n-> scrypt {} {}\n{}\n---",
+        base64::encode_config(&salt, base64::STANDARD_NO_PAD),
+        log_n,
+        base64::encode_config(&body, base64::STANDARD_NO_PAD)
+    );
+    file.extend_from_slice(stanza.as_bytes());
+    let mac = header_mac(&file_key, &file).finalize().into_bytes();
+    file.push(b' ');
+    file.extend_from_slice(base64::encode_config(&mac, base64::STANDARD_NO_PAD).as_bytes());
+    file.push(b'\n');
+
+    let mut nonce = [0_u8; PAYLOAD_NONCE_LEN];
+    rng.fill_bytes(&mut nonce);
+    file.extend_from_slice(&nonce);
+    let payload_key = expand_key(&file_key, &nonce, b"payload");
+    let cipher = ChaCha20Poly1305::new(GenericArray::from_slice(&payload_key));
+    // An empty message is encrypted as a single empty chunk.
+    let chunk_count = ((message.len() + CHUNK_SIZE - 1) / CHUNK_SIZE).max(1);
+    let chunks = message
+        .chunks(CHUNK_SIZE)
+        .chain(Some(&[][..]))
+        .take(chunk_count);
+    for (index, chunk) in chunks.enumerate() {
+        let start = file.len();
+        file.extend_from_slice(chunk);
+        let nonce = chunk_nonce(index, index + 1 == chunk_count);
+        let tag = cipher
+            .encrypt_in_place_detached(&nonce, b"", &mut file[start..])
+            .expect("cannot encrypt payload chunk");
+        file.extend_from_slice(&tag);
+    }
+    Ok(file)
+}
+
+/// Exports a box into a password-encrypted age file. The box is opened with the `password`,
+/// and its contents are re-encrypted under the same password with a fresh file key.
+///
+/// The `scrypt` work factor of the file is equal to `log_n` of the box KDF. Other `scrypt`
+/// params cannot be represented in age files; age always uses `r = 8` and `p = 1`.
+///
+/// # Errors
+///
+/// Returns an error if the box cannot be opened with the password, or was sealed
+/// with associated data.
+pub fn to_age<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    pwbox: &PwBox<Scrypt, ChaCha20Poly1305>,
+    password: impl AsRef<[u8]>,
+) -> Result<Vec<u8>, AgeError> {
+    if pwbox.has_associated_data() {
+        return Err(AgeError::AssociatedData);
+    }
+    let password = password.as_ref();
+    let message = pwbox.open(password).map_err(AgeError::Open)?;
+    encrypt(rng, password, &message, pwbox.inner.kdf.0.log_n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PwBoxBuilder;
+
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+    use sha2::Digest;
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    /// Test vectors from the age reference test kit.
+    macro_rules! test_vectors {
+        ($($name:tt),+ $(,)?) => {
+            &[$(($name, include_bytes!(concat!("../tests/data/age/", $name)))),+]
+        };
+    }
+
+    const SCRYPT_VECTORS: &[(&str, &[u8])] = test_vectors![
+        "scrypt",
+        "scrypt_and_x25519",
+        "scrypt_bad_tag",
+        "scrypt_double",
+        "scrypt_extra_argument",
+        "scrypt_long_file_key",
+        "scrypt_no_match",
+        "scrypt_not_canonical_body",
+        "scrypt_not_canonical_salt",
+        "scrypt_salt_long",
+        "scrypt_salt_missing",
+        "scrypt_salt_short",
+        "scrypt_uppercase",
+        "scrypt_work_factor_23",
+        "scrypt_work_factor_hex",
+        "scrypt_work_factor_leading_garbage",
+        "scrypt_work_factor_leading_plus",
+        "scrypt_work_factor_leading_zero_decimal",
+        "scrypt_work_factor_leading_zero_octal",
+        "scrypt_work_factor_missing",
+        "scrypt_work_factor_negative",
+        "scrypt_work_factor_overflow",
+        "scrypt_work_factor_trailing_garbage",
+        "scrypt_work_factor_wrong",
+        "scrypt_work_factor_zero",
+    ];
+
+    // These vectors use X25519 recipients, so only the payload is checked with the file key
+    // recorded in the vector.
+    const STREAM_VECTORS: &[(&str, &[u8])] = test_vectors![
+        "stream_bad_tag",
+        "stream_empty_payload",
+        "stream_last_chunk_empty",
+        "stream_last_chunk_full",
+        "stream_missing_tag",
+        "stream_no_chunks",
+        "stream_no_final",
+        "stream_no_nonce",
+        "stream_short_chunk",
+    ];
+
+    #[derive(Debug)]
+    struct TestVector<'a> {
+        expect: &'a str,
+        payload_hash: Option<&'a str>,
+        file_key: Vec<u8>,
+        passphrase: Option<&'a str>,
+        file: &'a [u8],
+    }
+
+    impl<'a> TestVector<'a> {
+        fn parse(bytes: &'a [u8]) -> Self {
+            let split = bytes
+                .windows(2)
+                .position(|window| window == b"\n\n")
+                .unwrap();
+            let meta = std::str::from_utf8(&bytes[..split]).unwrap();
+            let mut vector = TestVector {
+                expect: "",
+                payload_hash: None,
+                file_key: vec![],
+                passphrase: None,
+                file: &bytes[(split + 2)..],
+            };
+            for line in meta.lines() {
+                let pos = line.find(": ").unwrap();
+                let (key, value) = (&line[..pos], &line[(pos + 2)..]);
+                match key {
+                    "expect" => vector.expect = value,
+                    "payload" => vector.payload_hash = Some(value),
+                    "file key" => vector.file_key = hex::decode(value).unwrap(),
+                    // Only the first passphrase is used.
+                    "passphrase" if vector.passphrase.is_none() => {
+                        vector.passphrase = Some(value);
+                    }
+                    _ => { /* other fields are not used */ }
+                }
+            }
+            vector
+        }
+
+        fn payload(&self) -> &'a [u8] {
+            let mac_line = self.file.windows(5).position(|w| w == b"\n--- ").unwrap() + 1;
+            let mac_line_len = self.file[mac_line..].iter().position(|&ch| ch == b'\n');
+            &self.file[(mac_line + mac_line_len.unwrap() + 1)..]
+        }
+
+        fn assert_payload(&self, plaintext: &[u8]) {
+            let hash = hex::encode(Sha256::digest(plaintext));
+            assert_eq!(Some(hash.as_str()), self.payload_hash);
+        }
+    }
+
+    #[test]
+    fn scrypt_test_vectors() {
+        for &(name, bytes) in SCRYPT_VECTORS {
+            let vector = TestVector::parse(bytes);
+            let result = from_age(vector.file, vector.passphrase.unwrap());
+            match vector.expect {
+                "success" => {
+                    let plaintext = result.unwrap();
+                    vector.assert_payload(&plaintext);
+                }
+                "header failure" => assert_matches!(
+                    result.unwrap_err(),
+                    AgeError::MalformedHeader(_) | AgeError::WorkFactorTooLarge(_),
+                    "{}",
+                    name
+                ),
+                "no match" => assert_matches!(
+                    result.unwrap_err(),
+                    AgeError::IncorrectPassword | AgeError::UnsupportedStanza(_),
+                    "{}",
+                    name
+                ),
+                other => panic!("unexpected expectation in {}: {}", name, other),
+            }
+        }
+    }
+
+    #[test]
+    fn stream_test_vectors() {
+        for &(name, bytes) in STREAM_VECTORS {
+            let vector = TestVector::parse(bytes);
+            let result = decrypt_payload(&vector.file_key, vector.payload());
+            match vector.expect {
+                "success" => vector.assert_payload(&result.unwrap()),
+                "payload failure" | "header failure" => {
+                    assert_matches!(result.unwrap_err(), AgeError::Payload(_), "{}", name);
+                }
+                other => panic!("unexpected expectation in {}: {}", name, other),
+            }
+        }
+    }
+
+    #[test]
+    fn specific_errors() {
+        let vector = TestVector::parse(SCRYPT_VECTORS[0].1);
+        let file = vector.file.to_vec();
+        let mac_pos = file.windows(4).position(|w| w == b"--- ").unwrap() + 4;
+
+        let mut corrupted = file.clone();
+        corrupted[mac_pos] = if corrupted[mac_pos] == b'A' {
+            b'B'
+        } else {
+            b'A'
+        };
+        assert_matches!(
+            from_age(&corrupted, "password").unwrap_err(),
+            AgeError::HeaderMac
+        );
+
+        let other_recipient = String::from_utf8_lossy(&file).replace("scrypt", "X25519");
+        assert_matches!(
+            from_age(other_recipient.as_bytes(), "password").unwrap_err(),
+            AgeError::UnsupportedStanza(ref kind) if kind == "X25519"
+        );
+        let wrong_version = String::from_utf8_lossy(&file).replace("/v1", "/v2");
+        assert_matches!(
+            from_age(wrong_version.as_bytes(), "password").unwrap_err(),
+            AgeError::UnsupportedVersion
+        );
+        assert_matches!(
+            from_age(&file, "wrong").unwrap_err(),
+            AgeError::IncorrectPassword
+        );
+        assert_matches!(
+            from_age(&file[..(mac_pos - 1)], "password").unwrap_err(),
+            AgeError::MalformedHeader(_)
+        );
+        assert_matches!(
+            from_age(&file[..(file.len() - 1)], "password").unwrap_err(),
+            AgeError::Payload(_)
+        );
+    }
+
+    #[test]
+    fn export_roundtrip() {
+        let mut rng = thread_rng();
+        for &len in &[0, 1, 1_000, CHUNK_SIZE, 2 * CHUNK_SIZE + 1] {
+            let mut message = vec![0_u8; len];
+            rng.fill_bytes(&mut message);
+            let pwbox = PwBoxBuilder::<_, ChaCha20Poly1305>::new(&mut rng)
+                .kdf(Scrypt(ScryptParams::custom(10, 1)))
+                .seal(PASSWORD, &message)
+                .unwrap();
+            let file = to_age(&mut rng, &pwbox, PASSWORD).unwrap();
+            assert!(file.starts_with(b"age-encryption.org/v1\n-> scrypt "));
+            let header_len = file.windows(4).position(|w| w == b"--- ").unwrap();
+            let header = std::str::from_utf8(&file[..header_len]).unwrap();
+            assert!(header.contains(" 10\n"), "{}", header);
+            let expected_len = len + ((len + CHUNK_SIZE - 1) / CHUNK_SIZE).max(1) * TAG_LEN;
+            let (_, payload) = parse_header(&file).unwrap();
+            assert_eq!(payload.len(), PAYLOAD_NONCE_LEN + expected_len);
+
+            let plaintext = from_age(&file, PASSWORD).unwrap();
+            assert_eq!(&*plaintext, &*message);
+        }
+    }
+
+    #[test]
+    fn export_errors() {
+        let mut rng = thread_rng();
+        let pwbox = PwBoxBuilder::<_, ChaCha20Poly1305>::new(&mut rng)
+            .kdf(Scrypt(ScryptParams::custom(10, 1)))
+            .seal(PASSWORD, b"message")
+            .unwrap();
+        assert_matches!(
+            to_age(&mut rng, &pwbox, "wrong").unwrap_err(),
+            AgeError::Open(Error::MacMismatch)
+        );
+
+        let pwbox = PwBoxBuilder::<_, ChaCha20Poly1305>::new(&mut rng)
+            .kdf(Scrypt(ScryptParams::custom(10, 1)))
+            .associated_data(b"context")
+            .seal(PASSWORD, b"message")
+            .unwrap();
+        assert_matches!(
+            to_age(&mut rng, &pwbox, PASSWORD).unwrap_err(),
+            AgeError::AssociatedData
+        );
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Opening multiple boxes in parallel.
+
+use core::{
+    cmp,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use std::{panic, thread};
+
+use crate::{
+    alloc::{Arc, Vec},
+    Error, RestoredPwBox, SecretVec, SensitiveData,
+};
+
+/// Maximum number of threads spawned by [`open_any()`]. KDFs are usually memory-hard,
+/// so the number of parallel derivations is bounded to keep memory consumption reasonable.
+const MAX_THREADS: usize = 8;
+
+/// Opens multiple boxes with the same password, deriving keys in parallel.
+///
+/// This is useful when unlocking a keystore with many boxes: the salt differs per box,
+/// so the KDF needs to run for each of them. The boxes are opened on a pool of
+/// at most 8 threads; results are returned in the order of `boxes`.
+///
+/// The password is copied into a buffer zeroed on drop, so that it can be shared among
+/// the threads.
+///
+/// # Examples
+///
+/// ```
+/// # use rand::thread_rng;
+/// # use pwbox::{open_any, sodium::{Scrypt, Sodium}, Eraser, Error, Suite};
+/// # fn main() -> Result<(), Error> {
+/// let mut eraser = Eraser::new();
+/// eraser.add_suite::<Sodium>()?;
+/// let boxes = (0_u8..4)
+///     .map(|i| {
+///         let pwbox = Sodium::build_box(&mut thread_rng())
+/// #           .kdf(Scrypt::light())
+///             .seal("correct horse", [i; 8])
+///             .unwrap();
+///         eraser.restore(&eraser.erase(&pwbox).unwrap())
+///     })
+///     .collect::<Result<Vec<_>, _>>()?;
+///
+/// let results = open_any(&boxes, b"correct horse");
+/// for (res, i) in results.into_iter().zip(0_u8..) {
+///     assert_eq!(&*res?, &[i; 8]);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn open_any<'a>(
+    boxes: impl IntoIterator<Item = &'a RestoredPwBox>,
+    password: &[u8],
+) -> Vec<Result<SensitiveData, Error>> {
+    let boxes: Vec<_> = boxes.into_iter().map(|pwbox| pwbox.inner.clone()).collect();
+    let boxes = Arc::new(boxes);
+    let password = Arc::new(SecretVec::from(password.to_vec()));
+    let next_index = Arc::new(AtomicUsize::new(0));
+
+    let thread_count = cmp::min(boxes.len(), MAX_THREADS);
+    let handles: Vec<_> = (0..thread_count)
+        .map(|_| {
+            let (boxes, password) = (Arc::clone(&boxes), Arc::clone(&password));
+            let next_index = Arc::clone(&next_index);
+            thread::spawn(move || {
+                let mut results = Vec::new();
+                loop {
+                    let index = next_index.fetch_add(1, Ordering::Relaxed);
+                    match boxes.get(index) {
+                        Some(inner) => results.push((index, inner.open(&**password, &[]))),
+                        None => break results,
+                    }
+                }
+            })
+        })
+        .collect();
+
+    let mut results: Vec<_> = handles
+        .into_iter()
+        .flat_map(|handle| handle.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+        .collect();
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, res)| res).collect()
+}
+
+#[cfg(all(test, feature = "exonum_sodiumoxide"))]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    use super::*;
+    use crate::{
+        sodium::{Scrypt, Sodium},
+        Eraser, Suite,
+    };
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    #[test]
+    fn opening_boxes_in_parallel() {
+        let mut rng = thread_rng();
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+
+        // Use more boxes than there are threads, and make one of them use another password.
+        let boxes: Vec<_> = (0..MAX_THREADS + 3)
+            .map(|i| {
+                let password = if i == 5 { "Tr0ub4dor&3" } else { PASSWORD };
+                let pwbox = Sodium::build_box(&mut rng)
+                    .kdf(Scrypt::light())
+                    .seal(password, i.to_le_bytes())
+                    .unwrap();
+                eraser.restore(&eraser.erase(&pwbox).unwrap()).unwrap()
+            })
+            .collect();
+
+        let results = open_any(&boxes, PASSWORD.as_bytes());
+        assert_eq!(results.len(), boxes.len());
+        for (i, res) in results.into_iter().enumerate() {
+            if i == 5 {
+                assert_matches!(res.unwrap_err(), Error::MacMismatch);
+            } else {
+                assert_eq!(&*res.unwrap(), &i.to_le_bytes()[..]);
+            }
+        }
+
+        assert!(open_any(&[], PASSWORD.as_bytes()).is_empty());
+    }
+}
@@ -0,0 +1,306 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Caching derived keys.
+
+use constant_time_eq::constant_time_eq;
+use serde_json::Value as JsonValue;
+
+use core::fmt;
+
+use crate::{
+    alloc::{String, Vec},
+    Error, RestoredPwBox, SecretVec, SensitiveData,
+};
+
+/// Cache of derived keys, which allows to avoid re-running the KDF when opening
+/// several boxes sealed with the same password, salt and KDF params.
+///
+/// Cache entries are keyed by the KDF name and params, salt, password and key length.
+/// Since the crate has no hash function available independently of crypto backends,
+/// the password is stored as is rather than hashed. Both passwords and keys are stored
+/// in buffers zeroed on drop; thus, entries are zeroed when evicted, when the cache
+/// is [cleared](Self::clear()), or when it is dropped.
+///
+/// The cache has a fixed capacity; if it is full, the least recently used entry is evicted.
+/// Only keys that have successfully opened a box are cached.
+///
+/// # Examples
+///
+/// ```
+/// # use rand::thread_rng;
+/// # use pwbox::{cache::KeyCache, sodium::{Scrypt, Sodium}, Eraser, Suite};
+/// let mut eraser = Eraser::new();
+/// eraser.add_suite::<Sodium>().unwrap();
+/// let pwbox = Sodium::build_box(&mut thread_rng())
+/// #   .kdf(Scrypt::light())
+///     .seal("correct horse", b"battery staple")
+///     .unwrap();
+/// let restored = eraser.restore(&eraser.erase(&pwbox).unwrap()).unwrap();
+///
+/// let mut cache = KeyCache::new();
+/// let plaintext = restored.open_with_cache("correct horse", &mut cache).unwrap();
+/// assert_eq!(&*plaintext, b"battery staple");
+/// assert_eq!(cache.len(), 1);
+/// // The second call does not invoke the KDF.
+/// restored.open_with_cache("correct horse", &mut cache).unwrap();
+/// cache.clear();
+/// assert!(cache.is_empty());
+/// ```
+pub struct KeyCache {
+    /// Entries ordered from the most to the least recently used.
+    entries: Vec<CacheEntry>,
+    capacity: usize,
+}
+
+struct CacheEntry {
+    kdf_name: String,
+    kdf_params: JsonValue,
+    salt: Vec<u8>,
+    password: SecretVec,
+    key: SensitiveData,
+}
+
+impl CacheEntry {
+    fn matches(&self, pwbox: &RestoredPwBox, password: &[u8], key_len: usize) -> bool {
+        self.kdf_name == pwbox.kdf_name
+            && self.kdf_params == pwbox.kdf_params
+            && self.salt == pwbox.inner.salt
+            && self.key.len() == key_len
+            && self.password.len() == password.len()
+            && constant_time_eq(&self.password, password)
+    }
+}
+
+impl fmt::Debug for KeyCache {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("KeyCache")
+            .field("len", &self.entries.len())
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl Default for KeyCache {
+    fn default() -> Self {
+        Self::with_capacity(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl KeyCache {
+    /// Default maximum number of entries in the cache.
+    pub const DEFAULT_CAPACITY: usize = 16;
+
+    /// Creates an empty cache with the default capacity.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty cache with the specified maximum number of entries.
+    pub fn with_capacity(capacity: usize) -> Self {
+        KeyCache {
+            entries: Vec::new(),
+            capacity,
+        }
+    }
+
+    /// Returns the number of cached keys.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Checks whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the maximum number of entries in the cache.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Removes all entries from the cache, zeroing the cached passwords and keys.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Looks up a key for the box and marks it as the most recently used.
+    fn get(&mut self, pwbox: &RestoredPwBox, password: &[u8]) -> Option<&SensitiveData> {
+        let key_len = pwbox.inner.cipher.key_len();
+        let index = self
+            .entries
+            .iter()
+            .position(|entry| entry.matches(pwbox, password, key_len))?;
+        let entry = self.entries.remove(index);
+        self.entries.insert(0, entry);
+        Some(&self.entries[0].key)
+    }
+
+    fn insert(&mut self, pwbox: &RestoredPwBox, password: &[u8], key: SensitiveData) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.entries.truncate(self.capacity - 1);
+        let entry = CacheEntry {
+            kdf_name: pwbox.kdf_name.clone(),
+            kdf_params: pwbox.kdf_params.clone(),
+            salt: pwbox.inner.salt.clone(),
+            password: SecretVec::from(password.to_vec()),
+            key,
+        };
+        self.entries.insert(0, entry);
+    }
+}
+
+impl RestoredPwBox {
+    /// Decrypts the box using the `cache` of derived keys. If the cache contains a key
+    /// for the password, salt and KDF of this box, the KDF is not invoked. Otherwise,
+    /// the key is derived and, if the box is opened successfully, added to the cache.
+    pub fn open_with_cache(
+        &self,
+        password: impl AsRef<[u8]>,
+        cache: &mut KeyCache,
+    ) -> Result<SensitiveData, Error> {
+        let password = password.as_ref();
+        if let Some(key) = cache.get(self, password) {
+            return self.inner.open_with_key(key, &[]);
+        }
+
+        let key = self.inner.derive_key(password)?;
+        let plaintext = self.inner.open_with_key(&key, &[])?;
+        cache.insert(self, password, key);
+        Ok(plaintext)
+    }
+}
+
+#[cfg(all(test, feature = "exonum_sodiumoxide"))]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+    use serde::{Deserialize, Serialize};
+
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{
+        sodium::{Scrypt, XChaCha20Poly1305},
+        DeriveKey, Eraser, PwBoxBuilder, Suite,
+    };
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    static KDF_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    /// KDF counting its invocations.
+    #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+    #[serde(transparent)]
+    struct CountingKdf(Scrypt);
+
+    impl DeriveKey for CountingKdf {
+        fn salt_len(&self) -> usize {
+            self.0.salt_len()
+        }
+
+        fn derive_key(&self, buf: &mut [u8], password: &[u8], salt: &[u8]) -> anyhow::Result<()> {
+            KDF_CALLS.fetch_add(1, Ordering::SeqCst);
+            self.0.derive_key(buf, password, salt)
+        }
+    }
+
+    #[test]
+    fn opening_boxes_with_cache() {
+        let mut rng = thread_rng();
+        let mut eraser = Eraser::new();
+        eraser
+            .add_kdf::<CountingKdf>("counting")
+            .unwrap()
+            .add_cipher::<XChaCha20Poly1305>("xchacha20-poly1305")
+            .unwrap();
+
+        let salt = [42_u8; 32];
+        let boxes: Vec<_> = (0_u8..10)
+            .map(|i| {
+                let pwbox = PwBoxBuilder::<_, XChaCha20Poly1305>::new(&mut rng)
+                    .kdf(CountingKdf(Scrypt::light()))
+                    .salt(salt)
+                    .seal(PASSWORD, [i; 8])
+                    .unwrap();
+                eraser.restore(&eraser.erase(&pwbox).unwrap()).unwrap()
+            })
+            .collect();
+        KDF_CALLS.store(0, Ordering::SeqCst);
+
+        let mut cache = KeyCache::new();
+        for (pwbox, i) in boxes.iter().zip(0_u8..) {
+            assert_eq!(
+                &*pwbox.open_with_cache(PASSWORD, &mut cache).unwrap(),
+                [i; 8]
+            );
+        }
+        assert_eq!(KDF_CALLS.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.len(), 1);
+
+        // Incorrect passwords are not cached.
+        for pwbox in &boxes[..2] {
+            let err = pwbox
+                .open_with_cache("Tr0ub4dor&3", &mut cache)
+                .unwrap_err();
+            assert_matches!(err, Error::MacMismatch);
+        }
+        assert_eq!(KDF_CALLS.load(Ordering::SeqCst), 3);
+        assert_eq!(cache.len(), 1);
+
+        cache.clear();
+        assert!(cache.is_empty());
+        boxes[0].open_with_cache(PASSWORD, &mut cache).unwrap();
+        assert_eq!(KDF_CALLS.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn cache_eviction() {
+        let mut rng = thread_rng();
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<crate::sodium::Sodium>().unwrap();
+        let boxes: Vec<_> = (0_u8..3)
+            .map(|i| {
+                let pwbox = crate::sodium::Sodium::build_box(&mut rng)
+                    .kdf(Scrypt::light())
+                    .seal(PASSWORD, [i; 8])
+                    .unwrap();
+                eraser.restore(&eraser.erase(&pwbox).unwrap()).unwrap()
+            })
+            .collect();
+
+        let mut cache = KeyCache::with_capacity(2);
+        for pwbox in &boxes {
+            pwbox.open_with_cache(PASSWORD, &mut cache).unwrap();
+        }
+        assert_eq!(cache.len(), 2);
+        // The least recently used entry (for the first box) should be evicted.
+        let key_len = boxes[0].inner.cipher.key_len();
+        let password = PASSWORD.as_bytes();
+        assert!(!cache.entries[0].matches(&boxes[0], password, key_len));
+        assert!(cache.entries[0].matches(&boxes[2], password, key_len));
+        assert!(cache.entries[1].matches(&boxes[1], password, key_len));
+
+        // Using an entry moves it to the front.
+        boxes[1].open_with_cache(PASSWORD, &mut cache).unwrap();
+        assert!(cache.entries[0].matches(&boxes[1], password, key_len));
+
+        let mut cache = KeyCache::with_capacity(0);
+        boxes[0].open_with_cache(PASSWORD, &mut cache).unwrap();
+        assert!(cache.is_empty());
+    }
+}
@@ -16,9 +16,15 @@
 
 use constant_time_eq::constant_time_eq;
 
-use core::marker::PhantomData;
+use core::{any::TypeId, marker::PhantomData};
 
-use crate::{alloc::Vec, Cipher, CipherOutput, MacMismatch};
+#[cfg(feature = "tracing")]
+use crate::alloc::{format, Cow};
+use crate::{
+    alloc::{vec, Arc, Vec},
+    traits::{CipherObject, ObjectSafeCipher},
+    Cipher, CipherOutput, CipherOutputRef, MacMismatch, SecretVec,
+};
 
 /// Symmetric cipher without built-in authentication.
 pub trait UnauthenticatedCipher: 'static {
@@ -51,23 +57,146 @@ pub trait Mac: 'static {
     ///
     /// When used within [`PwBox`](crate::PwBox), `key` is guaranteed to have the correct size.
     fn digest(key: &[u8], message: &[u8]) -> Vec<u8>;
+
+    /// Verifies that `mac` is a valid MAC of the message under the specified key.
+    ///
+    /// The default implementation recomputes the MAC with [`Self::digest()`] and compares it
+    /// with `mac` in constant time. Implementations may override this method, e.g., to use
+    /// verification provided by the underlying crypto library; the comparison **must**
+    /// remain constant-time in this case.
+    ///
+    /// # Safety
+    ///
+    /// When used within [`PwBox`](crate::PwBox), `key` is guaranteed to have the correct size.
+    fn verify(key: &[u8], message: &[u8], mac: &[u8]) -> Result<(), MacMismatch> {
+        check_mac(&Self::digest(key, message), mac)
+    }
+}
+
+/// Partitioning of the [`CipherWithMac`] key into the cipher key and the MAC key.
+///
+/// The key passed to `CipherWithMac` (e.g., derived from the password by a KDF)
+/// has `cipher_key_len + mac_key_len` bytes. A split produces the keys for the cipher
+/// and the MAC from it; the keys may be post-processed (e.g., hashed) as long as
+/// they have the expected sizes.
+pub trait KeySplit: 'static {
+    /// Splits the `key` into the cipher key and the MAC key.
+    ///
+    /// The returned keys **must** have `cipher_key_len` and `mac_key_len` bytes,
+    /// respectively.
+    ///
+    /// # Safety
+    ///
+    /// When used within [`PwBox`](crate::PwBox), `key` is guaranteed to have
+    /// `cipher_key_len + mac_key_len` bytes.
+    fn split_key(key: &[u8], cipher_key_len: usize, mac_key_len: usize) -> (SecretVec, SecretVec);
+}
+
+/// Default [`KeySplit`]: the cipher key is the first `cipher_key_len` bytes of the key,
+/// and the MAC key is the remaining bytes.
+#[derive(Debug)]
+pub struct ConcatSplit(());
+
+impl KeySplit for ConcatSplit {
+    fn split_key(key: &[u8], cipher_key_len: usize, mac_key_len: usize) -> (SecretVec, SecretVec) {
+        debug_assert_eq!(key.len(), cipher_key_len + mac_key_len);
+        let (cipher_key, mac_key) = key.split_at(cipher_key_len);
+        (
+            SecretVec::from(cipher_key.to_vec()),
+            SecretVec::from(mac_key.to_vec()),
+        )
+    }
 }
 
+/// Key split used in Ethereum keystores ([Web3 Secret Storage]).
+///
+/// The keystore spec uses the first 16 bytes of the derived key as the AES-128 key,
+/// and computes the MAC as `keccak256(derived_key[16..32] || ciphertext)`. That is,
+/// the second half of the derived key is not transformed; it is prepended to the ciphertext
+/// by the [`Keccak256`] MAC. Thus, the split coincides with [`ConcatSplit`].
+///
+/// [Web3 Secret Storage]: https://github.com/ethereum/wiki/wiki/Web3-Secret-Storage-Definition
+/// [`Keccak256`]: crate::rcrypto::Keccak256
+pub type EthereumSplit = ConcatSplit;
+
 /// Authenticated cipher constructed from an ordinary symmetric cipher and a MAC construction.
 ///
-/// See [`Cipher` implementation] for details how this implementation works.
+/// The key is split into the cipher key and the MAC key according to `S`; by default,
+/// the cipher key precedes the MAC key. See [`Cipher` implementation] for details
+/// how this implementation works.
+///
+/// As in Ethereum keystores, the MAC does not cover the nonce. Hence, a modified nonce
+/// is not detected when opening a box; the box opens to a garbled plaintext instead.
 ///
 /// [`Cipher` implementation]: #impl-Cipher
 #[derive(Debug)]
-pub struct CipherWithMac<C, M> {
+pub struct CipherWithMac<C, M, S = ConcatSplit> {
     _cipher: PhantomData<C>,
     _mac: PhantomData<M>,
+    _split: PhantomData<S>,
+}
+
+/// Passes the message authenticated by the MAC over the ciphertext and associated data
+/// to `action`. To retain compatibility with boxes created without associated data,
+/// empty associated data is not digested.
+fn with_mac_input<T>(
+    ciphertext: &[u8],
+    associated_data: &[u8],
+    action: impl FnOnce(&[u8]) -> T,
+) -> T {
+    if associated_data.is_empty() {
+        return action(ciphertext);
+    }
+
+    let mut message = Vec::with_capacity(associated_data.len() + ciphertext.len() + 8);
+    message.extend_from_slice(associated_data);
+    message.extend_from_slice(ciphertext);
+    message.extend_from_slice(&(associated_data.len() as u64).to_le_bytes());
+    action(&message)
+}
+
+/// Compares the MACs in constant time.
+fn check_mac(expected: &[u8], actual: &[u8]) -> Result<(), MacMismatch> {
+    if constant_time_eq(expected, actual) {
+        Ok(())
+    } else {
+        Err(MacMismatch)
+    }
+}
+
+impl<C, M: Mac, S> CipherWithMac<C, M, S> {
+    fn digest(mac_key: &[u8], ciphertext: &[u8], associated_data: &[u8]) -> Vec<u8> {
+        with_mac_input(ciphertext, associated_data, |message| {
+            M::digest(mac_key, message)
+        })
+    }
+}
+
+impl<C: UnauthenticatedCipher, M: Mac, S: KeySplit> CipherWithMac<C, M, S> {
+    fn split_key(key: &[u8]) -> (SecretVec, SecretVec) {
+        let (cipher_key, mac_key) = S::split_key(key, C::KEY_LEN, M::KEY_LEN);
+        debug_assert_eq!(cipher_key.len(), C::KEY_LEN);
+        debug_assert_eq!(mac_key.len(), M::KEY_LEN);
+        (cipher_key, mac_key)
+    }
+
+    fn verify_with_mac_key(
+        ciphertext: &[u8],
+        mac: &[u8],
+        associated_data: &[u8],
+        mac_key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        with_mac_input(ciphertext, associated_data, |message| {
+            M::verify(mac_key, message, mac)
+        })
+    }
 }
 
-impl<C, M> Cipher for CipherWithMac<C, M>
+impl<C, M, S> Cipher for CipherWithMac<C, M, S>
 where
     C: UnauthenticatedCipher,
     M: Mac,
+    S: KeySplit,
 {
     /// Equals to the sum of key sizes for the cipher and MAC.
     const KEY_LEN: usize = C::KEY_LEN + M::KEY_LEN;
@@ -77,31 +206,41 @@ where
 
     /// Works as follows:
     ///
-    /// 1. Split the key into `cipher_key` (first bytes of the key) and `mac_key`
-    ///   (remaining bytes).
+    /// 1. Split the key into `cipher_key` and `mac_key` according to `S` (by default,
+    ///   `cipher_key` is the first bytes of the key, and `mac_key` is the remaining bytes).
     /// 2. Encrypt the `message` using the cipher under `cipher_key` and `nonce`.
-    /// 3. Compute MAC over the ciphertext with `mac_key`.
-    fn seal(message: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
-        let (cipher_key, mac_key) = (&key[..C::KEY_LEN], &key[C::KEY_LEN..]);
-        let mut ciphertext = message.to_vec();
-        C::seal_or_open(&mut ciphertext, nonce, cipher_key);
-
-        CipherOutput {
-            mac: M::digest(mac_key, &ciphertext),
-            ciphertext,
-        }
+    /// 3. Compute MAC over the ciphertext with `mac_key`. If `associated_data` is non-empty,
+    ///   the MAC is computed over `associated_data || ciphertext || len`, where `len`
+    ///   is the byte length of `associated_data` encoded as a little-endian `u64`.
+    fn seal(message: &[u8], associated_data: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
+        CipherOutput::seal_with::<Self>(message, associated_data, nonce, key)
+    }
+
+    /// Works the same as [`Self::seal()`], encrypting the message in `out.ciphertext`.
+    fn seal_into(
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        out: &mut CipherOutputRef<'_>,
+    ) {
+        let (cipher_key, mac_key) = Self::split_key(key);
+        out.ciphertext.copy_from_slice(message);
+        C::seal_or_open(out.ciphertext, nonce, &cipher_key);
+        let mac = Self::digest(&mac_key, out.ciphertext, associated_data);
+        out.mac.copy_from_slice(&mac);
     }
 
     /// Works as follows:
     ///
-    /// 1. Split the key into `cipher_key` (first bytes of the key) and `mac_key`
-    ///   (remaining bytes).
-    /// 2. Compute MAC over the ciphertext with `mac_key`. If MAC is not equal to
-    ///   the supplied one, return `None`.
+    /// 1. Split the key into `cipher_key` and `mac_key` as in [`Self::seal()`].
+    /// 2. Compute MAC over the ciphertext (and `associated_data`, if it is non-empty)
+    ///   with `mac_key`. If MAC is not equal to the supplied one, return an error.
     /// 3. Decrypt the ciphertext under the `cipher_key` and `nonce`.
     fn open(
         output: &mut [u8],
         enc: &CipherOutput,
+        associated_data: &[u8],
         nonce: &[u8],
         key: &[u8],
     ) -> Result<(), MacMismatch> {
@@ -109,13 +248,323 @@ where
         debug_assert_eq!(enc.mac.len(), Self::MAC_LEN);
         debug_assert_eq!(output.len(), enc.ciphertext.len());
 
-        let (cipher_key, mac_key) = (&key[..C::KEY_LEN], &key[C::KEY_LEN..]);
-        if !constant_time_eq(&M::digest(mac_key, &enc.ciphertext), &enc.mac) {
-            return Err(MacMismatch);
+        output.copy_from_slice(&enc.ciphertext);
+        Self::open_in_place(output, &enc.mac, associated_data, nonce, key)
+    }
+
+    /// Works the same as [`Self::open()`], decrypting the ciphertext in `buffer`.
+    fn open_in_place(
+        buffer: &mut [u8],
+        mac: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        let (cipher_key, mac_key) = Self::split_key(key);
+        Self::verify_with_mac_key(buffer, mac, associated_data, &mac_key)?;
+        C::seal_or_open(buffer, nonce, &cipher_key);
+        Ok(())
+    }
+
+    /// Computes MAC over the ciphertext as in [`Self::open()`], without decrypting it.
+    /// MACs are compared in constant time.
+    fn verify(
+        enc: &CipherOutput,
+        associated_data: &[u8],
+        _nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        let (_, mac_key) = Self::split_key(key);
+        Self::verify_with_mac_key(&enc.ciphertext, &enc.mac, associated_data, &mac_key)
+    }
+
+    /// Returns the cipher and MAC types if the key split is [`ConcatSplit`]. Only such
+    /// compositions can be restored from separately registered halves.
+    fn mac_composition() -> Option<(TypeId, TypeId)> {
+        if TypeId::of::<S>() == TypeId::of::<ConcatSplit>() {
+            Some((TypeId::of::<C>(), TypeId::of::<M>()))
+        } else {
+            None
         }
+    }
+}
 
-        output.copy_from_slice(&enc.ciphertext);
-        C::seal_or_open(output, nonce, cipher_key);
+/// Object-safe version of [`UnauthenticatedCipher`].
+pub(crate) trait ObjectSafeUnauthenticatedCipher: 'static + Send + Sync {
+    fn key_len(&self) -> usize;
+    fn nonce_len(&self) -> usize;
+    #[cfg(feature = "tracing")]
+    fn name(&self) -> &'static str;
+    fn seal_or_open(&self, message: &mut [u8], nonce: &[u8], key: &[u8]);
+}
+
+impl<T: UnauthenticatedCipher> ObjectSafeUnauthenticatedCipher for CipherObject<T> {
+    fn key_len(&self) -> usize {
+        T::KEY_LEN
+    }
+
+    fn nonce_len(&self) -> usize {
+        T::NONCE_LEN
+    }
+
+    #[cfg(feature = "tracing")]
+    fn name(&self) -> &'static str {
+        core::any::type_name::<T>()
+    }
+
+    fn seal_or_open(&self, message: &mut [u8], nonce: &[u8], key: &[u8]) {
+        T::seal_or_open(message, nonce, key);
+    }
+}
+
+/// Object-safe version of [`Mac`].
+pub(crate) trait ObjectSafeMac: 'static + Send + Sync {
+    fn key_len(&self) -> usize;
+    fn mac_len(&self) -> usize;
+    #[cfg(feature = "tracing")]
+    fn name(&self) -> &'static str;
+    fn digest(&self, key: &[u8], message: &[u8]) -> Vec<u8>;
+    fn verify(&self, key: &[u8], message: &[u8], mac: &[u8]) -> Result<(), MacMismatch>;
+}
+
+impl<T: Mac> ObjectSafeMac for CipherObject<T> {
+    fn key_len(&self) -> usize {
+        T::KEY_LEN
+    }
+
+    fn mac_len(&self) -> usize {
+        T::MAC_LEN
+    }
+
+    #[cfg(feature = "tracing")]
+    fn name(&self) -> &'static str {
+        core::any::type_name::<T>()
+    }
+
+    fn digest(&self, key: &[u8], message: &[u8]) -> Vec<u8> {
+        T::digest(key, message)
+    }
+
+    fn verify(&self, key: &[u8], message: &[u8], mac: &[u8]) -> Result<(), MacMismatch> {
+        T::verify(key, message, mac)
+    }
+}
+
+/// [`CipherWithMac`] with the [`ConcatSplit`] composed from its halves at runtime.
+/// This is used by [`Eraser`](crate::Eraser) to restore boxes sealed with compositions
+/// of separately registered ciphers and MACs.
+#[derive(Clone)]
+pub(crate) struct DynCipherWithMac {
+    cipher: Arc<dyn ObjectSafeUnauthenticatedCipher>,
+    mac: Arc<dyn ObjectSafeMac>,
+}
+
+impl DynCipherWithMac {
+    pub fn new(
+        cipher: Arc<dyn ObjectSafeUnauthenticatedCipher>,
+        mac: Arc<dyn ObjectSafeMac>,
+    ) -> Self {
+        DynCipherWithMac { cipher, mac }
+    }
+
+    fn split_key(&self, key: &[u8]) -> (SecretVec, SecretVec) {
+        ConcatSplit::split_key(key, self.cipher.key_len(), self.mac.key_len())
+    }
+
+    fn digest(&self, mac_key: &[u8], ciphertext: &[u8], associated_data: &[u8]) -> Vec<u8> {
+        with_mac_input(ciphertext, associated_data, |message| {
+            self.mac.digest(mac_key, message)
+        })
+    }
+
+    fn verify_mac(
+        &self,
+        mac_key: &[u8],
+        ciphertext: &[u8],
+        mac: &[u8],
+        associated_data: &[u8],
+    ) -> Result<(), MacMismatch> {
+        with_mac_input(ciphertext, associated_data, |message| {
+            self.mac.verify(mac_key, message, mac)
+        })
+    }
+}
+
+impl ObjectSafeCipher for DynCipherWithMac {
+    fn key_len(&self) -> usize {
+        self.cipher.key_len() + self.mac.key_len()
+    }
+
+    fn nonce_len(&self) -> usize {
+        self.cipher.nonce_len()
+    }
+
+    fn mac_len(&self) -> usize {
+        self.mac.mac_len()
+    }
+
+    fn supports_associated_data(&self) -> bool {
+        true
+    }
+
+    /// Returns the name in the `{cipher}+{mac}` format, similar to the one used by `Eraser`.
+    #[cfg(feature = "tracing")]
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Owned(format!("{}+{}", self.cipher.name(), self.mac.name()))
+    }
+
+    fn seal(
+        &self,
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> CipherOutput {
+        let mut output = CipherOutput {
+            ciphertext: vec![0; message.len()],
+            mac: vec![0; self.mac_len()],
+        };
+        let mut out = output.as_mut_ref();
+        self.seal_into(message, associated_data, nonce, key, &mut out);
+        output
+    }
+
+    fn seal_into(
+        &self,
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        out: &mut CipherOutputRef<'_>,
+    ) {
+        let (cipher_key, mac_key) = self.split_key(key);
+        out.ciphertext.copy_from_slice(message);
+        self.cipher.seal_or_open(out.ciphertext, nonce, &cipher_key);
+        let mac = self.digest(&mac_key, out.ciphertext, associated_data);
+        out.mac.copy_from_slice(&mac);
+    }
+
+    fn open(
+        &self,
+        output: &mut [u8],
+        encrypted: &CipherOutput,
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        output.copy_from_slice(&encrypted.ciphertext);
+        self.open_in_place(output, &encrypted.mac, associated_data, nonce, key)
+    }
+
+    fn open_in_place(
+        &self,
+        buffer: &mut [u8],
+        mac: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        let (cipher_key, mac_key) = self.split_key(key);
+        trace_cipher!(
+            cipher = self.name(),
+            len = buffer.len(),
+            self.verify_mac(&mac_key, buffer, mac, associated_data)
+        )?;
+        self.cipher.seal_or_open(buffer, nonce, &cipher_key);
         Ok(())
     }
+
+    fn verify(
+        &self,
+        encrypted: &CipherOutput,
+        associated_data: &[u8],
+        _nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        let (_, mac_key) = self.split_key(key);
+        trace_cipher!(
+            cipher = self.name(),
+            len = encrypted.ciphertext.len(),
+            self.verify_mac(
+                &mac_key,
+                &encrypted.ciphertext,
+                &encrypted.mac,
+                associated_data
+            )
+        )
+    }
+}
+
+/// Checks that a [`CipherWithMac`] built from the specified primitives and key split
+/// is sound: messages of various sizes roundtrip (with and without associated data),
+/// and opening fails with [`MacMismatch`] if the key, ciphertext, MAC or associated data
+/// are changed.
+///
+/// This function is intended for crates implementing their own [`UnauthenticatedCipher`],
+/// [`Mac`] or [`KeySplit`], and is only available with the `testing` crate feature.
+///
+/// # Panics
+///
+/// Panics if any of the checks fails.
+#[cfg(any(test, feature = "testing"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
+pub fn test_cipher_with_mac<C, M, S>(rng: &mut impl rand_core::RngCore)
+where
+    C: UnauthenticatedCipher,
+    M: Mac,
+    S: KeySplit,
+{
+    use crate::alloc::vec;
+
+    type Ci<C, M, S> = CipherWithMac<C, M, S>;
+
+    fn random_bytes(rng: &mut impl rand_core::RngCore, len: usize) -> Vec<u8> {
+        let mut bytes = vec![0_u8; len];
+        rng.fill_bytes(&mut bytes);
+        bytes
+    }
+
+    let key = random_bytes(rng, Ci::<C, M, S>::KEY_LEN);
+    let (cipher_key, mac_key) = S::split_key(&key, C::KEY_LEN, M::KEY_LEN);
+    assert_eq!(cipher_key.len(), C::KEY_LEN, "invalid cipher key length");
+    assert_eq!(mac_key.len(), M::KEY_LEN, "invalid MAC key length");
+    let digest = M::digest(&mac_key, b"message");
+    assert_eq!(digest.len(), M::MAC_LEN, "invalid MAC length");
+    assert_eq!(
+        digest,
+        M::digest(&mac_key, b"message"),
+        "MAC is not deterministic"
+    );
+
+    let other_key = random_bytes(rng, Ci::<C, M, S>::KEY_LEN);
+    for &len in &[0, 1, 15, 16, 17, 64, 1_000] {
+        let message = random_bytes(rng, len);
+        let nonce = random_bytes(rng, C::NONCE_LEN);
+        for &associated_data in &[&b""[..], b"associated data"] {
+            let mut enc = Ci::<C, M, S>::seal(&message, associated_data, &nonce, &key);
+            assert_eq!(enc.ciphertext.len(), len);
+            assert_eq!(enc.mac.len(), M::MAC_LEN);
+            if len >= 16 {
+                assert_ne!(enc.ciphertext, message, "message is not encrypted");
+            }
+
+            let mut output = vec![0_u8; len];
+            Ci::<C, M, S>::open(&mut output, &enc, associated_data, &nonce, &key).unwrap();
+            assert_eq!(output, message);
+
+            let open = |enc: &CipherOutput, associated_data: &[u8], key: &[u8]| {
+                let mut output = vec![0_u8; len];
+                Ci::<C, M, S>::open(&mut output, enc, associated_data, &nonce, key)
+            };
+            assert!(open(&enc, associated_data, &other_key).is_err());
+            assert!(open(&enc, b"other data", &key).is_err());
+            enc.mac[0] ^= 1;
+            assert!(open(&enc, associated_data, &key).is_err());
+            enc.mac[0] ^= 1;
+            if len > 0 {
+                enc.ciphertext[len / 2] ^= 1;
+                assert!(open(&enc, associated_data, &key).is_err());
+            }
+        }
+    }
 }
@@ -0,0 +1,215 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers for command-line tools: reading passwords from the terminal, and sealing
+//! and opening files.
+//!
+//! Sealed files are stored as JSON, in the same way as in the [`fs`](crate::fs) module.
+//! Outputs are written atomically via a temporary file in the same directory, which is removed
+//! on error; thus, failed operations never leave partially written files (in particular,
+//! plaintext ones) behind. Existing outputs are only overwritten if the `force` flag is set.
+//!
+//! # Examples
+//!
+//! ```
+//! use pwbox::{cli, FastInsecureSuite, SensitiveData};
+//! # fn main() -> anyhow::Result<()> {
+//! # let dir = std::env::temp_dir().join(format!("pwbox-cli-doc-{}", std::process::id()));
+//! # std::fs::create_dir_all(&dir)?;
+//! # std::fs::write(dir.join("secret.txt"), b"battery staple")?;
+//!
+//! // let password = cli::read_password_from_tty("Password: ")?;
+//! let password = SensitiveData::from(b"correct horse".to_vec());
+//! let (input, sealed) = (dir.join("secret.txt"), dir.join("secret.json"));
+//! cli::seal_file::<FastInsecureSuite>(&input, &sealed, &password, false)?;
+//! // The plaintext file exists, so it is not overwritten without `force`.
+//! assert!(cli::open_file::<FastInsecureSuite>(&sealed, &input, &password, false).is_err());
+//! cli::open_file::<FastInsecureSuite>(&sealed, &input, &password, true)?;
+//! assert_eq!(std::fs::read(&input)?, b"battery staple");
+//! # std::fs::remove_dir_all(&dir)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use rand_core::OsRng;
+use serde::Serialize;
+
+use std::{fs, path::Path};
+
+use crate::{fs::write_atomically, ErasedPwBox, Error, SensitiveData, Suite};
+
+/// Prompts for a password on the terminal without echoing the input.
+///
+/// # Errors
+///
+/// Returns [`Error::Io`] if the terminal cannot be read.
+pub fn read_password_from_tty(prompt: &str) -> Result<SensitiveData, Error> {
+    let password = rpassword::read_password_from_tty(Some(prompt)).map_err(Error::Io)?;
+    Ok(SensitiveData::from_vec(password.into_bytes()))
+}
+
+/// Seals the contents of the `input` file with the recommended cipher and KDF of the suite `S`
+/// and writes the sealed box as JSON to the `output` file.
+///
+/// # Errors
+///
+/// - [`Error::Io`] if the `input` cannot be read or the `output` cannot be written.
+///   If the `output` exists and `force` is not set, the error kind
+///   is [`AlreadyExists`](std::io::ErrorKind::AlreadyExists).
+/// - Errors returned by [`Suite::seal()`]
+pub fn seal_file<S>(
+    input: &Path,
+    output: &Path,
+    password: &SensitiveData,
+    force: bool,
+) -> Result<(), Error>
+where
+    S: Suite,
+    S::DeriveKey: Serialize,
+{
+    let message = SensitiveData::from_vec(fs::read(input).map_err(Error::Io)?);
+    let erased = S::seal(&mut OsRng, password, message)?;
+    let bytes = serde_json::to_vec(&erased).map_err(Error::Serialization)?;
+    write_atomically(output, &bytes, force).map_err(Error::Io)
+}
+
+/// Opens the box stored as JSON in the `input` file with the ciphers and KDFs
+/// of the suite `S`, and writes the plaintext to the `output` file. On Unix, the `output`
+/// is created with `0o600` permissions.
+///
+/// # Errors
+///
+/// - [`Error::Io`] if the `input` cannot be read or the `output` cannot be written.
+///   If the `output` exists and `force` is not set, the error kind
+///   is [`AlreadyExists`](std::io::ErrorKind::AlreadyExists).
+/// - [`Error::Serialization`] if the `input` is not a valid JSON box
+/// - Errors returned by [`Suite::open()`], e.g., if the password is incorrect
+pub fn open_file<S: Suite>(
+    input: &Path,
+    output: &Path,
+    password: &SensitiveData,
+    force: bool,
+) -> Result<(), Error> {
+    let bytes = fs::read(input).map_err(Error::Io)?;
+    let erased: ErasedPwBox = serde_json::from_slice(&bytes).map_err(Error::Serialization)?;
+    let plaintext = S::open(&erased, password)?;
+    write_atomically(output, &plaintext, force).map_err(Error::Io)
+}
+
+#[cfg(all(test, feature = "exonum_sodiumoxide"))]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use std::{io, path::PathBuf, process};
+
+    use super::*;
+    use crate::FastInsecureSuite;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pwbox-cli-{}-{}", process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn password(password: &str) -> SensitiveData {
+        SensitiveData::from_vec(password.as_bytes().to_vec())
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn sealing_and_opening_files() {
+        let dir = test_dir("roundtrip");
+        let (input, sealed, output) = (
+            dir.join("input.txt"),
+            dir.join("sealed.json"),
+            dir.join("output.txt"),
+        );
+        fs::write(&input, b"battery staple").unwrap();
+
+        let password = password("correct horse");
+        seal_file::<FastInsecureSuite>(&input, &sealed, &password, false).unwrap();
+        let erased: ErasedPwBox = serde_json::from_slice(&fs::read(&sealed).unwrap()).unwrap();
+        assert_eq!(erased.len(), 14);
+        open_file::<FastInsecureSuite>(&sealed, &output, &password, false).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), b"battery staple");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&output).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        assert_eq!(file_names(&dir), ["input.txt", "output.txt", "sealed.json"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn existing_outputs_are_only_overwritten_with_force() {
+        let dir = test_dir("force");
+        let (input, sealed) = (dir.join("input.txt"), dir.join("sealed.json"));
+        fs::write(&input, b"battery staple").unwrap();
+        fs::write(&sealed, b"existing").unwrap();
+
+        let password = password("correct horse");
+        let err = seal_file::<FastInsecureSuite>(&input, &sealed, &password, false).unwrap_err();
+        assert_matches!(err, Error::Io(ref e) if e.kind() == io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&sealed).unwrap(), b"existing");
+        seal_file::<FastInsecureSuite>(&input, &sealed, &password, true).unwrap();
+
+        fs::write(&input, b"existing").unwrap();
+        let err = open_file::<FastInsecureSuite>(&sealed, &input, &password, false).unwrap_err();
+        assert_matches!(err, Error::Io(ref e) if e.kind() == io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&input).unwrap(), b"existing");
+        open_file::<FastInsecureSuite>(&sealed, &input, &password, true).unwrap();
+        assert_eq!(fs::read(&input).unwrap(), b"battery staple");
+
+        // No temporary files should be left in the directory.
+        assert_eq!(file_names(&dir), ["input.txt", "sealed.json"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn opening_file_errors() {
+        let dir = test_dir("errors");
+        let (input, sealed, output) = (
+            dir.join("input.txt"),
+            dir.join("sealed.json"),
+            dir.join("output.txt"),
+        );
+
+        let password = password("correct horse");
+        let err = seal_file::<FastInsecureSuite>(&input, &sealed, &password, false).unwrap_err();
+        assert_matches!(err, Error::Io(ref e) if e.kind() == io::ErrorKind::NotFound);
+
+        fs::write(&input, b"battery staple").unwrap();
+        seal_file::<FastInsecureSuite>(&input, &sealed, &password, false).unwrap();
+        let wrong_password = self::password("incorrect horse");
+        let err =
+            open_file::<FastInsecureSuite>(&sealed, &output, &wrong_password, false).unwrap_err();
+        assert_matches!(err, Error::MacMismatch);
+
+        let err = open_file::<FastInsecureSuite>(&input, &output, &password, false).unwrap_err();
+        assert_matches!(err, Error::Serialization(_));
+        assert_eq!(file_names(&dir), ["input.txt", "sealed.json"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
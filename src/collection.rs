@@ -0,0 +1,416 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Collections of named boxes.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    alloc::{BTreeMap, String, ToOwned, Vec},
+    Cipher, DeriveKey, EraseError, ErasedPwBox, Eraser, Error, PwBox, PwBoxBuilder, RestoredPwBox,
+    SensitiveData,
+};
+
+/// Collection of named boxes, e.g., several secrets of an application stored
+/// in a single file.
+///
+/// A collection maps names to [`ErasedPwBox`]es. It is serialized as a map with entry names
+/// as keys and serialized boxes as values. Boxes in the collection are independent;
+/// they may be sealed with different passwords, KDFs and ciphers.
+///
+/// # Shared salt
+///
+/// Sealing several boxes with [`Self::seal()`] may use the shared salt optimization.
+/// In this case, all boxes sealed in one call have the same salt and KDF params, and the key
+/// is derived from the password only once; each box is encrypted with a distinct random nonce.
+/// As the salt and KDF params are recorded in each box, the boxes are restored in the same way
+/// as ordinary ones. To avoid deriving the key for each box when opening them,
+/// use [`RestoredPwBox::open_with_cache()`].
+///
+/// # Examples
+///
+/// ```
+/// # use rand::thread_rng;
+/// # use pwbox::{sodium::{Scrypt, Sodium}, Eraser, PwBoxCollection, Suite};
+/// let mut eraser = Eraser::new();
+/// eraser.add_suite::<Sodium>().unwrap();
+/// let mut rng = thread_rng();
+/// let mut builder = Sodium::build_box(&mut rng);
+/// # builder.kdf(Scrypt::light());
+///
+/// let mut collection = PwBoxCollection::new();
+/// let entries = vec![("seed", [1_u8; 32]), ("signing_key", [2; 32])];
+/// collection.seal(&eraser, &mut builder, "correct horse", entries, true)?;
+/// let json = serde_json::to_string(&collection)?;
+///
+/// let collection: PwBoxCollection = serde_json::from_str(&json)?;
+/// assert_eq!(collection.iter_names().collect::<Vec<_>>(), ["seed", "signing_key"]);
+/// let seed = collection.open("seed", "correct horse", &eraser)?;
+/// assert_eq!(&*seed, &[1; 32]);
+/// # Ok::<_, anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PwBoxCollection {
+    entries: BTreeMap<String, ErasedPwBox>,
+}
+
+impl PwBoxCollection {
+    /// Creates an empty collection.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of entries in the collection.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Checks whether the collection is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterates over entry names in the lexicographic order.
+    pub fn iter_names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// Checks whether the collection contains an entry with the specified name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    /// Returns the box with the specified name.
+    pub fn get(&self, name: &str) -> Option<&ErasedPwBox> {
+        self.entries.get(name)
+    }
+
+    /// Inserts a `pwbox` into the collection, converting it into serializable form
+    /// with the `eraser`. If an entry with the same name is present in the collection,
+    /// it is replaced; the replaced box is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the box cannot be erased (see [`Eraser::erase()`]).
+    /// In this case, the collection is not changed.
+    pub fn insert<K, C>(
+        &mut self,
+        name: impl Into<String>,
+        pwbox: &PwBox<K, C>,
+        eraser: &Eraser,
+    ) -> Result<Option<ErasedPwBox>, EraseError>
+    where
+        K: DeriveKey + Serialize,
+        C: Cipher,
+    {
+        eraser
+            .erase(pwbox)
+            .map(|pwbox| self.insert_erased(name, pwbox))
+    }
+
+    /// Inserts a box in serializable form into the collection. If an entry with the same name
+    /// is present in the collection, it is replaced; the replaced box is returned.
+    pub fn insert_erased(
+        &mut self,
+        name: impl Into<String>,
+        pwbox: ErasedPwBox,
+    ) -> Option<ErasedPwBox> {
+        self.entries.insert(name.into(), pwbox)
+    }
+
+    /// Seals boxes with the specified `(name, message)` entries and inserts them
+    /// into the collection, replacing entries with the same names. All boxes are sealed
+    /// with the same `password` using the `builder` and are converted into serializable form
+    /// with the `eraser` (see [`Eraser::seal()`]).
+    ///
+    /// If `shared_salt` is set, the boxes share the salt, so the key is derived only once.
+    /// See [the type-level docs](#shared-salt) for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if sealing any of the boxes fails. In this case, the collection
+    /// is not changed.
+    pub fn seal<K, C, N, M>(
+        &mut self,
+        eraser: &Eraser,
+        builder: &mut PwBoxBuilder<'_, K, C>,
+        password: impl AsRef<[u8]>,
+        entries: impl IntoIterator<Item = (N, M)>,
+        shared_salt: bool,
+    ) -> Result<(), Error>
+    where
+        K: DeriveKey + Clone + Default + Serialize,
+        C: Cipher,
+        N: Into<String>,
+        M: AsRef<[u8]>,
+    {
+        let password = password.as_ref();
+        let (names, messages): (Vec<String>, Vec<M>) = entries
+            .into_iter()
+            .map(|(name, message)| (name.into(), message))
+            .unzip();
+
+        let boxes = if shared_salt {
+            let messages: Vec<&[u8]> = messages.iter().map(AsRef::as_ref).collect();
+            eraser.seal_shared(builder, password, &messages)?
+        } else {
+            messages
+                .iter()
+                .map(|message| eraser.seal(builder, password, message))
+                .collect::<Result<_, _>>()?
+        };
+        self.entries.extend(names.into_iter().zip(boxes));
+        Ok(())
+    }
+
+    /// Restores the box with the specified name using the `eraser`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoEntry`] if the collection does not contain the entry,
+    /// or an error returned by [`Eraser::restore()`].
+    pub fn restore(&self, name: &str, eraser: &Eraser) -> Result<RestoredPwBox, Error> {
+        let pwbox = self
+            .entries
+            .get(name)
+            .ok_or_else(|| Error::NoEntry(name.to_owned()))?;
+        eraser.restore(pwbox)
+    }
+
+    /// Restores and opens the box with the specified name.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoEntry`] if the collection does not contain the entry, or an error
+    /// returned by [`Eraser::restore()`] or [`RestoredPwBox::open()`].
+    pub fn open(
+        &self,
+        name: &str,
+        password: impl AsRef<[u8]>,
+        eraser: &Eraser,
+    ) -> Result<SensitiveData, Error> {
+        self.restore(name, eraser)?.open(password)
+    }
+
+    /// Renames an entry. Boxes do not depend on their names, so the box is not re-encrypted.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoEntry`] if the collection does not contain an entry named `from`,
+    /// or [`Error::DuplicateName`] if it already contains an entry named `to`.
+    pub fn rename(&mut self, from: &str, to: impl Into<String>) -> Result<(), Error> {
+        let to = to.into();
+        if from != to && self.entries.contains_key(&to) {
+            return Err(Error::DuplicateName(to));
+        }
+        let pwbox = self
+            .entries
+            .remove(from)
+            .ok_or_else(|| Error::NoEntry(from.to_owned()))?;
+        self.entries.insert(to, pwbox);
+        Ok(())
+    }
+
+    /// Removes an entry from the collection and returns the removed box.
+    pub fn remove(&mut self, name: &str) -> Option<ErasedPwBox> {
+        self.entries.remove(name)
+    }
+}
+
+#[cfg(all(test, feature = "exonum_sodiumoxide"))]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    use super::*;
+    use crate::{
+        cache::KeyCache,
+        sodium::{Scrypt, Sodium, XSalsa20Poly1305},
+        FormatVersion, Suite,
+    };
+
+    const PASSWORD: &str = "correct horse battery staple";
+    const BACKUP_PASSWORD: &str = "Tr0ub4dor&3";
+
+    fn eraser() -> Eraser {
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        eraser
+    }
+
+    fn three_entry_collection(eraser: &Eraser) -> PwBoxCollection {
+        let mut rng = thread_rng();
+        let mut collection = PwBoxCollection::new();
+        let mut builder = Sodium::build_box(&mut rng);
+        builder.kdf(Scrypt::light());
+        let entries = vec![("seed", vec![1_u8; 32]), ("signing_key", vec![2; 64])];
+        collection
+            .seal(eraser, &mut builder, PASSWORD, entries, true)
+            .unwrap();
+
+        let backup = Sodium::build_box(&mut rng)
+            .cipher::<XSalsa20Poly1305>()
+            .kdf(Scrypt::light())
+            .seal(BACKUP_PASSWORD, [3; 32])
+            .unwrap();
+        assert!(collection
+            .insert("backup_key", &backup, eraser)
+            .unwrap()
+            .is_none());
+        collection
+    }
+
+    fn assert_entries(collection: &PwBoxCollection, eraser: &Eraser) {
+        let seed = collection.open("seed", PASSWORD, eraser).unwrap();
+        assert_eq!(&*seed, &[1; 32]);
+        let signing_key = collection.open("signing_key", PASSWORD, eraser).unwrap();
+        assert_eq!(&*signing_key, &[2; 64][..]);
+        let backup_key = collection
+            .open("backup_key", BACKUP_PASSWORD, eraser)
+            .unwrap();
+        assert_eq!(&*backup_key, &[3; 32]);
+    }
+
+    #[test]
+    fn collection_roundtrip() {
+        let eraser = eraser();
+        let collection = three_entry_collection(&eraser);
+        assert_eq!(collection.len(), 3);
+        assert_eq!(
+            collection.iter_names().collect::<Vec<_>>(),
+            ["backup_key", "seed", "signing_key"]
+        );
+        assert_entries(&collection, &eraser);
+
+        let json = serde_json::to_string_pretty(&collection).unwrap();
+        let restored: PwBoxCollection = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, collection);
+        assert_entries(&restored, &eraser);
+
+        let toml = toml::to_string(&collection).unwrap();
+        let restored: PwBoxCollection = toml::from_str(&toml).unwrap();
+        assert_eq!(restored, collection);
+        assert_entries(&restored, &eraser);
+    }
+
+    #[test]
+    fn shared_salt() {
+        let eraser = eraser();
+        let collection = three_entry_collection(&eraser);
+        let json = serde_json::to_value(&collection).unwrap();
+        let (seed, signing_key) = (&json["seed"], &json["signing_key"]);
+        assert_eq!(seed["kdfparams"], signing_key["kdfparams"]);
+        assert_ne!(seed["cipherparams"], signing_key["cipherparams"]);
+        assert_ne!(seed["kdfparams"], json["backup_key"]["kdfparams"]);
+
+        // The key is derived once when opening boxes with the shared salt.
+        let mut cache = KeyCache::new();
+        for name in &["seed", "signing_key"] {
+            let restored = collection.restore(name, &eraser).unwrap();
+            restored.open_with_cache(PASSWORD, &mut cache).unwrap();
+        }
+        assert_eq!(cache.len(), 1);
+
+        // Without the flag, each box has its own salt.
+        let mut rng = thread_rng();
+        let mut builder = Sodium::build_box(&mut rng);
+        builder.kdf(Scrypt::light());
+        let mut other_collection = PwBoxCollection::new();
+        let entries = vec![("first", b"first"), ("second", b"secon")];
+        other_collection
+            .seal(&eraser, &mut builder, PASSWORD, entries, false)
+            .unwrap();
+        let json = serde_json::to_value(&other_collection).unwrap();
+        assert_ne!(json["first"]["kdfparams"], json["second"]["kdfparams"]);
+        assert_eq!(
+            &*other_collection.open("second", PASSWORD, &eraser).unwrap(),
+            b"secon"
+        );
+    }
+
+    #[test]
+    fn shared_salt_in_v2_format() {
+        let mut eraser = eraser();
+        eraser.set_format_version(FormatVersion::V2);
+        let mut rng = thread_rng();
+        let mut builder = Sodium::build_box(&mut rng);
+        builder.kdf(Scrypt::light()).associated_data(b"context");
+        let mut collection = PwBoxCollection::new();
+        let entries = vec![("first", [1; 16]), ("second", [2; 16])];
+        collection
+            .seal(&eraser, &mut builder, PASSWORD, entries, true)
+            .unwrap();
+
+        for (name, expected) in &[("first", [1; 16]), ("second", [2; 16])] {
+            let version = collection.get(name).unwrap().format_version();
+            assert_eq!(version, FormatVersion::V2);
+            let restored = collection.restore(name, &eraser).unwrap();
+            assert_matches!(restored.open(PASSWORD).unwrap_err(), Error::MacMismatch);
+            let plaintext = restored.open_with_aad(PASSWORD, b"context").unwrap();
+            assert_eq!(&*plaintext, expected);
+        }
+
+        // The v2 format is not supported by `XSalsa20Poly1305`.
+        let mut builder = Sodium::build_box(&mut rng).cipher::<XSalsa20Poly1305>();
+        builder.kdf(Scrypt::light());
+        let entries = vec![("third", [3; 16])];
+        let err = collection
+            .seal(&eraser, &mut builder, PASSWORD, entries, true)
+            .unwrap_err();
+        assert!(err.to_string().contains("associated data"));
+        assert_eq!(collection.len(), 2);
+    }
+
+    #[test]
+    fn removing_and_renaming_entries() {
+        let eraser = eraser();
+        let mut collection = three_entry_collection(&eraser);
+        let signing_key = collection.get("signing_key").cloned().unwrap();
+        assert_eq!(collection.remove("signing_key"), Some(signing_key));
+        assert!(collection.remove("signing_key").is_none());
+        assert_eq!(collection.len(), 2);
+        assert_eq!(
+            &*collection.open("seed", PASSWORD, &eraser).unwrap(),
+            &[1; 32]
+        );
+        assert_matches!(
+            collection.open("signing_key", PASSWORD, &eraser).unwrap_err(),
+            Error::NoEntry(ref name) if name == "signing_key"
+        );
+
+        collection.rename("seed", "main_seed").unwrap();
+        assert!(!collection.contains("seed"));
+        let seed = collection.open("main_seed", PASSWORD, &eraser).unwrap();
+        assert_eq!(&*seed, &[1; 32]);
+        assert_matches!(
+            collection.rename("main_seed", "backup_key").unwrap_err(),
+            Error::DuplicateName(ref name) if name == "backup_key"
+        );
+        assert_matches!(
+            collection.rename("seed", "other").unwrap_err(),
+            Error::NoEntry(ref name) if name == "seed"
+        );
+        collection.rename("main_seed", "main_seed").unwrap();
+
+        let backup_key = collection
+            .open("backup_key", BACKUP_PASSWORD, &eraser)
+            .unwrap();
+        assert_eq!(&*backup_key, &[3; 32]);
+        assert_eq!(
+            collection.iter_names().collect::<Vec<_>>(),
+            ["backup_key", "main_seed"]
+        );
+    }
+}
@@ -0,0 +1,232 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Suites selected based on the enabled crypto backends.
+
+use rand_core::{CryptoRng, RngCore};
+
+#[cfg(all(
+    feature = "pure",
+    not(any(feature = "exonum_sodiumoxide", feature = "rust-crypto"))
+))]
+use crate::pure::{PureCrypto as Backend, Scrypt};
+#[cfg(all(feature = "rust-crypto", not(feature = "exonum_sodiumoxide")))]
+use crate::rcrypto::{RustCrypto as Backend, Scrypt};
+#[cfg(feature = "exonum_sodiumoxide")]
+use crate::sodium::{Scrypt, Sodium as Backend};
+use crate::{Eraser, Error, KdfDifficulty, KdfPreset, PwBoxBuilder, Suite};
+
+#[cfg(feature = "exonum_sodiumoxide")]
+fn light_kdf() -> Scrypt {
+    Scrypt::light()
+}
+
+#[cfg(not(feature = "exonum_sodiumoxide"))]
+fn light_kdf() -> Scrypt {
+    Scrypt(crate::ScryptParams::light())
+}
+
+/// Suite with the strongest primitives available with the enabled crate features.
+/// The suite is returned by [`recommended()`].
+///
+/// The primitives are taken from the following suite:
+///
+/// - `Sodium` if the `exonum_sodiumoxide` feature is enabled
+/// - `RustCrypto` if the `rust-crypto` feature is enabled, and `exonum_sodiumoxide` is not
+/// - `PureCrypto` otherwise
+///
+/// [`Suite::build_box()`] and [`Suite::seal()`] use the recommended cipher of the chosen suite
+/// and the `scrypt` KDF with [`KdfPreset::Sensitive`] params. The ciphers and KDFs added
+/// by [`Eraser::add_suite()`] are the same as for the chosen suite.
+///
+/// The cipher and KDF of the suite depend on crate features, so the code using the suite
+/// should not rely on the specific `Cipher` and `DeriveKey` types. Note that a box sealed
+/// with one backend can be restored with another one only if both backends support
+/// the cipher and KDF of the box.
+///
+/// # Examples
+///
+/// ```
+/// # use rand::thread_rng;
+/// # use pwbox::{DefaultSuite, Eraser, Suite};
+/// let pwbox = DefaultSuite::build_box(&mut thread_rng())
+/// #   .kdf(pwbox::fast_insecure_for_tests().kdf())
+///     .seal("correct horse", "battery staple")?;
+/// let mut eraser = Eraser::new();
+/// eraser.add_suite::<DefaultSuite>()?;
+/// let erased = eraser.erase(&pwbox)?;
+/// let restored = eraser.restore(&erased)?;
+/// assert_eq!(&*restored.open("correct horse")?, b"battery staple");
+/// # Ok::<_, anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct DefaultSuite(());
+
+impl Suite for DefaultSuite {
+    type Cipher = <Backend as Suite>::Cipher;
+    type DeriveKey = Scrypt;
+
+    fn build_box<R: RngCore + CryptoRng>(
+        rng: &mut R,
+    ) -> PwBoxBuilder<'_, Self::DeriveKey, Self::Cipher> {
+        let mut builder = PwBoxBuilder::new(rng);
+        builder.kdf(Scrypt::preset(KdfPreset::Sensitive));
+        builder
+    }
+
+    fn add_ciphers_and_kdfs(eraser: &mut Eraser) -> Result<(), Error> {
+        Backend::add_ciphers_and_kdfs(eraser)
+    }
+}
+
+/// Suite with the same primitives as [`DefaultSuite`], but with the KDF params chosen
+/// for speed rather than security. The suite is returned by [`fast_insecure_for_tests()`].
+///
+/// Boxes sealed with this suite can be restored and opened with [`DefaultSuite`]
+/// and vice versa. The suite should only be used in tests.
+#[derive(Debug, Clone, Copy)]
+pub struct FastInsecureSuite(());
+
+impl FastInsecureSuite {
+    /// Returns the KDF used by this suite. The KDF can be supplied to a [`PwBoxBuilder`]
+    /// obtained from [`DefaultSuite`] to speed up tests.
+    pub fn kdf(self) -> <Self as Suite>::DeriveKey {
+        light_kdf()
+    }
+}
+
+impl Suite for FastInsecureSuite {
+    type Cipher = <DefaultSuite as Suite>::Cipher;
+    type DeriveKey = <DefaultSuite as Suite>::DeriveKey;
+
+    fn build_box<R: RngCore + CryptoRng>(
+        rng: &mut R,
+    ) -> PwBoxBuilder<'_, Self::DeriveKey, Self::Cipher> {
+        let mut builder = PwBoxBuilder::new(rng);
+        builder.kdf(light_kdf());
+        builder
+    }
+
+    fn add_ciphers_and_kdfs(eraser: &mut Eraser) -> Result<(), Error> {
+        DefaultSuite::add_ciphers_and_kdfs(eraser)
+    }
+}
+
+/// Returns the suite with the strongest primitives available with the enabled crate features.
+/// See [`DefaultSuite`] for details.
+///
+/// # Examples
+///
+/// ```
+/// # use rand::thread_rng;
+/// # use pwbox::{ErasedPwBox, Error, Suite};
+/// fn encrypt<S: Suite>(_suite: S, secret: &[u8]) -> Result<ErasedPwBox, Error>
+/// where
+///     S::DeriveKey: serde::Serialize,
+/// {
+///     S::seal(&mut thread_rng(), "correct horse", secret)
+/// }
+///
+/// # let suite = pwbox::fast_insecure_for_tests();
+/// # let _ = pwbox::recommended();
+/// let erased = encrypt(suite, b"battery staple")?;
+/// # Ok::<_, Error>(())
+/// ```
+pub fn recommended() -> DefaultSuite {
+    DefaultSuite(())
+}
+
+/// Returns the suite with the same primitives as [`recommended()`], but with fast
+/// and insecure KDF params. Should only be used in tests.
+pub fn fast_insecure_for_tests() -> FastInsecureSuite {
+    FastInsecureSuite(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+
+    use super::*;
+    use crate::{names, ErasedPwBox, Eraser};
+
+    fn seal_and_restore<S: Suite>() -> ErasedPwBox
+    where
+        S::DeriveKey: serde::Serialize,
+    {
+        const PASSWORD: &str = "correct horse";
+        const MESSAGE: &[u8] = b"battery staple";
+
+        let pwbox = FastInsecureSuite::build_box(&mut thread_rng())
+            .seal(PASSWORD, MESSAGE)
+            .unwrap();
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<S>().unwrap();
+        let erased_box = eraser.erase(&pwbox).unwrap();
+        let restored = eraser.restore(&erased_box).unwrap();
+        assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+
+        let erased_with_suite = FastInsecureSuite::seal(&mut thread_rng(), PASSWORD, MESSAGE);
+        let opened = S::open(&erased_with_suite.unwrap(), PASSWORD).unwrap();
+        assert_eq!(&*opened, MESSAGE);
+        erased_box
+    }
+
+    #[test]
+    fn recommended_kdf_params() {
+        let mut rng = thread_rng();
+        let builder = DefaultSuite::build_box(&mut rng);
+        let kdf = builder.kdf.as_ref().unwrap();
+        let sensitive = Scrypt::preset(KdfPreset::Sensitive);
+        assert_eq!(kdf.memory_cost(), sensitive.memory_cost());
+        assert_eq!(kdf.ops_cost(), sensitive.ops_cost());
+
+        let builder = FastInsecureSuite::build_box(&mut rng);
+        let kdf = builder.kdf.as_ref().unwrap();
+        assert!(kdf.memory_cost() < sensitive.memory_cost());
+        assert!(kdf.ops_cost() < sensitive.ops_cost());
+    }
+
+    #[test]
+    fn boxes_roundtrip() {
+        seal_and_restore::<DefaultSuite>();
+        seal_and_restore::<FastInsecureSuite>();
+    }
+
+    #[cfg(feature = "exonum_sodiumoxide")]
+    #[test]
+    fn sodium_is_selected() {
+        let erased = seal_and_restore::<DefaultSuite>();
+        assert_eq!(erased.kdf_name(), names::SCRYPT_NACL);
+        assert_eq!(erased.cipher_name(), names::XCHACHA20_POLY1305);
+    }
+
+    #[cfg(all(feature = "rust-crypto", not(feature = "exonum_sodiumoxide")))]
+    #[test]
+    fn rust_crypto_is_selected() {
+        let erased = seal_and_restore::<DefaultSuite>();
+        assert_eq!(erased.kdf_name(), names::SCRYPT);
+        assert_eq!(erased.cipher_name(), names::AES_128_CTR);
+    }
+
+    #[cfg(all(
+        feature = "pure",
+        not(any(feature = "exonum_sodiumoxide", feature = "rust-crypto"))
+    ))]
+    #[test]
+    fn pure_crypto_is_selected() {
+        let erased = seal_and_restore::<DefaultSuite>();
+        assert_eq!(erased.kdf_name(), names::SCRYPT);
+        assert_eq!(erased.cipher_name(), names::CHACHA20_POLY1305);
+    }
+}
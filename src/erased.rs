@@ -14,17 +14,102 @@
 
 use hex_buffer_serde::{Hex as _Hex, HexForm};
 use rand_core::{CryptoRng, RngCore};
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use serde_json::{self, Error as JsonError, Value as JsonValue};
+use serde::{
+    de::{DeserializeOwned, Error as DeError, MapAccess, SeqAccess, Visitor},
+    ser::{Error as _, SerializeMap},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use serde_json::{
+    self, Error as JsonError, Map as JsonMap, Number as JsonNumber, Value as JsonValue,
+};
 
-use core::{any::TypeId, fmt};
+use core::{
+    any::{type_name, TypeId},
+    convert::TryFrom,
+    fmt::{self, Write as _},
+    mem,
+};
 
 use crate::{
-    alloc::{BTreeMap, Box, String, ToOwned as _, Vec},
-    traits::{CipherObject, ObjectSafeCipher},
-    Cipher, CipherOutput, DeriveKey, Error, PwBox, PwBoxBuilder, PwBoxInner, RestoredPwBox,
+    alloc::{format, Arc, BTreeMap, Cow, String, ToOwned as _, ToString as _, Vec},
+    cipher_with_mac::{DynCipherWithMac, ObjectSafeMac, ObjectSafeUnauthenticatedCipher},
+    traits::{CipherObject, DynCipherObject, ObjectSafeCipher},
+    BoxParts, Cipher, CipherOutput, DeriveKey, DynCipher, Error, KdfCost, Mac, PasswordPolicy,
+    PhcString, PwBox, PwBoxBuilder, PwBoxInner, RestoredPwBox, SensitiveData,
+    UnauthenticatedCipher,
+};
+#[cfg(feature = "std")]
+use crate::{
+    stream::{StreamInner, MIN_NONCE_LEN},
+    RestoredStreamPwBox, StreamPwBox,
 };
 
+/// Limits on the resources consumed by a KDF, which are enforced by an [`Eraser`]
+/// when restoring boxes. See [`Eraser::set_kdf_limits()`] for details.
+///
+/// The default limits are maximal, i.e., the resources are not limited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfLimits {
+    /// Maximum peak memory consumption in bytes.
+    pub max_memory_bytes: u64,
+    /// Maximum time cost as defined in [`KdfCost`].
+    pub max_time_cost: u64,
+}
+
+impl Default for KdfLimits {
+    fn default() -> Self {
+        KdfLimits {
+            max_memory_bytes: u64::MAX,
+            max_time_cost: u64::MAX,
+        }
+    }
+}
+
+impl KdfLimits {
+    pub(crate) fn allows(self, cost: KdfCost) -> bool {
+        cost.memory_bytes <= self.max_memory_bytes && cost.time_cost <= self.max_time_cost
+    }
+}
+
+/// Policy determining which erased boxes need to be re-sealed with stronger parameters.
+/// See [`Eraser::needs_upgrade()`] and [`Eraser::migrate()`].
+///
+/// The default policy does not require upgrading any boxes with known KDF costs.
+///
+/// # Examples
+///
+/// ```
+/// # use pwbox::{names, KdfPreset, ScryptParams, UpgradePolicy};
+/// let min_params = ScryptParams::preset(KdfPreset::Moderate);
+/// let policy = UpgradePolicy {
+///     allowed_ciphers: vec![names::XCHACHA20_POLY1305.to_owned()],
+///     ..UpgradePolicy::min_kdf_cost(min_params.cost())
+/// };
+/// # drop(policy);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpgradePolicy {
+    /// Minimum peak memory consumption of the KDF in bytes.
+    pub min_memory_bytes: u64,
+    /// Minimum time cost of the KDF as defined in [`KdfCost`].
+    pub min_time_cost: u64,
+    /// Names of allowed ciphers. Aliases registered in the [`Eraser`] are resolved
+    /// before comparison. If empty, all ciphers are allowed.
+    pub allowed_ciphers: Vec<String>,
+}
+
+impl UpgradePolicy {
+    /// Creates a policy requiring that the KDF cost is at least the specified `cost`.
+    /// All ciphers are allowed.
+    pub fn min_kdf_cost(cost: KdfCost) -> Self {
+        UpgradePolicy {
+            min_memory_bytes: cost.memory_bytes,
+            min_time_cost: cost.time_cost,
+            allowed_ciphers: Vec::new(),
+        }
+    }
+}
+
 /// Password-encrypted box suitable for (de)serialization.
 ///
 /// # Serialization
@@ -51,454 +136,4627 @@ use crate::{
 /// "#;
 ///
 /// let pwbox = toml::from_str(TOML).unwrap();
-/// let pwbox = Eraser::new().add_suite::<Sodium>().restore(&pwbox).unwrap();
+/// let pwbox = Eraser::new().add_suite::<Sodium>().unwrap().restore(&pwbox).unwrap();
 /// assert!(pwbox.open("correct horse battery staple").is_ok());
 /// ```
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// `kdf` and `cipher` fields are absent if the box was serialized directly from a [`PwBox`]
+/// (i.e., without using an [`Eraser`]). Such boxes can only be restored with
+/// [`Eraser::restore_typed()`] or [`Eraser::restore_as()`].
+///
+/// KDF params are written in a way representable in all these formats: params with
+/// `null` values are skipped, and nested params are placed after plain ones. When
+/// deserializing, param values without a JSON equivalent (such as integer map keys in YAML)
+/// are converted to strings; if the KDF cannot parse such params, restoring the box
+/// fails with [`Error::KdfParams`].
+///
+/// With binary formats (bincode, CBOR, ...), the box is serialized as a flat structure
+/// with byte buffers (salt, nonce, ciphertext and MAC) written as raw bytes, and KDF params
+/// encoded as a JSON string.
+///
+/// Boxes with an empty message are supported; the ciphertext of such a box is serialized
+/// as an empty string (or an empty byte buffer in binary formats), while the MAC is still
+/// present and is verified on opening.
+///
+/// Boxes sealed with a [`PasswordPolicy`] other than `raw` contain the `passwordpolicy`
+/// field (e.g., `"passwordpolicy": "nfkd"`). Boxes without this field use the `raw` policy.
+///
+/// Boxes can be compared for equality; all fields, including KDF params, are compared.
+/// This can be used, e.g., to check that a box was not modified on re-saving.
+///
+/// # Format versions
+///
+/// In the v1 format (the default one), only the ciphertext and associated data are
+/// authenticated; the KDF and cipher names and the KDF params are not. In the v2 format,
+/// the box additionally contains the `version` field (equal to 2), and the KDF name,
+/// KDF params, the cipher name, the salt and the nonce are authenticated by the cipher
+/// as associated data. Thus, tampering with this metadata (e.g., downgrading KDF params)
+/// leads to [`Error::MacMismatch`] when opening the box. See [`FormatVersion`] for details.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ErasedPwBox {
-    #[serde(flatten)]
+    version: FormatVersion,
     encrypted: CipherOutput,
     kdf: String,
     cipher: String,
-    #[serde(rename = "kdfparams")]
     kdf_params: KdfParams,
-    #[serde(rename = "cipherparams")]
     cipher_params: CipherParams,
+    password_policy: PasswordPolicy,
 }
 
-// `is_empty()` method wouldn't make much sense; in *all* valid use cases, `len() > 0`.
-#[allow(clippy::len_without_is_empty)]
-impl ErasedPwBox {
-    /// Returns the byte size of the encrypted data stored in this box.
-    pub fn len(&self) -> usize {
-        self.encrypted.ciphertext.len()
-    }
+/// Version of the [`ErasedPwBox`] format.
+///
+/// The format version of boxes sealed with [`Eraser::seal()`] is set with
+/// [`Eraser::set_format_version()`]. [`Eraser::restore()`] accepts boxes of all versions.
+///
+/// # Examples
+///
+/// ```
+/// # use rand::thread_rng;
+/// # use pwbox::{sodium::{Scrypt, Sodium}, Eraser, Error, FormatVersion, PwBoxBuilder, Suite};
+/// let mut eraser = Eraser::new();
+/// eraser.add_suite::<Sodium>()?.set_format_version(FormatVersion::V2);
+/// let mut rng = thread_rng();
+/// let mut builder = Sodium::build_box(&mut rng);
+/// # builder.kdf(Scrypt::light());
+/// let mut erased = eraser.seal(&mut builder, "correct horse", b"battery staple").unwrap();
+/// assert_eq!(erased.format_version(), FormatVersion::V2);
+/// let json = serde_json::to_value(&erased).unwrap();
+/// assert_eq!(json["version"], 2);
+///
+/// // Tampering with KDF params is detected.
+/// let mut json = json;
+/// json["kdfparams"]["opslimit"] = (1 << 16).into();
+/// let tampered = serde_json::from_value(json).unwrap();
+/// let restored = eraser.restore(&tampered)?;
+/// assert!(matches!(restored.open("correct horse"), Err(Error::MacMismatch)));
+/// # Ok::<_, Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub enum FormatVersion {
+    /// Original format, in which the KDF and cipher metadata is not authenticated.
+    V1,
+    /// Format with the KDF name, KDF params, the cipher name, the salt and the nonce
+    /// authenticated as associated data of the cipher. Requires a cipher supporting
+    /// associated data.
+    V2,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct KdfParams {
-    #[serde(with = "HexForm")]
-    salt: Vec<u8>,
-    #[serde(flatten)]
-    inner: JsonValue,
+// `#[default]` enum variants are not supported by the minimum supported Rust version.
+#[allow(clippy::derivable_impls)]
+impl Default for FormatVersion {
+    fn default() -> Self {
+        FormatVersion::V1
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct CipherParams {
-    #[serde(with = "HexForm")]
-    iv: Vec<u8>,
+impl From<FormatVersion> for u8 {
+    fn from(version: FormatVersion) -> Self {
+        match version {
+            FormatVersion::V1 => 1,
+            FormatVersion::V2 => 2,
+        }
+    }
 }
 
-type CipherFactory = Box<dyn Fn() -> Box<dyn ObjectSafeCipher>>;
-type KdfFactory = Box<dyn Fn(JsonValue) -> Result<Box<dyn DeriveKey>, JsonError>>;
+impl TryFrom<u8> for FormatVersion {
+    type Error = String;
 
-/// Errors occurring during erasing a `PwBox`.
-#[derive(Debug)]
-pub enum EraseError {
-    /// KDF used in the box is not registered with the `Eraser`.
-    NoKdf,
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(FormatVersion::V1),
+            2 => Ok(FormatVersion::V2),
+            _ => Err(format!(
+                "unsupported box format version: {} (supported versions are 1 and 2)",
+                value
+            )),
+        }
+    }
+}
 
-    /// Cipher used in the box is not registered with the `Eraser`.
-    NoCipher,
+#[allow(clippy::trivially_copy_pass_by_ref)] // required by `serde`
+fn is_v1(version: &FormatVersion) -> bool {
+    *version == FormatVersion::V1
+}
 
-    /// Error serializing KDF params.
-    SerializeKdf(JsonError),
+/// Name of the field storing the format version in the KDF params of the binary layout.
+const BINARY_VERSION_FIELD: &str = "$version";
+/// Name of the field storing the password policy in the KDF params of the binary layout.
+const BINARY_POLICY_FIELD: &str = "$passwordpolicy";
+
+/// Computes metadata bound to boxes in the v2 format: the KDF name, the KDF params
+/// and the cipher name. Fields are length-prefixed, and KDF params are encoded as JSON
+/// with sorted object keys and without `null` object entries, so that the encoding
+/// does not depend on the serialization format of the box.
+///
+/// The salt and nonce are generated during sealing, so they are not included in the metadata
+/// stored in the box; instead, they are appended (also length-prefixed) to the metadata
+/// when it is supplied to the cipher as associated data.
+fn bound_metadata(kdf: &str, kdf_params: &JsonValue, cipher: &str) -> Vec<u8> {
+    let mut params = Vec::new();
+    write_canonical_json(kdf_params, &mut params);
+    let mut metadata = b"pwbox/v2".to_vec();
+    for field in &[kdf.as_bytes(), &params, cipher.as_bytes()] {
+        metadata.extend_from_slice(&(field.len() as u64).to_le_bytes());
+        metadata.extend_from_slice(field);
+    }
+    metadata
 }
 
-impl fmt::Display for EraseError {
-    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            EraseError::NoKdf => {
-                formatter.write_str("KDF used in the box is not registered with the `Eraser`")
+fn write_canonical_json(value: &JsonValue, buffer: &mut Vec<u8>) {
+    match value {
+        JsonValue::Array(items) => {
+            buffer.push(b'[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    buffer.push(b',');
+                }
+                write_canonical_json(item, buffer);
             }
-            EraseError::NoCipher => {
-                formatter.write_str("cipher used in the box is not registered with the `Eraser`")
+            buffer.push(b']');
+        }
+        JsonValue::Object(map) => {
+            // `null` entries are skipped, since they are not serialized in the box.
+            let mut entries: Vec<_> = map.iter().filter(|(_, item)| !item.is_null()).collect();
+            entries.sort_unstable_by_key(|(key, _)| *key);
+            buffer.push(b'{');
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    buffer.push(b',');
+                }
+                write_canonical_json(&JsonValue::String(key.clone()), buffer);
+                buffer.push(b':');
+                write_canonical_json(item, buffer);
             }
-            EraseError::SerializeKdf(e) => write!(formatter, "error serializing KDF params: {}", e),
+            buffer.push(b'}');
+        }
+        scalar => {
+            let bytes = serde_json::to_vec(scalar).expect("JSON scalars are always serializable");
+            buffer.extend_from_slice(&bytes);
         }
     }
 }
 
-#[cfg(feature = "std")]
-impl std::error::Error for EraseError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        if let EraseError::SerializeKdf(e) = self {
-            Some(e)
-        } else {
-            None
-        }
-    }
+/// Layout of `ErasedPwBox` for human-readable formats.
+#[derive(Serialize, Deserialize)]
+struct HumanReadablePwBox {
+    #[serde(default, skip_serializing_if = "is_v1")]
+    version: FormatVersion,
+    #[serde(flatten)]
+    encrypted: CipherOutput,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    kdf: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    cipher: String,
+    #[serde(rename = "kdfparams")]
+    kdf_params: KdfParams,
+    #[serde(rename = "cipherparams")]
+    cipher_params: CipherParams,
+    #[serde(
+        default,
+        rename = "passwordpolicy",
+        skip_serializing_if = "PasswordPolicy::is_raw"
+    )]
+    password_policy: PasswordPolicy,
 }
 
-/// Helper structure to convert password-encrypted boxes to a serializable format and back.
-///
-/// # Examples
-///
-/// ```
-/// # #[cfg(all(feature = "exonum_sodiumoxide", feature = "rust-crypto"))]
-/// # fn main() {
-/// # use rand::thread_rng;
-/// # use pwbox::{Eraser, Suite,
-/// #     rcrypto::{Scrypt as SomeKdf, Aes128Gcm as SomeCipher},
-/// #     sodium::Sodium as SomeSuite};
-/// let mut eraser = Eraser::new();
-/// // Register separate KDFs and ciphers
-/// eraser.add_kdf::<SomeKdf>("some-kdf");
-/// eraser.add_cipher::<SomeCipher>("some-cipher");
-/// // Add a suite.
-/// eraser.add_suite::<SomeSuite>();
-///
-/// // Erase a `PwBox`.
-/// let pwbox = SomeSuite::build_box(&mut thread_rng())
-///     .seal("password", b"some data")
-///     .unwrap();
-/// let erased = eraser.erase(&pwbox).unwrap();
-/// // `erased` can now be serialized somewhere, e.g., in JSON format.
+/// Layout of `ErasedPwBox` for binary formats. Unlike `HumanReadablePwBox`, this layout
+/// does not use flattening, which is not supported by many binary formats.
 ///
-/// // Restore a `PwBox`.
-/// let restored = eraser.restore(&erased).unwrap();
-/// assert_eq!(&*restored.open("password").unwrap(), b"some data");
-/// # } // main
-/// # #[cfg(not(all(feature = "exonum_sodiumoxide", feature = "rust-crypto")))]
-/// # fn main() {}
-/// ```
-pub struct Eraser {
-    ciphers: BTreeMap<String, CipherFactory>,
-    kdfs: BTreeMap<String, KdfFactory>,
-    cipher_names: BTreeMap<TypeId, String>,
-    kdf_names: BTreeMap<TypeId, String>,
+/// To keep the layout compatible with existing boxes, the format version is not a separate
+/// field; instead, for versions other than v1, it is stored in the KDF params under
+/// the `BINARY_VERSION_FIELD` name. Likewise, a non-raw password policy is stored
+/// under the `BINARY_POLICY_FIELD` name.
+#[derive(Serialize, Deserialize)]
+struct BinaryPwBox {
+    kdf: String,
+    cipher: String,
+    #[serde(with = "HexForm")]
+    salt: Vec<u8>,
+    kdf_params: String,
+    #[serde(with = "HexForm")]
+    nonce: Vec<u8>,
+    associated_data: bool,
+    chunk_size: Option<u32>,
+    #[serde(with = "HexForm")]
+    ciphertext: Vec<u8>,
+    #[serde(with = "HexForm")]
+    mac: Vec<u8>,
 }
 
-impl fmt::Debug for Eraser {
-    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-        formatter
-            .debug_struct("Eraser")
-            .field("ciphers", &self.ciphers.keys().collect::<Vec<_>>())
-            .field("kdfs", &self.kdfs.keys().collect::<Vec<_>>())
-            .finish()
+impl Serialize for ErasedPwBox {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let this = self.clone();
+        if serializer.is_human_readable() {
+            HumanReadablePwBox {
+                version: this.version,
+                encrypted: this.encrypted,
+                kdf: this.kdf,
+                cipher: this.cipher,
+                kdf_params: this.kdf_params,
+                cipher_params: this.cipher_params,
+                password_policy: this.password_policy,
+            }
+            .serialize(serializer)
+        } else {
+            let mut kdf_params = this.kdf_params.inner;
+            if let Some(params) = kdf_params.as_object_mut() {
+                if !is_v1(&this.version) {
+                    let version = u8::from(this.version);
+                    params.insert(BINARY_VERSION_FIELD.to_owned(), version.into());
+                }
+                if !this.password_policy.is_raw() {
+                    let policy = serde_json::to_value(this.password_policy)
+                        .expect("password policy is always serializable");
+                    params.insert(BINARY_POLICY_FIELD.to_owned(), policy);
+                }
+            }
+            let kdf_params = serde_json::to_string(&kdf_params).map_err(S::Error::custom)?;
+            BinaryPwBox {
+                kdf: this.kdf,
+                cipher: this.cipher,
+                salt: this.kdf_params.salt,
+                kdf_params,
+                nonce: this.cipher_params.iv,
+                associated_data: this.cipher_params.aad,
+                chunk_size: this.cipher_params.chunk_size,
+                ciphertext: this.encrypted.ciphertext,
+                mac: this.encrypted.mac,
+            }
+            .serialize(serializer)
+        }
     }
 }
 
-impl Default for Eraser {
-    fn default() -> Self {
-        Eraser::new()
+impl<'de> Deserialize<'de> for ErasedPwBox {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let this = HumanReadablePwBox::deserialize(deserializer)?;
+            Ok(ErasedPwBox {
+                version: this.version,
+                encrypted: this.encrypted,
+                kdf: this.kdf,
+                cipher: this.cipher,
+                kdf_params: this.kdf_params,
+                cipher_params: this.cipher_params,
+                password_policy: this.password_policy,
+            })
+        } else {
+            let this = BinaryPwBox::deserialize(deserializer)?;
+            let mut kdf_params: JsonValue =
+                serde_json::from_str(&this.kdf_params).map_err(D::Error::custom)?;
+            let version = kdf_params
+                .as_object_mut()
+                .and_then(|params| params.remove(BINARY_VERSION_FIELD));
+            let version = match version {
+                Some(version) => serde_json::from_value(version).map_err(D::Error::custom)?,
+                None => FormatVersion::V1,
+            };
+            let policy = kdf_params
+                .as_object_mut()
+                .and_then(|params| params.remove(BINARY_POLICY_FIELD));
+            let password_policy = match policy {
+                Some(policy) => serde_json::from_value(policy).map_err(D::Error::custom)?,
+                None => PasswordPolicy::Raw,
+            };
+            Ok(ErasedPwBox {
+                version,
+                encrypted: CipherOutput {
+                    ciphertext: this.ciphertext,
+                    mac: this.mac,
+                },
+                kdf: this.kdf,
+                cipher: this.cipher,
+                kdf_params: KdfParams {
+                    salt: this.salt,
+                    inner: kdf_params,
+                },
+                cipher_params: CipherParams {
+                    iv: this.nonce,
+                    aad: this.associated_data,
+                    chunk_size: this.chunk_size,
+                },
+                password_policy,
+            })
+        }
     }
 }
 
-impl Eraser {
-    /// Creates an `Eraser` with no ciphers or KDFs.
-    pub fn new() -> Self {
-        Eraser {
-            ciphers: BTreeMap::new(),
-            kdfs: BTreeMap::new(),
-            cipher_names: BTreeMap::new(),
-            kdf_names: BTreeMap::new(),
-        }
+impl ErasedPwBox {
+    /// Returns the byte size of the encrypted data stored in this box.
+    pub fn len(&self) -> usize {
+        self.encrypted.ciphertext.len()
+    }
+
+    /// Checks whether this box contains an empty message.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
-    /// Adds a cipher.
+    /// Assembles a box from its parts, e.g., when converting a box from a foreign format.
     ///
-    /// # Panics
+    /// `kdf_params` must be a JSON object with KDF params excluding the salt (i.e.,
+    /// the params as serialized by the KDF implementation). The created box is assumed
+    /// to be sealed without associated data. The box is not checked against the KDF
+    /// and cipher implementations; this happens when it is restored by an [`Eraser`].
     ///
-    /// Panics if the cipher is already registered under a different name, or if `cipher_name`
-    /// is already registered.
-    pub fn add_cipher<C>(&mut self, cipher_name: &str) -> &mut Self
-    where
-        C: Cipher,
-    {
-        let factory = || {
-            let cipher_object = CipherObject::<C>::default();
-            Box::new(cipher_object) as Box<dyn ObjectSafeCipher>
-        };
-        let old_cipher = self
-            .ciphers
-            .insert(cipher_name.to_owned(), Box::new(factory));
-        assert!(
-            old_cipher.is_none(),
-            "cipher name already registered: {}",
-            cipher_name
-        );
-
-        let old_name = self
-            .cipher_names
-            .insert(TypeId::of::<C>(), cipher_name.to_owned());
-        if let Some(old_name) = old_name {
-            panic!(
-                "cipher {} already registered under name {}",
-                cipher_name, old_name
-            );
+    /// # Errors
+    ///
+    /// Returns [`Error::MalformedBox`] if `kdf_params` is not an object or contains
+    /// the `salt` field, or a length error if the salt, nonce or MAC are empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pwbox::{sodium::Sodium, CipherOutput, ErasedPwBox, Eraser};
+    /// # use serde_json::json;
+    /// # fn main() -> Result<(), pwbox::Error> {
+    /// let kdf_params = json!({ "opslimit": 524_288, "memlimit": 16_777_216 });
+    /// let encrypted = CipherOutput {
+    ///     ciphertext: hex::decode("cd9d2fb2355d8c60d92dcc860abc0c4b20ddd12dd52a4dd53caca0a2f87f7f5f")
+    ///         .unwrap(),
+    ///     mac: hex::decode("83ae22646d7834f254caea78862eafda").unwrap(),
+    /// };
+    /// let erased = ErasedPwBox::from_parts(
+    ///     "scrypt-nacl",
+    ///     kdf_params,
+    ///     hex::decode("87d68fb57d9c2331cf2bd9fdd7551057798bd36d0d2999481311cfae39863691")
+    ///         .unwrap(),
+    ///     "xsalsa20-poly1305",
+    ///     hex::decode("db39c466e2f8ae7fbbc857df48d99254017b059624af7106").unwrap(),
+    ///     encrypted,
+    /// )?;
+    /// assert_eq!(erased.kdf_name(), "scrypt-nacl");
+    ///
+    /// let mut eraser = Eraser::new();
+    /// eraser.add_suite::<Sodium>()?;
+    /// let pwbox = eraser.restore(&erased)?;
+    /// assert!(pwbox.open("correct horse battery staple").is_ok());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_parts(
+        kdf_name: &str,
+        kdf_params: JsonValue,
+        salt: Vec<u8>,
+        cipher_name: &str,
+        nonce: Vec<u8>,
+        encrypted: CipherOutput,
+    ) -> Result<Self, Error> {
+        match kdf_params.as_object() {
+            Some(params) if params.contains_key("salt") => {
+                let reason = "KDF params must not contain salt".to_owned();
+                return Err(Error::MalformedBox(reason));
+            }
+            Some(_) => { /* OK */ }
+            None => {
+                let reason = "KDF params must be an object".to_owned();
+                return Err(Error::MalformedBox(reason));
+            }
+        }
+        if salt.is_empty() {
+            return Err(Error::SaltLen);
+        }
+        if nonce.is_empty() {
+            return Err(Error::NonceLen);
+        }
+        if encrypted.mac.is_empty() {
+            return Err(Error::MacLen);
         }
 
-        self
+        Ok(ErasedPwBox {
+            version: FormatVersion::V1,
+            encrypted,
+            kdf: kdf_name.to_owned(),
+            cipher: cipher_name.to_owned(),
+            kdf_params: KdfParams {
+                salt,
+                inner: kdf_params,
+            },
+            cipher_params: CipherParams {
+                iv: nonce,
+                aad: false,
+                chunk_size: None,
+            },
+            password_policy: PasswordPolicy::Raw,
+        })
     }
 
-    /// Adds a key derivation function.
-    ///
-    /// # Panics
-    ///
-    /// Panics if the KDF is already registered under a different name, or if `kdf_name`
-    /// is already registered.
-    pub fn add_kdf<K>(&mut self, kdf_name: &str) -> &mut Self
-    where
-        K: DeriveKey + DeserializeOwned + Default,
-    {
-        let factory = |options| {
-            let kdf: K = serde_json::from_value(options)?;
-            Ok(Box::new(kdf) as Box<dyn DeriveKey>)
-        };
+    /// Returns the format version of this box.
+    pub fn format_version(&self) -> FormatVersion {
+        self.version
+    }
 
-        let old_kdf = self.kdfs.insert(kdf_name.to_owned(), Box::new(factory));
-        assert!(
-            old_kdf.is_none(),
-            "cipher name already registered: {}",
-            kdf_name
-        );
+    /// Returns the policy of transforming passwords used by this box.
+    pub fn password_policy(&self) -> PasswordPolicy {
+        self.password_policy
+    }
 
-        let old_name = self
-            .kdf_names
-            .insert(TypeId::of::<K>(), kdf_name.to_owned());
-        if let Some(old_name) = old_name {
-            panic!(
-                "KDF {} already registered under name {}",
-                kdf_name, old_name
-            );
-        }
+    /// Returns the name of the KDF used in this box. The name is empty if the box
+    /// was serialized directly from a [`PwBox`].
+    pub fn kdf_name(&self) -> &str {
+        &self.kdf
+    }
 
-        self
+    /// Returns the name of the cipher used in this box. The name is empty if the box
+    /// was serialized directly from a [`PwBox`].
+    pub fn cipher_name(&self) -> &str {
+        &self.cipher
     }
 
-    /// Adds all KDFs and ciphers from the specified `Suite`.
-    ///
-    /// # Panics
+    /// Returns the KDF params stored in this box, excluding the salt.
+    /// See also [`Self::parse_kdf()`].
     ///
-    /// This method panics if any KDF or cipher in the suite (or its name)
-    /// have been registered previously. A panic is also raised if the suite
-    /// has not registered its recommended cipher or KDF.
-    pub fn add_suite<S: Suite>(&mut self) -> &mut Self {
-        S::add_ciphers_and_kdfs(self);
-        assert!(
-            self.lookup_kdf::<S::DeriveKey>().is_some(),
-            "recommended KDF from suite not added"
-        );
-        assert!(
-            self.lookup_cipher::<S::Cipher>().is_some(),
-            "recommended cipher from suite not added"
-        );
-        self
+    /// If the params are recorded as a PHC string (see [`Eraser::add_phc_kdf()`]),
+    /// the string is returned as is, and the salt of the box is empty.
+    pub fn kdf_params(&self) -> &JsonValue {
+        &self.kdf_params.inner
     }
 
-    fn lookup_cipher<C>(&self) -> Option<&String>
-    where
-        C: Cipher,
-    {
-        self.cipher_names.get(&TypeId::of::<C>())
+    /// Returns the salt supplied to the KDF.
+    pub fn salt(&self) -> &[u8] {
+        &self.kdf_params.salt
     }
 
-    fn lookup_kdf<K>(&self) -> Option<&String>
-    where
-        K: DeriveKey,
-    {
-        self.kdf_names.get(&TypeId::of::<K>())
+    /// Returns the cipher nonce (aka initialization vector, or IV).
+    pub fn nonce(&self) -> &[u8] {
+        &self.cipher_params.iv
     }
 
-    /// Converts a `pwbox` into serializable form.
-    pub fn erase<K, C>(&self, pwbox: &PwBox<K, C>) -> Result<ErasedPwBox, EraseError>
+    /// Returns the encrypted data.
+    pub fn ciphertext(&self) -> &[u8] {
+        &self.encrypted.ciphertext
+    }
+
+    /// Returns the message authentication code for the encrypted data.
+    pub fn mac(&self) -> &[u8] {
+        &self.encrypted.mac
+    }
+
+    /// Checks whether the box was sealed with non-empty associated data.
+    pub fn has_associated_data(&self) -> bool {
+        self.cipher_params.aad
+    }
+
+    /// Parses the KDF params stored in this box. This can be used to check the difficulty
+    /// of the stored params (e.g., with the help of [`KdfDifficulty`]) before
+    /// trying to open the box.
+    ///
+    /// The name of the KDF is not checked.
+    ///
+    /// [`KdfDifficulty`]: trait.KdfDifficulty.html
+    pub fn parse_kdf<K>(&self) -> Result<K, Error>
     where
-        K: DeriveKey + Serialize,
-        C: Cipher,
+        K: DeriveKey + DeserializeOwned,
     {
-        let kdf = match self.lookup_kdf::<K>() {
-            Some(kdf) => kdf,
-            None => return Err(EraseError::NoKdf),
-        };
-        let cipher = match self.lookup_cipher::<C>() {
-            Some(cipher) => cipher,
-            None => return Err(EraseError::NoCipher),
+        serde_json::from_value(self.kdf_params.inner.clone()).map_err(Error::KdfParams)
+    }
+
+    fn from_inner<K, C>(
+        pwbox: &PwBoxInner<K, C>,
+        kdf: String,
+        cipher: String,
+        kdf_params: JsonValue,
+    ) -> Self {
+        let version = if pwbox.metadata.is_empty() {
+            FormatVersion::V1
+        } else {
+            FormatVersion::V2
         };
-        let kdf_params = match serde_json::to_value(&pwbox.inner.kdf) {
-            Ok(params) => params,
-            Err(e) => return Err(EraseError::SerializeKdf(e)),
-        };
-
-        let pwbox = &pwbox.inner;
-        Ok(ErasedPwBox {
+        ErasedPwBox {
+            version,
             encrypted: pwbox.encrypted.clone(),
-            kdf: kdf.to_owned(),
+            kdf,
             kdf_params: KdfParams {
                 salt: pwbox.salt.clone(),
                 inner: kdf_params,
             },
-            cipher: cipher.to_owned(),
+            cipher,
             cipher_params: CipherParams {
                 iv: pwbox.nonce.clone(),
+                aad: pwbox.associated_data,
+                chunk_size: None,
             },
+            password_policy: pwbox.password_policy,
+        }
+    }
+
+    /// Parses KDF params and converts this box into a strongly typed `PwBox`.
+    /// KDF and cipher names are not checked.
+    fn to_typed<K, C>(&self) -> Result<PwBox<K, C>, Error>
+    where
+        K: DeriveKey + DeserializeOwned,
+        C: Cipher,
+    {
+        let kdf = self.parse_kdf::<K>()?;
+        self.to_inner(kdf, CipherObject::default())
+            .map(|inner| PwBox { inner })
+    }
+
+    /// Checks buffer lengths and converts this box into the core representation.
+    fn to_inner<K, C>(&self, kdf: K, cipher: C) -> Result<PwBoxInner<K, C>, Error>
+    where
+        K: DeriveKey,
+        C: ObjectSafeCipher,
+    {
+        let metadata = self.check_params(&kdf, &cipher)?;
+        Ok(PwBoxInner {
+            salt: self.kdf_params.salt.clone(),
+            nonce: self.cipher_params.iv.clone(),
+            encrypted: self.encrypted.clone(),
+            associated_data: self.cipher_params.aad,
+            metadata,
+            password_policy: self.password_policy,
+            kdf,
+            cipher,
         })
     }
 
-    /// Restores a `PwBox` from the serialized form.
-    pub fn restore(&self, erased: &ErasedPwBox) -> Result<RestoredPwBox, Error> {
-        let kdf_factory = self
-            .kdfs
-            .get(&erased.kdf)
-            .ok_or_else(|| Error::NoKdf(erased.kdf.clone()))?;
-        let cipher = self
-            .ciphers
-            .get(&erased.cipher)
-            .ok_or_else(|| Error::NoCipher(erased.cipher.clone()))?();
-        let kdf = kdf_factory(erased.kdf_params.inner.clone()).map_err(Error::KdfParams)?;
+    /// Borrows the parts of the box necessary to open it with the specified KDF and cipher.
+    /// The `metadata` must be obtained from [`Self::check_params()`].
+    fn to_parts<'a, K, C>(
+        &'a self,
+        kdf: &'a K,
+        cipher: &'a C,
+        metadata: &'a [u8],
+    ) -> BoxParts<'a, K, C>
+    where
+        K: DeriveKey + ?Sized,
+        C: ObjectSafeCipher + ?Sized,
+    {
+        BoxParts {
+            salt: &self.kdf_params.salt,
+            nonce: &self.cipher_params.iv,
+            encrypted: &self.encrypted,
+            metadata,
+            password_policy: self.password_policy,
+            kdf,
+            cipher,
+        }
+    }
 
-        // Check buffer lengths.
-        if erased.kdf_params.salt.len() != kdf.salt_len() {
+    /// Checks the salt, nonce and MAC lengths against the KDF and cipher. Returns
+    /// the metadata authenticated by the cipher.
+    fn check_params<K, C>(&self, kdf: &K, cipher: &C) -> Result<Vec<u8>, Error>
+    where
+        K: DeriveKey + ?Sized,
+        C: ObjectSafeCipher + ?Sized,
+    {
+        if self.cipher_params.chunk_size.is_some() {
+            return Err(Error::ChunkSize);
+        }
+        if !kdf.valid_salt_len(self.kdf_params.salt.len()) {
             return Err(Error::SaltLen);
         }
-        if erased.cipher_params.iv.len() != cipher.nonce_len() {
+        if self.cipher_params.iv.len() != cipher.nonce_len() {
             return Err(Error::NonceLen);
         }
-        if erased.encrypted.mac.len() != cipher.mac_len() {
+        if self.encrypted.mac.len() != cipher.mac_len() {
             return Err(Error::MacLen);
         }
-
-        let inner = PwBoxInner {
-            salt: erased.kdf_params.salt.clone(),
-            nonce: erased.cipher_params.iv.clone(),
-            encrypted: erased.encrypted.clone(),
-            kdf,
-            cipher,
+        let metadata = match self.version {
+            FormatVersion::V1 => Vec::new(),
+            FormatVersion::V2 => {
+                if !cipher.supports_associated_data() {
+                    let reason = "cipher does not support the v2 box format".to_owned();
+                    return Err(Error::MalformedBox(reason));
+                }
+                bound_metadata(&self.kdf, &self.kdf_params.inner, &self.cipher)
+            }
         };
-        Ok(RestoredPwBox { inner })
+        Ok(metadata)
     }
 }
 
-/// Cryptographic suite providing ciphers and KDFs for password-based encryption.
-pub trait Suite {
-    /// Recommended cipher for this suite.
-    type Cipher: Cipher;
-    /// Recommended KDF for this suite.
-    type DeriveKey: DeriveKey + Clone + Default;
+/// KDF params together with the salt.
+///
+/// In human-readable formats, the params are serialized as a map with the `salt` entry
+/// followed by the params themselves. To make the map representable in any format,
+/// map entries with `null` values are skipped (TOML has no `null`; such entries are
+/// also removed when erasing or deserializing a box), and nested maps are placed
+/// after other entries (TOML requires tables to go after plain values).
+/// On deserialization, values without a JSON equivalent are converted to strings
+/// (see [`ParamValue`]), so that an unexpected param leads to [`Error::KdfParams`]
+/// when restoring the box rather than to a deserialization error.
+///
+/// Alternatively, the params may be recorded as a PHC string, which includes the salt.
+/// Such params are (de)serialized as a string, and are converted to the map form
+/// when the box is restored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct KdfParams {
+    salt: Vec<u8>,
+    /// Params excluding the salt. This is a JSON object for all boxes, except for boxes
+    /// with a PHC string (in which case `salt` is empty) and malformed boxes produced
+    /// by binary formats.
+    inner: JsonValue,
+}
 
-    /// Initializes a `PwBoxBuilder` with the recommended cipher and KDF.
-    fn build_box<R: RngCore + CryptoRng>(
-        rng: &mut R,
-    ) -> PwBoxBuilder<'_, Self::DeriveKey, Self::Cipher> {
-        PwBoxBuilder::new(rng)
+impl Serialize for KdfParams {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let empty_params = JsonMap::new();
+        let params = match &self.inner {
+            JsonValue::Object(params) => params,
+            JsonValue::Null => &empty_params,
+            JsonValue::String(phc) if self.salt.is_empty() => {
+                return serializer.serialize_str(phc);
+            }
+            _ => return Err(S::Error::custom("KDF params must be an object")),
+        };
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("salt", &HexSalt(&self.salt))?;
+        serialize_params(&mut map, params)?;
+        map.end()
     }
-
-    /// Adds ciphers and KDFs from this suite into the specified `Eraser`.
-    fn add_ciphers_and_kdfs(eraser: &mut Eraser);
 }
 
-// This function is used in testing cryptographic backends, so it's intentionally kept public.
-#[cfg(test)]
-pub fn test_kdf_and_cipher_corruption<K, C>(kdf: K)
-where
-    K: DeriveKey + Clone + Default + Serialize + DeserializeOwned,
-    C: Cipher,
-{
-    use crate::alloc::vec;
-    use assert_matches::assert_matches;
-    use rand::thread_rng;
+impl<'de> Deserialize<'de> for KdfParams {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KdfParamsVisitor;
 
-    const PASSWORD: &str = "correct horse battery staple";
+        impl<'de> Visitor<'de> for KdfParamsVisitor {
+            type Value = KdfParams;
 
-    let mut rng = thread_rng();
-    let mut message = vec![0_u8; 64];
-    rng.fill_bytes(&mut message);
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("KDF params with salt or a PHC string")
+            }
 
-    let pwbox = PwBoxBuilder::<_, C>::new(&mut rng)
-        .kdf(kdf)
-        .seal(PASSWORD, &message)
-        .unwrap();
+            fn visit_str<E: DeError>(self, value: &str) -> Result<Self::Value, E> {
+                Ok(KdfParams {
+                    salt: Vec::new(),
+                    inner: JsonValue::String(value.to_owned()),
+                })
+            }
 
-    // All corrupted input needs to pass through `Eraser` / `ErasedPwBox`, so we test them.
-    let mut eraser = Eraser::new();
-    let eraser = eraser.add_cipher::<C>("cipher").add_kdf::<K>("kdf");
-    let mut erased_box = eraser.erase(&pwbox).unwrap();
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut salt = None;
+                let mut params = JsonMap::new();
+                while let Some(ParamKey(key)) = map.next_key()? {
+                    if key == "salt" {
+                        if salt.is_some() {
+                            return Err(A::Error::duplicate_field("salt"));
+                        }
+                        salt = Some(map.next_value::<HexSalt<Vec<u8>>>()?.0);
+                    } else {
+                        let ParamValue(value) = map.next_value()?;
+                        if !value.is_null() {
+                            params.insert(key, value);
+                        }
+                    }
+                }
+                Ok(KdfParams {
+                    salt: salt.ok_or_else(|| A::Error::missing_field("salt"))?,
+                    inner: JsonValue::Object(params),
+                })
+            }
+        }
 
-    // Lengthen MAC.
-    erased_box.encrypted.mac.push(b'!');
-    assert_matches!(
-        eraser.restore(&erased_box).map(drop).unwrap_err(),
-        Error::MacLen
-    );
-    // Shorten MAC.
-    erased_box.encrypted.mac.pop();
-    if let Some(last_byte) = erased_box.encrypted.mac.pop() {
-        assert_matches!(
-            eraser.restore(&erased_box).map(drop).unwrap_err(),
-            Error::MacLen
-        );
-        erased_box.encrypted.mac.push(last_byte);
+        deserializer.deserialize_any(KdfParamsVisitor)
     }
+}
 
-    // Lengthen salt.
-    erased_box.kdf_params.salt.push(b'!');
-    assert_matches!(
-        eraser.restore(&erased_box).map(drop).unwrap_err(),
-        Error::SaltLen
-    );
-    // Shorten salt.
-    erased_box.kdf_params.salt.pop();
-    if let Some(last_byte) = erased_box.kdf_params.salt.pop() {
-        assert_matches!(
-            eraser.restore(&erased_box).map(drop).unwrap_err(),
-            Error::SaltLen
-        );
-        erased_box.kdf_params.salt.push(last_byte);
+/// Salt (de)serialized in the hex form.
+struct HexSalt<T>(T);
+
+impl Serialize for HexSalt<&Vec<u8>> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        HexForm::serialize(self.0, serializer)
     }
+}
 
-    // Lengthen nonce.
-    erased_box.cipher_params.iv.push(b'!');
-    assert_matches!(
-        eraser.restore(&erased_box).map(drop).unwrap_err(),
-        Error::NonceLen
-    );
-    // Shorten nonce.
-    erased_box.cipher_params.iv.pop();
-    if let Some(last_byte) = erased_box.cipher_params.iv.pop() {
-        assert_matches!(
-            eraser.restore(&erased_box).map(drop).unwrap_err(),
-            Error::NonceLen
-        );
-        erased_box.cipher_params.iv.push(last_byte);
+impl<'de> Deserialize<'de> for HexSalt<Vec<u8>> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        HexForm::deserialize(deserializer).map(HexSalt)
     }
+}
 
-    // Mutate MAC.
-    erased_box.encrypted.mac[0] ^= 1;
-    let restored = eraser.restore(&erased_box).unwrap();
-    assert_matches!(restored.open(PASSWORD).unwrap_err(), Error::MacMismatch);
-    erased_box.encrypted.mac[0] ^= 1;
+/// Removes map entries with `null` values, which are not serialized.
+fn without_nulls(value: JsonValue) -> JsonValue {
+    match value {
+        JsonValue::Object(map) => map
+            .into_iter()
+            .filter(|(_, item)| !item.is_null())
+            .map(|(key, item)| (key, without_nulls(item)))
+            .collect::<JsonMap<_, _>>()
+            .into(),
+        JsonValue::Array(items) => items.into_iter().map(without_nulls).collect(),
+        value => value,
+    }
+}
 
-    // Mutate ciphertext.
-    erased_box.encrypted.ciphertext[1] ^= 128;
-    let restored = eraser.restore(&erased_box).unwrap();
-    assert_matches!(restored.open(PASSWORD).unwrap_err(), Error::MacMismatch);
-    erased_box.encrypted.ciphertext[1] ^= 128;
+/// Checks whether the value is serialized as a table in TOML.
+fn is_table(value: &JsonValue) -> bool {
+    match value {
+        JsonValue::Object(_) => true,
+        JsonValue::Array(items) => !items.is_empty() && items.iter().all(JsonValue::is_object),
+        _ => false,
+    }
+}
 
-    // Mutate password.
-    let mut password = PASSWORD.as_bytes().to_vec();
-    password[2] ^= 16;
-    assert_matches!(restored.open(&password).unwrap_err(), Error::MacMismatch);
+fn serialize_params<M: SerializeMap>(
+    map: &mut M,
+    params: &JsonMap<String, JsonValue>,
+) -> Result<(), M::Error> {
+    let entries = params.iter().filter(|(_, value)| !value.is_null());
+    let plain_entries = entries.clone().filter(|(_, value)| !is_table(value));
+    let table_entries = entries.filter(|(_, value)| is_table(value));
+    for (key, value) in plain_entries.chain(table_entries) {
+        map.serialize_entry(key, &ParamRef(value))?;
+    }
+    Ok(())
 }
 
-#[cfg(feature = "exonum_sodiumoxide")]
-#[test]
-fn erase_pwbox() {
-    use crate::sodium::{Scrypt, XSalsa20Poly1305};
-    use rand::thread_rng;
+/// Reference to a KDF param serialized with the same rules as the top-level params.
+struct ParamRef<'a>(&'a JsonValue);
 
-    const PASSWORD: &str = "correct horse battery staple";
-    const MESSAGE: &[u8] = b"1234567890";
+impl Serialize for ParamRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            JsonValue::Object(params) => {
+                let mut map = serializer.serialize_map(None)?;
+                serialize_params(&mut map, params)?;
+                map.end()
+            }
+            JsonValue::Array(items) => serializer.collect_seq(items.iter().map(ParamRef)),
+            value => value.serialize(serializer),
+        }
+    }
+}
 
-    let mut eraser = Eraser::new();
-    let eraser = eraser
-        .add_kdf::<Scrypt>("scrypt-nacl")
-        .add_cipher::<XSalsa20Poly1305>("xsalsa20-poly1305");
+/// KDF param deserialized from an arbitrary format. Values without a JSON equivalent
+/// are converted to strings: byte buffers are hex-encoded, non-finite floats
+/// are written in the textual form, and TOML datetimes are written in the RFC 3339 form.
+struct ParamValue(JsonValue);
 
-    let pwbox =
-        PwBox::<Scrypt, XSalsa20Poly1305>::new(&mut thread_rng(), PASSWORD, MESSAGE).unwrap();
+/// Name of the single field in the map `toml` uses to represent datetimes.
+const TOML_DATETIME_FIELD: &str = "$__toml_private_datetime";
 
-    let erased_box = eraser.erase(&pwbox).unwrap();
-    let pwbox_copy = eraser.restore(&erased_box).unwrap();
-    assert_eq!(MESSAGE.len(), pwbox_copy.len());
-    assert_eq!(MESSAGE, &*pwbox_copy.open(PASSWORD).unwrap());
+impl<'de> Deserialize<'de> for ParamValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ParamVisitor;
+
+        impl<'de> Visitor<'de> for ParamVisitor {
+            type Value = JsonValue;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("KDF param")
+            }
+
+            fn visit_bool<E: DeError>(self, value: bool) -> Result<Self::Value, E> {
+                Ok(value.into())
+            }
+
+            fn visit_i64<E: DeError>(self, value: i64) -> Result<Self::Value, E> {
+                Ok(value.into())
+            }
+
+            fn visit_u64<E: DeError>(self, value: u64) -> Result<Self::Value, E> {
+                Ok(value.into())
+            }
+
+            fn visit_f64<E: DeError>(self, value: f64) -> Result<Self::Value, E> {
+                Ok(JsonNumber::from_f64(value)
+                    .map_or_else(|| JsonValue::String(value.to_string()), JsonValue::Number))
+            }
+
+            fn visit_str<E: DeError>(self, value: &str) -> Result<Self::Value, E> {
+                Ok(value.into())
+            }
+
+            fn visit_string<E: DeError>(self, value: String) -> Result<Self::Value, E> {
+                Ok(value.into())
+            }
+
+            fn visit_bytes<E: DeError>(self, value: &[u8]) -> Result<Self::Value, E> {
+                let mut hex = String::with_capacity(value.len() * 2);
+                for byte in value {
+                    write!(hex, "{:02x}", byte).expect("writing to string cannot fail");
+                }
+                Ok(hex.into())
+            }
+
+            fn visit_none<E: DeError>(self) -> Result<Self::Value, E> {
+                Ok(JsonValue::Null)
+            }
+
+            fn visit_unit<E: DeError>(self) -> Result<Self::Value, E> {
+                Ok(JsonValue::Null)
+            }
+
+            fn visit_some<D: Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> Result<Self::Value, D::Error> {
+                deserializer.deserialize_any(self)
+            }
+
+            fn visit_newtype_struct<D: Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> Result<Self::Value, D::Error> {
+                deserializer.deserialize_any(self)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut items = Vec::new();
+                while let Some(ParamValue(item)) = seq.next_element()? {
+                    items.push(item);
+                }
+                Ok(JsonValue::Array(items))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut params = JsonMap::new();
+                while let Some(ParamKey(key)) = map.next_key()? {
+                    let ParamValue(value) = map.next_value()?;
+                    if !value.is_null() {
+                        params.insert(key, value);
+                    }
+                }
+
+                if params.len() == 1 {
+                    if let Some(JsonValue::String(datetime)) = params.get(TOML_DATETIME_FIELD) {
+                        return Ok(JsonValue::String(datetime.clone()));
+                    }
+                }
+                Ok(JsonValue::Object(params))
+            }
+        }
+
+        deserializer.deserialize_any(ParamVisitor).map(ParamValue)
+    }
+}
+
+/// Key of a KDF param. Some formats (e.g., YAML) allow non-string keys; such keys
+/// are converted to strings.
+struct ParamKey(String);
+
+impl<'de> Deserialize<'de> for ParamKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KeyVisitor;
+
+        impl<'de> Visitor<'de> for KeyVisitor {
+            type Value = String;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("KDF param name")
+            }
+
+            fn visit_bool<E: DeError>(self, value: bool) -> Result<Self::Value, E> {
+                Ok(value.to_string())
+            }
+
+            fn visit_i64<E: DeError>(self, value: i64) -> Result<Self::Value, E> {
+                Ok(value.to_string())
+            }
+
+            fn visit_u64<E: DeError>(self, value: u64) -> Result<Self::Value, E> {
+                Ok(value.to_string())
+            }
+
+            fn visit_f64<E: DeError>(self, value: f64) -> Result<Self::Value, E> {
+                Ok(value.to_string())
+            }
+
+            fn visit_str<E: DeError>(self, value: &str) -> Result<Self::Value, E> {
+                Ok(value.to_owned())
+            }
+
+            fn visit_string<E: DeError>(self, value: String) -> Result<Self::Value, E> {
+                Ok(value)
+            }
+        }
+
+        deserializer.deserialize_any(KeyVisitor).map(ParamKey)
+    }
+}
+
+impl<K, C> PwBox<K, C>
+where
+    K: DeriveKey + Serialize,
+    C: Cipher,
+{
+    /// Converts the box into the serializable form using the specified `eraser`.
+    /// This is equivalent to [`Eraser::erase()`]; the box is retained and can still be used.
+    pub fn to_erased(&self, eraser: &Eraser) -> Result<ErasedPwBox, EraseError> {
+        eraser.erase(self)
+    }
+}
+
+impl<K, C> PwBox<K, C>
+where
+    K: DeriveKey + Clone + Serialize,
+    C: Cipher,
+{
+    /// Converts the box into a [`RestoredPwBox`] with the type-erased KDF and cipher.
+    /// The KDF and cipher names are looked up in the specified `eraser`, so that
+    /// the returned box can be erased via [`Eraser::erase_restored()`].
+    ///
+    /// This allows to store freshly sealed boxes together with restored ones, e.g., in a `Vec`.
+    /// Unlike restoring the erased box, the conversion does not check the KDF limits
+    /// of the `eraser`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as [`Eraser::erase()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rand::thread_rng;
+    /// # use pwbox::{rcrypto::RustCrypto, sodium::{Scrypt, Sodium}, Eraser, Suite};
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut eraser = Eraser::new();
+    /// eraser.add_suite::<Sodium>()?;
+    /// let mut other_eraser = Eraser::new();
+    /// other_eraser.add_suite::<RustCrypto>()?;
+    /// # let light_scrypt = pwbox::rcrypto::Scrypt(pwbox::ScryptParams::custom(6, 16));
+    ///
+    /// let boxes = vec![
+    ///     Sodium::build_box(&mut thread_rng())
+    /// #       .kdf(Scrypt::light())
+    ///         .seal("correct horse", b"battery staple")?
+    ///         .to_restored(&eraser)?,
+    ///     RustCrypto::build_box(&mut thread_rng())
+    /// #       .kdf(light_scrypt)
+    ///         .seal("correct horse", b"battery staple")?
+    ///         .to_restored(&other_eraser)?,
+    /// ];
+    /// for pwbox in &boxes {
+    ///     assert_eq!(&*pwbox.open("correct horse")?, b"battery staple");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_restored(&self, eraser: &Eraser) -> Result<RestoredPwBox, EraseError> {
+        let erased_box = eraser.erase(self)?;
+        let kdf: DynKdf = Arc::new(self.inner.kdf.clone());
+        let cipher: ErasedCipher = Arc::new(CipherObject::<C>::default());
+        let inner = PwBoxInner {
+            salt: erased_box.kdf_params.salt,
+            nonce: erased_box.cipher_params.iv,
+            encrypted: erased_box.encrypted,
+            associated_data: self.inner.associated_data,
+            metadata: self.inner.metadata.clone(),
+            password_policy: self.inner.password_policy,
+            kdf,
+            cipher,
+        };
+        Ok(RestoredPwBox {
+            inner,
+            kdf_name: erased_box.kdf,
+            cipher_name: erased_box.cipher,
+            kdf_params: erased_box.kdf_params.inner,
+        })
+    }
+}
+
+/// Serializes the box in the same format as [`ErasedPwBox`], except that KDF and cipher
+/// names are not included.
+impl<K, C> Serialize for PwBox<K, C>
+where
+    K: DeriveKey + Serialize,
+    C: Cipher,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !self.inner.metadata.is_empty() {
+            // KDF and cipher names are required to verify the box.
+            return Err(S::Error::custom(
+                "boxes in the v2 format can only be serialized with an `Eraser`",
+            ));
+        }
+        let kdf_params = serde_json::to_value(&self.inner.kdf).map_err(S::Error::custom)?;
+        let kdf_params = without_nulls(kdf_params);
+        let erased = ErasedPwBox::from_inner(&self.inner, String::new(), String::new(), kdf_params);
+        erased.serialize(serializer)
+    }
+}
+
+/// Deserializes the box from the [`ErasedPwBox`] format. KDF and cipher names, if present,
+/// are not checked; salt, nonce and MAC lengths are checked against `K` and `C`.
+impl<'de, K, C> Deserialize<'de> for PwBox<K, C>
+where
+    K: DeriveKey + DeserializeOwned,
+    C: Cipher,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let erased = ErasedPwBox::deserialize(deserializer)?;
+        erased.to_typed().map_err(D::Error::custom)
+    }
+}
+
+/// Header of a box with the ciphertext stored separately, e.g., in a binary file
+/// or an object store. Created with [`Eraser::erase_detached()`] and restored with
+/// [`Eraser::restore_detached()`].
+///
+/// The header is serialized in the same way as [`ErasedPwBox`], except that
+/// the `ciphertext` field is replaced with `ciphertext_len` (the byte length
+/// of the ciphertext) and `ciphertext_checksum` (a 64-bit FNV-1a checksum
+/// of the ciphertext in the hex form). The checksum allows detecting a mismatched
+/// or damaged ciphertext file before decryption. It is not cryptographically secure;
+/// the integrity of the ciphertext is still ensured by the MAC.
+///
+/// # Examples
+///
+/// ```
+/// # use rand::thread_rng;
+/// # use pwbox::{sodium::{Scrypt, Sodium}, Eraser, ErasedPwBoxHeader, Suite};
+/// let pwbox = Sodium::build_box(&mut thread_rng())
+/// #   .kdf(Scrypt::light())
+///     .seal("correct horse", vec![0_u8; 1 << 16])?;
+/// let mut eraser = Eraser::new();
+/// eraser.add_suite::<Sodium>()?;
+/// let (header, ciphertext) = eraser.erase_detached(&pwbox)?;
+/// assert_eq!(header.ciphertext_len(), 1 << 16);
+/// let header_json = serde_json::to_string(&header)?;
+/// assert!(header_json.len() < 1_000);
+///
+/// let header: ErasedPwBoxHeader = serde_json::from_str(&header_json)?;
+/// let restored = eraser.restore_detached(&header, &ciphertext)?;
+/// assert_eq!(restored.open("correct horse")?.len(), 1 << 16);
+/// # Ok::<_, anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErasedPwBoxHeader {
+    /// Box with an empty ciphertext.
+    pwbox: ErasedPwBox,
+    ciphertext_len: u64,
+    ciphertext_checksum: [u8; 8],
+}
+
+/// Computes the 64-bit FNV-1a checksum of the ciphertext.
+fn ciphertext_checksum(ciphertext: &[u8]) -> [u8; 8] {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    let checksum = ciphertext.iter().fold(OFFSET_BASIS, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    });
+    checksum.to_be_bytes()
+}
+
+impl ErasedPwBoxHeader {
+    fn detach(mut pwbox: ErasedPwBox) -> (Self, Vec<u8>) {
+        let ciphertext = mem::take(&mut pwbox.encrypted.ciphertext);
+        let header = ErasedPwBoxHeader {
+            pwbox,
+            ciphertext_len: ciphertext.len() as u64,
+            ciphertext_checksum: ciphertext_checksum(&ciphertext),
+        };
+        (header, ciphertext)
+    }
+
+    fn attach(&self, ciphertext: &[u8]) -> Result<ErasedPwBox, Error> {
+        if ciphertext.len() as u64 != self.ciphertext_len
+            || ciphertext_checksum(ciphertext) != self.ciphertext_checksum
+        {
+            return Err(Error::CiphertextMismatch);
+        }
+        let mut pwbox = self.pwbox.clone();
+        pwbox.encrypted.ciphertext = ciphertext.to_vec();
+        Ok(pwbox)
+    }
+
+    /// Returns the expected byte length of the detached ciphertext.
+    pub fn ciphertext_len(&self) -> u64 {
+        self.ciphertext_len
+    }
+
+    /// Returns the format version of the box.
+    pub fn format_version(&self) -> FormatVersion {
+        self.pwbox.version
+    }
+
+    /// Returns the name of the KDF used in the box.
+    pub fn kdf_name(&self) -> &str {
+        &self.pwbox.kdf
+    }
+
+    /// Returns the name of the cipher used in the box.
+    pub fn cipher_name(&self) -> &str {
+        &self.pwbox.cipher
+    }
+}
+
+/// Layout of `ErasedPwBoxHeader` for human-readable formats.
+#[derive(Serialize, Deserialize)]
+struct HumanReadableHeader {
+    #[serde(default, skip_serializing_if = "is_v1")]
+    version: FormatVersion,
+    ciphertext_len: u64,
+    #[serde(with = "HexForm")]
+    ciphertext_checksum: [u8; 8],
+    #[serde(with = "HexForm")]
+    mac: Vec<u8>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    kdf: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    cipher: String,
+    #[serde(rename = "kdfparams")]
+    kdf_params: KdfParams,
+    #[serde(rename = "cipherparams")]
+    cipher_params: CipherParams,
+    #[serde(
+        default,
+        rename = "passwordpolicy",
+        skip_serializing_if = "PasswordPolicy::is_raw"
+    )]
+    password_policy: PasswordPolicy,
+}
+
+/// In binary formats, the header is serialized as a tuple of the box with an empty
+/// ciphertext, the ciphertext length and its checksum.
+impl Serialize for ErasedPwBoxHeader {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            let pwbox = self.pwbox.clone();
+            HumanReadableHeader {
+                version: pwbox.version,
+                ciphertext_len: self.ciphertext_len,
+                ciphertext_checksum: self.ciphertext_checksum,
+                mac: pwbox.encrypted.mac,
+                kdf: pwbox.kdf,
+                cipher: pwbox.cipher,
+                kdf_params: pwbox.kdf_params,
+                cipher_params: pwbox.cipher_params,
+                password_policy: pwbox.password_policy,
+            }
+            .serialize(serializer)
+        } else {
+            let checksum = self.ciphertext_checksum;
+            (&self.pwbox, self.ciphertext_len, checksum).serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ErasedPwBoxHeader {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let this = HumanReadableHeader::deserialize(deserializer)?;
+            Ok(ErasedPwBoxHeader {
+                pwbox: ErasedPwBox {
+                    version: this.version,
+                    encrypted: CipherOutput {
+                        ciphertext: Vec::new(),
+                        mac: this.mac,
+                    },
+                    kdf: this.kdf,
+                    cipher: this.cipher,
+                    kdf_params: this.kdf_params,
+                    cipher_params: this.cipher_params,
+                    password_policy: this.password_policy,
+                },
+                ciphertext_len: this.ciphertext_len,
+                ciphertext_checksum: this.ciphertext_checksum,
+            })
+        } else {
+            let (pwbox, ciphertext_len, ciphertext_checksum) =
+                <(ErasedPwBox, u64, [u8; 8])>::deserialize(deserializer)?;
+            if !pwbox.encrypted.ciphertext.is_empty() {
+                return Err(D::Error::custom("header must not contain ciphertext"));
+            }
+            Ok(ErasedPwBoxHeader {
+                pwbox,
+                ciphertext_len,
+                ciphertext_checksum,
+            })
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CipherParams {
+    #[serde(with = "HexForm")]
+    iv: Vec<u8>,
+    /// Was the box sealed with associated data? The data itself is not stored.
+    #[serde(default, skip_serializing_if = "is_false")]
+    aad: bool,
+    /// Byte size of plaintext chunks for streaming boxes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    chunk_size: Option<u32>,
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)] // required by `serde`
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// Canonical names of KDFs and ciphers.
+///
+/// Suites in this crate register implementations under these names. An algorithm implemented
+/// by several backends is registered under the same name in each of them, so that a box
+/// sealed with one backend can be restored and opened with another one.
+pub mod names {
+    /// `scrypt` KDF with the `libsodium` parametrization (`opslimit` and `memlimit`).
+    pub const SCRYPT_NACL: &str = "scrypt-nacl";
+    /// `scrypt` KDF with the original parametrization (`n`, `r` and `p`).
+    pub const SCRYPT: &str = "scrypt";
+    /// Argon2 KDF.
+    pub const ARGON2: &str = "argon2";
+    /// PBKDF2 KDF.
+    pub const PBKDF2: &str = "pbkdf2";
+    /// Using the password as the key, i.e., [`NoKdf`](crate::NoKdf).
+    pub const NONE: &str = "none";
+    /// Alias for [`NONE`] registered by the suites in this crate, so that boxes
+    /// recording the KDF under this name can be restored.
+    pub const RAW: &str = "raw";
+    /// KDF external to the box, i.e., [`ExternalKdf`](crate::ExternalKdf).
+    pub const EXTERNAL: &str = "external";
+
+    /// XSalsa20 stream cipher with Poly1305 MAC, i.e., the `secretbox` construction of NaCl.
+    pub const XSALSA20_POLY1305: &str = "xsalsa20-poly1305";
+    /// XChaCha20 stream cipher with Poly1305 MAC.
+    pub const XCHACHA20_POLY1305: &str = "xchacha20-poly1305";
+    /// ChaCha20 stream cipher with Poly1305 MAC as per RFC 8439.
+    pub const CHACHA20_POLY1305: &str = "chacha20-poly1305";
+    /// Explicit name for [`CHACHA20_POLY1305`].
+    pub const CHACHA20_POLY1305_IETF: &str = "chacha20-poly1305-ietf";
+    /// AES-128 cipher in CTR mode with Keccak256-based MAC, as used in Ethereum keystores.
+    pub const AES_128_CTR: &str = "aes-128-ctr";
+    /// AES-256 cipher in CTR mode with Keccak256-based MAC.
+    pub const AES_256_CTR: &str = "aes-256-ctr";
+    /// AES-128 cipher in GCM mode.
+    pub const AES_128_GCM: &str = "aes-128-gcm";
+    /// AES-256 cipher in GCM mode.
+    pub const AES_256_GCM: &str = "aes-256-gcm";
+    /// AES-128 cipher in CTR mode with HMAC-SHA-512.
+    pub const AES_128_CTR_HMAC_SHA512: &str = "aes-128-ctr/hmac-sha512";
+    /// AES-256 cipher in CTR mode with HMAC-SHA-512.
+    pub const AES_256_CTR_HMAC_SHA512: &str = "aes-256-ctr/hmac-sha512";
+}
+
+type ErasedCipher = Arc<dyn ObjectSafeCipher>;
+type DynKdf = Arc<dyn DeriveKey>;
+type CipherFactory = Arc<dyn Fn() -> ErasedCipher + Send + Sync>;
+type KdfFactory = Arc<dyn Fn(JsonValue) -> Result<DynKdf, JsonError> + Send + Sync>;
+/// Parses a PHC string into serialized KDF params and the salt.
+type PhcParser = Arc<dyn Fn(&str) -> Result<(JsonValue, Vec<u8>), Error> + Send + Sync>;
+
+/// Errors occurring during erasing a `PwBox`.
+#[derive(Debug)]
+pub enum EraseError {
+    /// KDF used in the box is not registered with the `Eraser`.
+    NoKdf,
+
+    /// Cipher used in the box is not registered with the `Eraser`.
+    NoCipher,
+
+    /// Error serializing KDF params.
+    SerializeKdf(JsonError),
+
+    /// The box is in the v2 format, and its authenticated metadata does not match
+    /// the KDF and cipher names and KDF params of the box in the `Eraser`.
+    MetadataMismatch,
+}
+
+impl fmt::Display for EraseError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EraseError::NoKdf => {
+                formatter.write_str("KDF used in the box is not registered with the `Eraser`")
+            }
+            EraseError::NoCipher => {
+                formatter.write_str("cipher used in the box is not registered with the `Eraser`")
+            }
+            EraseError::SerializeKdf(e) => write!(formatter, "error serializing KDF params: {}", e),
+            EraseError::MetadataMismatch => {
+                formatter.write_str("box metadata does not match KDF and cipher names")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EraseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        if let EraseError::SerializeKdf(e) = self {
+            Some(e)
+        } else {
+            None
+        }
+    }
+}
+
+/// Helper structure to convert password-encrypted boxes to a serializable format and back.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(all(feature = "exonum_sodiumoxide", feature = "rust-crypto"))]
+/// # fn main() {
+/// # use rand::thread_rng;
+/// # use pwbox::{Eraser, Suite,
+/// #     rcrypto::{Scrypt as SomeKdf, Aes128Gcm as SomeCipher},
+/// #     sodium::Sodium as SomeSuite};
+/// let mut eraser = Eraser::new();
+/// // Register separate KDFs and ciphers
+/// eraser.add_kdf::<SomeKdf>("some-kdf").unwrap();
+/// eraser.add_cipher::<SomeCipher>("some-cipher").unwrap();
+/// // Add a suite.
+/// eraser.add_suite::<SomeSuite>().unwrap();
+///
+/// // Erase a `PwBox`.
+/// let pwbox = SomeSuite::build_box(&mut thread_rng())
+///     .seal("password", b"some data")
+///     .unwrap();
+/// let erased = eraser.erase(&pwbox).unwrap();
+/// // `erased` can now be serialized somewhere, e.g., in JSON format.
+///
+/// // Restore a `PwBox`.
+/// let restored = eraser.restore(&erased).unwrap();
+/// assert_eq!(&*restored.open("password").unwrap(), b"some data");
+/// # } // main
+/// # #[cfg(not(all(feature = "exonum_sodiumoxide", feature = "rust-crypto")))]
+/// # fn main() {}
+/// ```
+///
+/// # Sharing
+///
+/// An `Eraser` is `Send` and `Sync`, so a single instance can be shared among threads
+/// (e.g., in a `static` or an `Arc`). Cloning an `Eraser` is cheap: registrations are
+/// stored behind an `Arc` and are only copied when a clone registers new implementations.
+/// Request-specific registrations can also be layered over a shared `Eraser` without
+/// modifying it; see [`Self::restore_with()`].
+///
+/// ```
+/// # use rand::thread_rng;
+/// # use std::thread;
+/// # use pwbox::{sodium::{Scrypt, Sodium}, Eraser, Suite};
+/// let eraser = Eraser::with_suites::<(Sodium,)>()?;
+/// let pwbox = Sodium::build_box(&mut thread_rng())
+/// #   .kdf(Scrypt::light())
+///     .seal("correct horse", b"battery staple")?;
+/// let erased = eraser.erase(&pwbox)?;
+///
+/// let eraser_copy = eraser.clone();
+/// let handle = thread::spawn(move || {
+///     let restored = eraser_copy.restore(&erased).unwrap();
+///     restored.open("correct horse").unwrap()
+/// });
+/// assert_eq!(&*handle.join().unwrap(), b"battery staple");
+/// # Ok::<_, anyhow::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct Eraser {
+    registry: Arc<Registry>,
+    format_version: FormatVersion,
+    kdf_limits: KdfLimits,
+}
+
+/// Implementations registered in an [`Eraser`], shared among its clones.
+#[derive(Clone, Default)]
+struct Registry {
+    ciphers: BTreeMap<String, CipherFactory>,
+    kdfs: BTreeMap<String, KdfFactory>,
+    cipher_names: BTreeMap<TypeId, String>,
+    kdf_names: BTreeMap<TypeId, String>,
+    phc_parsers: BTreeMap<String, PhcParser>,
+    unauthenticated_ciphers: BTreeMap<String, Arc<dyn ObjectSafeUnauthenticatedCipher>>,
+    macs: BTreeMap<String, Arc<dyn ObjectSafeMac>>,
+    unauthenticated_cipher_names: BTreeMap<TypeId, String>,
+    mac_names: BTreeMap<TypeId, String>,
+    aliases: BTreeMap<String, String>,
+}
+
+impl fmt::Debug for Eraser {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let registry = &*self.registry;
+        formatter
+            .debug_struct("Eraser")
+            .field("ciphers", &registry.ciphers.keys().collect::<Vec<_>>())
+            .field("kdfs", &registry.kdfs.keys().collect::<Vec<_>>())
+            .field(
+                "unauthenticated_ciphers",
+                &registry.unauthenticated_ciphers.keys().collect::<Vec<_>>(),
+            )
+            .field("macs", &registry.macs.keys().collect::<Vec<_>>())
+            .field("aliases", &registry.aliases)
+            .field("format_version", &self.format_version)
+            .field("kdf_limits", &self.kdf_limits)
+            .finish()
+    }
+}
+
+impl Default for Eraser {
+    fn default() -> Self {
+        Eraser::new()
+    }
+}
+
+impl Eraser {
+    /// Creates an `Eraser` with no ciphers or KDFs.
+    pub fn new() -> Self {
+        Eraser {
+            registry: Arc::new(Registry::default()),
+            format_version: FormatVersion::V1,
+            kdf_limits: KdfLimits::default(),
+        }
+    }
+
+    /// Creates an `Eraser` with all KDFs and ciphers from the specified suites, which
+    /// are given as a tuple (e.g., `(Sodium,)` or `(MySuite, OtherSuite)`). This is
+    /// equivalent to calling [`Self::add_suite()`] for each suite in order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as [`Self::add_suite()`]. In particular,
+    /// the crypto backends provided by this crate register their implementations under
+    /// the same [names], so they cannot be combined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(all(feature = "exonum_sodiumoxide", feature = "rust-crypto"))]
+    /// # fn main() -> Result<(), pwbox::Error> {
+    /// # use pwbox::{rcrypto::RustCrypto, sodium::Sodium, Eraser, Error};
+    /// let eraser = Eraser::with_suites::<(Sodium,)>()?;
+    /// assert!(eraser.kdfs().any(|name| name == "scrypt-nacl"));
+    ///
+    /// let err = Eraser::with_suites::<(Sodium, RustCrypto)>().unwrap_err();
+    /// assert!(matches!(err, Error::DuplicateName(_)));
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(all(feature = "exonum_sodiumoxide", feature = "rust-crypto")))]
+    /// # fn main() {}
+    /// ```
+    pub fn with_suites<T: Suites>() -> Result<Self, Error> {
+        let mut eraser = Eraser::new();
+        T::add_suites(&mut eraser)?;
+        Ok(eraser)
+    }
+
+    /// Returns the format version of boxes sealed with [`Self::seal()`].
+    pub fn format_version(&self) -> FormatVersion {
+        self.format_version
+    }
+
+    /// Sets the format version of boxes sealed with [`Self::seal()`]. The default version
+    /// is [`FormatVersion::V1`].
+    ///
+    /// The version does not influence other methods: [`Self::erase()`] preserves the format
+    /// a box was sealed in, since converting a box to another format requires re-sealing it,
+    /// and restoring methods accept boxes in all formats.
+    pub fn set_format_version(&mut self, version: FormatVersion) -> &mut Self {
+        self.format_version = version;
+        self
+    }
+
+    /// Returns the limits on the resources consumed by KDFs of restored boxes.
+    pub fn kdf_limits(&self) -> KdfLimits {
+        self.kdf_limits
+    }
+
+    /// Sets the limits on the resources consumed by KDFs of restored boxes. By default,
+    /// the resources are not limited.
+    ///
+    /// The limits are checked by restoring methods (e.g., [`Self::restore()`]) before
+    /// a box is returned; thus, a box with KDF params exceeding the limits is rejected
+    /// without attempting key derivation. The limits are only enforced for KDFs providing
+    /// [a cost estimate](DeriveKey::cost()).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pwbox::{Eraser, KdfLimits};
+    /// let mut eraser = Eraser::new();
+    /// eraser.set_kdf_limits(KdfLimits {
+    ///     max_memory_bytes: 64 << 20,
+    ///     ..KdfLimits::default()
+    /// });
+    /// ```
+    pub fn set_kdf_limits(&mut self, limits: KdfLimits) -> &mut Self {
+        self.kdf_limits = limits;
+        self
+    }
+
+    /// Checks that the KDF params are valid and do not exceed the KDF limits.
+    fn check_kdf(&self, kdf: &dyn DeriveKey) -> Result<(), Error> {
+        kdf.validate_params().map_err(Error::DeriveKey)?;
+        match kdf.cost() {
+            Some(cost) if !self.kdf_limits.allows(cost) => Err(Error::KdfParamsTooExpensive(cost)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks whether an implementation with the specified `type_id` can be registered
+    /// under `name`. Returns `Ok(false)` if the implementation is already registered
+    /// under the same name, and thus the registration is a no-op.
+    fn check_registration<T>(
+        names: &BTreeMap<TypeId, String>,
+        factories: &BTreeMap<String, T>,
+        aliases: &BTreeMap<String, String>,
+        type_id: TypeId,
+        name: &str,
+    ) -> Result<bool, Error> {
+        if aliases.contains_key(name) {
+            return Err(Error::DuplicateName(name.to_owned()));
+        }
+        match names.get(&type_id) {
+            Some(old_name) if old_name == name => Ok(false),
+            Some(_) => Err(Error::DuplicateName(name.to_owned())),
+            None if factories.contains_key(name) => Err(Error::DuplicateName(name.to_owned())),
+            None => Ok(true),
+        }
+    }
+
+    /// Adds a cipher. Registering the same cipher under the same name several times
+    /// is a no-op.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DuplicateName`] if `cipher_name` is already taken by another cipher
+    /// or an alias, or if the cipher is already registered under a different name.
+    pub fn add_cipher<C>(&mut self, cipher_name: &str) -> Result<&mut Self, Error>
+    where
+        C: Cipher,
+    {
+        let type_id = TypeId::of::<C>();
+        let Registry {
+            cipher_names: names,
+            ciphers,
+            aliases,
+            ..
+        } = &*self.registry;
+        if Self::check_registration(names, ciphers, aliases, type_id, cipher_name)? {
+            let factory = || {
+                let cipher_object = CipherObject::<C>::default();
+                Arc::new(cipher_object) as Arc<dyn ObjectSafeCipher>
+            };
+            let registry = Arc::make_mut(&mut self.registry);
+            registry
+                .ciphers
+                .insert(cipher_name.to_owned(), Arc::new(factory));
+            registry
+                .cipher_names
+                .insert(type_id, cipher_name.to_owned());
+        }
+        Ok(self)
+    }
+
+    /// Adds a cipher with sizes of keys, nonces and MACs determined at runtime.
+    /// The `factory` is called each time the cipher is instantiated, e.g., when restoring
+    /// a box. Boxes can be sealed with a dynamic cipher via [`Self::seal_with_cipher()`].
+    ///
+    /// Unlike with [`Self::add_cipher()`], there is no way to detect whether the same
+    /// cipher is registered again, so registering a dynamic cipher under a taken name
+    /// is always an error.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DuplicateName`] if `cipher_name` is already taken by another cipher
+    /// or an alias.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(feature = "rust-crypto")]
+    /// # fn main() -> anyhow::Result<()> {
+    /// # use rand::thread_rng;
+    /// use pwbox::{rcrypto::{Aes256Gcm, RustCrypto, Scrypt}, Cipher, CipherOutput, DynCipher,
+    ///     Eraser, MacMismatch, ScryptParams};
+    /// use std::sync::Arc;
+    ///
+    /// /// Cipher configured at runtime, e.g., from a config file.
+    /// struct ConfiguredCipher;
+    ///
+    /// impl DynCipher for ConfiguredCipher {
+    ///     fn key_len(&self) -> usize { Aes256Gcm::KEY_LEN }
+    ///     fn nonce_len(&self) -> usize { Aes256Gcm::NONCE_LEN }
+    ///     fn mac_len(&self) -> usize { Aes256Gcm::MAC_LEN }
+    ///
+    ///     fn seal(&self, msg: &[u8], aad: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
+    ///         Aes256Gcm::seal(msg, aad, nonce, key)
+    ///     }
+    ///
+    ///     fn open(
+    ///         &self,
+    ///         output: &mut [u8],
+    ///         enc: &CipherOutput,
+    ///         aad: &[u8],
+    ///         nonce: &[u8],
+    ///         key: &[u8],
+    ///     ) -> Result<(), MacMismatch> {
+    ///         Aes256Gcm::open(output, enc, aad, nonce, key)
+    ///     }
+    /// }
+    ///
+    /// let mut eraser = Eraser::new();
+    /// eraser
+    ///     .add_suite::<RustCrypto>()?
+    ///     .add_dyn_cipher("configured", || Arc::new(ConfiguredCipher))?;
+    /// # let kdf = Scrypt(ScryptParams::custom(6, 16));
+    /// # let rng = &mut thread_rng();
+    /// // let kdf = Scrypt::default();
+    /// let erased = eraser.seal_with_cipher(rng, "configured", kdf, "correct horse", "staple")?;
+    /// assert_eq!(erased.cipher_name(), "configured");
+    /// let restored = eraser.restore(&erased)?;
+    /// assert_eq!(&*restored.open("correct horse")?, b"staple");
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(feature = "rust-crypto"))]
+    /// # fn main() {}
+    /// ```
+    pub fn add_dyn_cipher<F>(&mut self, cipher_name: &str, factory: F) -> Result<&mut Self, Error>
+    where
+        F: Fn() -> Arc<dyn DynCipher> + Send + Sync + 'static,
+    {
+        let registry = &*self.registry;
+        if registry.ciphers.contains_key(cipher_name) || registry.aliases.contains_key(cipher_name)
+        {
+            return Err(Error::DuplicateName(cipher_name.to_owned()));
+        }
+
+        let name = cipher_name.to_owned();
+        let factory = move || {
+            let cipher_object = DynCipherObject::new(factory(), &name);
+            Arc::new(cipher_object) as Arc<dyn ObjectSafeCipher>
+        };
+        Arc::make_mut(&mut self.registry)
+            .ciphers
+            .insert(cipher_name.to_owned(), Arc::new(factory));
+        Ok(self)
+    }
+
+    /// Adds an unauthenticated cipher, which can be composed with any MAC registered
+    /// via [`Self::add_mac()`]. Registering the same cipher under the same name several
+    /// times is a no-op.
+    ///
+    /// A [`CipherWithMac`](crate::CipherWithMac) with the default key split, which is
+    /// not registered via [`Self::add_cipher()`], is named after its halves as
+    /// `{cipher_name}+{mac_name}` (e.g., `aes-128-ctr+hmac-sha256`). Thus, `cipher_name`
+    /// should not contain `+` chars.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DuplicateName`] if `cipher_name` is already taken by another
+    /// unauthenticated cipher or an alias, or if the cipher is already registered
+    /// under a different name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(feature = "rust-crypto")]
+    /// # fn main() -> anyhow::Result<()> {
+    /// # use rand::thread_rng;
+    /// use pwbox::{rcrypto::{Aes128Ctr, HmacSha512, Pbkdf2}, CipherWithMac, Eraser, PwBoxBuilder};
+    ///
+    /// let mut eraser = Eraser::new();
+    /// eraser
+    ///     .add_kdf::<Pbkdf2>("pbkdf2")?
+    ///     .add_unauthenticated_cipher::<Aes128Ctr>("aes-128-ctr")?
+    ///     .add_mac::<HmacSha512>("hmac-sha512")?;
+    ///
+    /// type Aes128CtrHmac = CipherWithMac<Aes128Ctr, HmacSha512>;
+    /// let pwbox = PwBoxBuilder::<Pbkdf2, Aes128CtrHmac>::new(&mut thread_rng())
+    /// #   .kdf(Pbkdf2 { c: 1_000, ..Pbkdf2::default() })
+    ///     .seal("correct horse", "battery staple")?;
+    /// let erased = eraser.erase(&pwbox)?;
+    /// assert_eq!(erased.cipher_name(), "aes-128-ctr+hmac-sha512");
+    /// let restored = eraser.restore(&erased)?;
+    /// assert_eq!(&*restored.open("correct horse")?, b"battery staple");
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(feature = "rust-crypto"))]
+    /// # fn main() {}
+    /// ```
+    pub fn add_unauthenticated_cipher<C>(&mut self, cipher_name: &str) -> Result<&mut Self, Error>
+    where
+        C: UnauthenticatedCipher,
+    {
+        let type_id = TypeId::of::<C>();
+        let Registry {
+            unauthenticated_cipher_names: names,
+            unauthenticated_ciphers: ciphers,
+            aliases,
+            ..
+        } = &*self.registry;
+        if Self::check_registration(names, ciphers, aliases, type_id, cipher_name)? {
+            let cipher_object = Arc::new(CipherObject::<C>::default());
+            let registry = Arc::make_mut(&mut self.registry);
+            registry
+                .unauthenticated_ciphers
+                .insert(cipher_name.to_owned(), cipher_object);
+            registry
+                .unauthenticated_cipher_names
+                .insert(type_id, cipher_name.to_owned());
+        }
+        Ok(self)
+    }
+
+    /// Adds a MAC, which can be composed with any cipher registered via
+    /// [`Self::add_unauthenticated_cipher()`]. Registering the same MAC under the same name
+    /// several times is a no-op. See `add_unauthenticated_cipher()` for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DuplicateName`] if `mac_name` is already taken by another MAC
+    /// or an alias, or if the MAC is already registered under a different name.
+    pub fn add_mac<M>(&mut self, mac_name: &str) -> Result<&mut Self, Error>
+    where
+        M: Mac,
+    {
+        let type_id = TypeId::of::<M>();
+        let Registry {
+            mac_names: names,
+            macs,
+            aliases,
+            ..
+        } = &*self.registry;
+        if Self::check_registration(names, macs, aliases, type_id, mac_name)? {
+            let mac_object = Arc::new(CipherObject::<M>::default());
+            let registry = Arc::make_mut(&mut self.registry);
+            registry.macs.insert(mac_name.to_owned(), mac_object);
+            registry.mac_names.insert(type_id, mac_name.to_owned());
+        }
+        Ok(self)
+    }
+
+    /// Adds a key derivation function. Registering the same KDF under the same name
+    /// several times is a no-op.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DuplicateName`] if `kdf_name` is already taken by another KDF
+    /// or an alias, or if the KDF is already registered under a different name.
+    pub fn add_kdf<K>(&mut self, kdf_name: &str) -> Result<&mut Self, Error>
+    where
+        K: DeriveKey + DeserializeOwned + Default,
+    {
+        let type_id = TypeId::of::<K>();
+        let Registry {
+            kdf_names: names,
+            kdfs,
+            aliases,
+            ..
+        } = &*self.registry;
+        if Self::check_registration(names, kdfs, aliases, type_id, kdf_name)? {
+            let factory = |options| {
+                let kdf: K = serde_json::from_value(options)?;
+                Ok(Arc::new(kdf) as Arc<dyn DeriveKey>)
+            };
+            let registry = Arc::make_mut(&mut self.registry);
+            registry.kdfs.insert(kdf_name.to_owned(), Arc::new(factory));
+            registry.kdf_names.insert(type_id, kdf_name.to_owned());
+        }
+        Ok(self)
+    }
+
+    /// Adds a key derivation function similarly to [`Self::add_kdf()`], additionally
+    /// allowing to restore boxes with the KDF params recorded as a [PHC string],
+    /// such as `"kdfparams": "$scrypt$ln=15,r=8,p=1$c29tZXNhbHQ"`. The salt of such boxes
+    /// is taken from the string. This is used by the built-in suites for `scrypt`
+    /// and Argon2 KDFs.
+    ///
+    /// When a box with a PHC string is restored, its params are converted into
+    /// the ordinary form; thus, erasing the restored box records the params as a map.
+    /// PHC strings are not supported in the v2 format, which authenticates KDF params.
+    ///
+    /// [PHC string]: crate::PhcString
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as `add_kdf()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pwbox::{sodium::{Argon2, XSalsa20Poly1305}, ErasedPwBox, Eraser};
+    /// # use serde_json::json;
+    /// let mut eraser = Eraser::new();
+    /// eraser.add_phc_kdf::<Argon2>("argon2")?;
+    /// let erased: ErasedPwBox = serde_json::from_value(json!({
+    ///     "kdf": "argon2",
+    ///     "kdfparams": "$argon2id$v=19$m=256,t=2,p=1$c29tZXNhbHRzb21lc2FsdA",
+    ///     // other fields...
+    /// #   "cipher": "xsalsa20-poly1305",
+    /// #   "cipherparams": { "iv": "00".repeat(24) },
+    /// #   "ciphertext": "00",
+    /// #   "mac": "00".repeat(16),
+    /// }))?;
+    /// # eraser.add_cipher::<XSalsa20Poly1305>("xsalsa20-poly1305")?;
+    /// let restored = eraser.restore(&erased)?;
+    /// let erased = eraser.erase_restored(&restored)?;
+    /// assert_eq!(erased.kdf_params()["mem_cost"], 256);
+    /// assert_eq!(erased.salt(), b"somesaltsomesalt");
+    /// # Ok::<_, anyhow::Error>(())
+    /// ```
+    pub fn add_phc_kdf<K>(&mut self, kdf_name: &str) -> Result<&mut Self, Error>
+    where
+        K: PhcString + Serialize + DeserializeOwned + Default,
+    {
+        self.add_kdf::<K>(kdf_name)?;
+        if !self.registry.phc_parsers.contains_key(kdf_name) {
+            let parser = |phc: &str| {
+                let (kdf, salt) = K::from_phc_string(phc)?;
+                let params = serde_json::to_value(kdf).map_err(Error::KdfParams)?;
+                Ok((params, salt))
+            };
+            let registry = Arc::make_mut(&mut self.registry);
+            registry
+                .phc_parsers
+                .insert(kdf_name.to_owned(), Arc::new(parser));
+        }
+        Ok(self)
+    }
+
+    /// Adds an alias for a KDF or cipher name, so that boxes recording `alias` as the name
+    /// of their KDF or cipher are restored as if they recorded `name`. This allows to restore
+    /// boxes created with legacy names. Restored boxes use `name`; thus, erasing a restored box
+    /// migrates it to the new name.
+    ///
+    /// If `name` is an alias itself, the new alias points to the name it resolves to.
+    /// `name` does not need to be registered at the time of the call. Adding the same alias
+    /// several times is a no-op.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DuplicateName`] if `alias` is a name of a registered KDF or cipher,
+    /// or if it is already an alias for a different name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pwbox::{names, sodium::XSalsa20Poly1305, Eraser};
+    /// let mut eraser = Eraser::new();
+    /// eraser
+    ///     .add_cipher::<XSalsa20Poly1305>(names::XSALSA20_POLY1305)?
+    ///     .add_alias("secretbox", names::XSALSA20_POLY1305)?;
+    /// // Boxes with the cipher recorded as `secretbox` can now be restored.
+    /// # Ok::<_, pwbox::Error>(())
+    /// ```
+    pub fn add_alias(&mut self, alias: &str, name: &str) -> Result<&mut Self, Error> {
+        let name = self.resolve(name).to_owned();
+        let registry = &self.registry;
+        let is_taken = alias == name
+            || registry.ciphers.contains_key(alias)
+            || registry.kdfs.contains_key(alias);
+        match registry.aliases.get(alias) {
+            Some(old_name) if *old_name == name => {}
+            Some(_) => return Err(Error::DuplicateName(alias.to_owned())),
+            None if is_taken => return Err(Error::DuplicateName(alias.to_owned())),
+            None => {
+                let registry = Arc::make_mut(&mut self.registry);
+                registry.aliases.insert(alias.to_owned(), name);
+            }
+        }
+        Ok(self)
+    }
+
+    /// Adds an alias for a registered cipher. Unlike [`Self::add_alias()`], this checks
+    /// that `existing_name` (or the name it resolves to) refers to a cipher known
+    /// to this `Eraser`, including `{cipher_name}+{mac_name}` compositions.
+    ///
+    /// Erasing a box always records the canonical name the cipher is registered under,
+    /// never an alias.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoCipher`] if `existing_name` does not refer to a known cipher.
+    /// Otherwise, returns an error under the same conditions as [`Self::add_alias()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pwbox::{names, sodium::Sodium, Eraser, Error};
+    /// let mut eraser = Eraser::new();
+    /// eraser
+    ///     .add_suite::<Sodium>()?
+    ///     .alias_cipher(names::XSALSA20_POLY1305, "secretbox")?;
+    /// let err = eraser.alias_cipher("aes-128-cbc", "aes").unwrap_err();
+    /// assert!(matches!(err, Error::NoCipher(_)));
+    /// # Ok::<_, Error>(())
+    /// ```
+    pub fn alias_cipher(&mut self, existing_name: &str, alias: &str) -> Result<&mut Self, Error> {
+        self.restore_cipher(existing_name)?;
+        self.add_alias(alias, existing_name)
+    }
+
+    /// Adds an alias for a registered KDF. Unlike [`Self::add_alias()`], this checks
+    /// that `existing_name` (or the name it resolves to) refers to a KDF known
+    /// to this `Eraser`.
+    ///
+    /// Erasing a box always records the canonical name the KDF is registered under,
+    /// never an alias.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoKdf`] if `existing_name` does not refer to a known KDF.
+    /// Otherwise, returns an error under the same conditions as [`Self::add_alias()`].
+    pub fn alias_kdf(&mut self, existing_name: &str, alias: &str) -> Result<&mut Self, Error> {
+        if !self.registry.kdfs.contains_key(self.resolve(existing_name)) {
+            return Err(Error::NoKdf(existing_name.to_owned()));
+        }
+        self.add_alias(alias, existing_name)
+    }
+
+    /// Resolves a possible alias to the name it points to.
+    fn resolve<'a>(&'a self, name: &'a str) -> &'a str {
+        self.registry.aliases.get(name).map_or(name, String::as_str)
+    }
+
+    /// Adds all KDFs and ciphers from the specified `Suite`. Adding the same suite
+    /// several times is a no-op.
+    ///
+    /// The suite is added atomically: if an error occurs, the `Eraser` is not modified.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DuplicateName`] if any KDF or cipher name from the suite
+    /// is already taken by another implementation, or if any KDF or cipher in the suite
+    /// is already registered under a different name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the suite has not registered its recommended cipher or KDF.
+    pub fn add_suite<S: Suite>(&mut self) -> Result<&mut Self, Error> {
+        let mut suite_eraser = Eraser::new();
+        S::add_ciphers_and_kdfs(&mut suite_eraser)?;
+        assert!(
+            suite_eraser.lookup_kdf::<S::DeriveKey>().is_some(),
+            "recommended KDF from suite not added"
+        );
+        assert!(
+            suite_eraser.lookup_cipher::<S::Cipher>().is_some(),
+            "recommended cipher from suite not added"
+        );
+
+        // Check all names first, so that the `Eraser` is not modified on error.
+        let (suite, registry) = (&*suite_eraser.registry, &*self.registry);
+        for (&type_id, name) in &suite.cipher_names {
+            let (names, ciphers) = (&registry.cipher_names, &registry.ciphers);
+            Self::check_registration(names, ciphers, &registry.aliases, type_id, name)?;
+        }
+        for (&type_id, name) in &suite.kdf_names {
+            let (names, kdfs) = (&registry.kdf_names, &registry.kdfs);
+            Self::check_registration(names, kdfs, &registry.aliases, type_id, name)?;
+        }
+        for (alias, name) in &suite.aliases {
+            let is_taken = match registry.aliases.get(alias) {
+                Some(old_name) => old_name != name,
+                None => registry.ciphers.contains_key(alias) || registry.kdfs.contains_key(alias),
+            };
+            if is_taken {
+                return Err(Error::DuplicateName(alias.clone()));
+            }
+        }
+
+        // Do not copy registrations shared with clones if the suite is already added.
+        let is_added = suite
+            .cipher_names
+            .keys()
+            .all(|type_id| registry.cipher_names.contains_key(type_id))
+            && suite
+                .kdf_names
+                .keys()
+                .all(|type_id| registry.kdf_names.contains_key(type_id))
+            && suite
+                .aliases
+                .keys()
+                .all(|alias| registry.aliases.contains_key(alias));
+        if is_added {
+            return Ok(self);
+        }
+
+        let registry = Arc::make_mut(&mut self.registry);
+        for (type_id, name) in &suite.cipher_names {
+            if registry.cipher_names.contains_key(type_id) {
+                continue;
+            }
+            if let Some(factory) = suite.ciphers.get(name) {
+                registry.ciphers.insert(name.clone(), Arc::clone(factory));
+                registry.cipher_names.insert(*type_id, name.clone());
+            }
+        }
+        for (type_id, name) in &suite.kdf_names {
+            if registry.kdf_names.contains_key(type_id) {
+                continue;
+            }
+            if let Some(factory) = suite.kdfs.get(name) {
+                registry.kdfs.insert(name.clone(), Arc::clone(factory));
+                registry.kdf_names.insert(*type_id, name.clone());
+            }
+            if let Some(parser) = suite.phc_parsers.get(name) {
+                registry
+                    .phc_parsers
+                    .insert(name.clone(), Arc::clone(parser));
+            }
+        }
+        let aliases = suite.aliases.iter();
+        registry
+            .aliases
+            .extend(aliases.map(|(alias, name)| (alias.clone(), name.clone())));
+        Ok(self)
+    }
+
+    /// Returns names of the registered ciphers in the lexicographic order.
+    pub fn ciphers(&self) -> impl Iterator<Item = &str> + '_ {
+        self.registry.ciphers.keys().map(String::as_str)
+    }
+
+    /// Returns names of the registered KDFs in the lexicographic order.
+    pub fn kdfs(&self) -> impl Iterator<Item = &str> + '_ {
+        self.registry.kdfs.keys().map(String::as_str)
+    }
+
+    fn lookup_cipher<C>(&self) -> Option<Cow<'_, str>>
+    where
+        C: Cipher,
+    {
+        let registry = &*self.registry;
+        if let Some(name) = registry.cipher_names.get(&TypeId::of::<C>()) {
+            return Some(Cow::Borrowed(name));
+        }
+        let (cipher_id, mac_id) = C::mac_composition()?;
+        let cipher_name = registry.unauthenticated_cipher_names.get(&cipher_id)?;
+        let mac_name = registry.mac_names.get(&mac_id)?;
+        let mut name = cipher_name.clone();
+        name.push('+');
+        name.push_str(mac_name);
+        Some(Cow::Owned(name))
+    }
+
+    /// Instantiates the cipher with the specified name. If the name is not registered,
+    /// it is interpreted as a `{cipher_name}+{mac_name}` composition
+    /// (see [`Self::add_unauthenticated_cipher()`]).
+    fn restore_cipher(&self, name: &str) -> Result<ErasedCipher, Error> {
+        let resolved_name = self.resolve(name);
+        if let Some(factory) = self.registry.ciphers.get(resolved_name) {
+            return Ok(factory());
+        }
+
+        let pos = resolved_name
+            .find('+')
+            .ok_or_else(|| Error::NoCipher(name.to_owned()))?;
+        let (cipher_name, mac_name) = (&resolved_name[..pos], &resolved_name[(pos + 1)..]);
+        let cipher = self
+            .registry
+            .unauthenticated_ciphers
+            .get(cipher_name)
+            .ok_or_else(|| Error::NoCipher(cipher_name.to_owned()))?;
+        let mac = self
+            .registry
+            .macs
+            .get(mac_name)
+            .ok_or_else(|| Error::NoCipher(mac_name.to_owned()))?;
+        let cipher = DynCipherWithMac::new(Arc::clone(cipher), Arc::clone(mac));
+        Ok(Arc::new(cipher))
+    }
+
+    fn lookup_kdf<K>(&self) -> Option<&String>
+    where
+        K: DeriveKey,
+    {
+        self.registry.kdf_names.get(&TypeId::of::<K>())
+    }
+
+    /// Returns the names of the KDF and cipher, and serialized KDF params.
+    fn erase_kdf_and_cipher<K, C>(&self, kdf: &K) -> Result<(String, String, JsonValue), EraseError>
+    where
+        K: DeriveKey + Serialize,
+        C: Cipher,
+    {
+        let kdf_name = match self.lookup_kdf::<K>() {
+            Some(kdf) => kdf,
+            None => return Err(EraseError::NoKdf),
+        };
+        let cipher_name = match self.lookup_cipher::<C>() {
+            Some(cipher) => cipher,
+            None => return Err(EraseError::NoCipher),
+        };
+        let kdf_params = match serde_json::to_value(kdf) {
+            Ok(params) => without_nulls(params),
+            Err(e) => return Err(EraseError::SerializeKdf(e)),
+        };
+        Ok((kdf_name.to_owned(), cipher_name.into_owned(), kdf_params))
+    }
+
+    /// Seals a box with the specified `builder` and converts it into serializable form.
+    /// The box is sealed in the format version of this `Eraser`
+    /// (see [`Self::set_format_version()`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the KDF or the cipher is not registered with this `Eraser`,
+    /// if sealing fails, or if the v2 format is used with a cipher not supporting
+    /// associated data.
+    pub fn seal<K, C>(
+        &self,
+        builder: &mut PwBoxBuilder<'_, K, C>,
+        password: impl AsRef<[u8]>,
+        message: impl AsRef<[u8]>,
+    ) -> Result<ErasedPwBox, Error>
+    where
+        K: DeriveKey + Clone + Default + Serialize,
+        C: Cipher,
+    {
+        let kdf = builder.kdf.clone().unwrap_or_default();
+        let (kdf_name, cipher_name, kdf_params, metadata) = self.seal_metadata::<K, C>(&kdf)?;
+        let (password, message) = (password.as_ref(), message.as_ref());
+        let policy = builder.password_policy;
+        let pwbox = builder.seal_with_kdf(kdf, policy, password, message, metadata)?;
+        Ok(ErasedPwBox::from_inner(
+            &pwbox.inner,
+            kdf_name,
+            cipher_name,
+            kdf_params,
+        ))
+    }
+
+    /// Seals a box with the cipher registered under `cipher_name` and converts it into
+    /// serializable form. Unlike [`Self::seal()`], the cipher is not required to be known
+    /// at compile time; in particular, it may be registered via [`Self::add_dyn_cipher()`].
+    /// The box is sealed in the format version of this `Eraser`
+    /// (see [`Self::set_format_version()`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the KDF or the cipher is not registered with this `Eraser`,
+    /// if sealing fails, or if the v2 format is used with a cipher not supporting
+    /// associated data.
+    pub fn seal_with_cipher<K, R>(
+        &self,
+        rng: &mut R,
+        cipher_name: &str,
+        kdf: K,
+        password: impl AsRef<[u8]>,
+        message: impl AsRef<[u8]>,
+    ) -> Result<ErasedPwBox, Error>
+    where
+        K: DeriveKey + Serialize,
+        R: RngCore + CryptoRng,
+    {
+        let kdf_name = self
+            .lookup_kdf::<K>()
+            .ok_or_else(|| Error::NoKdf(type_name::<K>().to_owned()))?
+            .to_owned();
+        let cipher = self.restore_cipher(cipher_name)?;
+        let cipher_name = self.resolve(cipher_name).to_owned();
+        let kdf_params = serde_json::to_value(&kdf).map_err(Error::KdfParams)?;
+        let kdf_params = without_nulls(kdf_params);
+        let metadata = match self.format_version {
+            FormatVersion::V1 => Vec::new(),
+            FormatVersion::V2 => bound_metadata(&kdf_name, &kdf_params, &cipher_name),
+        };
+
+        let inner = PwBoxInner::seal(kdf, cipher, rng, password, message, &[], metadata)?;
+        Ok(ErasedPwBox::from_inner(
+            &inner,
+            kdf_name,
+            cipher_name,
+            kdf_params,
+        ))
+    }
+
+    /// Seals several boxes similarly to [`Self::seal()`]. The boxes share the salt,
+    /// so that the key is derived from the `password` only once.
+    pub(crate) fn seal_shared<K, C>(
+        &self,
+        builder: &mut PwBoxBuilder<'_, K, C>,
+        password: &[u8],
+        messages: &[&[u8]],
+    ) -> Result<Vec<ErasedPwBox>, Error>
+    where
+        K: DeriveKey + Clone + Default + Serialize,
+        C: Cipher,
+    {
+        let kdf = builder.kdf.clone().unwrap_or_default();
+        let (kdf_name, cipher_name, kdf_params, metadata) = self.seal_metadata::<K, C>(&kdf)?;
+        let boxes = builder.seal_shared_with_kdf(&kdf, password, messages, &metadata)?;
+        let boxes = boxes.iter().map(|pwbox| {
+            let (kdf_name, cipher_name) = (kdf_name.clone(), cipher_name.clone());
+            ErasedPwBox::from_inner(&pwbox.inner, kdf_name, cipher_name, kdf_params.clone())
+        });
+        Ok(boxes.collect())
+    }
+
+    /// Returns the KDF and cipher names, KDF params and the metadata to bind to boxes
+    /// sealed with the specified `kdf`.
+    fn seal_metadata<K, C>(&self, kdf: &K) -> Result<(String, String, JsonValue, Vec<u8>), Error>
+    where
+        K: DeriveKey + Serialize,
+        C: Cipher,
+    {
+        let (kdf_name, cipher_name, kdf_params) = self
+            .erase_kdf_and_cipher::<K, C>(kdf)
+            .map_err(sealing_error::<K, C>)?;
+        let metadata = match self.format_version {
+            FormatVersion::V1 => Vec::new(),
+            FormatVersion::V2 => bound_metadata(&kdf_name, &kdf_params, &cipher_name),
+        };
+        Ok((kdf_name, cipher_name, kdf_params, metadata))
+    }
+
+    /// Converts a `pwbox` into serializable form. The box retains the format version
+    /// it was sealed in.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the KDF or the cipher of the box is not registered with this
+    /// `Eraser`. For boxes in the v2 format, an error is also returned if the KDF
+    /// or the cipher is registered under a name other than the one authenticated in the box.
+    pub fn erase<K, C>(&self, pwbox: &PwBox<K, C>) -> Result<ErasedPwBox, EraseError>
+    where
+        K: DeriveKey + Serialize,
+        C: Cipher,
+    {
+        let metadata = &pwbox.inner.metadata;
+        let erased = self
+            .erase_kdf_and_cipher::<K, C>(&pwbox.inner.kdf)
+            .and_then(|(kdf, cipher, kdf_params)| {
+                if !metadata.is_empty() && *metadata != bound_metadata(&kdf, &kdf_params, &cipher) {
+                    return Err(EraseError::MetadataMismatch);
+                }
+                Ok(ErasedPwBox::from_inner(
+                    &pwbox.inner,
+                    kdf,
+                    cipher,
+                    kdf_params,
+                ))
+            });
+
+        traced! {
+            let len = pwbox.len();
+            match &erased {
+                Ok(erased) => tracing::debug!(
+                    kdf = %erased.kdf,
+                    cipher = %erased.cipher,
+                    version = ?erased.version,
+                    len,
+                    "erased box"
+                ),
+                Err(err) => tracing::warn!(error = %err, len, "cannot erase box"),
+            }
+        }
+        erased
+    }
+
+    /// Converts a restored box back into serializable form. The KDF and cipher names
+    /// and the KDF params are preserved exactly as they were in the box the `pwbox`
+    /// was restored from.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the KDF or the cipher of the box is not registered
+    /// with this `Eraser`.
+    pub fn erase_restored(&self, pwbox: &RestoredPwBox) -> Result<ErasedPwBox, EraseError> {
+        if !self
+            .registry
+            .kdfs
+            .contains_key(self.resolve(&pwbox.kdf_name))
+        {
+            return Err(EraseError::NoKdf);
+        }
+        if self.restore_cipher(&pwbox.cipher_name).is_err() {
+            return Err(EraseError::NoCipher);
+        }
+        Ok(ErasedPwBox::from_inner(
+            &pwbox.inner,
+            pwbox.kdf_name.clone(),
+            pwbox.cipher_name.clone(),
+            pwbox.kdf_params.clone(),
+        ))
+    }
+
+    /// Converts the box into serializable form with the ciphertext detached from the rest
+    /// of the box. See [`ErasedPwBoxHeader`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as [`Self::erase()`].
+    pub fn erase_detached<K, C>(
+        &self,
+        pwbox: &PwBox<K, C>,
+    ) -> Result<(ErasedPwBoxHeader, Vec<u8>), EraseError>
+    where
+        K: DeriveKey + Serialize,
+        C: Cipher,
+    {
+        self.erase(pwbox).map(ErasedPwBoxHeader::detach)
+    }
+
+    /// Converts a streaming box into serializable form. The ciphertext and MAC fields
+    /// of the erased box are empty; the encrypted stream needs to be stored separately.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn erase_stream<K, C>(&self, pwbox: &StreamPwBox<K, C>) -> Result<ErasedPwBox, EraseError>
+    where
+        K: DeriveKey + Serialize,
+        C: Cipher,
+    {
+        let pwbox = &pwbox.inner;
+        let (kdf, cipher, kdf_params) = self.erase_kdf_and_cipher::<K, C>(&pwbox.kdf)?;
+
+        Ok(ErasedPwBox {
+            version: FormatVersion::V1,
+            encrypted: CipherOutput {
+                ciphertext: Vec::new(),
+                mac: Vec::new(),
+            },
+            kdf,
+            kdf_params: KdfParams {
+                salt: pwbox.salt.clone(),
+                inner: kdf_params,
+            },
+            cipher,
+            cipher_params: CipherParams {
+                iv: pwbox.nonce.clone(),
+                aad: false,
+                chunk_size: Some(pwbox.chunk_size),
+            },
+            password_policy: PasswordPolicy::Raw,
+        })
+    }
+
+    /// Converts KDF params recorded as a PHC string into the ordinary form using the parser
+    /// registered for the KDF of the box. Other boxes are returned as is.
+    fn parse_phc_params<'a>(&self, erased: &'a ErasedPwBox) -> Result<Cow<'a, ErasedPwBox>, Error> {
+        let phc = match (&erased.kdf_params.inner, erased.version) {
+            (JsonValue::String(phc), FormatVersion::V1) => phc,
+            _ => return Ok(Cow::Borrowed(erased)),
+        };
+        let kdf_name = self.resolve(&erased.kdf);
+        let parser = match self.registry.phc_parsers.get(kdf_name) {
+            Some(parser) => parser,
+            None if self.registry.kdfs.contains_key(kdf_name) => {
+                let message = format!("KDF `{}` does not support PHC strings", erased.kdf);
+                return Err(Error::KdfParams(<JsonError as DeError>::custom(message)));
+            }
+            None => return Err(Error::NoKdf(erased.kdf.clone())),
+        };
+
+        let (inner, salt) = parser(phc)?;
+        let mut erased = erased.clone();
+        erased.kdf_params = KdfParams { salt, inner };
+        Ok(Cow::Owned(erased))
+    }
+
+    /// Instantiates the KDF and cipher recorded in the erased box.
+    #[cfg(feature = "std")]
+    fn restore_kdf_and_cipher(
+        &self,
+        erased: &ErasedPwBox,
+    ) -> Result<(DynKdf, ErasedCipher), Error> {
+        self.restore_kdf_and_cipher_from(erased, (self, self))
+    }
+
+    /// Instantiates the KDF and cipher recorded in the erased box using implementations
+    /// registered in the specified `Eraser`s. KDF limits of this `Eraser` are checked.
+    fn restore_kdf_and_cipher_from(
+        &self,
+        erased: &ErasedPwBox,
+        (kdfs, ciphers): (&Self, &Self),
+    ) -> Result<(DynKdf, ErasedCipher), Error> {
+        let kdf_factory = kdfs
+            .registry
+            .kdfs
+            .get(kdfs.resolve(&erased.kdf))
+            .ok_or_else(|| Error::NoKdf(erased.kdf.clone()))?;
+        let cipher = ciphers.restore_cipher(&erased.cipher)?;
+        if !erased.kdf_params.inner.is_object() {
+            let reason = "KDF params must be an object".to_owned();
+            return Err(Error::MalformedBox(reason));
+        }
+        let kdf = kdf_factory(erased.kdf_params.inner.clone()).map_err(Error::KdfParams)?;
+        self.check_kdf(&*kdf)?;
+        Ok((kdf, cipher))
+    }
+
+    /// Restores a `PwBox` from the serialized form. Boxes in all format versions
+    /// are supported.
+    ///
+    /// Aliases for the KDF and cipher names (see [`Self::add_alias()`]) are replaced with
+    /// the names they point to, unless the box is in the v2 format; in this case, the names
+    /// are authenticated and thus are retained.
+    ///
+    /// # Errors
+    ///
+    /// The structure of the box is validated eagerly, so that a restored box can only fail
+    /// to open because of an incorrect password, corrupted contents or a KDF failure.
+    /// In particular, an error is returned in the following cases:
+    ///
+    /// - [`Error::NoKdf`] / [`Error::NoCipher`] if the KDF or cipher is not registered
+    /// - [`Error::KdfParams`] if the KDF params cannot be parsed, and [`Error::DeriveKey`]
+    ///   if they are invalid
+    /// - [`Error::KdfParamsTooExpensive`] if the KDF params exceed
+    ///   [the limits](Self::set_kdf_limits())
+    /// - [`Error::SaltLen`] if the salt is not accepted by the KDF
+    /// - [`Error::NonceLen`] / [`Error::MacLen`] if the nonce or MAC length does not match
+    ///   the cipher
+    /// - [`Error::ChunkSize`] if the box is a streaming one
+    /// - [`Error::MalformedBox`] for other structural errors
+    ///
+    /// Empty ciphertexts are valid, since they correspond to boxes with empty contents.
+    pub fn restore(&self, erased: &ErasedPwBox) -> Result<RestoredPwBox, Error> {
+        self.restore_from(erased, (self, self))
+    }
+
+    /// Restores a `PwBox` similarly to [`Self::restore()`], looking up the KDF and cipher
+    /// of the box in `overrides` first and falling back to this `Eraser`. This allows
+    /// to layer request-specific registrations over a shared `Eraser` without cloning
+    /// or modifying it.
+    ///
+    /// Aliases are resolved by the `Eraser` providing the KDF or cipher. KDF limits
+    /// of this `Eraser` are checked; the limits of `overrides` are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as [`Self::restore()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rand::thread_rng;
+    /// # use pwbox::{sodium::{Scrypt, Sodium, XChaCha20Poly1305}, Eraser, Suite};
+    /// let pwbox = Sodium::build_box(&mut thread_rng())
+    /// #   .kdf(Scrypt::light())
+    ///     .seal("correct horse", b"battery staple")?;
+    /// let eraser = Eraser::with_suites::<(Sodium,)>()?;
+    /// let erased = eraser.erase(&pwbox)?;
+    ///
+    /// // Shared `Eraser` without the KDF used in the box.
+    /// let mut base = Eraser::new();
+    /// base.add_cipher::<XChaCha20Poly1305>("xchacha20-poly1305")?;
+    /// let mut overrides = Eraser::new();
+    /// overrides.add_kdf::<Scrypt>("scrypt-nacl")?;
+    /// assert!(base.restore(&erased).is_err());
+    /// let restored = base.restore_with(&erased, &overrides)?;
+    /// assert_eq!(&*restored.open("correct horse")?, b"battery staple");
+    /// # Ok::<_, anyhow::Error>(())
+    /// ```
+    pub fn restore_with(
+        &self,
+        erased: &ErasedPwBox,
+        overrides: &Eraser,
+    ) -> Result<RestoredPwBox, Error> {
+        let kdf_name = overrides.resolve(&erased.kdf);
+        let kdfs = if overrides.registry.kdfs.contains_key(kdf_name) {
+            overrides
+        } else {
+            self
+        };
+        let ciphers = if overrides.restore_cipher(&erased.cipher).is_ok() {
+            overrides
+        } else {
+            self
+        };
+        self.restore_from(erased, (kdfs, ciphers))
+    }
+
+    /// Restores a `PwBox` using KDFs and ciphers registered in the specified `Eraser`s.
+    fn restore_from(
+        &self,
+        erased: &ErasedPwBox,
+        (kdfs, ciphers): (&Self, &Self),
+    ) -> Result<RestoredPwBox, Error> {
+        let restored = self.restore_untraced(erased, (kdfs, ciphers));
+        traced! {
+            let len = erased.encrypted.ciphertext.len();
+            match &restored {
+                Ok(restored) => tracing::debug!(
+                    kdf = %restored.kdf_name,
+                    cipher = %restored.cipher_name,
+                    version = ?erased.version,
+                    len,
+                    "restored box"
+                ),
+                Err(err) => tracing::warn!(
+                    kdf = %erased.kdf,
+                    cipher = %erased.cipher,
+                    version = ?erased.version,
+                    len,
+                    error = err.kind(),
+                    "cannot restore box"
+                ),
+            }
+        }
+        restored
+    }
+
+    /// Implementation of [`Self::restore_from()`] without tracing instrumentation.
+    fn restore_untraced(
+        &self,
+        erased: &ErasedPwBox,
+        (kdfs, ciphers): (&Self, &Self),
+    ) -> Result<RestoredPwBox, Error> {
+        let erased = &*kdfs.parse_phc_params(erased)?;
+        let (kdf, cipher) = self.restore_kdf_and_cipher_from(erased, (kdfs, ciphers))?;
+        let (kdf_name, cipher_name) = match erased.version {
+            FormatVersion::V1 => (kdfs.resolve(&erased.kdf), ciphers.resolve(&erased.cipher)),
+            FormatVersion::V2 => (erased.kdf.as_str(), erased.cipher.as_str()),
+        };
+        erased.to_inner(kdf, cipher).map(|inner| RestoredPwBox {
+            inner,
+            kdf_name: kdf_name.to_owned(),
+            cipher_name: cipher_name.to_owned(),
+            kdf_params: erased.kdf_params.inner.clone(),
+        })
+    }
+
+    /// Opens the erased box without restoring it. This is equivalent to
+    /// `self.restore(erased)?.open(password)`, but does not copy the ciphertext
+    /// and KDF params of the box, which makes it preferable if the box is only opened once.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as [`Self::restore()`]
+    /// and [`RestoredPwBox::open()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rand::thread_rng;
+    /// # use pwbox::{sodium::{Scrypt, Sodium}, Eraser, Suite};
+    /// let eraser = Eraser::with_suites::<(Sodium,)>()?;
+    /// let pwbox = Sodium::build_box(&mut thread_rng())
+    /// #   .kdf(Scrypt::light())
+    ///     .seal("correct horse", b"battery staple")?;
+    /// let erased = eraser.erase(&pwbox)?;
+    /// let plaintext = eraser.open(&erased, "correct horse")?;
+    /// assert_eq!(&*plaintext, b"battery staple");
+    /// # Ok::<_, anyhow::Error>(())
+    /// ```
+    pub fn open(
+        &self,
+        erased: &ErasedPwBox,
+        password: impl AsRef<[u8]>,
+    ) -> Result<SensitiveData, Error> {
+        self.with_parts(erased, |parts| {
+            let mut output = SensitiveData::zeros(erased.len());
+            parts
+                .open_into(output.bytes_mut(), password.as_ref(), &[])
+                .map(|_| output)
+        })
+    }
+
+    /// Opens the erased box into the specified container without restoring it.
+    /// See [`Self::open()`] and [`RestoredPwBox::open_into()`] for details.
+    pub fn open_into(
+        &self,
+        erased: &ErasedPwBox,
+        mut output: impl AsMut<[u8]>,
+        password: impl AsRef<[u8]>,
+    ) -> Result<usize, Error> {
+        self.with_parts(erased, |parts| {
+            parts.open_into(output.as_mut(), password.as_ref(), &[])
+        })
+    }
+
+    /// Instantiates the KDF and cipher for the erased box and passes the borrowed
+    /// box parts to `action`.
+    fn with_parts<T>(
+        &self,
+        erased: &ErasedPwBox,
+        action: impl FnOnce(BoxParts<'_, dyn DeriveKey, dyn ObjectSafeCipher>) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let erased = &*self.parse_phc_params(erased)?;
+        let (kdf, cipher) = self.restore_kdf_and_cipher_from(erased, (self, self))?;
+        let metadata = erased.check_params(&*kdf, &*cipher)?;
+        action(erased.to_parts(&*kdf, &*cipher, &metadata))
+    }
+
+    /// Restores a box from the header produced by [`Self::erase_detached()`]
+    /// and the detached ciphertext.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::CiphertextMismatch`] if the length or checksum of `ciphertext`
+    /// does not match the header. Otherwise, returns an error under the same conditions
+    /// as [`Self::restore()`].
+    pub fn restore_detached(
+        &self,
+        header: &ErasedPwBoxHeader,
+        ciphertext: &[u8],
+    ) -> Result<RestoredPwBox, Error> {
+        self.restore(&header.attach(ciphertext)?)
+    }
+
+    /// Restores a streaming box from the serialized form.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn restore_stream(&self, erased: &ErasedPwBox) -> Result<RestoredStreamPwBox, Error> {
+        let chunk_size = match erased.cipher_params.chunk_size {
+            Some(size) if size > 0 => size,
+            _ => return Err(Error::ChunkSize),
+        };
+        if erased.version != FormatVersion::V1 {
+            let reason = "streaming boxes only support the v1 format".to_owned();
+            return Err(Error::MalformedBox(reason));
+        }
+        if !erased.password_policy.is_raw() {
+            let reason = "streaming boxes do not support password policies".to_owned();
+            return Err(Error::MalformedBox(reason));
+        }
+        let erased = &*self.parse_phc_params(erased)?;
+        let (kdf, cipher) = self.restore_kdf_and_cipher(erased)?;
+
+        if !kdf.valid_salt_len(erased.kdf_params.salt.len()) {
+            return Err(Error::SaltLen);
+        }
+        let nonce_len = erased.cipher_params.iv.len();
+        if nonce_len != cipher.nonce_len() || nonce_len < MIN_NONCE_LEN {
+            return Err(Error::NonceLen);
+        }
+
+        let inner = StreamInner {
+            salt: erased.kdf_params.salt.clone(),
+            nonce: erased.cipher_params.iv.clone(),
+            chunk_size,
+            kdf,
+            cipher,
+        };
+        Ok(RestoredStreamPwBox { inner })
+    }
+
+    /// Restores a `PwBox` with the recommended KDF and cipher of the specified `Suite`.
+    ///
+    /// Unlike [`Self::restore()`], this method returns a strongly typed box, which does not
+    /// use dynamic dispatch.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoKdf`] or [`Error::NoCipher`] if the KDF or cipher recorded
+    /// in the box do not match the names the suite's recommended KDF or cipher
+    /// are registered under in this `Eraser`.
+    pub fn restore_as<S: Suite>(
+        &self,
+        erased: &ErasedPwBox,
+    ) -> Result<PwBox<S::DeriveKey, S::Cipher>, Error>
+    where
+        S::DeriveKey: DeserializeOwned,
+    {
+        self.restore_typed(erased)
+    }
+
+    /// Restores a strongly typed `PwBox` with the specified KDF and cipher.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoKdf`] or [`Error::NoCipher`] if the KDF or cipher recorded
+    /// in the box do not match the names `K` and `C` are registered under in this `Eraser`.
+    pub fn restore_typed<K, C>(&self, erased: &ErasedPwBox) -> Result<PwBox<K, C>, Error>
+    where
+        K: DeriveKey + DeserializeOwned,
+        C: Cipher,
+    {
+        // Empty names correspond to boxes serialized directly from a `PwBox`.
+        let kdf_name = self.resolve(&erased.kdf);
+        if !kdf_name.is_empty() && self.lookup_kdf::<K>().map(String::as_str) != Some(kdf_name) {
+            return Err(Error::NoKdf(erased.kdf.clone()));
+        }
+        let cipher_name = self.resolve(&erased.cipher);
+        if !cipher_name.is_empty() && self.lookup_cipher::<C>().as_deref() != Some(cipher_name) {
+            return Err(Error::NoCipher(erased.cipher.clone()));
+        }
+        let pwbox = self.parse_phc_params(erased)?.to_typed::<K, C>()?;
+        self.check_kdf(&pwbox.inner.kdf)?;
+        Ok(pwbox)
+    }
+
+    /// Checks whether the erased box should be re-sealed to satisfy the upgrade `policy`.
+    ///
+    /// The box needs an upgrade if its cipher is not allowed by the policy, or if the cost
+    /// of its KDF is below the minimum. Boxes with KDFs that cannot be restored
+    /// with this `Eraser` or that do not provide [a cost estimate](DeriveKey::cost())
+    /// are conservatively considered as needing an upgrade.
+    pub fn needs_upgrade(&self, erased: &ErasedPwBox, policy: &UpgradePolicy) -> bool {
+        let cipher_name = self.resolve(&erased.cipher);
+        let is_cipher_allowed = policy.allowed_ciphers.is_empty()
+            || policy
+                .allowed_ciphers
+                .iter()
+                .any(|name| self.resolve(name) == cipher_name);
+        if !is_cipher_allowed {
+            return true;
+        }
+
+        let erased = match self.parse_phc_params(erased) {
+            Ok(erased) => erased,
+            Err(_) => return true,
+        };
+        let cost = self
+            .registry
+            .kdfs
+            .get(self.resolve(&erased.kdf))
+            .and_then(|factory| factory(erased.kdf_params.inner.clone()).ok())
+            .and_then(|kdf| kdf.cost());
+        cost.map_or(true, |cost| {
+            cost.memory_bytes < policy.min_memory_bytes || cost.time_cost < policy.min_time_cost
+        })
+    }
+
+    /// Opens the erased box with the `password` and re-seals its contents with the same
+    /// password using the `builder`. The re-sealed box is returned in the serializable form
+    /// in the format version of this `Eraser`.
+    ///
+    /// The decrypted contents are never exposed to the caller; they are kept in a container
+    /// zeroed on drop.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MacMismatch`] if the `password` is incorrect. The box cannot be
+    /// migrated if it was sealed with associated data; in this case, `MacMismatch`
+    /// is returned as well. [`Error::NoKdf`] or [`Error::NoCipher`] is returned if the KDF
+    /// or cipher of the `builder` is not registered in this `Eraser`. Otherwise, an error
+    /// is returned under the same conditions as for [`Self::restore()`]
+    /// and [`Self::seal()`].
+    pub fn migrate<K, C>(
+        &self,
+        erased: &ErasedPwBox,
+        password: impl AsRef<[u8]>,
+        builder: &mut PwBoxBuilder<'_, K, C>,
+    ) -> Result<ErasedPwBox, Error>
+    where
+        K: DeriveKey + Clone + Default + Serialize,
+        C: Cipher,
+    {
+        // Check the target KDF and cipher before the expensive key derivation.
+        if self.lookup_kdf::<K>().is_none() {
+            return Err(Error::NoKdf(type_name::<K>().to_owned()));
+        }
+        if self.lookup_cipher::<C>().is_none() {
+            return Err(Error::NoCipher(type_name::<C>().to_owned()));
+        }
+
+        let password = password.as_ref();
+        let contents = self.restore(erased)?.open(password)?;
+        self.seal(builder, password, &*contents)
+    }
+}
+
+/// Converts an error erasing a newly sealed box with the KDF `K` and cipher `C`.
+fn sealing_error<K, C>(err: EraseError) -> Error {
+    match err {
+        EraseError::NoKdf => Error::NoKdf(type_name::<K>().to_owned()),
+        EraseError::NoCipher => Error::NoCipher(type_name::<C>().to_owned()),
+        EraseError::SerializeKdf(e) => Error::KdfParams(e),
+        EraseError::MetadataMismatch => unreachable!("metadata is bound to the box by `Eraser`"),
+    }
+}
+
+/// Cryptographic suite providing ciphers and KDFs for password-based encryption.
+pub trait Suite {
+    /// Recommended cipher for this suite.
+    type Cipher: Cipher;
+    /// Recommended KDF for this suite.
+    type DeriveKey: DeriveKey + Clone + Default;
+
+    /// Initializes a `PwBoxBuilder` with the recommended cipher and KDF.
+    fn build_box<R: RngCore + CryptoRng>(
+        rng: &mut R,
+    ) -> PwBoxBuilder<'_, Self::DeriveKey, Self::Cipher> {
+        PwBoxBuilder::new(rng)
+    }
+
+    /// Adds ciphers and KDFs from this suite into the specified `Eraser`.
+    ///
+    /// This method is called on an empty `Eraser` by [`Eraser::add_suite()`], so
+    /// implementations may assume that registration only fails on name collisions
+    /// within the suite.
+    fn add_ciphers_and_kdfs(eraser: &mut Eraser) -> Result<(), Error>;
+
+    /// Seals the `message` with the recommended cipher and KDF and converts the box
+    /// into serializable form.
+    fn seal<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        password: impl AsRef<[u8]>,
+        message: impl AsRef<[u8]>,
+    ) -> Result<ErasedPwBox, Error>
+    where
+        Self::DeriveKey: Serialize,
+    {
+        let pwbox = Self::build_box(rng).seal(password, message)?;
+        let mut eraser = Eraser::new();
+        Self::add_ciphers_and_kdfs(&mut eraser)?;
+        eraser
+            .erase(&pwbox)
+            .map_err(sealing_error::<Self::DeriveKey, Self::Cipher>)
+    }
+
+    /// Opens a box using ciphers and KDFs from this suite.
+    fn open(erased: &ErasedPwBox, password: impl AsRef<[u8]>) -> Result<SensitiveData, Error> {
+        let mut eraser = Eraser::new();
+        Self::add_ciphers_and_kdfs(&mut eraser)?;
+        eraser.open(erased, password)
+    }
+
+    /// Seals the `message` similarly to [`Self::seal()`] and serializes the box to JSON.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rand::thread_rng;
+    /// use pwbox::{sodium::Sodium, Suite};
+    ///
+    /// let json = Sodium::seal_to_json(&mut thread_rng(), "correct horse", b"battery staple")?;
+    /// // `json` can be saved to a file...
+    /// let plaintext = Sodium::open_json(&json, "correct horse")?;
+    /// assert_eq!(&*plaintext, b"battery staple");
+    /// # Ok::<_, pwbox::Error>(())
+    /// ```
+    fn seal_to_json<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        password: impl AsRef<[u8]>,
+        message: impl AsRef<[u8]>,
+    ) -> Result<String, Error>
+    where
+        Self::DeriveKey: Serialize,
+    {
+        let erased = Self::seal(rng, password, message)?;
+        serde_json::to_string(&erased).map_err(Error::Serialization)
+    }
+
+    /// Deserializes a box from JSON and opens it using ciphers and KDFs from this suite.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Serialization`] if `json` is not a valid box; otherwise, errors
+    /// are the same as for [`Self::open()`].
+    fn open_json(json: &str, password: impl AsRef<[u8]>) -> Result<SensitiveData, Error> {
+        let erased: ErasedPwBox = serde_json::from_str(json).map_err(Error::Serialization)?;
+        Self::open(&erased, password)
+    }
+}
+
+/// Set of [`Suite`]s added to an `Eraser` at once by [`Eraser::with_suites()`].
+///
+/// The trait is implemented for tuples of up to 4 suites, e.g., `(Sodium,)` or
+/// `(Sodium, RustCrypto)`.
+pub trait Suites {
+    /// Adds all KDFs and ciphers from the suites into the specified `Eraser`.
+    fn add_suites(eraser: &mut Eraser) -> Result<(), Error>;
+}
+
+macro_rules! impl_suites_for_tuple {
+    ($($suite:ident),+) => {
+        impl<$($suite: Suite,)+> Suites for ($($suite,)+) {
+            fn add_suites(eraser: &mut Eraser) -> Result<(), Error> {
+                $(eraser.add_suite::<$suite>()?;)+
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_suites_for_tuple!(S1);
+impl_suites_for_tuple!(S1, S2);
+impl_suites_for_tuple!(S1, S2, S3);
+impl_suites_for_tuple!(S1, S2, S3, S4);
+
+// This function is used in testing cryptographic backends, so it's intentionally kept public.
+#[cfg(test)]
+pub fn test_kdf_and_cipher_corruption<K, C>(kdf: K)
+where
+    K: DeriveKey + Clone + Default + Serialize + DeserializeOwned,
+    C: Cipher,
+{
+    use crate::alloc::vec;
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    let mut rng = thread_rng();
+    let mut message = vec![0_u8; 64];
+    rng.fill_bytes(&mut message);
+
+    let kdf_copy = kdf.clone();
+    let pwbox = PwBoxBuilder::<_, C>::new(&mut rng)
+        .kdf(kdf)
+        .seal(PASSWORD, &message)
+        .unwrap();
+
+    // All corrupted input needs to pass through `Eraser` / `ErasedPwBox`, so we test them.
+    let mut eraser = Eraser::new();
+    let eraser = eraser
+        .add_cipher::<C>("cipher")
+        .unwrap()
+        .add_kdf::<K>("kdf")
+        .unwrap();
+    let mut erased_box = eraser.erase(&pwbox).unwrap();
+
+    let check_salt = |erased_box: &ErasedPwBox| {
+        if kdf_copy.valid_salt_len(erased_box.kdf_params.salt.len()) {
+            // The salt is accepted by the KDF, but the derived key is different.
+            assert_matches!(
+                eraser
+                    .restore(erased_box)
+                    .unwrap()
+                    .open(PASSWORD)
+                    .unwrap_err(),
+                Error::MacMismatch
+            );
+        } else {
+            assert_matches!(
+                eraser.restore(erased_box).map(drop).unwrap_err(),
+                Error::SaltLen
+            );
+        }
+    };
+
+    // Lengthen MAC.
+    erased_box.encrypted.mac.push(b'!');
+    assert_matches!(
+        eraser.restore(&erased_box).map(drop).unwrap_err(),
+        Error::MacLen
+    );
+    assert_matches!(
+        eraser.open(&erased_box, PASSWORD).unwrap_err(),
+        Error::MacLen
+    );
+    // Shorten MAC.
+    erased_box.encrypted.mac.pop();
+    if let Some(last_byte) = erased_box.encrypted.mac.pop() {
+        assert_matches!(
+            eraser.restore(&erased_box).map(drop).unwrap_err(),
+            Error::MacLen
+        );
+        erased_box.encrypted.mac.push(last_byte);
+    }
+
+    // Lengthen salt.
+    erased_box.kdf_params.salt.push(b'!');
+    check_salt(&erased_box);
+    // Shorten salt.
+    erased_box.kdf_params.salt.pop();
+    if let Some(last_byte) = erased_box.kdf_params.salt.pop() {
+        check_salt(&erased_box);
+        erased_box.kdf_params.salt.push(last_byte);
+    }
+
+    // Lengthen nonce.
+    erased_box.cipher_params.iv.push(b'!');
+    assert_matches!(
+        eraser.restore(&erased_box).map(drop).unwrap_err(),
+        Error::NonceLen
+    );
+    assert_matches!(
+        eraser.open(&erased_box, PASSWORD).unwrap_err(),
+        Error::NonceLen
+    );
+    // Shorten nonce.
+    erased_box.cipher_params.iv.pop();
+    if let Some(last_byte) = erased_box.cipher_params.iv.pop() {
+        assert_matches!(
+            eraser.restore(&erased_box).map(drop).unwrap_err(),
+            Error::NonceLen
+        );
+        erased_box.cipher_params.iv.push(last_byte);
+    }
+
+    // Mutate MAC.
+    erased_box.encrypted.mac[0] ^= 1;
+    let restored = eraser.restore(&erased_box).unwrap();
+    assert_matches!(restored.open(PASSWORD).unwrap_err(), Error::MacMismatch);
+    assert_matches!(
+        eraser.open(&erased_box, PASSWORD).unwrap_err(),
+        Error::MacMismatch
+    );
+    erased_box.encrypted.mac[0] ^= 1;
+
+    // Mutate ciphertext.
+    erased_box.encrypted.ciphertext[1] ^= 128;
+    let restored = eraser.restore(&erased_box).unwrap();
+    assert_matches!(restored.open(PASSWORD).unwrap_err(), Error::MacMismatch);
+    erased_box.encrypted.ciphertext[1] ^= 128;
+
+    // Mutate password.
+    let mut password = PASSWORD.as_bytes().to_vec();
+    password[2] ^= 16;
+    assert_matches!(restored.open(&password).unwrap_err(), Error::MacMismatch);
+}
+
+// This function is used in testing cryptographic backends, so it's intentionally kept public.
+#[cfg(test)]
+pub fn test_kdf_and_cipher_with_aad<K, C>(kdf: K)
+where
+    K: DeriveKey + Clone + Default + Serialize + DeserializeOwned,
+    C: Cipher,
+{
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    const PASSWORD: &str = "correct horse battery staple";
+    const MESSAGE: &[u8] = b"battery staple";
+    const AAD: &[u8] = b"account #1";
+
+    let mut rng = thread_rng();
+    let pwbox = PwBoxBuilder::<_, C>::new(&mut rng)
+        .kdf(kdf)
+        .associated_data(AAD)
+        .seal(PASSWORD, MESSAGE)
+        .unwrap();
+    assert!(pwbox.has_associated_data());
+    assert_eq!(&*pwbox.open_with_aad(PASSWORD, AAD).unwrap(), MESSAGE);
+    assert_matches!(
+        pwbox.open_with_aad(PASSWORD, b"account #2").unwrap_err(),
+        Error::MacMismatch
+    );
+    assert_matches!(pwbox.open(PASSWORD).unwrap_err(), Error::MacMismatch);
+    pwbox.verify_with_aad(PASSWORD, AAD).unwrap();
+    assert_matches!(
+        pwbox.verify_with_aad(PASSWORD, b"account #2").unwrap_err(),
+        Error::MacMismatch
+    );
+    assert_matches!(pwbox.verify(PASSWORD).unwrap_err(), Error::MacMismatch);
+    let mut output = [0_u8; 32];
+    let len = pwbox
+        .open_into_with_aad(&mut output, PASSWORD, AAD)
+        .unwrap();
+    assert_eq!(&output[..len], MESSAGE);
+    assert_matches!(
+        pwbox.open_into(&mut output, PASSWORD).unwrap_err(),
+        Error::MacMismatch
+    );
+
+    let mut eraser = Eraser::new();
+    let eraser = eraser
+        .add_cipher::<C>("cipher")
+        .unwrap()
+        .add_kdf::<K>("kdf")
+        .unwrap();
+    let erased_box = eraser.erase(&pwbox).unwrap();
+    let json = serde_json::to_value(&erased_box).unwrap();
+    assert_eq!(json["cipherparams"]["aad"], true);
+    let erased_box: ErasedPwBox = serde_json::from_value(json).unwrap();
+
+    let restored = eraser.restore(&erased_box).unwrap();
+    assert!(restored.has_associated_data());
+    assert_eq!(&*restored.open_with_aad(PASSWORD, AAD).unwrap(), MESSAGE);
+    assert_matches!(restored.open(PASSWORD).unwrap_err(), Error::MacMismatch);
+    let len = restored
+        .open_into_with_aad(&mut output, PASSWORD, AAD)
+        .unwrap();
+    assert_eq!(&output[..len], MESSAGE);
+    assert_matches!(
+        restored
+            .open_into_with_aad(&mut output, PASSWORD, b"account #2")
+            .unwrap_err(),
+        Error::MacMismatch
+    );
+    restored.verify_with_aad(PASSWORD, AAD).unwrap();
+    assert_matches!(
+        restored.verify_with_aad("Tr0ub4dor&3", AAD).unwrap_err(),
+        Error::MacMismatch
+    );
+}
+
+// This function is used in testing cryptographic backends, so it's intentionally kept public.
+#[cfg(test)]
+pub fn test_kdf_and_cipher_message_sizes<K, C>(kdf: K)
+where
+    K: DeriveKey + Clone + Default + Serialize + DeserializeOwned,
+    C: Cipher,
+{
+    use crate::alloc::vec;
+    use rand::thread_rng;
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    let mut rng = thread_rng();
+    let mut eraser = Eraser::new();
+    let eraser = eraser
+        .add_cipher::<C>("cipher")
+        .unwrap()
+        .add_kdf::<K>("kdf")
+        .unwrap();
+
+    // Empty message.
+    let pwbox = PwBoxBuilder::<_, C>::new(&mut rng)
+        .kdf(kdf.clone())
+        .seal(PASSWORD, b"")
+        .unwrap();
+    assert_eq!(pwbox.len(), 0);
+    assert!(pwbox.is_empty());
+    assert!(pwbox.open(PASSWORD).unwrap().is_empty());
+    assert_eq!(pwbox.open_into(&mut [][..], PASSWORD).unwrap(), 0);
+
+    let json = serde_json::to_string(&eraser.erase(&pwbox).unwrap()).unwrap();
+    let erased_box: ErasedPwBox = serde_json::from_str(&json).unwrap();
+    assert!(erased_box.is_empty());
+    let restored = eraser.restore(&erased_box).unwrap();
+    assert!(restored.is_empty());
+    assert!(restored.open(PASSWORD).unwrap().is_empty());
+    assert!(!restored.check_password("Tr0ub4dor&3").unwrap());
+
+    // Message larger than the stack buffer of `SensitiveData`.
+    let mut message = vec![0_u8; 1 << 20];
+    rng.fill_bytes(&mut message);
+    let pwbox = PwBoxBuilder::<_, C>::new(&mut rng)
+        .kdf(kdf)
+        .seal(PASSWORD, &message)
+        .unwrap();
+    assert_eq!(pwbox.len(), message.len());
+    assert!(!pwbox.is_empty());
+    assert_eq!(*pwbox.open(PASSWORD).unwrap(), *message);
+    let restored = eraser.restore(&eraser.erase(&pwbox).unwrap()).unwrap();
+    let mut buffer = vec![0_u8; message.len()];
+    restored.open_into(&mut buffer, PASSWORD).unwrap();
+    assert_eq!(buffer, message);
+}
+
+// This function is used in testing cryptographic backends, so it's intentionally kept public.
+#[cfg(test)]
+pub fn test_kdf_presets<K>()
+where
+    K: crate::KdfDifficulty + Serialize + DeserializeOwned,
+{
+    use crate::KdfPreset;
+
+    let presets = [
+        K::preset(KdfPreset::Interactive),
+        K::preset(KdfPreset::Moderate),
+        K::preset(KdfPreset::Sensitive),
+    ];
+    for window in presets.windows(2) {
+        assert!(window[0].memory_cost() < window[1].memory_cost());
+        assert!(window[0].ops_cost() < window[1].ops_cost());
+    }
+
+    for kdf in &presets {
+        let json = serde_json::to_value(kdf).unwrap();
+        let restored: K = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.memory_cost(), kdf.memory_cost());
+        assert_eq!(restored.ops_cost(), kdf.ops_cost());
+    }
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn registering_names() {
+    use crate::sodium::{Scrypt, Sodium, XSalsa20Poly1305};
+    use assert_matches::assert_matches;
+
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+    assert_eq!(
+        eraser.ciphers().collect::<Vec<_>>(),
+        [
+            "chacha20-poly1305",
+            "chacha20-poly1305-ietf",
+            "xchacha20-poly1305",
+            "xsalsa20-poly1305"
+        ]
+    );
+    assert_eq!(
+        eraser.kdfs().collect::<Vec<_>>(),
+        ["argon2", "external", "none", "scrypt", "scrypt-nacl"]
+    );
+
+    // Idempotent re-registration.
+    eraser
+        .add_suite::<Sodium>()
+        .unwrap()
+        .add_kdf::<Scrypt>("scrypt-nacl")
+        .unwrap()
+        .add_cipher::<XSalsa20Poly1305>("xsalsa20-poly1305")
+        .unwrap();
+    assert_eq!(eraser.kdfs().count(), 5);
+    assert_eq!(eraser.ciphers().count(), 4);
+
+    // Registering an implementation under a different name.
+    assert_matches!(
+        eraser.add_kdf::<Scrypt>("other-scrypt").unwrap_err(),
+        Error::DuplicateName(ref name) if name == "other-scrypt"
+    );
+    assert_matches!(
+        eraser.add_cipher::<XSalsa20Poly1305>("other-salsa").unwrap_err(),
+        Error::DuplicateName(ref name) if name == "other-salsa"
+    );
+    assert_eq!(eraser.kdfs().count(), 5);
+    assert_eq!(eraser.ciphers().count(), 4);
+}
+
+#[cfg(all(feature = "exonum_sodiumoxide", feature = "rust-crypto"))]
+#[test]
+fn registering_conflicting_names() {
+    use crate::{
+        rcrypto::{Aes128Gcm, RustCrypto, Scrypt},
+        sodium::Sodium,
+    };
+    use assert_matches::assert_matches;
+
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+
+    // KDF over suite.
+    assert_matches!(
+        eraser.add_kdf::<Scrypt>("scrypt").unwrap_err(),
+        Error::DuplicateName(ref name) if name == "scrypt"
+    );
+    assert_matches!(
+        eraser.add_cipher::<Aes128Gcm>("xsalsa20-poly1305").unwrap_err(),
+        Error::DuplicateName(ref name) if name == "xsalsa20-poly1305"
+    );
+
+    // Suite over suite. Both suites register `scrypt`, `scrypt-nacl` and `argon2` KDFs
+    // and the `xsalsa20-poly1305` and `chacha20-poly1305` ciphers.
+    let conflicting_names = [
+        "scrypt",
+        "scrypt-nacl",
+        "argon2",
+        "xsalsa20-poly1305",
+        "chacha20-poly1305",
+    ];
+    assert_matches!(
+        eraser.add_suite::<RustCrypto>().unwrap_err(),
+        Error::DuplicateName(ref name) if conflicting_names.contains(&name.as_str())
+    );
+    // The eraser should not be modified.
+    assert_eq!(
+        eraser.ciphers().collect::<Vec<_>>(),
+        [
+            "chacha20-poly1305",
+            "chacha20-poly1305-ietf",
+            "xchacha20-poly1305",
+            "xsalsa20-poly1305"
+        ]
+    );
+    assert_eq!(
+        eraser.kdfs().collect::<Vec<_>>(),
+        ["argon2", "external", "none", "scrypt", "scrypt-nacl"]
+    );
+
+    // Non-conflicting KDFs and ciphers can be registered.
+    eraser
+        .add_kdf::<Scrypt>("scrypt-rcrypto")
+        .unwrap()
+        .add_cipher::<Aes128Gcm>("aes-128-gcm")
+        .unwrap();
+    assert_eq!(eraser.kdfs().count(), 6);
+    assert_eq!(eraser.ciphers().count(), 5);
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn restoring_boxes_with_aliases() {
+    use crate::sodium::{Scrypt, Sodium, XSalsa20Poly1305};
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    let mut eraser = Eraser::new();
+    eraser
+        .add_suite::<Sodium>()
+        .unwrap()
+        .add_alias("secretbox", names::XSALSA20_POLY1305)
+        .unwrap()
+        .add_alias("scrypt-sodium", names::SCRYPT_NACL)
+        .unwrap()
+        // Aliases are resolved when added.
+        .add_alias("nacl", "secretbox")
+        .unwrap()
+        // Idempotent re-registration.
+        .add_alias("secretbox", names::XSALSA20_POLY1305)
+        .unwrap();
+    assert_eq!(eraser.registry.aliases["nacl"], names::XSALSA20_POLY1305);
+
+    assert_matches!(
+        eraser.add_alias("secretbox", names::CHACHA20_POLY1305).unwrap_err(),
+        Error::DuplicateName(ref name) if name == "secretbox"
+    );
+    assert_matches!(
+        eraser.add_alias(names::SCRYPT, names::SCRYPT_NACL).unwrap_err(),
+        Error::DuplicateName(ref name) if name == names::SCRYPT
+    );
+    assert_matches!(
+        eraser.add_alias("loop", "loop").unwrap_err(),
+        Error::DuplicateName(ref name) if name == "loop"
+    );
+    assert_matches!(
+        eraser.add_kdf::<crate::NoKdf>("scrypt-sodium").unwrap_err(),
+        Error::DuplicateName(ref name) if name == "scrypt-sodium"
+    );
+
+    let pwbox = Sodium::build_box(&mut thread_rng())
+        .cipher::<XSalsa20Poly1305>()
+        .kdf(Scrypt::light())
+        .seal("password", b"message")
+        .unwrap();
+    let mut erased_box = eraser.erase(&pwbox).unwrap();
+    erased_box.kdf = "scrypt-sodium".to_owned();
+    erased_box.cipher = "nacl".to_owned();
+
+    let restored = eraser.restore(&erased_box).unwrap();
+    assert_eq!(&*restored.open("password").unwrap(), b"message");
+    // Erasing the restored box migrates it to canonical names.
+    let migrated_box = eraser.erase_restored(&restored).unwrap();
+    assert_eq!(migrated_box.kdf_name(), names::SCRYPT_NACL);
+    assert_eq!(migrated_box.cipher_name(), names::XSALSA20_POLY1305);
+
+    let typed_box = eraser
+        .restore_typed::<Scrypt, XSalsa20Poly1305>(&erased_box)
+        .unwrap();
+    assert_eq!(&*typed_box.open("password").unwrap(), b"message");
+
+    // Aliases are not resolved without registration.
+    let mut other_eraser = Eraser::new();
+    other_eraser.add_suite::<Sodium>().unwrap();
+    assert_matches!(
+        other_eraser.restore(&erased_box).unwrap_err(),
+        Error::NoKdf(ref name) if name == "scrypt-sodium"
+    );
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn checked_aliases_for_ciphers_and_kdfs() {
+    use crate::sodium::{Scrypt, Sodium, XSalsa20Poly1305};
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    let mut eraser = Eraser::new();
+    eraser
+        .add_suite::<Sodium>()
+        .unwrap()
+        .alias_cipher(names::XSALSA20_POLY1305, "secretbox")
+        .unwrap()
+        .alias_cipher("secretbox", "nacl")
+        .unwrap()
+        .alias_kdf(names::SCRYPT_NACL, "scrypt-sodium")
+        .unwrap();
+    assert_eq!(eraser.registry.aliases["nacl"], names::XSALSA20_POLY1305);
+
+    // Names of the wrong kind or unknown names are rejected.
+    assert_matches!(
+        eraser.alias_cipher(names::SCRYPT_NACL, "kdf").unwrap_err(),
+        Error::NoCipher(ref name) if name == names::SCRYPT_NACL
+    );
+    assert_matches!(
+        eraser.alias_kdf("secretbox", "cipher").unwrap_err(),
+        Error::NoKdf(ref name) if name == "secretbox"
+    );
+    assert_matches!(
+        eraser.alias_kdf("pbkdf2", "pbkdf").unwrap_err(),
+        Error::NoKdf(ref name) if name == "pbkdf2"
+    );
+    // Existing names and aliases are not overwritten.
+    assert_matches!(
+        eraser
+            .alias_cipher(names::XSALSA20_POLY1305, names::XCHACHA20_POLY1305)
+            .unwrap_err(),
+        Error::DuplicateName(ref name) if name == names::XCHACHA20_POLY1305
+    );
+    assert_matches!(
+        eraser.alias_cipher(names::XCHACHA20_POLY1305, "nacl").unwrap_err(),
+        Error::DuplicateName(ref name) if name == "nacl"
+    );
+    assert!(!eraser.registry.aliases.contains_key("kdf"));
+    assert!(!eraser.registry.aliases.contains_key("cipher"));
+
+    let pwbox = Sodium::build_box(&mut thread_rng())
+        .cipher::<XSalsa20Poly1305>()
+        .kdf(Scrypt::light())
+        .seal("password", b"message")
+        .unwrap();
+    let mut erased_box = eraser.erase(&pwbox).unwrap();
+    // Erasing records canonical names.
+    assert_eq!(erased_box.kdf_name(), names::SCRYPT_NACL);
+    assert_eq!(erased_box.cipher_name(), names::XSALSA20_POLY1305);
+
+    erased_box.kdf = "scrypt-sodium".to_owned();
+    erased_box.cipher = "nacl".to_owned();
+    let restored = eraser.restore(&erased_box).unwrap();
+    assert_eq!(&*restored.open("password").unwrap(), b"message");
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn v2_format() {
+    use crate::sodium::{Scrypt, Sodium, XSalsa20Poly1305};
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    let mut rng = thread_rng();
+    let mut eraser = Eraser::new();
+    eraser
+        .add_suite::<Sodium>()
+        .unwrap()
+        .add_alias("scrypt-sodium", names::SCRYPT_NACL)
+        .unwrap()
+        .add_alias("xchacha", names::XCHACHA20_POLY1305)
+        .unwrap()
+        .set_format_version(FormatVersion::V2);
+
+    let mut builder = Sodium::build_box(&mut rng);
+    builder.kdf(Scrypt::light());
+    let erased_box = eraser.seal(&mut builder, PASSWORD, b"message").unwrap();
+    assert_eq!(erased_box.format_version(), FormatVersion::V2);
+    let restored = eraser.restore(&erased_box).unwrap();
+    assert_eq!(&*restored.open(PASSWORD).unwrap(), b"message");
+    assert_eq!(eraser.erase_restored(&restored).unwrap(), erased_box);
+    // Changing the password retains the format.
+    let changed_box = restored
+        .change_password(&mut rng, PASSWORD, "other")
+        .unwrap();
+    let changed_box = eraser.erase_restored(&changed_box).unwrap();
+    let changed_box = eraser.restore(&changed_box).unwrap();
+    assert_eq!(&*changed_box.open("other").unwrap(), b"message");
+    let typed_box = eraser
+        .restore_as::<Sodium>(&erased_box)
+        .unwrap()
+        .change_password(&mut rng, PASSWORD, "other")
+        .unwrap();
+    let changed_box = eraser.erase(&typed_box).unwrap();
+    assert_eq!(changed_box.format_version(), FormatVersion::V2);
+    assert_eq!(
+        &*eraser.restore(&changed_box).unwrap().open("other").unwrap(),
+        b"message"
+    );
+
+    // Serialization roundtrips.
+    let json = serde_json::to_value(&erased_box).unwrap();
+    assert_eq!(json["version"], 2);
+    assert_eq!(
+        serde_json::from_value::<ErasedPwBox>(json).unwrap(),
+        erased_box
+    );
+    let bytes = bincode::serialize(&erased_box).unwrap();
+    assert_eq!(
+        bincode::deserialize::<ErasedPwBox>(&bytes).unwrap(),
+        erased_box
+    );
+    let bytes = serde_cbor::to_vec(&erased_box).unwrap();
+    assert_eq!(
+        serde_cbor::from_slice::<ErasedPwBox>(&bytes).unwrap(),
+        erased_box
+    );
+
+    // Tampering with metadata.
+    let mut tampered_boxes = vec![erased_box.clone(); 7];
+    tampered_boxes[0].kdf = "scrypt-sodium".to_owned();
+    tampered_boxes[1].cipher = "xchacha".to_owned();
+    tampered_boxes[2].kdf_params.inner["opslimit"] = (1 << 16).into();
+    // Unknown params are ignored by the KDF, but are still authenticated.
+    tampered_boxes[3].kdf_params.inner["unknown"] = true.into();
+    tampered_boxes[4].version = FormatVersion::V1;
+    tampered_boxes[5].kdf_params.salt[0] ^= 1;
+    tampered_boxes[6].cipher_params.iv[0] ^= 1;
+    for tampered_box in &tampered_boxes {
+        let restored = eraser.restore(tampered_box).unwrap();
+        assert_matches!(restored.open(PASSWORD).unwrap_err(), Error::MacMismatch);
+        let typed_box = eraser.restore_as::<Sodium>(tampered_box).unwrap();
+        assert_matches!(typed_box.open(PASSWORD).unwrap_err(), Error::MacMismatch);
+    }
+
+    // Boxes in the v2 format require names matching the authenticated ones.
+    let mut other_eraser = Eraser::new();
+    other_eraser
+        .add_kdf::<Scrypt>("other-scrypt")
+        .unwrap()
+        .add_cipher::<crate::sodium::XChaCha20Poly1305>("other-xchacha")
+        .unwrap();
+    let typed_box = eraser.restore_as::<Sodium>(&erased_box).unwrap();
+    assert_matches!(
+        other_eraser.erase(&typed_box).unwrap_err(),
+        EraseError::MetadataMismatch
+    );
+    assert!(serde_json::to_value(&typed_box).is_err());
+
+    // The v2 format requires a cipher supporting associated data.
+    let mut builder = Sodium::build_box(&mut rng).cipher::<XSalsa20Poly1305>();
+    builder.kdf(Scrypt::light());
+    assert!(eraser.seal(&mut builder, PASSWORD, b"message").is_err());
+    let mut salsa_box = tampered_boxes[4].clone();
+    salsa_box.cipher = names::XSALSA20_POLY1305.to_owned();
+    salsa_box.version = FormatVersion::V2;
+    assert_matches!(
+        eraser.restore(&salsa_box).unwrap_err(),
+        Error::MalformedBox(ref reason) if reason.contains("v2")
+    );
+}
+
+#[cfg(feature = "rust-crypto")]
+#[test]
+fn v2_format_authenticates_nonce_for_ctr_ciphers() {
+    use crate::{
+        rcrypto::{RustCrypto, Scrypt},
+        ScryptParams,
+    };
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    let mut rng = thread_rng();
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<RustCrypto>().unwrap();
+    let mut builder = RustCrypto::build_box(&mut rng);
+    builder.kdf(Scrypt(ScryptParams::custom(6, 16)));
+
+    // The MAC of the `aes-128-ctr` cipher does not cover the nonce, so in the v1 format,
+    // a box with a tampered nonce is opened with garbled contents.
+    let mut v1_box = eraser.seal(&mut builder, PASSWORD, b"message").unwrap();
+    assert_eq!(v1_box.cipher_name(), names::AES_128_CTR);
+    v1_box.cipher_params.iv[0] ^= 1;
+    let restored = eraser.restore(&v1_box).unwrap();
+    assert_ne!(&*restored.open(PASSWORD).unwrap(), b"message");
+
+    eraser.set_format_version(FormatVersion::V2);
+    let v2_box = eraser.seal(&mut builder, PASSWORD, b"message").unwrap();
+    assert_eq!(v2_box.format_version(), FormatVersion::V2);
+    let restored = eraser.restore(&v2_box).unwrap();
+    assert_eq!(&*restored.open(PASSWORD).unwrap(), b"message");
+
+    let mut tampered_box = v2_box.clone();
+    tampered_box.cipher_params.iv[0] ^= 1;
+    let restored = eraser.restore(&tampered_box).unwrap();
+    assert_matches!(restored.open(PASSWORD).unwrap_err(), Error::MacMismatch);
+    let typed_box = eraser.restore_as::<RustCrypto>(&tampered_box).unwrap();
+    assert_matches!(typed_box.open(PASSWORD).unwrap_err(), Error::MacMismatch);
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn v1_format_with_v2_eraser() {
+    use crate::sodium::{Scrypt, Sodium};
+    use rand::thread_rng;
+
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+    let mut rng = thread_rng();
+    let mut builder = Sodium::build_box(&mut rng);
+    builder.kdf(Scrypt::light());
+    let v1_box = eraser.seal(&mut builder, "password", b"message").unwrap();
+    assert_eq!(v1_box.format_version(), FormatVersion::V1);
+    let json = serde_json::to_value(&v1_box).unwrap();
+    assert!(json.get("version").is_none());
+    let bytes = bincode::serialize(&v1_box).unwrap();
+    assert_eq!(bincode::deserialize::<ErasedPwBox>(&bytes).unwrap(), v1_box);
+
+    eraser.set_format_version(FormatVersion::V2);
+    let restored = eraser.restore(&v1_box).unwrap();
+    assert_eq!(&*restored.open("password").unwrap(), b"message");
+    assert_eq!(eraser.erase_restored(&restored).unwrap(), v1_box);
+
+    // The version may be specified explicitly.
+    let mut json = serde_json::to_value(&v1_box).unwrap();
+    json["version"] = 1.into();
+    assert_eq!(serde_json::from_value::<ErasedPwBox>(json).unwrap(), v1_box);
+
+    let mut json = serde_json::to_value(&v1_box).unwrap();
+    for &version in &[0, 3, 99] {
+        json["version"] = version.into();
+        let err = serde_json::from_value::<ErasedPwBox>(json.clone()).unwrap_err();
+        let expected = format!("unsupported box format version: {}", version);
+        assert!(err.to_string().contains(&expected), "{}", err);
+    }
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn erase_pwbox() {
+    use crate::sodium::{Scrypt, XSalsa20Poly1305};
+    use rand::thread_rng;
+
+    const PASSWORD: &str = "correct horse battery staple";
+    const MESSAGE: &[u8] = b"1234567890";
+
+    let mut eraser = Eraser::new();
+    let eraser = eraser
+        .add_kdf::<Scrypt>("scrypt-nacl")
+        .unwrap()
+        .add_cipher::<XSalsa20Poly1305>("xsalsa20-poly1305")
+        .unwrap();
+
+    let pwbox =
+        PwBox::<Scrypt, XSalsa20Poly1305>::new(&mut thread_rng(), PASSWORD, MESSAGE).unwrap();
+
+    let erased_box = eraser.erase(&pwbox).unwrap();
+    let json = serde_json::to_value(&erased_box).unwrap();
+    assert!(json["cipherparams"].get("aad").is_none());
+    let pwbox_copy = eraser.restore(&erased_box).unwrap();
+    assert!(!pwbox_copy.has_associated_data());
+    assert_eq!(MESSAGE.len(), pwbox_copy.len());
+    assert_eq!(MESSAGE, &*pwbox_copy.open(PASSWORD).unwrap());
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn comparing_and_cloning_boxes() {
+    use crate::sodium::{Scrypt, Sodium};
+    use rand::thread_rng;
+
+    const PASSWORD: &str = "correct horse battery staple";
+    const MESSAGE: &[u8] = b"1234567890";
+
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+    let pwbox = Sodium::build_box(&mut thread_rng())
+        .kdf(Scrypt::light())
+        .seal(PASSWORD, MESSAGE)
+        .unwrap();
+
+    let erased_box = pwbox.to_erased(&eraser).unwrap();
+    assert_eq!(erased_box, eraser.erase(&pwbox).unwrap());
+    let json = serde_json::to_string(&erased_box).unwrap();
+    let box_copy: ErasedPwBox = serde_json::from_str(&json).unwrap();
+    assert_eq!(box_copy, erased_box);
+    let restored = eraser.restore(&box_copy).unwrap();
+    assert_eq!(eraser.erase_restored(&restored).unwrap(), erased_box);
+
+    // Boxes with the same contents differ due to random salt and nonce.
+    let other_box = Sodium::build_box(&mut thread_rng())
+        .kdf(Scrypt::light())
+        .seal(PASSWORD, MESSAGE)
+        .unwrap();
+    assert_ne!(eraser.erase(&other_box).unwrap(), erased_box);
+    let mut mauled_box = erased_box.clone();
+    mauled_box.encrypted.mac[0] ^= 1;
+    assert_ne!(mauled_box, erased_box);
+    assert_ne!(mauled_box.encrypted, erased_box.encrypted);
+
+    let pwbox_copy = pwbox.clone();
+    assert_eq!(&*pwbox_copy.open(PASSWORD).unwrap(), MESSAGE);
+    assert_eq!(&*pwbox.open(PASSWORD).unwrap(), MESSAGE);
+    let restored_copy = restored.clone();
+    assert_eq!(&*restored_copy.open(PASSWORD).unwrap(), MESSAGE);
+    assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn erase_restored_pwbox() {
+    use crate::sodium::{Scrypt, Sodium};
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    const PASSWORD: &str = "correct horse battery staple";
+    const MESSAGE: &[u8] = b"1234567890";
+
+    let pwbox = Sodium::build_box(&mut thread_rng())
+        .kdf(Scrypt::light())
+        .seal(PASSWORD, MESSAGE)
+        .unwrap();
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+    let erased_box = eraser.erase(&pwbox).unwrap();
+    let json = serde_json::to_string(&erased_box).unwrap();
+
+    let restored = eraser.restore(&erased_box).unwrap();
+    assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+    let erased_copy = eraser.erase_restored(&restored).unwrap();
+    assert_eq!(serde_json::to_string(&erased_copy).unwrap(), json);
+
+    // Restore the box from the JSON with a non-canonical KDF params.
+    let mut json = serde_json::to_value(&erased_box).unwrap();
+    json["kdfparams"]["extra"] = 42.into();
+    let erased_box: ErasedPwBox = serde_json::from_value(json.clone()).unwrap();
+    let restored = eraser.restore(&erased_box).unwrap();
+    let erased_copy = eraser.erase_restored(&restored).unwrap();
+    assert_eq!(serde_json::to_value(&erased_copy).unwrap(), json);
+
+    // Boxes with changed password preserve the KDF params as well.
+    let restored = restored
+        .change_password(&mut thread_rng(), PASSWORD, "new password")
+        .unwrap();
+    let erased_copy = serde_json::to_value(eraser.erase_restored(&restored).unwrap()).unwrap();
+    assert_eq!(erased_copy["kdfparams"]["extra"], 42);
+    assert_ne!(erased_copy["kdfparams"]["salt"], json["kdfparams"]["salt"]);
+
+    let empty_eraser = Eraser::new();
+    assert_matches!(
+        empty_eraser.erase_restored(&restored).unwrap_err(),
+        EraseError::NoKdf
+    );
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn converting_pwbox_to_restored() {
+    use crate::sodium::{Scrypt, Sodium};
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+    for &version in &[FormatVersion::V1, FormatVersion::V2] {
+        eraser.set_format_version(version);
+        let mut rng = thread_rng();
+        let mut builder = Sodium::build_box(&mut rng);
+        builder.kdf(Scrypt::light()).associated_data(b"context");
+        let erased_box = eraser.seal(&mut builder, PASSWORD, b"message").unwrap();
+        let pwbox = eraser.restore_as::<Sodium>(&erased_box).unwrap();
+
+        let restored = pwbox.to_restored(&eraser).unwrap();
+        assert_eq!(restored.kdf_name(), names::SCRYPT_NACL);
+        assert_eq!(restored.cipher_name(), names::XCHACHA20_POLY1305);
+        assert!(restored.content_eq(&eraser.restore(&erased_box).unwrap()));
+        let plaintext = restored.open_with_aad(PASSWORD, b"context").unwrap();
+        assert_eq!(&*plaintext, b"message");
+        assert_eq!(eraser.erase_restored(&restored).unwrap(), erased_box);
+    }
+
+    let pwbox = Sodium::build_box(&mut thread_rng())
+        .kdf(Scrypt::light())
+        .seal(PASSWORD, b"message")
+        .unwrap();
+    assert_matches!(
+        pwbox.to_restored(&Eraser::new()).unwrap_err(),
+        EraseError::NoKdf
+    );
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn parse_kdf_from_erased_pwbox() {
+    use crate::{
+        sodium::{Scrypt, Sodium},
+        KdfDifficulty,
+    };
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    let pwbox = Sodium::build_box(&mut thread_rng())
+        .kdf(Scrypt::light())
+        .seal("correct horse battery staple", b"1234567890")
+        .unwrap();
+    let erased_box = Eraser::new()
+        .add_suite::<Sodium>()
+        .unwrap()
+        .erase(&pwbox)
+        .unwrap();
+
+    let kdf: Scrypt = erased_box.parse_kdf().unwrap();
+    assert_eq!(kdf.memory_cost(), Scrypt::light().memory_cost());
+    assert_eq!(kdf.ops_cost(), Scrypt::light().ops_cost());
+    assert!(kdf.memory_cost() < Scrypt::interactive().memory_cost());
+
+    let mut json = serde_json::to_value(&erased_box).unwrap();
+    json["kdfparams"]["memlimit"] = "not a number".into();
+    let erased_box: ErasedPwBox = serde_json::from_value(json).unwrap();
+    assert_matches!(
+        erased_box.parse_kdf::<Scrypt>().unwrap_err(),
+        Error::KdfParams(_)
+    );
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn erased_pwbox_from_parts() {
+    use crate::sodium::{Scrypt, Sodium};
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    const PASSWORD: &str = "correct horse battery staple";
+    const MESSAGE: &[u8] = b"1234567890";
+
+    let pwbox = Sodium::build_box(&mut thread_rng())
+        .kdf(Scrypt::light())
+        .seal(PASSWORD, MESSAGE)
+        .unwrap();
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+    let erased_box = eraser.erase(&pwbox).unwrap();
+
+    assert_eq!(erased_box.kdf_name(), "scrypt-nacl");
+    assert_eq!(erased_box.cipher_name(), "xchacha20-poly1305");
+    assert_eq!(
+        *erased_box.kdf_params(),
+        serde_json::to_value(Scrypt::light()).unwrap()
+    );
+    assert_eq!(erased_box.salt().len(), 32);
+    assert_eq!(erased_box.nonce().len(), 24);
+    assert_eq!(erased_box.ciphertext().len(), MESSAGE.len());
+    assert_eq!(erased_box.mac().len(), 16);
+    assert!(!erased_box.has_associated_data());
+
+    let encrypted = CipherOutput {
+        ciphertext: erased_box.ciphertext().to_vec(),
+        mac: erased_box.mac().to_vec(),
+    };
+    let assembled_box = ErasedPwBox::from_parts(
+        erased_box.kdf_name(),
+        erased_box.kdf_params().clone(),
+        erased_box.salt().to_vec(),
+        erased_box.cipher_name(),
+        erased_box.nonce().to_vec(),
+        encrypted.clone(),
+    )
+    .unwrap();
+    assert_eq!(
+        serde_json::to_value(&assembled_box).unwrap(),
+        serde_json::to_value(&erased_box).unwrap()
+    );
+    let restored = eraser.restore(&assembled_box).unwrap();
+    assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+
+    // Invalid parts.
+    let salt = erased_box.salt().to_vec();
+    let nonce = erased_box.nonce().to_vec();
+    let err = ErasedPwBox::from_parts(
+        "scrypt-nacl",
+        serde_json::json!([1, 2]),
+        salt.clone(),
+        "xsalsa20-poly1305",
+        nonce.clone(),
+        encrypted.clone(),
+    )
+    .unwrap_err();
+    assert_matches!(err, Error::MalformedBox(ref e) if e.contains("object"));
+
+    let mut kdf_params = erased_box.kdf_params().clone();
+    kdf_params["salt"] = "00".into();
+    let err = ErasedPwBox::from_parts(
+        "scrypt-nacl",
+        kdf_params,
+        salt,
+        "xsalsa20-poly1305",
+        nonce,
+        encrypted.clone(),
+    )
+    .unwrap_err();
+    assert_matches!(err, Error::MalformedBox(ref e) if e.contains("salt"));
+
+    let err = ErasedPwBox::from_parts(
+        "scrypt-nacl",
+        erased_box.kdf_params().clone(),
+        vec![],
+        "xsalsa20-poly1305",
+        erased_box.nonce().to_vec(),
+        encrypted,
+    )
+    .unwrap_err();
+    assert_matches!(err, Error::SaltLen);
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn restoring_malformed_boxes() {
+    use crate::sodium::{ScryptCompat, Sodium, XSalsa20Poly1305};
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    let pwbox = PwBoxBuilder::<_, XSalsa20Poly1305>::new(&mut thread_rng())
+        .kdf(ScryptCompat(crate::ScryptParams::light()))
+        .seal(PASSWORD, b"1234567890")
+        .unwrap();
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+    let erased_box = eraser.erase(&pwbox).unwrap();
+    assert_eq!(erased_box.kdf_name(), "scrypt");
+
+    // Scrypt params overflowing `libsodium` limits.
+    let mut json = serde_json::to_value(&erased_box).unwrap();
+    json["kdfparams"]["n"] = (1_u64 << 20).into();
+    json["kdfparams"]["p"] = 1_024.into();
+    let malformed_box: ErasedPwBox = serde_json::from_value(json).unwrap();
+    let restored = eraser.restore(&malformed_box).unwrap();
+    let err = restored.open(PASSWORD).unwrap_err();
+    assert_matches!(err, Error::DeriveKey(ref e) if e.to_string().contains("too large"));
+
+    // Scrypt params violating constraints from the spec.
+    let mut json = serde_json::to_value(&erased_box).unwrap();
+    json["kdfparams"]["n"] = (1_u64 << 31).into();
+    json["kdfparams"]["r"] = u32::MAX.into();
+    json["kdfparams"]["p"] = u32::MAX.into();
+    let malformed_box: ErasedPwBox = serde_json::from_value(json).unwrap();
+    let err = eraser.restore(&malformed_box).unwrap_err();
+    assert_matches!(err, Error::DeriveKey(ref e) if e.to_string().contains("invalid"));
+
+    // Too large `n`.
+    let mut json = serde_json::to_value(&erased_box).unwrap();
+    json["kdfparams"]["n"] = (1_u64 << 40).into();
+    let malformed_box: ErasedPwBox = serde_json::from_value(json).unwrap();
+    assert_matches!(
+        eraser.restore(&malformed_box).unwrap_err(),
+        Error::KdfParams(_)
+    );
+
+    // KDF params that are not an object may be produced by binary formats.
+    let mut malformed_box = erased_box;
+    malformed_box.kdf_params.inner = serde_json::json!([1, 2, 3]);
+    let err = eraser.restore(&malformed_box).unwrap_err();
+    assert_matches!(err, Error::MalformedBox(ref e) if e.contains("object"));
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn restore_typed_pwbox() {
+    use crate::sodium::{ChaCha20Poly1305, Scrypt, ScryptCompat, Sodium};
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    const PASSWORD: &str = "correct horse battery staple";
+    const MESSAGE: &[u8] = b"1234567890";
+
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+
+    let mut rng = thread_rng();
+    let pwbox = Sodium::build_box(&mut rng)
+        .kdf(Scrypt::light())
+        .seal(PASSWORD, MESSAGE)
+        .unwrap();
+    let erased_box = eraser.erase(&pwbox).unwrap();
+    let pwbox_copy: PwBox<Scrypt, _> = eraser.restore_as::<Sodium>(&erased_box).unwrap();
+    assert_eq!(MESSAGE, &*pwbox_copy.open(PASSWORD).unwrap());
+
+    // Non-recommended cipher.
+    let pwbox = PwBoxBuilder::<_, ChaCha20Poly1305>::new(&mut rng)
+        .kdf(Scrypt::light())
+        .seal(PASSWORD, MESSAGE)
+        .unwrap();
+    let erased_box = eraser.erase(&pwbox).unwrap();
+    assert_matches!(
+        eraser.restore_as::<Sodium>(&erased_box).map(drop).unwrap_err(),
+        Error::NoCipher(ref name) if name == "chacha20-poly1305"
+    );
+    let pwbox_copy = eraser
+        .restore_typed::<Scrypt, ChaCha20Poly1305>(&erased_box)
+        .unwrap();
+    assert_eq!(MESSAGE, &*pwbox_copy.open(PASSWORD).unwrap());
+
+    // Non-recommended KDF.
+    assert_matches!(
+        eraser
+            .restore_typed::<ScryptCompat, ChaCha20Poly1305>(&erased_box)
+            .map(drop)
+            .unwrap_err(),
+        Error::NoKdf(ref name) if name == "scrypt-nacl"
+    );
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn suite_seal_and_open() {
+    use crate::sodium::Sodium;
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    const PASSWORD: &str = "correct horse battery staple";
+    const MESSAGE: &[u8] = b"1234567890";
+
+    let erased_box = Sodium::seal(&mut thread_rng(), PASSWORD, MESSAGE).unwrap();
+    assert_eq!(erased_box.cipher, "xchacha20-poly1305");
+    assert_eq!(&*Sodium::open(&erased_box, PASSWORD).unwrap(), MESSAGE);
+    assert_matches!(
+        Sodium::open(&erased_box, "incorrect password").unwrap_err(),
+        Error::MacMismatch
+    );
+
+    let mut json = serde_json::to_value(&erased_box).unwrap();
+    json["cipher"] = "unknown-cipher".into();
+    let erased_box: ErasedPwBox = serde_json::from_value(json).unwrap();
+    assert_matches!(
+        Sodium::open(&erased_box, PASSWORD).unwrap_err(),
+        Error::NoCipher(ref name) if name == "unknown-cipher"
+    );
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn suite_json_roundtrip() {
+    use crate::sodium::Sodium;
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    const PASSWORD: &str = "correct horse battery staple";
+    const MESSAGE: &[u8] = b"1234567890";
+
+    let json = Sodium::seal_to_json(&mut thread_rng(), PASSWORD, MESSAGE).unwrap();
+    let erased_box: ErasedPwBox = serde_json::from_str(&json).unwrap();
+    assert_eq!(erased_box.cipher_name(), names::XCHACHA20_POLY1305);
+    assert_eq!(&*Sodium::open_json(&json, PASSWORD).unwrap(), MESSAGE);
+    assert_matches!(
+        Sodium::open_json(&json, "incorrect password").unwrap_err(),
+        Error::MacMismatch
+    );
+
+    let err = Sodium::open_json(&json[1..], PASSWORD).unwrap_err();
+    assert_matches!(err, Error::Serialization(_));
+    let err = Sodium::open_json(r#"{ "kdf": "scrypt-nacl" }"#, PASSWORD).unwrap_err();
+    assert_matches!(err, Error::Serialization(_));
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn opening_erased_box_directly() {
+    use crate::sodium::{Scrypt, Sodium};
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    const PASSWORD: &str = "correct horse battery staple";
+    const MESSAGE: &[u8] = b"1234567890";
+
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+    let pwbox = Sodium::build_box(&mut thread_rng())
+        .kdf(Scrypt::light())
+        .seal(PASSWORD, MESSAGE)
+        .unwrap();
+    let erased_box = eraser.erase(&pwbox).unwrap();
+    assert_eq!(&*eraser.open(&erased_box, PASSWORD).unwrap(), MESSAGE);
+
+    let mut output = [0_u8; 16];
+    let len = eraser
+        .open_into(&erased_box, &mut output, PASSWORD)
+        .unwrap();
+    assert_eq!(&output[..len], MESSAGE);
+    assert_matches!(
+        eraser
+            .open_into(&erased_box, &mut output[..4], PASSWORD)
+            .unwrap_err(),
+        Error::OutputLen {
+            expected: 10,
+            actual: 4
+        }
+    );
+    assert_matches!(
+        eraser.open(&erased_box, "incorrect password").unwrap_err(),
+        Error::MacMismatch
+    );
+
+    // Boxes in the v2 format are supported as well.
+    eraser.set_format_version(FormatVersion::V2);
+    let mut erased_box = eraser.erase(&pwbox).unwrap();
+    assert_eq!(&*eraser.open(&erased_box, PASSWORD).unwrap(), MESSAGE);
+    erased_box.kdf = "unknown-kdf".to_owned();
+    assert_matches!(
+        Eraser::new().open(&erased_box, PASSWORD).unwrap_err(),
+        Error::NoKdf(ref name) if name == "unknown-kdf"
+    );
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn detached_ciphertext() {
+    use crate::sodium::{Scrypt, Sodium};
+    use assert_matches::assert_matches;
+    use rand::{thread_rng, RngCore};
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    let mut rng = thread_rng();
+    let mut message = vec![0_u8; 5 << 20];
+    rng.fill_bytes(&mut message);
+    let pwbox = Sodium::build_box(&mut rng)
+        .kdf(Scrypt::light())
+        .seal(PASSWORD, &message)
+        .unwrap();
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+
+    let (header, ciphertext) = eraser.erase_detached(&pwbox).unwrap();
+    assert_eq!(header.ciphertext_len(), 5 << 20);
+    assert_eq!(ciphertext, pwbox.inner.encrypted.ciphertext);
+    let json = serde_json::to_value(&header).unwrap();
+    assert!(json.get("ciphertext").is_none());
+    assert_eq!(json["ciphertext_len"], 5 << 20);
+    assert_eq!(json["ciphertext_checksum"].as_str().unwrap().len(), 16);
+    assert_eq!(json["kdf"], names::SCRYPT_NACL);
+
+    let header_copy: ErasedPwBoxHeader = serde_json::from_value(json).unwrap();
+    assert_eq!(header_copy, header);
+    let header_copy: ErasedPwBoxHeader =
+        toml::from_str(&toml::to_string(&header).unwrap()).unwrap();
+    assert_eq!(header_copy, header);
+    let header_copy: ErasedPwBoxHeader =
+        bincode::deserialize(&bincode::serialize(&header).unwrap()).unwrap();
+    assert_eq!(header_copy, header);
+
+    let restored = eraser.restore_detached(&header, &ciphertext).unwrap();
+    assert_eq!(&*restored.open(PASSWORD).unwrap(), &*message);
+
+    // Mismatched ciphertexts are rejected before decryption.
+    assert_matches!(
+        eraser
+            .restore_detached(&header, &ciphertext[1..])
+            .unwrap_err(),
+        Error::CiphertextMismatch
+    );
+    let mut corrupted = ciphertext;
+    corrupted[1_000] ^= 1;
+    assert_matches!(
+        eraser.restore_detached(&header, &corrupted).unwrap_err(),
+        Error::CiphertextMismatch
+    );
+
+    // The checksum is not a security measure; if it is updated together with
+    // the ciphertext, the corruption is detected by the MAC.
+    let mut forged_header = header;
+    forged_header.ciphertext_checksum = ciphertext_checksum(&corrupted);
+    let restored = eraser.restore_detached(&forged_header, &corrupted).unwrap();
+    assert_matches!(restored.open(PASSWORD).unwrap_err(), Error::MacMismatch);
+}
+
+#[test]
+fn fnv_checksum() {
+    // Test vectors from the reference implementation.
+    assert_eq!(
+        ciphertext_checksum(b""),
+        0xcbf2_9ce4_8422_2325_u64.to_be_bytes()
+    );
+    assert_eq!(
+        ciphertext_checksum(b"a"),
+        0xaf63_dc4c_8601_ec8c_u64.to_be_bytes()
+    );
+    assert_eq!(
+        ciphertext_checksum(b"foobar"),
+        0x8594_4171_f739_67e8_u64.to_be_bytes()
+    );
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn kdf_limits() {
+    use crate::{
+        sodium::{Argon2, Sodium, XSalsa20Poly1305},
+        Argon2Params, Argon2Variant, KdfCost, KdfLimits,
+    };
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    use std::time::{Duration, Instant};
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    let kdf = Argon2(Argon2Params::custom(Argon2Variant::Argon2id, 1 << 10, 3, 1));
+    let pwbox = PwBoxBuilder::<_, XSalsa20Poly1305>::new(&mut thread_rng())
+        .kdf(kdf)
+        .seal(PASSWORD, b"1234567890")
+        .unwrap();
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+    let erased_box = eraser.erase(&pwbox).unwrap();
+
+    let limits = KdfLimits {
+        max_memory_bytes: 64 << 20,
+        max_time_cost: 1 << 30,
+    };
+    eraser.set_kdf_limits(limits);
+    assert_eq!(eraser.kdf_limits(), limits);
+    let restored = eraser.restore(&erased_box).unwrap();
+    assert_eq!(&*restored.open(PASSWORD).unwrap(), b"1234567890");
+
+    // Argon2 params requesting 4 TiB of memory.
+    let mut json = serde_json::to_value(&erased_box).unwrap();
+    json["kdfparams"]["mem_cost"] = u32::MAX.into();
+    let pathological_box: ErasedPwBox = serde_json::from_value(json).unwrap();
+    let start = Instant::now();
+    let err = eraser.restore(&pathological_box).unwrap_err();
+    assert!(start.elapsed() < Duration::from_secs(1));
+    let expected_cost = KdfCost {
+        memory_bytes: u64::from(u32::MAX) << 10,
+        time_cost: (u64::from(u32::MAX) << 10) * u64::from(kdf.0.time_cost),
+    };
+    assert_matches!(err, Error::KdfParamsTooExpensive(cost) if cost == expected_cost);
+    let err = eraser
+        .restore_typed::<Argon2, XSalsa20Poly1305>(&pathological_box)
+        .unwrap_err();
+    assert_matches!(err, Error::KdfParamsTooExpensive(_));
+
+    // Too many passes over the memory.
+    let mut json = serde_json::to_value(&erased_box).unwrap();
+    json["kdfparams"]["time_cost"] = u32::MAX.into();
+    let pathological_box: ErasedPwBox = serde_json::from_value(json).unwrap();
+    let err = eraser.restore(&pathological_box).unwrap_err();
+    assert_matches!(err, Error::KdfParamsTooExpensive(_));
+
+    // Invalid params are rejected regardless of limits.
+    eraser.set_kdf_limits(KdfLimits::default());
+    let mut json = serde_json::to_value(&erased_box).unwrap();
+    json["kdfparams"]["parallelism"] = 0.into();
+    let invalid_box: ErasedPwBox = serde_json::from_value(json).unwrap();
+    let err = eraser.restore(&invalid_box).unwrap_err();
+    assert_matches!(err, Error::DeriveKey(ref e) if e.to_string().contains("parallelism"));
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn migrating_boxes() {
+    use crate::{
+        sodium::{Scrypt, Sodium, XChaCha20Poly1305, XSalsa20Poly1305},
+        UpgradePolicy,
+    };
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    const PASSWORD: &str = "correct horse battery staple";
+    const MESSAGE: &[u8] = b"battery staple";
+
+    let mut rng = thread_rng();
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+    let mut builder = PwBoxBuilder::<_, XSalsa20Poly1305>::new(&mut rng);
+    builder.kdf(Scrypt::light());
+    let legacy_box = eraser.seal(&mut builder, PASSWORD, MESSAGE).unwrap();
+
+    // Sensitive params would make the test too slow; interactive ones are stronger
+    // than light params as well.
+    let strong_kdf = Scrypt::interactive();
+    let policy = UpgradePolicy::min_kdf_cost(strong_kdf.cost().unwrap());
+    assert!(eraser.needs_upgrade(&legacy_box, &policy));
+    assert!(!eraser.needs_upgrade(&legacy_box, &UpgradePolicy::default()));
+
+    // Upgrade the KDF.
+    let mut builder = PwBoxBuilder::<_, XSalsa20Poly1305>::new(&mut rng);
+    builder.kdf(strong_kdf);
+    let err = eraser
+        .migrate(&legacy_box, "incorrect password", &mut builder)
+        .unwrap_err();
+    assert_matches!(err, Error::MacMismatch);
+    let migrated_box = eraser.migrate(&legacy_box, PASSWORD, &mut builder).unwrap();
+    assert!(!eraser.needs_upgrade(&migrated_box, &policy));
+    assert_eq!(migrated_box.cipher_name(), names::XSALSA20_POLY1305);
+    let restored = eraser.restore(&migrated_box).unwrap();
+    assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+    // The legacy box can still be opened.
+    let restored = eraser.restore(&legacy_box).unwrap();
+    assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+
+    // Upgrade the cipher.
+    let policy = UpgradePolicy {
+        allowed_ciphers: vec![names::XCHACHA20_POLY1305.to_owned()],
+        ..UpgradePolicy::default()
+    };
+    assert!(eraser.needs_upgrade(&legacy_box, &policy));
+    let mut builder = PwBoxBuilder::<_, XChaCha20Poly1305>::new(&mut rng);
+    builder.kdf(Scrypt::light());
+    let migrated_box = eraser.migrate(&legacy_box, PASSWORD, &mut builder).unwrap();
+    assert!(!eraser.needs_upgrade(&migrated_box, &policy));
+    assert_eq!(migrated_box.cipher_name(), names::XCHACHA20_POLY1305);
+    let restored = eraser.restore(&migrated_box).unwrap();
+    assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+
+    // Targets not registered in the eraser are rejected before opening the box.
+    let err = Eraser::new().migrate(&legacy_box, PASSWORD, &mut builder);
+    assert_matches!(err.unwrap_err(), Error::NoKdf(_));
+}
+
+#[cfg(feature = "rust-crypto")]
+#[test]
+fn composing_cipher_with_mac() {
+    use crate::{
+        rcrypto::{Aes128Ctr, Aes128CtrHmacSha512, Aes256Ctr, HmacSha512, Pbkdf2, RustCrypto},
+        CipherWithMac,
+    };
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    const PASSWORD: &str = "correct horse battery staple";
+    const MESSAGE: &[u8] = b"battery staple";
+    type Composed = CipherWithMac<Aes128Ctr, HmacSha512>;
+
+    let mut rng = thread_rng();
+    let mut eraser = Eraser::new();
+    eraser
+        .add_kdf::<Pbkdf2>(names::PBKDF2)
+        .unwrap()
+        .add_unauthenticated_cipher::<Aes128Ctr>("aes-128-ctr")
+        .unwrap()
+        .add_mac::<HmacSha512>("hmac-sha512")
+        .unwrap();
+    // Repeated registration is a no-op.
+    eraser.add_mac::<HmacSha512>("hmac-sha512").unwrap();
+    assert_matches!(
+        eraser.add_mac::<HmacSha512>("other-hmac").unwrap_err(),
+        Error::DuplicateName(ref name) if name == "other-hmac"
+    );
+    assert_matches!(
+        eraser
+            .add_unauthenticated_cipher::<Aes256Ctr>("aes-128-ctr")
+            .unwrap_err(),
+        Error::DuplicateName(_)
+    );
+
+    let kdf = Pbkdf2 {
+        c: 1_000,
+        ..Pbkdf2::default()
+    };
+    let pwbox = PwBoxBuilder::<_, Composed>::new(&mut rng)
+        .kdf(kdf)
+        .seal(PASSWORD, MESSAGE)
+        .unwrap();
+    let erased_box = eraser.erase(&pwbox).unwrap();
+    assert_eq!(erased_box.cipher_name(), "aes-128-ctr+hmac-sha512");
+    let json = serde_json::to_string(&erased_box).unwrap();
+    let erased_box: ErasedPwBox = serde_json::from_str(&json).unwrap();
+    let restored = eraser.restore(&erased_box).unwrap();
+    assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+    assert_eq!(eraser.erase_restored(&restored).unwrap(), erased_box);
+    let typed = eraser
+        .restore_typed::<Pbkdf2, Composed>(&erased_box)
+        .unwrap();
+    assert_eq!(&*typed.open(PASSWORD).unwrap(), MESSAGE);
+
+    // Boxes in the v2 format authenticate the composed name.
+    eraser.set_format_version(FormatVersion::V2);
+    let mut builder = PwBoxBuilder::<_, Composed>::new(&mut rng);
+    builder.kdf(kdf);
+    let erased_box = eraser.seal(&mut builder, PASSWORD, MESSAGE).unwrap();
+    let restored = eraser.restore(&erased_box).unwrap();
+    assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+
+    // The composition restored from halves is compatible with the concrete type.
+    let mut suite_eraser = Eraser::new();
+    suite_eraser.add_suite::<RustCrypto>().unwrap();
+    let mut builder = PwBoxBuilder::<_, Aes128CtrHmacSha512>::new(&mut rng);
+    builder.kdf(kdf);
+    let mut erased_box = suite_eraser.seal(&mut builder, PASSWORD, MESSAGE).unwrap();
+    assert_eq!(erased_box.cipher_name(), names::AES_128_CTR_HMAC_SHA512);
+    erased_box.cipher = "aes-128-ctr+hmac-sha512".to_owned();
+    let restored = eraser.restore(&erased_box).unwrap();
+    assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+
+    // Unknown halves are reported by name.
+    erased_box.cipher = "aes-256-ctr+hmac-sha512".to_owned();
+    let err = eraser.restore(&erased_box).unwrap_err();
+    assert_matches!(err, Error::NoCipher(ref name) if name == "aes-256-ctr");
+    erased_box.cipher = "aes-128-ctr+keccak256".to_owned();
+    let err = eraser.restore(&erased_box).unwrap_err();
+    assert_matches!(err, Error::NoCipher(ref name) if name == "keccak256");
+    erased_box.cipher = "aes-128-ctr".to_owned();
+    let err = eraser.restore(&erased_box).unwrap_err();
+    assert_matches!(err, Error::NoCipher(ref name) if name == "aes-128-ctr");
+
+    let pwbox = PwBoxBuilder::<_, CipherWithMac<Aes256Ctr, HmacSha512>>::new(&mut rng)
+        .kdf(kdf)
+        .seal(PASSWORD, MESSAGE)
+        .unwrap();
+    assert_matches!(eraser.erase(&pwbox).unwrap_err(), EraseError::NoCipher);
+}
+
+#[cfg(feature = "rust-crypto")]
+#[test]
+fn sealing_with_dyn_cipher() {
+    use crate::{
+        rcrypto::{Aes128Gcm, Aes256Gcm, RustCrypto, Scrypt},
+        MacMismatch, ScryptParams,
+    };
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    const PASSWORD: &str = "correct horse battery staple";
+    const MESSAGE: &[u8] = b"battery staple";
+
+    /// AES-GCM with the key size chosen at runtime.
+    struct RuntimeGcm {
+        key_len: usize,
+    }
+
+    impl DynCipher for RuntimeGcm {
+        fn key_len(&self) -> usize {
+            self.key_len
+        }
+
+        fn nonce_len(&self) -> usize {
+            Aes256Gcm::NONCE_LEN
+        }
+
+        fn mac_len(&self) -> usize {
+            Aes256Gcm::MAC_LEN
+        }
+
+        fn seal(&self, message: &[u8], aad: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
+            match self.key_len {
+                16 => Aes128Gcm::seal(message, aad, nonce, key),
+                _ => Aes256Gcm::seal(message, aad, nonce, key),
+            }
+        }
+
+        fn open(
+            &self,
+            output: &mut [u8],
+            enc: &CipherOutput,
+            aad: &[u8],
+            nonce: &[u8],
+            key: &[u8],
+        ) -> Result<(), MacMismatch> {
+            match self.key_len {
+                16 => Aes128Gcm::open(output, enc, aad, nonce, key),
+                _ => Aes256Gcm::open(output, enc, aad, nonce, key),
+            }
+        }
+    }
+
+    let mut rng = thread_rng();
+    let kdf = Scrypt(ScryptParams::custom(6, 16));
+    let mut suite_eraser = Eraser::new();
+    suite_eraser.add_suite::<RustCrypto>().unwrap();
+    let mut eraser = Eraser::new();
+    eraser
+        .add_phc_kdf::<Scrypt>(names::SCRYPT)
+        .unwrap()
+        .add_dyn_cipher(names::AES_128_GCM, || Arc::new(RuntimeGcm { key_len: 16 }))
+        .unwrap()
+        .add_dyn_cipher(names::AES_256_GCM, || Arc::new(RuntimeGcm { key_len: 32 }))
+        .unwrap();
+    assert_eq!(eraser.ciphers().count(), 2);
+
+    for &version in &[FormatVersion::V1, FormatVersion::V2] {
+        eraser.set_format_version(version);
+        suite_eraser.set_format_version(version);
+
+        // Boxes sealed with the dynamic cipher are compatible with the static one.
+        for &cipher_name in &[names::AES_128_GCM, names::AES_256_GCM] {
+            let erased_box = eraser
+                .seal_with_cipher(&mut rng, cipher_name, kdf, PASSWORD, MESSAGE)
+                .unwrap();
+            assert_eq!(erased_box.format_version(), version);
+            assert_eq!(erased_box.cipher_name(), cipher_name);
+            let json = serde_json::to_string(&erased_box).unwrap();
+            let erased_box: ErasedPwBox = serde_json::from_str(&json).unwrap();
+            let restored = eraser.restore(&erased_box).unwrap();
+            assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+            assert_eq!(eraser.erase_restored(&restored).unwrap(), erased_box);
+            let restored = suite_eraser.restore(&erased_box).unwrap();
+            assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+        }
+
+        // ...and vice versa.
+        let mut builder = PwBoxBuilder::<_, Aes128Gcm>::new(&mut rng);
+        builder.kdf(kdf);
+        let erased_box = suite_eraser.seal(&mut builder, PASSWORD, MESSAGE).unwrap();
+        let restored = eraser.restore(&erased_box).unwrap();
+        assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+
+        // Statically typed ciphers can be used for sealing by name as well.
+        let erased_box = suite_eraser
+            .seal_with_cipher(&mut rng, names::AES_256_GCM, kdf, PASSWORD, MESSAGE)
+            .unwrap();
+        let restored = eraser.restore(&erased_box).unwrap();
+        assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+    }
+
+    // Dynamic ciphers cannot be registered under taken names.
+    assert_matches!(
+        eraser
+            .add_dyn_cipher(names::AES_256_GCM, || Arc::new(RuntimeGcm { key_len: 32 }))
+            .unwrap_err(),
+        Error::DuplicateName(ref name) if name == names::AES_256_GCM
+    );
+    assert_matches!(
+        suite_eraser
+            .add_dyn_cipher(names::AES_128_GCM, || Arc::new(RuntimeGcm { key_len: 16 }))
+            .unwrap_err(),
+        Error::DuplicateName(ref name) if name == names::AES_128_GCM
+    );
+    assert_matches!(
+        eraser.add_cipher::<Aes128Gcm>(names::AES_128_GCM).unwrap_err(),
+        Error::DuplicateName(ref name) if name == names::AES_128_GCM
+    );
+
+    let err = eraser
+        .seal_with_cipher(&mut rng, "aes-512-gcm", kdf, PASSWORD, MESSAGE)
+        .unwrap_err();
+    assert_matches!(err, Error::NoCipher(ref name) if name == "aes-512-gcm");
+    let err = Eraser::new()
+        .seal_with_cipher(&mut rng, names::AES_128_GCM, kdf, PASSWORD, MESSAGE)
+        .unwrap_err();
+    assert_matches!(err, Error::NoKdf(_));
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn sharing_eraser() {
+    use crate::sodium::{Scrypt, Sodium, XChaCha20Poly1305};
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+    use std::thread;
+
+    const PASSWORD: &str = "correct horse battery staple";
+    const MESSAGE: &[u8] = b"battery staple";
+
+    let eraser = Eraser::with_suites::<(Sodium,)>().unwrap();
+    let mut rng = thread_rng();
+    let mut builder = Sodium::build_box(&mut rng);
+    builder.kdf(Scrypt::light());
+    let erased_box = eraser.seal(&mut builder, PASSWORD, MESSAGE).unwrap();
+
+    // Restore the same box from several threads concurrently.
+    let (eraser, erased_box) = (Arc::new(eraser), Arc::new(erased_box));
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let (eraser, erased_box) = (Arc::clone(&eraser), Arc::clone(&erased_box));
+            thread::spawn(move || eraser.restore(&erased_box)?.open(PASSWORD))
+        })
+        .collect();
+    for handle in handles {
+        assert_eq!(&*handle.join().unwrap().unwrap(), MESSAGE);
+    }
+
+    // Clones share registrations until they are modified.
+    let mut eraser_copy = (*eraser).clone();
+    assert!(Arc::ptr_eq(&eraser.registry, &eraser_copy.registry));
+    eraser_copy.add_suite::<Sodium>().unwrap();
+    assert!(Arc::ptr_eq(&eraser.registry, &eraser_copy.registry));
+    eraser_copy
+        .add_alias("secretbox", names::XSALSA20_POLY1305)
+        .unwrap();
+    assert!(!Arc::ptr_eq(&eraser.registry, &eraser_copy.registry));
+    assert!(eraser.resolve("secretbox") == "secretbox");
+
+    // Layering registrations over a shared `Eraser`.
+    let mut base = Eraser::new();
+    base.add_cipher::<XChaCha20Poly1305>(names::XCHACHA20_POLY1305)
+        .unwrap();
+    let err = base.restore(&erased_box).unwrap_err();
+    assert_matches!(err, Error::NoKdf(ref name) if name == names::SCRYPT_NACL);
+    let mut overrides = Eraser::new();
+    overrides.add_kdf::<Scrypt>(names::SCRYPT_NACL).unwrap();
+    let err = overrides.restore(&erased_box).unwrap_err();
+    assert_matches!(err, Error::NoCipher(_));
+    let restored = base.restore_with(&erased_box, &overrides).unwrap();
+    assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+    assert_eq!(base.kdfs().count(), 0);
+
+    // KDF limits of the base `Eraser` apply to overridden KDFs.
+    base.set_kdf_limits(KdfLimits {
+        max_memory_bytes: 1,
+        ..KdfLimits::default()
+    });
+    let err = base.restore_with(&erased_box, &overrides).unwrap_err();
+    assert_matches!(err, Error::KdfParamsTooExpensive(_));
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+#[test]
+fn restoring_boxes_with_phc_strings() {
+    use crate::sodium::{ScryptCompat, Sodium, XSalsa20Poly1305};
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    let kdf = ScryptCompat(crate::ScryptParams::light());
+    let pwbox = PwBoxBuilder::<_, XSalsa20Poly1305>::new(&mut thread_rng())
+        .kdf(kdf)
+        .seal(PASSWORD, b"1234567890")
+        .unwrap();
+    let eraser = Eraser::with_suites::<(Sodium,)>().unwrap();
+    let erased_box = eraser.erase(&pwbox).unwrap();
+    let mut json = serde_json::to_value(&erased_box).unwrap();
+    let phc = kdf.to_phc_string(erased_box.salt());
+    json["kdfparams"] = phc.clone().into();
+
+    let phc_box: ErasedPwBox = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(phc_box.kdf_params(), &JsonValue::String(phc));
+    assert!(phc_box.salt().is_empty());
+    assert_eq!(serde_json::to_value(&phc_box).unwrap(), json);
+    let bytes = bincode::serialize(&phc_box).unwrap();
+    assert_eq!(
+        bincode::deserialize::<ErasedPwBox>(&bytes).unwrap(),
+        phc_box
+    );
+
+    assert!(!eraser.needs_upgrade(&phc_box, &UpgradePolicy::default()));
+    let restored = eraser.restore(&phc_box).unwrap();
+    assert_eq!(restored.kdf_name(), "scrypt");
+    assert_eq!(restored.kdf_params(), erased_box.kdf_params());
+    assert_eq!(restored.kdf_cost(), kdf.cost());
+    assert_eq!(&*restored.open(PASSWORD).unwrap(), b"1234567890");
+    let pwbox = eraser
+        .restore_typed::<ScryptCompat, XSalsa20Poly1305>(&phc_box)
+        .unwrap();
+    assert_eq!(eraser.erase(&pwbox).unwrap(), erased_box);
+
+    // Malformed PHC string.
+    let mut malformed_json = json.clone();
+    malformed_json["kdfparams"] = "$scrypt$ln=12,r=8$AAAA".into();
+    let malformed_box: ErasedPwBox = serde_json::from_value(malformed_json).unwrap();
+    let err = eraser.restore(&malformed_box).unwrap_err();
+    assert_matches!(err, Error::KdfParams(ref e) if e.to_string().contains("`p` is missing"));
+
+    // KDF without PHC string support.
+    let mut eraser_without_phc = Eraser::new();
+    eraser_without_phc
+        .add_kdf::<ScryptCompat>("scrypt")
+        .unwrap()
+        .add_cipher::<XSalsa20Poly1305>("xsalsa20-poly1305")
+        .unwrap();
+    let err = eraser_without_phc.restore(&phc_box).unwrap_err();
+    assert_matches!(err, Error::KdfParams(ref e) if e.to_string().contains("PHC strings"));
+    let err = Eraser::new().restore(&phc_box).unwrap_err();
+    assert_matches!(err, Error::NoKdf(ref name) if name == "scrypt");
+
+    // PHC strings are not supported in the v2 format.
+    let mut v2_json = json;
+    v2_json["version"] = 2.into();
+    let v2_box: ErasedPwBox = serde_json::from_value(v2_json).unwrap();
+    let err = eraser.restore(&v2_box).unwrap_err();
+    assert_matches!(err, Error::MalformedBox(ref e) if e.contains("object"));
 }
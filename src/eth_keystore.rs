@@ -0,0 +1,484 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Import and export of Ethereum keystores ([Web3 Secret Storage], version 3).
+//!
+//! A keystore wraps the encrypted data into an outer object with `crypto`, `id`, `version`
+//! and (optionally) `address` fields. The `crypto` object has the same layout as
+//! an [`ErasedPwBox`]. Supported KDFs are `scrypt` and `pbkdf2` (with the params as per
+//! [`rcrypto::Scrypt`] and [`rcrypto::Pbkdf2`]); the only supported cipher is `aes-128-ctr`
+//! with the Keccak256-based MAC.
+//!
+//! Keys are derived with the `rust-crypto` backend, which rejects `scrypt` params
+//! with `n >= 2^(16 * r)`. Such params violate the `scrypt` spec, but are used in some keystores
+//! (e.g., in the scrypt test vector from the spec).
+//!
+//! [Web3 Secret Storage]: https://github.com/ethereum/wiki/wiki/Web3-Secret-Storage-Definition
+//! [`rcrypto::Scrypt`]: crate::rcrypto::Scrypt
+//! [`rcrypto::Pbkdf2`]: crate::rcrypto::Pbkdf2
+//!
+//! # Examples
+//!
+//! ```
+//! use rand::thread_rng;
+//! use pwbox::{eth_keystore, rcrypto::RustCrypto, Suite};
+//! # use pwbox::{rcrypto::Scrypt, ScryptParams};
+//!
+//! # fn main() -> Result<(), eth_keystore::KeystoreError> {
+//! let pwbox = RustCrypto::build_box(&mut thread_rng())
+//! #   .kdf(Scrypt(ScryptParams::custom(2, 1)))
+//!     .seal(b"correct horse", [1; 32])
+//!     .unwrap();
+//! let address = "0x008aeeda4d805471df9b2a5b0f38a0c3bcba786b";
+//! let json = eth_keystore::to_keystore_json(&pwbox, Some(address))?;
+//!
+//! let (restored, meta) = eth_keystore::from_keystore_json(&json)?;
+//! assert_eq!(meta.address.unwrap(), &address[2..]);
+//! assert_eq!(&*restored.open(b"correct horse").unwrap(), [1; 32]);
+//! # Ok(())
+//! # }
+//! ```
+
+use crypto::{digest::Digest, sha3::Sha3};
+use serde::{Deserialize, Serialize};
+use serde_json::{Error as JsonError, Value as JsonValue};
+
+use std::fmt::{self, Write as _};
+
+use crate::{
+    names,
+    rcrypto::{Aes128Ctr, Keccak256, Pbkdf2, Scrypt},
+    Cipher, CipherWithMac, DeriveKey, EraseError, ErasedPwBox, Eraser, Error, PwBox, RestoredPwBox,
+};
+
+/// Keystore version supported by this module.
+const VERSION: u64 = 3;
+
+/// Cipher used in Ethereum keystores.
+pub type KeystoreCipher = CipherWithMac<Aes128Ctr, Keccak256>;
+
+/// Keystore metadata not related to encryption.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeystoreMeta {
+    /// Keystore identifier (normally, a UUID).
+    pub id: Option<String>,
+    /// Ethereum address corresponding to the encrypted key, as a hex string
+    /// without the `0x` prefix.
+    pub address: Option<String>,
+}
+
+/// Errors occurring during keystore import / export.
+#[derive(Debug)]
+pub enum KeystoreError {
+    /// Error (de)serializing the keystore JSON.
+    Json(JsonError),
+
+    /// Keystore version is not supported. Only version 3 keystores can be imported.
+    UnsupportedVersion(u64),
+
+    /// KDF or cipher used in the box cannot be represented in a keystore.
+    Erase(EraseError),
+
+    /// Box was sealed with associated data, which is not supported by keystores.
+    AssociatedData,
+
+    /// Error restoring the box from the keystore.
+    Restore(Error),
+}
+
+impl fmt::Display for KeystoreError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeystoreError::Json(e) => write!(formatter, "error (de)serializing keystore: {}", e),
+            KeystoreError::UnsupportedVersion(version) => {
+                write!(formatter, "unsupported keystore version: {}", version)
+            }
+            KeystoreError::Erase(e) => write!(formatter, "cannot export box to keystore: {}", e),
+            KeystoreError::AssociatedData => {
+                formatter.write_str("boxes sealed with associated data cannot be exported")
+            }
+            KeystoreError::Restore(e) => write!(formatter, "cannot restore box: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for KeystoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            KeystoreError::Json(e) => Some(e),
+            KeystoreError::Erase(e) => Some(e),
+            KeystoreError::Restore(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<JsonError> for KeystoreError {
+    fn from(error: JsonError) -> Self {
+        KeystoreError::Json(error)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Keystore {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+    // Some older `geth` versions capitalize the field name.
+    #[serde(alias = "Crypto")]
+    crypto: JsonValue,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    version: u64,
+}
+
+fn keystore_eraser() -> Eraser {
+    let mut eraser = Eraser::new();
+    eraser
+        .add_cipher::<KeystoreCipher>(names::AES_128_CTR)
+        .and_then(|eraser| eraser.add_kdf::<Scrypt>(names::SCRYPT))
+        .and_then(|eraser| eraser.add_kdf::<Pbkdf2>(names::PBKDF2))
+        .expect("KDF and cipher names do not collide");
+    eraser
+}
+
+/// Derives a UUID (version 4 format) from the box salt, so that exporting the same box
+/// always yields the same identifier.
+fn keystore_id(salt: &[u8]) -> String {
+    let mut hasher = Sha3::keccak256();
+    hasher.input(salt);
+    let mut digest = [0_u8; 32];
+    hasher.result(&mut digest);
+    digest[6] = (digest[6] & 0x0f) | 0x40;
+    digest[8] = (digest[8] & 0x3f) | 0x80;
+
+    let mut id = String::with_capacity(36);
+    for (i, byte) in digest[..16].iter().enumerate() {
+        if i == 4 || i == 6 || i == 8 || i == 10 {
+            id.push('-');
+        }
+        write!(id, "{:02x}", byte).expect("writing to string never fails");
+    }
+    id
+}
+
+/// Parses a version 3 Ethereum keystore.
+///
+/// # Errors
+///
+/// Returns an error if the keystore is malformed, has an unsupported version, or uses
+/// an unsupported KDF or cipher.
+pub fn from_keystore_json(json: &str) -> Result<(RestoredPwBox, KeystoreMeta), KeystoreError> {
+    let keystore: Keystore = serde_json::from_str(json)?;
+    if keystore.version != VERSION {
+        return Err(KeystoreError::UnsupportedVersion(keystore.version));
+    }
+
+    let erased: ErasedPwBox = serde_json::from_value(keystore.crypto)?;
+    let pwbox = keystore_eraser()
+        .restore(&erased)
+        .map_err(KeystoreError::Restore)?;
+    let meta = KeystoreMeta {
+        id: keystore.id,
+        address: keystore.address,
+    };
+    Ok((pwbox, meta))
+}
+
+/// Exports a box into a version 3 Ethereum keystore. The `address`, if specified,
+/// is recorded without the `0x` prefix.
+///
+/// The keystore ID is derived from the box salt, so it is the same for repeated exports
+/// of the same box.
+///
+/// # Errors
+///
+/// Returns an error if the box uses a KDF or cipher not supported by keystores,
+/// or was sealed with associated data.
+pub fn to_keystore_json<K, C>(
+    pwbox: &PwBox<K, C>,
+    address: Option<&str>,
+) -> Result<String, KeystoreError>
+where
+    K: DeriveKey + Serialize,
+    C: Cipher,
+{
+    let meta = KeystoreMeta {
+        id: None,
+        address: address.map(ToOwned::to_owned),
+    };
+    to_keystore_json_with_meta(pwbox, &meta)
+}
+
+/// Exports a box into a version 3 Ethereum keystore with the specified metadata.
+/// Unlike [`to_keystore_json()`], this allows to set the keystore ID, e.g., to retain
+/// the ID of an imported keystore. If `meta.id` is not set, the ID is derived
+/// from the box salt.
+///
+/// # Errors
+///
+/// Returns an error under the same conditions as [`to_keystore_json()`].
+///
+/// # Examples
+///
+/// ```
+/// use rand::thread_rng;
+/// use pwbox::{eth_keystore::{self, KeystoreMeta}, rcrypto::RustCrypto, Suite};
+/// # use pwbox::{rcrypto::Scrypt, ScryptParams};
+///
+/// # fn main() -> Result<(), eth_keystore::KeystoreError> {
+/// let pwbox = RustCrypto::build_box(&mut thread_rng())
+/// #   .kdf(Scrypt(ScryptParams::custom(2, 1)))
+///     .seal(b"correct horse", [1; 32])
+///     .unwrap();
+/// let meta = KeystoreMeta {
+///     id: Some("3198bc9c-6672-5ab3-d995-4942343ae5b6".to_owned()),
+///     address: None,
+/// };
+/// let json = eth_keystore::to_keystore_json_with_meta(&pwbox, &meta)?;
+/// let (_, restored_meta) = eth_keystore::from_keystore_json(&json)?;
+/// assert_eq!(restored_meta, meta);
+/// # Ok(())
+/// # }
+/// ```
+pub fn to_keystore_json_with_meta<K, C>(
+    pwbox: &PwBox<K, C>,
+    meta: &KeystoreMeta,
+) -> Result<String, KeystoreError>
+where
+    K: DeriveKey + Serialize,
+    C: Cipher,
+{
+    if pwbox.has_associated_data() {
+        return Err(KeystoreError::AssociatedData);
+    }
+    let erased = keystore_eraser()
+        .erase(pwbox)
+        .map_err(KeystoreError::Erase)?;
+
+    let mut crypto = serde_json::to_value(erased)?;
+    // `geth` requires the derived key length to be present in the KDF params.
+    if let Some(kdf_params) = crypto["kdfparams"].as_object_mut() {
+        kdf_params
+            .entry("dklen")
+            .or_insert_with(|| C::KEY_LEN.into());
+    }
+
+    let address = meta
+        .address
+        .as_ref()
+        .map(|address| address.trim_start_matches("0x").to_ascii_lowercase());
+    let id = meta
+        .id
+        .clone()
+        .unwrap_or_else(|| keystore_id(&pwbox.inner.salt));
+    let keystore = Keystore {
+        address,
+        crypto,
+        id: Some(id),
+        version: VERSION,
+    };
+    serde_json::to_string(&keystore).map_err(From::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rcrypto::{Aes128Gcm, Pbkdf2Prf},
+        PwBoxBuilder, ScryptParams,
+    };
+
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    // PBKDF2 test vector from the Web3 Secret Storage definition.
+    const PBKDF2_KEYSTORE: &str = r#"{
+        "crypto" : {
+            "cipher" : "aes-128-ctr",
+            "cipherparams" : {
+                "iv" : "6087dab2f9fdbbfaddc31a909735c1e6"
+            },
+            "ciphertext" : "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+            "kdf" : "pbkdf2",
+            "kdfparams" : {
+                "c" : 262144,
+                "dklen" : 32,
+                "prf" : "hmac-sha256",
+                "salt" : "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"
+            },
+            "mac" : "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+        },
+        "id" : "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+        "version" : 3
+    }"#;
+
+    // Scrypt test vector from the Web3 Secret Storage definition.
+    const SCRYPT_KEYSTORE: &str = r#"{
+        "crypto" : {
+            "cipher" : "aes-128-ctr",
+            "cipherparams" : {
+                "iv" : "83dbcc02d8ccb40e466191a123791e0e"
+            },
+            "ciphertext" : "d172bf743a674da9cdad04534d56926ef8358534d458fffccd4e6ad2fbde479c",
+            "kdf" : "scrypt",
+            "kdfparams" : {
+                "dklen" : 32,
+                "n" : 262144,
+                "p" : 8,
+                "r" : 1,
+                "salt" : "ab0c7876052600dd703518d6fc3fe8984592145b591fc8fb5c6d43190334ba19"
+            },
+            "mac" : "2103ac29920d71da29f15d75b4a16dbe95cfd7ff8faea1056c33131d846e3097"
+        },
+        "id" : "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+        "version" : 3
+    }"#;
+
+    const PASSWORD: &str = "testpassword";
+    const PRIVATE_KEY: &str = "7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d";
+
+    #[test]
+    fn pbkdf2_test_vector() {
+        let (pwbox, meta) = from_keystore_json(PBKDF2_KEYSTORE).unwrap();
+        assert_eq!(meta.id.unwrap(), "3198bc9c-6672-5ab3-d995-4942343ae5b6");
+        assert_eq!(meta.address, None);
+        let key = hex::decode(PRIVATE_KEY).unwrap();
+        assert_eq!(&*pwbox.open(PASSWORD).unwrap(), &*key);
+    }
+
+    #[test]
+    fn scrypt_test_vector() {
+        // The test vector uses `n = 2^18, r = 1`, which violates the `n < 2^(16 * r)`
+        // requirement of the scrypt spec and is thus rejected when restoring the box.
+        let err = from_keystore_json(SCRYPT_KEYSTORE).unwrap_err();
+        assert!(err.to_string().contains("invalid scrypt params"));
+    }
+
+    #[test]
+    fn capitalized_crypto_field() {
+        let json = PBKDF2_KEYSTORE.replace("\"crypto\"", "\"Crypto\"");
+        let (pwbox, _) = from_keystore_json(&json).unwrap();
+        assert_eq!(pwbox.len(), 32);
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let json = PBKDF2_KEYSTORE.replace("\"version\" : 3", "\"version\" : 1");
+        assert_matches!(
+            from_keystore_json(&json).unwrap_err(),
+            KeystoreError::UnsupportedVersion(1)
+        );
+    }
+
+    #[test]
+    fn unsupported_kdf() {
+        let json = SCRYPT_KEYSTORE.replace("\"kdf\" : \"scrypt\"", "\"kdf\" : \"argon2\"");
+        assert_matches!(
+            from_keystore_json(&json).unwrap_err(),
+            KeystoreError::Restore(Error::NoKdf(ref name)) if name == "argon2"
+        );
+    }
+
+    #[test]
+    fn export_roundtrip_with_scrypt() {
+        let pwbox = PwBoxBuilder::<_, KeystoreCipher>::new(&mut thread_rng())
+            .kdf(Scrypt(ScryptParams::custom(6, 1)))
+            .seal(PASSWORD, [42; 32])
+            .unwrap();
+        let address = "0x008AEEDA4D805471DF9B2A5B0F38A0C3BCBA786B";
+        let json = to_keystore_json(&pwbox, Some(address)).unwrap();
+
+        let value: JsonValue = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["version"], 3);
+        assert_eq!(value["address"], "008aeeda4d805471df9b2a5b0f38a0c3bcba786b");
+        assert_eq!(value["crypto"]["kdf"], "scrypt");
+        assert_eq!(value["crypto"]["kdfparams"]["dklen"], 32);
+        assert_eq!(value["crypto"]["cipher"], "aes-128-ctr");
+        let id = value["id"].as_str().unwrap();
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "4");
+        assert!(to_keystore_json(&pwbox, None).unwrap().contains(id));
+
+        let (restored, meta) = from_keystore_json(&json).unwrap();
+        assert_eq!(meta.id.as_deref(), Some(id));
+        assert_eq!(&*restored.open(PASSWORD).unwrap(), [42; 32]);
+    }
+
+    #[test]
+    fn export_with_meta() {
+        let pwbox = PwBoxBuilder::<_, KeystoreCipher>::new(&mut thread_rng())
+            .kdf(Scrypt(ScryptParams::custom(6, 1)))
+            .seal(PASSWORD, [42; 32])
+            .unwrap();
+        let meta = KeystoreMeta {
+            id: Some("3198bc9c-6672-5ab3-d995-4942343ae5b6".to_owned()),
+            address: Some("0x008aeeda4d805471df9b2a5b0f38a0c3bcba786b".to_owned()),
+        };
+        let json = to_keystore_json_with_meta(&pwbox, &meta).unwrap();
+        let (restored, restored_meta) = from_keystore_json(&json).unwrap();
+        assert_eq!(restored_meta.id, meta.id);
+        assert_eq!(
+            restored_meta.address.unwrap(),
+            "008aeeda4d805471df9b2a5b0f38a0c3bcba786b"
+        );
+        assert_eq!(&*restored.open(PASSWORD).unwrap(), [42; 32]);
+
+        let meta = KeystoreMeta::default();
+        let json = to_keystore_json_with_meta(&pwbox, &meta).unwrap();
+        assert_eq!(json, to_keystore_json(&pwbox, None).unwrap());
+    }
+
+    #[test]
+    fn export_roundtrip_with_pbkdf2() {
+        let kdf = Pbkdf2 {
+            c: 16,
+            prf: Pbkdf2Prf::HmacSha256,
+            dklen: None,
+        };
+        let pwbox = PwBoxBuilder::<_, KeystoreCipher>::new(&mut thread_rng())
+            .kdf(kdf)
+            .seal(PASSWORD, [42; 32])
+            .unwrap();
+        let json = to_keystore_json(&pwbox, None).unwrap();
+        let value: JsonValue = serde_json::from_str(&json).unwrap();
+        assert!(value.get("address").is_none());
+        assert_eq!(value["crypto"]["kdfparams"]["c"], 16);
+        assert_eq!(value["crypto"]["kdfparams"]["dklen"], 32);
+
+        let (restored, _) = from_keystore_json(&json).unwrap();
+        assert_eq!(&*restored.open(PASSWORD).unwrap(), [42; 32]);
+    }
+
+    #[test]
+    fn export_errors() {
+        let pwbox = PwBoxBuilder::<_, Aes128Gcm>::new(&mut thread_rng())
+            .kdf(Scrypt(ScryptParams::custom(6, 1)))
+            .seal(PASSWORD, [42; 32])
+            .unwrap();
+        assert_matches!(
+            to_keystore_json(&pwbox, None).unwrap_err(),
+            KeystoreError::Erase(EraseError::NoCipher)
+        );
+
+        let pwbox = PwBoxBuilder::<_, KeystoreCipher>::new(&mut thread_rng())
+            .kdf(Scrypt(ScryptParams::custom(6, 1)))
+            .associated_data(b"context")
+            .seal(PASSWORD, [42; 32])
+            .unwrap();
+        assert_matches!(
+            to_keystore_json(&pwbox, None).unwrap_err(),
+            KeystoreError::AssociatedData
+        );
+    }
+}
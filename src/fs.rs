@@ -0,0 +1,243 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Storing boxes in files.
+//!
+//! Boxes are stored as JSON. Writes are atomic: the box is written to a temporary file
+//! in the same directory, which is then renamed over the target, so that a crash
+//! or a concurrent reader never observes a partially written file.
+//!
+//! # Examples
+//!
+//! ```
+//! # use rand::thread_rng;
+//! # use pwbox::{fs, sodium::{Scrypt, Sodium}, Eraser, Suite};
+//! # fn main() -> anyhow::Result<()> {
+//! let mut eraser = Eraser::new();
+//! eraser.add_suite::<Sodium>()?;
+//! let pwbox = Sodium::build_box(&mut thread_rng())
+//! #   .kdf(Scrypt::light())
+//!     .seal("correct horse", b"battery staple")?;
+//!
+//! let path = std::env::temp_dir().join(format!("pwbox-doc-{}.json", std::process::id()));
+//! fs::save_to_path(&path, &eraser.erase(&pwbox)?)?;
+//! let restored = fs::load_from_path(&path, &eraser)?;
+//! assert_eq!(&*restored.open("correct horse")?, b"battery staple");
+//! # std::fs::remove_file(&path)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::{
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+use crate::{ErasedPwBox, Eraser, Error, RestoredPwBox};
+
+/// Counter making temporary file names unique within the process.
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Atomically writes a box to the specified path, replacing the file if it exists.
+///
+/// The box is serialized to JSON and written to a temporary file in the same directory
+/// as `path`. The temporary file is synced to disk and then renamed to `path`;
+/// on Unix, the containing directory is synced as well, so that the rename is durable.
+/// On Unix, the file is created with `0o600` permissions (i.e., readable and writable
+/// only by the owner).
+///
+/// # Errors
+///
+/// Returns an I/O error if any of the file operations fails. In this case, the file
+/// at `path` (if any) is left intact, and the temporary file is removed.
+pub fn save_to_path(path: impl AsRef<Path>, erased: &ErasedPwBox) -> io::Result<()> {
+    let bytes =
+        serde_json::to_vec(erased).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    write_atomically(path.as_ref(), &bytes, true)
+}
+
+/// Reads a box from the specified path and restores it with the provided `eraser`.
+///
+/// # Errors
+///
+/// - [`Error::Io`] if the file cannot be read
+/// - [`Error::Serialization`] if the file contents are not a valid JSON box
+/// - Errors returned by [`Eraser::restore()`] if the box cannot be restored
+pub fn load_from_path(path: impl AsRef<Path>, eraser: &Eraser) -> Result<RestoredPwBox, Error> {
+    let bytes = fs::read(path).map_err(Error::Io)?;
+    let erased_box: ErasedPwBox = serde_json::from_slice(&bytes).map_err(Error::Serialization)?;
+    eraser.restore(&erased_box)
+}
+
+/// Atomically writes `bytes` to the specified path via a temporary file, which is removed
+/// on error. If `overwrite` is `false` and the file at `path` exists, the write fails
+/// with [`io::ErrorKind::AlreadyExists`].
+pub(crate) fn write_atomically(path: &Path, bytes: &[u8], overwrite: bool) -> io::Result<()> {
+    let temp_path = temp_path(path)?;
+    let res = write_synced(&temp_path, bytes).and_then(|()| {
+        if overwrite {
+            fs::rename(&temp_path, path)
+        } else {
+            // Unlike `rename()`, `hard_link()` fails if the target exists.
+            fs::hard_link(&temp_path, path).and_then(|()| fs::remove_file(&temp_path))
+        }
+    });
+    if let Err(e) = res {
+        fs::remove_file(&temp_path).ok();
+        return Err(e);
+    }
+    sync_dir(path)
+}
+
+fn temp_path(path: &Path) -> io::Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path does not name a file"))?;
+
+    let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let mut temp_name = OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".{}.{}.tmp", process::id(), counter));
+    Ok(path.with_file_name(temp_name))
+}
+
+fn write_synced(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()
+}
+
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::unnecessary_wraps)]
+fn sync_dir(_path: &Path) -> io::Result<()> {
+    // Directories cannot be opened as files on other platforms.
+    Ok(())
+}
+
+#[cfg(all(test, feature = "exonum_sodiumoxide"))]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    use super::*;
+    use crate::{
+        sodium::{Scrypt, Sodium},
+        Suite,
+    };
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pwbox-fs-{}-{}", process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn erased_box(eraser: &Eraser, message: &[u8]) -> ErasedPwBox {
+        let pwbox = Sodium::build_box(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .seal(PASSWORD, message)
+            .unwrap();
+        eraser.erase(&pwbox).unwrap()
+    }
+
+    #[test]
+    fn saving_and_loading_box() {
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let dir = test_dir("roundtrip");
+        let path = dir.join("box.json");
+
+        save_to_path(&path, &erased_box(&eraser, b"first")).unwrap();
+        let restored = load_from_path(&path, &eraser).unwrap();
+        assert_eq!(&*restored.open(PASSWORD).unwrap(), b"first");
+
+        // Overwrite the existing file.
+        save_to_path(&path, &erased_box(&eraser, b"second")).unwrap();
+        let restored = load_from_path(&path, &eraser).unwrap();
+        assert_eq!(&*restored.open(PASSWORD).unwrap(), b"second");
+
+        // No temporary files should be left in the directory.
+        let file_names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(file_names, [OsString::from("box.json")]);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn saving_box_to_invalid_path() {
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let dir = test_dir("invalid");
+
+        let err = save_to_path(&dir.join(".."), &erased_box(&eraser, b"")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err =
+            save_to_path(dir.join("missing/box.json"), &erased_box(&eraser, b"")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn loading_box_errors() {
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let dir = test_dir("errors");
+
+        let err = load_from_path(dir.join("missing.json"), &eraser).unwrap_err();
+        assert_matches!(err, Error::Io(ref e) if e.kind() == io::ErrorKind::NotFound);
+
+        let path = dir.join("garbage.json");
+        fs::write(&path, b"not a box").unwrap();
+        let err = load_from_path(&path, &eraser).unwrap_err();
+        assert_matches!(err, Error::Serialization(_));
+
+        // Valid JSON, but the KDF is not known to the eraser.
+        save_to_path(&path, &erased_box(&eraser, b"")).unwrap();
+        let err = load_from_path(&path, &Eraser::new()).unwrap_err();
+        assert_matches!(err, Error::NoKdf(_));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
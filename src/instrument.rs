@@ -0,0 +1,131 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Instrumentation with `tracing` spans and events.
+//!
+//! The macros defined in this module expand to nothing (or to the instrumented expression)
+//! if the `tracing` feature is disabled; their other arguments are not compiled in this case.
+//! Spans and events must only carry non-sensitive metadata, such as algorithm names,
+//! byte lengths, durations and error variants; never passwords, keys, salts or plaintexts.
+
+#[cfg(feature = "tracing")]
+use core::fmt;
+
+#[cfg(feature = "tracing")]
+use crate::MacMismatch;
+
+/// Enters a `DEBUG` span, which is exited at the end of the enclosing block.
+macro_rules! enter_span {
+    ($($args:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!($($args)*).entered();
+    };
+}
+
+/// Evaluates the statements only if the `tracing` feature is enabled.
+macro_rules! traced {
+    ($($stmt:tt)*) => {
+        #[cfg(feature = "tracing")]
+        {
+            $($stmt)*
+        }
+    };
+}
+
+/// Emits an event with the specified level (`ERROR`, `WARN`, `INFO`, `DEBUG` or `TRACE`).
+macro_rules! trace_event {
+    ($level:ident, $($args:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::event!(tracing::Level::$level, $($args)*);
+    };
+}
+
+/// Invokes a KDF, reporting the duration and outcome of key derivation.
+macro_rules! trace_kdf {
+    (salt_len = $salt_len:expr, $derive:expr) => {{
+        #[cfg(feature = "tracing")]
+        let stopwatch = $crate::instrument::Stopwatch::start();
+        let result = $derive;
+        #[cfg(feature = "tracing")]
+        $crate::instrument::key_derived($salt_len, &stopwatch, result.is_ok());
+        result
+    }};
+}
+
+/// Invokes a cipher to open or verify a ciphertext, reporting the outcome.
+macro_rules! trace_cipher {
+    (cipher = $cipher:expr, len = $len:expr, $open:expr) => {{
+        let result = $open;
+        #[cfg(feature = "tracing")]
+        $crate::instrument::ciphertext_opened(&$cipher, $len, &result);
+        result
+    }};
+}
+
+/// Measures durations for tracing events. Durations are only available with the `std` feature.
+#[cfg(feature = "tracing")]
+pub(crate) struct Stopwatch {
+    #[cfg(feature = "std")]
+    start: std::time::Instant,
+}
+
+#[cfg(feature = "tracing")]
+impl Stopwatch {
+    pub fn start() -> Self {
+        Self {
+            #[cfg(feature = "std")]
+            start: std::time::Instant::now(),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[allow(clippy::unnecessary_wraps)] // `Option` is returned for consistency with no-std builds
+    fn elapsed(&self) -> Option<core::time::Duration> {
+        Some(self.start.elapsed())
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[allow(clippy::unused_self)]
+    fn elapsed(&self) -> Option<core::time::Duration> {
+        None
+    }
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn key_derived(salt_len: usize, stopwatch: &Stopwatch, is_ok: bool) {
+    let elapsed = stopwatch.elapsed();
+    if is_ok {
+        tracing::debug!(salt_len, elapsed = ?elapsed, "derived key");
+    } else {
+        tracing::warn!(salt_len, elapsed = ?elapsed, error = "DeriveKey", "KDF failed");
+    }
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn ciphertext_opened(
+    cipher: &dyn fmt::Display,
+    len: usize,
+    result: &Result<(), MacMismatch>,
+) {
+    if result.is_ok() {
+        tracing::trace!(cipher = %cipher, len, "verified MAC");
+    } else {
+        tracing::warn!(
+            cipher = %cipher,
+            len,
+            error = "MacMismatch",
+            "MAC verification failed"
+        );
+    }
+}
@@ -0,0 +1,229 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Splitting opening a box into key derivation and decryption.
+
+use core::fmt;
+
+use crate::{
+    alloc::{Arc, Vec},
+    traits::ObjectSafeCipher,
+    Cipher, DeriveKey, Error, PwBox, PwBoxInner, RestoredPwBox, SecretVec, SensitiveData,
+};
+
+/// Key derivation for opening a box, detached from the box itself.
+///
+/// Deriving a key is the expensive part of opening a box; with recommended KDF params,
+/// it may take hundreds of milliseconds. A job is `Send + 'static`, so it can be run
+/// on a thread pool, e.g., in order to not block an async executor. A job is created with
+/// `prepare_open()` method of [`PwBox`] or [`RestoredPwBox`], and the key returned by
+/// [`Self::run()`] is then passed to the `finish_open()` method of the same box.
+///
+/// The job stores a copy of the password, which is zeroed on drop.
+///
+/// # Examples
+///
+/// Running a job on a separate thread:
+///
+/// ```
+/// # use rand::thread_rng;
+/// # use std::thread;
+/// # use pwbox::{sodium::{Scrypt, Sodium}, Suite};
+/// let pwbox = Sodium::build_box(&mut thread_rng())
+/// #   .kdf(Scrypt::light())
+///     .seal("correct horse", b"battery staple")
+///     .unwrap();
+/// let job = pwbox.prepare_open("correct horse");
+/// let key = thread::spawn(move || job.run()).join().unwrap().unwrap();
+/// let plaintext = pwbox.finish_open(&key).unwrap();
+/// assert_eq!(&*plaintext, b"battery staple");
+/// ```
+///
+/// With `tokio`, the job can be run on the blocking thread pool:
+///
+/// ```ignore
+/// let job = pwbox.prepare_open(password);
+/// let key = tokio::task::spawn_blocking(move || job.run()).await??;
+/// let plaintext = pwbox.finish_open(&key)?;
+/// ```
+pub struct KeyDerivationJob {
+    kdf: Arc<dyn DeriveKey>,
+    salt: Vec<u8>,
+    password: SecretVec,
+    key_len: usize,
+}
+
+impl fmt::Debug for KeyDerivationJob {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("KeyDerivationJob")
+            .field("key_len", &self.key_len)
+            .finish()
+    }
+}
+
+impl KeyDerivationJob {
+    /// Runs the KDF.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DeriveKey`] if the KDF fails.
+    pub fn run(self) -> Result<DerivedKey, Error> {
+        let mut key = SensitiveData::zeros(self.key_len);
+        self.kdf
+            .derive_key(key.bytes_mut(), &self.password, &self.salt)
+            .map_err(Error::DeriveKey)?;
+        Ok(DerivedKey(key))
+    }
+}
+
+/// Key derived by a [`KeyDerivationJob`]. The key is zeroed on drop.
+pub struct DerivedKey(SensitiveData);
+
+impl fmt::Debug for DerivedKey {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.debug_tuple("DerivedKey").field(&"_").finish()
+    }
+}
+
+impl<K: DeriveKey, C: ObjectSafeCipher> PwBoxInner<K, C> {
+    fn prepare_open(&self, kdf: Arc<dyn DeriveKey>, password: &[u8]) -> KeyDerivationJob {
+        KeyDerivationJob {
+            kdf,
+            salt: self.salt.clone(),
+            password: self.password_policy.apply(password),
+            key_len: self.cipher.key_len(),
+        }
+    }
+
+    fn finish_open(&self, key: &DerivedKey) -> Result<SensitiveData, Error> {
+        // A key of an unexpected length could not have been derived for this box.
+        if key.0.len() != self.cipher.key_len() {
+            return Err(Error::MacMismatch);
+        }
+        self.open_with_key(&key.0, &[])
+    }
+}
+
+impl<K: DeriveKey + Clone, C: Cipher> PwBox<K, C> {
+    /// Prepares a key derivation job for opening this box with the specified password.
+    /// See [`KeyDerivationJob`] for details.
+    pub fn prepare_open(&self, password: impl AsRef<[u8]>) -> KeyDerivationJob {
+        let kdf = Arc::new(self.inner.kdf.clone());
+        self.inner.prepare_open(kdf, password.as_ref())
+    }
+
+    /// Decrypts the box with the key derived by a job from [`Self::prepare_open()`].
+    /// The result is the same as for [`Self::open()`] with the password supplied
+    /// to `prepare_open()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MacMismatch`] if the password is incorrect, or if the key
+    /// was derived for another box.
+    pub fn finish_open(&self, key: &DerivedKey) -> Result<SensitiveData, Error> {
+        self.inner.finish_open(key)
+    }
+}
+
+impl RestoredPwBox {
+    /// Prepares a key derivation job for opening this box with the specified password.
+    /// See [`KeyDerivationJob`] for details.
+    pub fn prepare_open(&self, password: impl AsRef<[u8]>) -> KeyDerivationJob {
+        let kdf = Arc::clone(&self.inner.kdf);
+        self.inner.prepare_open(kdf, password.as_ref())
+    }
+
+    /// Decrypts the box with the key derived by a job from [`Self::prepare_open()`].
+    /// The result is the same as for [`Self::open()`] with the password supplied
+    /// to `prepare_open()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MacMismatch`] if the password is incorrect, or if the key
+    /// was derived for another box.
+    pub fn finish_open(&self, key: &DerivedKey) -> Result<SensitiveData, Error> {
+        self.inner.finish_open(key)
+    }
+}
+
+#[cfg(all(test, feature = "exonum_sodiumoxide"))]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    use std::thread;
+
+    use super::*;
+    use crate::{
+        sodium::{Scrypt, Sodium, XSalsa20Poly1305},
+        Eraser, Suite,
+    };
+
+    const PASSWORD: &str = "correct horse battery staple";
+    const MESSAGE: &[u8] = b"battery staple";
+
+    fn assert_send<T: Send + 'static>(_: &T) {}
+
+    #[test]
+    fn opening_box_in_two_steps() {
+        let pwbox = Sodium::build_box(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .seal(PASSWORD, MESSAGE)
+            .unwrap();
+        let job = pwbox.prepare_open(PASSWORD);
+        assert_send(&job);
+        let key = thread::spawn(move || job.run()).join().unwrap().unwrap();
+        assert_eq!(
+            *pwbox.finish_open(&key).unwrap(),
+            *pwbox.open(PASSWORD).unwrap()
+        );
+
+        let key = pwbox.prepare_open("Tr0ub4dor&3").run().unwrap();
+        assert_matches!(pwbox.finish_open(&key).unwrap_err(), Error::MacMismatch);
+
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let restored = eraser.restore(&eraser.erase(&pwbox).unwrap()).unwrap();
+        let job = restored.prepare_open(PASSWORD);
+        let key = thread::spawn(move || job.run()).join().unwrap().unwrap();
+        assert_eq!(&*restored.finish_open(&key).unwrap(), MESSAGE);
+    }
+
+    #[test]
+    fn finishing_with_key_for_other_box() {
+        let mut rng = thread_rng();
+        let pwbox = Sodium::build_box(&mut rng)
+            .kdf(Scrypt::light())
+            .seal(PASSWORD, MESSAGE)
+            .unwrap();
+        let other_box = Sodium::build_box(&mut rng)
+            .kdf(Scrypt::light())
+            .seal(PASSWORD, MESSAGE)
+            .unwrap();
+        let key = other_box.prepare_open(PASSWORD).run().unwrap();
+        assert_matches!(pwbox.finish_open(&key).unwrap_err(), Error::MacMismatch);
+
+        // Keys of an unexpected length are rejected rather than causing a panic.
+        let key = DerivedKey(SensitiveData::zeros(16));
+        assert_matches!(pwbox.finish_open(&key).unwrap_err(), Error::MacMismatch);
+        let salsa_box = Sodium::build_box(&mut rng)
+            .cipher::<XSalsa20Poly1305>()
+            .kdf(Scrypt::light())
+            .seal(PASSWORD, MESSAGE)
+            .unwrap();
+        let key = salsa_box.prepare_open(PASSWORD).run().unwrap();
+        assert_eq!(&*salsa_box.finish_open(&key).unwrap(), MESSAGE);
+    }
+}
@@ -28,6 +28,10 @@
 //! - [`PureCrypto`] (pure Rust implementation; good for comiling into WASM
 //!   or for other constrained environments).
 //!
+//! If you do not want to choose the primitives yourself, use [`DefaultSuite`] (also returned
+//! by [`recommended()`]), which is backed by the strongest suite available with the enabled
+//! crate features.
+//!
 //! There is also [`Eraser`], which allows to (de)serialize [`PwBox`]es from any `serde`-compatible
 //! format, such as JSON or TOML.
 //!
@@ -41,6 +45,15 @@
 //! `PwBox` name was produced by combining two libsodium names: `pwhash` for password-based KDFs
 //! and `*box` for ciphers.
 //!
+//! # Thread Safety
+//!
+//! Public types of the crate are `Send + Sync`.
+//! [`DeriveKey`] implementations are required to be thread-safe, and ciphers are stateless,
+//! so boxes (including [`RestoredPwBox`]es with type-erased KDFs and ciphers), [`Eraser`]s
+//! and [`SensitiveData`] can be shared among threads. The only exception is [`PwBoxBuilder`],
+//! which borrows a type-erased RNG; builders are cheap, so a builder should be created
+//! in each thread sealing boxes.
+//!
 //! # Crate Features
 //!
 //! - `std` (enabled by default): Enables types from the Rust standard library. Switching
@@ -49,6 +62,22 @@
 //!   if the `std` feature is disabled.
 //! - `exonum_sodiumoxide` (enabled by default), `rust-crypto`, `pure` (both disabled by default):
 //!   Provide the cryptographic backends described above.
+//! - `eth-keystore` (disabled by default): Provides import and export of Ethereum keystores
+//!   in the [`eth_keystore`] module. Implies `std` and `rust-crypto`.
+//! - `age-compat` (disabled by default): Provides import and export of password-encrypted
+//!   [age](https://age-encryption.org/) files in the [`age_compat`] module.
+//!   Implies `std` and `pure`.
+//! - `os-rng` (disabled by default): Provides sealing boxes with the OS random number
+//!   generator in the [`os_rng`] module, which is useful for WASM.
+//! - `cli` (disabled by default): Provides helpers for command-line tools, such as reading
+//!   passwords from the terminal and sealing / opening files, in the [`cli`] module.
+//!   Implies `std` and `os-rng`.
+//! - `tracing` (disabled by default): Instruments sealing, opening, erasing and restoring
+//!   boxes with [`tracing`](https://docs.rs/tracing/) spans and events. The events only carry
+//!   non-sensitive metadata, such as algorithm names, byte lengths, durations of key
+//!   derivation and error variants; passwords, keys, salts and plaintexts are never recorded.
+//! - `testing` (disabled by default): Allows to set salt and nonce in [`PwBoxBuilder`]
+//!   in order to reproduce test vectors. Should not be used in production.
 //!
 //! # Examples
 //!
@@ -68,7 +97,7 @@
 //!
 //! // Serialize box.
 //! let mut eraser = Eraser::new();
-//! eraser.add_suite::<Sodium>();
+//! eraser.add_suite::<Sodium>().unwrap();
 //! let erased: ErasedPwBox = eraser.erase(&pwbox)?;
 //! println!("{}", serde_json::to_string_pretty(&erased)?);
 //! // Deserialize box back.
@@ -90,14 +119,53 @@
     clippy::doc_markdown
 )]
 
+use hkdf::Hkdf;
 use rand_core::{CryptoRng, RngCore};
-use serde_json::Error as JsonError;
+use serde::{Deserialize, Serialize};
+use serde_json::{Error as JsonError, Value as JsonValue};
+use sha2::{Digest, Sha256};
+use unicode_normalization::UnicodeNormalization;
+use zeroize::Zeroize;
+
+use core::{fmt, marker::PhantomData, mem, str};
 
-use core::{fmt, marker::PhantomData};
+// Declared first, so that the instrumentation macros are available in other modules.
+#[macro_use]
+mod instrument;
 
+#[cfg(feature = "age-compat")]
+#[cfg_attr(docsrs, doc(cfg(feature = "age-compat")))]
+pub mod age_compat;
+#[cfg(feature = "std")]
+mod batch;
+pub mod cache;
 mod cipher_with_mac;
+#[cfg(feature = "cli")]
+#[cfg_attr(docsrs, doc(cfg(feature = "cli")))]
+pub mod cli;
+mod collection;
+#[cfg(any(
+    feature = "exonum_sodiumoxide",
+    feature = "rust-crypto",
+    feature = "pure"
+))]
+mod default_suite;
 mod erased;
+#[cfg(feature = "eth-keystore")]
+#[cfg_attr(docsrs, doc(cfg(feature = "eth-keystore")))]
+pub mod eth_keystore;
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod fs;
+mod job;
+#[cfg(feature = "os-rng")]
+#[cfg_attr(docsrs, doc(cfg(feature = "os-rng")))]
+pub mod os_rng;
+mod phc;
+#[cfg(feature = "std")]
+mod stream;
 mod traits;
+pub mod tune;
 mod utils;
 
 // Polyfill for `alloc` types.
@@ -107,11 +175,25 @@ mod alloc {
 
     #[cfg(not(feature = "std"))]
     pub use alloc::{
-        borrow::ToOwned, boxed::Box, collections::BTreeMap, string::String, vec, vec::Vec,
+        borrow::{Cow, ToOwned},
+        boxed::Box,
+        collections::BTreeMap,
+        format,
+        string::{String, ToString},
+        sync::Arc,
+        vec,
+        vec::Vec,
     };
     #[cfg(feature = "std")]
     pub use std::{
-        borrow::ToOwned, boxed::Box, collections::BTreeMap, string::String, vec, vec::Vec,
+        borrow::{Cow, ToOwned},
+        boxed::Box,
+        collections::BTreeMap,
+        format,
+        string::{String, ToString},
+        sync::Arc,
+        vec,
+        vec::Vec,
     };
 }
 
@@ -126,16 +208,52 @@ pub mod rcrypto;
 #[cfg_attr(docsrs, doc(cfg(feature = "exonum_sodiumoxide")))]
 pub mod sodium;
 
+#[cfg(any(test, feature = "testing"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
+pub use crate::cipher_with_mac::test_cipher_with_mac;
+#[cfg(any(
+    feature = "exonum_sodiumoxide",
+    feature = "rust-crypto",
+    feature = "pure"
+))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(any(
+        feature = "exonum_sodiumoxide",
+        feature = "rust-crypto",
+        feature = "pure"
+    )))
+)]
+pub use crate::default_suite::{
+    fast_insecure_for_tests, recommended, DefaultSuite, FastInsecureSuite,
+};
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub use crate::{
+    batch::open_any,
+    stream::{PwBoxReader, PwBoxWriter, RestoredStreamPwBox, StreamPwBox},
+};
 pub use crate::{
-    cipher_with_mac::{CipherWithMac, Mac, UnauthenticatedCipher},
-    erased::{EraseError, ErasedPwBox, Eraser, Suite},
-    traits::{Cipher, CipherOutput, DeriveKey, MacMismatch},
-    utils::{ScryptParams, SensitiveData},
+    cipher_with_mac::{
+        CipherWithMac, ConcatSplit, EthereumSplit, KeySplit, Mac, UnauthenticatedCipher,
+    },
+    collection::PwBoxCollection,
+    erased::{
+        names, EraseError, ErasedPwBox, ErasedPwBoxHeader, Eraser, FormatVersion, KdfLimits, Suite,
+        Suites, UpgradePolicy,
+    },
+    job::{DerivedKey, KeyDerivationJob},
+    phc::PhcString,
+    traits::{
+        Cipher, CipherOutput, CipherOutputRef, DeriveKey, DeriveKeyMut, DynCipher, ExternalKdf,
+        KdfCost, KdfDifficulty, KdfPreset, MacMismatch, NoKdf,
+    },
+    utils::{Argon2Params, Argon2Variant, ScryptParams, SecretVec, SensitiveData},
 };
 
 use crate::{
-    alloc::{Box, String, Vec},
-    traits::{CipherObject, ObjectSafeCipher},
+    alloc::{vec, Arc, Box, Cow, String, Vec},
+    traits::{CipherObject, KdfRef, ObjectSafeCipher},
 };
 
 /// Errors occurring during `PwBox` operations.
@@ -157,9 +275,22 @@ pub enum Error {
     /// or [`Eraser::add_suite()`] methods.
     NoKdf(String),
 
+    /// A cipher or KDF name is already registered in an [`Eraser`] for a different
+    /// implementation, or the implementation is already registered under a different name.
+    /// Also returned if an entry with the specified name is already present
+    /// in a [`PwBoxCollection`].
+    DuplicateName(String),
+
+    /// An entry with the specified name is not present in a [`PwBoxCollection`].
+    NoEntry(String),
+
     /// Failed to parse KDF parameters.
     KdfParams(JsonError),
 
+    /// Failed to serialize a box to JSON or to deserialize it from JSON
+    /// (e.g., in [`Suite::open_json()`]).
+    Serialization(JsonError),
+
     /// Incorrect nonce length encountered.
     ///
     /// This error usually means that the box is corrupted.
@@ -175,17 +306,95 @@ pub enum Error {
     /// This error usually means that the box is corrupted.
     SaltLen,
 
+    /// Chunk size is absent, invalid, or is present where it is not expected.
+    ///
+    /// This error usually means that a streaming box (see [`StreamPwBox`]) is being restored
+    /// as an ordinary one, or vice versa.
+    ChunkSize,
+
+    /// Output buffer supplied to `open_into()` is too small to fit the decrypted data.
+    OutputLen {
+        /// Minimum acceptable buffer length (i.e., the length of the encrypted data).
+        expected: usize,
+        /// Actual buffer length.
+        actual: usize,
+    },
+
+    /// Key supplied to a method accepting a previously derived key (e.g.,
+    /// [`PwBox::open_with_key()`]) has an incorrect byte size.
+    KeyLen {
+        /// Expected key length (i.e., [`Cipher::KEY_LEN`]).
+        expected: usize,
+        /// Actual key length.
+        actual: usize,
+    },
+
     /// Failed to verify MAC code.
     ///
     /// This error means that either the supplied password is incorrect,
     /// or the box is corrupted.
     MacMismatch,
 
+    /// The detached ciphertext supplied to [`Eraser::restore_detached()`] does not match
+    /// the length or checksum recorded in the box header.
+    ///
+    /// This error usually means that the ciphertext was stored for another box,
+    /// or is damaged.
+    CiphertextMismatch,
+
+    /// KDF params recorded in a box exceed the limits set with [`Eraser::set_kdf_limits()`].
+    /// The estimated cost of the KDF is attached to the error.
+    KdfParamsTooExpensive(KdfCost),
+
+    /// The box is malformed in a way not covered by other error variants (e.g., KDF params
+    /// are not a JSON object).
+    MalformedBox(String),
+
+    /// Options supplied for sealing a box are invalid or not supported by the cipher
+    /// (e.g., associated data for a cipher not supporting it, or a nonce strategy requiring
+    /// nonces longer than the cipher provides). Also returned if the nonce counter
+    /// of a [`PwBoxBuilder`] is exhausted.
+    SealOptions(String),
+
     /// Error during KDF invocation.
     ///
     /// This error can arise if the KDF was supplied with invalid parameters,
     /// which may lead or have led to a KDF-specific error (e.g., out-of-memory).
     DeriveKey(anyhow::Error),
+
+    /// I/O error during streaming decryption.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    Io(std::io::Error),
+}
+
+impl Error {
+    /// Returns the name of the error variant, which is recorded in tracing events.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Error::NoCipher(_) => "NoCipher",
+            Error::NoKdf(_) => "NoKdf",
+            Error::DuplicateName(_) => "DuplicateName",
+            Error::NoEntry(_) => "NoEntry",
+            Error::KdfParams(_) => "KdfParams",
+            Error::Serialization(_) => "Serialization",
+            Error::NonceLen => "NonceLen",
+            Error::MacLen => "MacLen",
+            Error::SaltLen => "SaltLen",
+            Error::ChunkSize => "ChunkSize",
+            Error::OutputLen { .. } => "OutputLen",
+            Error::KeyLen { .. } => "KeyLen",
+            Error::MacMismatch => "MacMismatch",
+            Error::CiphertextMismatch => "CiphertextMismatch",
+            Error::KdfParamsTooExpensive(_) => "KdfParamsTooExpensive",
+            Error::MalformedBox(_) => "MalformedBox",
+            Error::SealOptions(_) => "SealOptions",
+            Error::DeriveKey(_) => "DeriveKey",
+            #[cfg(feature = "std")]
+            Error::Io(_) => "Io",
+        }
+    }
 }
 
 impl From<MacMismatch> for Error {
@@ -199,12 +408,38 @@ impl fmt::Display for Error {
         match self {
             Error::NoCipher(cipher) => write!(formatter, "unknown cipher: {}", cipher),
             Error::NoKdf(kdf) => write!(formatter, "unknown KDF: {}", kdf),
+            Error::DuplicateName(name) => write!(formatter, "name already registered: {}", name),
+            Error::NoEntry(name) => write!(formatter, "no entry in collection: {}", name),
             Error::KdfParams(e) => write!(formatter, "failed to parse KDF parameters: {}", e),
+            Error::Serialization(e) => write!(formatter, "failed to (de)serialize box: {}", e),
             Error::NonceLen => formatter.write_str("incorrect nonce length"),
             Error::MacLen => formatter.write_str("incorrect MAC length"),
             Error::SaltLen => formatter.write_str("incorrect salt length"),
+            Error::ChunkSize => formatter.write_str("incorrect chunk size"),
+            Error::OutputLen { expected, actual } => write!(
+                formatter,
+                "output buffer is too small: expected at least {} bytes, got {}",
+                expected, actual
+            ),
+            Error::KeyLen { expected, actual } => write!(
+                formatter,
+                "incorrect key length: expected {} bytes, got {}",
+                expected, actual
+            ),
             Error::MacMismatch => formatter.write_str("incorrect password or corrupted box"),
+            Error::CiphertextMismatch => {
+                formatter.write_str("detached ciphertext does not match box header")
+            }
+            Error::KdfParamsTooExpensive(cost) => write!(
+                formatter,
+                "KDF params exceed resource limits: {} bytes of memory, time cost {}",
+                cost.memory_bytes, cost.time_cost
+            ),
+            Error::MalformedBox(reason) => write!(formatter, "malformed box: {}", reason),
+            Error::SealOptions(reason) => write!(formatter, "invalid sealing options: {}", reason),
             Error::DeriveKey(e) => write!(formatter, "error during key derivation: {}", e),
+            #[cfg(feature = "std")]
+            Error::Io(e) => write!(formatter, "I/O error: {}", e),
         }
     }
 }
@@ -213,8 +448,9 @@ impl fmt::Display for Error {
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
-            Error::KdfParams(e) => Some(e),
+            Error::KdfParams(e) | Error::Serialization(e) => Some(e),
             Error::DeriveKey(e) => Some(e.as_ref()),
+            Error::Io(e) => Some(e),
             _ => None,
         }
     }
@@ -224,11 +460,18 @@ impl std::error::Error for Error {
 /// and cipher `nonce`, as well as the ciphertext and the KDF / cipher info.
 ///
 /// Reused within `PwBox` and `RestoredPwBox`.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct PwBoxInner<K, C> {
     salt: Vec<u8>,
     nonce: Vec<u8>,
     encrypted: CipherOutput,
+    /// Was the box sealed with non-empty associated data?
+    associated_data: bool,
+    /// Metadata authenticated by the cipher in addition to associated data. Empty for boxes
+    /// in the v1 format.
+    metadata: Vec<u8>,
+    /// Transform applied to the password before key derivation.
+    password_policy: PasswordPolicy,
     kdf: K,
     cipher: C,
 }
@@ -240,64 +483,403 @@ impl<K: DeriveKey, C: ObjectSafeCipher> PwBoxInner<K, C> {
         rng: &mut R,
         password: impl AsRef<[u8]>,
         message: impl AsRef<[u8]>,
-    ) -> anyhow::Result<Self> {
+        associated_data: &[u8],
+        metadata: Vec<u8>,
+    ) -> Result<Self, Error> {
         // Create salt and nonce from RNG.
-        let mut salt = SensitiveData::zeros(kdf.salt_len());
-        rng.fill_bytes(salt.bytes_mut());
-        let mut nonce = SensitiveData::zeros(cipher.nonce_len());
-        rng.fill_bytes(nonce.bytes_mut());
+        let mut salt = vec![0_u8; kdf.salt_len()];
+        rng.fill_bytes(&mut salt);
+        let mut nonce = vec![0_u8; cipher.nonce_len()];
+        rng.fill_bytes(&mut nonce);
+        let (password, message) = (password.as_ref(), message.as_ref());
+        let aad = (associated_data, metadata);
+        Self::seal_with_salt_and_nonce(kdf, cipher, salt, nonce, password, message, aad)
+    }
+
+    /// Seals a box using the specified salt and nonce. Lengths of the salt and nonce
+    /// are assumed to be checked by the caller. `aad` consists of the associated data
+    /// and the metadata bound to the box.
+    fn seal_with_salt_and_nonce(
+        kdf: K,
+        cipher: C,
+        salt: Vec<u8>,
+        nonce: Vec<u8>,
+        password: &[u8],
+        message: &[u8],
+        (associated_data, metadata): (&[u8], Vec<u8>),
+    ) -> Result<Self, Error> {
+        check_aad_support(&cipher, associated_data, &metadata)?;
+        enter_span!("seal", cipher = %cipher.name(), len = message.len());
 
         // Derive key from password and salt.
         let mut key = SensitiveData::zeros(cipher.key_len());
-        kdf.derive_key(key.bytes_mut(), password.as_ref(), &*salt)?;
+        trace_kdf!(
+            salt_len = salt.len(),
+            kdf.derive_key(key.bytes_mut(), password, &salt)
+        )
+        .map_err(Error::DeriveKey)?;
 
-        let encrypted = cipher.seal(message.as_ref(), &*nonce, &*key);
-        Ok(PwBoxInner {
-            salt: salt[..].to_vec(),
-            nonce: nonce[..].to_vec(),
+        let aad = (associated_data, metadata);
+        Ok(Self::seal_with_key(
+            kdf, cipher, salt, nonce, &key, message, aad,
+        ))
+    }
+
+    /// Seals a box with the `key` previously derived from the password and `salt`.
+    /// Support of associated data by the cipher is assumed to be checked by the caller.
+    fn seal_with_key(
+        kdf: K,
+        cipher: C,
+        salt: Vec<u8>,
+        nonce: Vec<u8>,
+        key: &[u8],
+        message: &[u8],
+        (associated_data, metadata): (&[u8], Vec<u8>),
+    ) -> Self {
+        let cipher_aad = cipher_aad(&metadata, (&salt, &nonce), associated_data);
+        let encrypted = cipher.seal(message, &cipher_aad, &nonce, key);
+        trace_event!(DEBUG, len = message.len(), "sealed box");
+        PwBoxInner {
+            salt,
+            nonce,
             encrypted,
+            associated_data: !associated_data.is_empty(),
+            metadata,
+            password_policy: PasswordPolicy::Raw,
             kdf,
             cipher,
-        })
+        }
     }
 
     fn len(&self) -> usize {
         self.encrypted.ciphertext.len()
     }
 
+    /// Borrows the parts of the box necessary to open it.
+    fn as_parts(&self) -> BoxParts<'_, K, C> {
+        BoxParts {
+            salt: &self.salt,
+            nonce: &self.nonce,
+            encrypted: &self.encrypted,
+            metadata: &self.metadata,
+            password_policy: self.password_policy,
+            kdf: &self.kdf,
+            cipher: &self.cipher,
+        }
+    }
+
     fn open_into(
         &self,
         mut output: impl AsMut<[u8]>,
         password: impl AsRef<[u8]>,
-    ) -> Result<(), Error> {
-        assert_eq!(
-            output.as_mut().len(),
-            self.len(),
-            "please check `PwBox::len()` and provide output of fitting size"
-        );
-
-        let key_len = self.cipher.key_len();
+        associated_data: &[u8],
+    ) -> Result<usize, Error> {
+        self.as_parts()
+            .open_into(output.as_mut(), password.as_ref(), associated_data)
+    }
 
-        // Derive key from password and salt.
-        let mut key = SensitiveData::zeros(key_len);
-        self.kdf
-            .derive_key(key.bytes_mut(), password.as_ref(), &self.salt)
-            .map_err(Error::DeriveKey)?;
+    /// Derives key from password and salt.
+    fn derive_key(&self, password: impl AsRef<[u8]>) -> Result<SensitiveData, Error> {
+        self.as_parts().derive_key(password.as_ref())
+    }
 
+    fn verify(&self, password: impl AsRef<[u8]>, associated_data: &[u8]) -> Result<(), Error> {
+        enter_span!("verify", cipher = %self.cipher.name(), len = self.len());
+        let key = self.derive_key(password)?;
+        let aad = cipher_aad(&self.metadata, (&self.salt, &self.nonce), associated_data);
         self.cipher
-            .open(output.as_mut(), &self.encrypted, &self.nonce, &*key)
-            .map_err(From::from)
+            .verify(&self.encrypted, &aad, &self.nonce, &*key)?;
+        Ok(())
+    }
+
+    fn check_password(&self, password: impl AsRef<[u8]>) -> Result<bool, Error> {
+        match self.verify(password, &[]) {
+            Ok(()) => Ok(true),
+            Err(Error::MacMismatch) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn open(
+        &self,
+        password: impl AsRef<[u8]>,
+        associated_data: &[u8],
+    ) -> Result<SensitiveData, Error> {
+        let mut output = SensitiveData::zeros(self.len());
+        self.open_into(output.bytes_mut(), password, associated_data)
+            .map(|_| output)
+    }
+
+    /// Opens the box with the first matching password, returning its index.
+    fn open_any<'a>(
+        &self,
+        passwords: impl IntoIterator<Item = &'a [u8]>,
+    ) -> Result<(usize, SensitiveData), Error> {
+        for (i, password) in passwords.into_iter().enumerate() {
+            match self.open(password, &[]) {
+                Ok(output) => return Ok((i, output)),
+                Err(Error::MacMismatch) => { /* try the next password */ }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(Error::MacMismatch)
     }
 
-    fn open(&self, password: impl AsRef<[u8]>) -> Result<SensitiveData, Error> {
+    /// Opens the box reporting progress to the callback.
+    fn open_with_progress(
+        &self,
+        password: &[u8],
+        associated_data: &[u8],
+        progress: &mut dyn FnMut(Progress),
+    ) -> Result<SensitiveData, Error> {
+        progress(Progress::DerivingKey);
+        let key = self.derive_key(password)?;
+        progress(Progress::KeyDerived);
+        progress(Progress::Verifying);
+        self.open_with_key(&key, associated_data)
+    }
+
+    /// Opens the box deriving the key with the external KDF.
+    fn open_with_kdf(
+        &self,
+        kdf: &mut dyn DeriveKeyMut,
+        password: &[u8],
+    ) -> Result<SensitiveData, Error> {
+        enter_span!("open_with_kdf", cipher = %self.cipher.name(), len = self.len());
+        if !kdf.valid_salt_len(self.salt.len()) {
+            trace_event!(
+                WARN,
+                error = "SaltLen",
+                "salt is not accepted by external KDF"
+            );
+            return Err(Error::SaltLen);
+        }
+        let password = self.password_policy.apply(password);
+        let mut key = SensitiveData::zeros(self.cipher.key_len());
+        trace_kdf!(
+            salt_len = self.salt.len(),
+            kdf.derive_key(key.bytes_mut(), &password, &self.salt)
+        )
+        .map_err(Error::DeriveKey)?;
+        self.open_with_key(&key, &[])
+    }
+
+    /// Opens the box consuming it, so that the ciphertext buffer is reused for the plaintext.
+    /// The remaining buffers of the box are zeroed on drop regardless of the outcome.
+    fn open_in_place(mut self, password: &[u8]) -> Result<SensitiveData, Error> {
+        enter_span!("open_in_place", cipher = %self.cipher.name(), len = self.len());
+        let result = self.derive_key(password).and_then(|key| {
+            // If the ciphertext is stored on heap in `SensitiveData`, the allocation is reused.
+            let mut output = SensitiveData::from(mem::take(&mut self.encrypted.ciphertext));
+            let aad = cipher_aad(&self.metadata, (&self.salt, &self.nonce), &[]);
+            self.cipher.open_in_place(
+                output.bytes_mut(),
+                &self.encrypted.mac,
+                &aad,
+                &self.nonce,
+                &*key,
+            )?;
+            Ok(output)
+        });
+        // The remaining buffers are zeroed when `self` is dropped.
+        result
+    }
+
+    /// Checks that the `key` has the byte size of the cipher key.
+    fn check_key_len(&self, key: &[u8]) -> Result<(), Error> {
+        let expected = self.cipher.key_len();
+        if key.len() == expected {
+            Ok(())
+        } else {
+            Err(Error::KeyLen {
+                expected,
+                actual: key.len(),
+            })
+        }
+    }
+
+    /// Opens the box with a previously derived key.
+    fn open_with_key(&self, key: &[u8], associated_data: &[u8]) -> Result<SensitiveData, Error> {
         let mut output = SensitiveData::zeros(self.len());
-        self.open_into(output.bytes_mut(), password)
-            .map(|()| output)
+        let aad = cipher_aad(&self.metadata, (&self.salt, &self.nonce), associated_data);
+        self.cipher
+            .open(output.bytes_mut(), &self.encrypted, &aad, &self.nonce, key)?;
+        Ok(output)
+    }
+}
+
+impl<K, C> PwBoxInner<K, C> {
+    /// Compares the public contents of the boxes, ignoring their KDFs and ciphers.
+    fn content_eq<L, D>(&self, other: &PwBoxInner<L, D>) -> bool {
+        self.salt == other.salt
+            && self.nonce == other.nonce
+            && self.encrypted == other.encrypted
+            && self.associated_data == other.associated_data
+            && self.metadata == other.metadata
+            && self.password_policy == other.password_policy
+    }
+}
+
+impl<K, C> Zeroize for PwBoxInner<K, C> {
+    fn zeroize(&mut self) {
+        self.salt.zeroize();
+        self.nonce.zeroize();
+        self.encrypted.ciphertext.zeroize();
+        self.encrypted.mac.zeroize();
+        self.metadata.zeroize();
+    }
+}
+
+impl<K, C> Drop for PwBoxInner<K, C> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+/// Borrowed parts of a box necessary to open it. Allows to open boxes without copying
+/// their contents, e.g., in [`Eraser::open()`].
+struct BoxParts<'a, K: ?Sized, C: ?Sized> {
+    salt: &'a [u8],
+    nonce: &'a [u8],
+    encrypted: &'a CipherOutput,
+    metadata: &'a [u8],
+    password_policy: PasswordPolicy,
+    kdf: &'a K,
+    cipher: &'a C,
+}
+
+impl<K, C> BoxParts<'_, K, C>
+where
+    K: DeriveKey + ?Sized,
+    C: ObjectSafeCipher + ?Sized,
+{
+    fn open_into(
+        &self,
+        output: &mut [u8],
+        password: &[u8],
+        associated_data: &[u8],
+    ) -> Result<usize, Error> {
+        let len = self.encrypted.ciphertext.len();
+        enter_span!("open", cipher = %self.cipher.name(), len);
+        if output.len() < len {
+            trace_event!(
+                WARN,
+                error = "OutputLen",
+                actual = output.len(),
+                "output is too small"
+            );
+            return Err(Error::OutputLen {
+                expected: len,
+                actual: output.len(),
+            });
+        }
+        let output = &mut output[..len];
+
+        let key = self.derive_key(password)?;
+        let aad = cipher_aad(self.metadata, (self.salt, self.nonce), associated_data);
+        self.cipher
+            .open(output, self.encrypted, &aad, self.nonce, &*key)?;
+        Ok(len)
+    }
+
+    /// Derives key from password and salt.
+    fn derive_key(&self, password: &[u8]) -> Result<SensitiveData, Error> {
+        let password = self.password_policy.apply(password);
+        let mut key = SensitiveData::zeros(self.cipher.key_len());
+        trace_kdf!(
+            salt_len = self.salt.len(),
+            self.kdf.derive_key(key.bytes_mut(), &password, self.salt)
+        )
+        .map_err(Error::DeriveKey)?;
+        Ok(key)
+    }
+}
+
+fn check_aad_support(
+    cipher: &impl ObjectSafeCipher,
+    associated_data: &[u8],
+    metadata: &[u8],
+) -> Result<(), Error> {
+    let has_aad = !associated_data.is_empty() || !metadata.is_empty();
+    if has_aad && !cipher.supports_associated_data() {
+        let reason = "cipher does not support associated data";
+        return Err(Error::SealOptions(reason.into()));
+    }
+    Ok(())
+}
+
+/// Returns associated data supplied to the cipher. For boxes in the v2 format (i.e., with
+/// non-empty `metadata`), this is the metadata bound to the box, followed by the length-prefixed
+/// salt and nonce and the user-provided associated data. Otherwise, only the user-provided
+/// associated data is authenticated.
+fn cipher_aad<'a>(
+    metadata: &[u8],
+    (salt, nonce): (&[u8], &[u8]),
+    associated_data: &'a [u8],
+) -> Cow<'a, [u8]> {
+    if metadata.is_empty() {
+        return Cow::Borrowed(associated_data);
+    }
+
+    let prefix_len = mem::size_of::<u64>();
+    let capacity = metadata.len() + 2 * prefix_len + salt.len() + nonce.len();
+    let mut aad = Vec::with_capacity(capacity + associated_data.len());
+    aad.extend_from_slice(metadata);
+    for field in &[salt, nonce] {
+        aad.extend_from_slice(&(field.len() as u64).to_le_bytes());
+        aad.extend_from_slice(field);
+    }
+    aad.extend_from_slice(associated_data);
+    Cow::Owned(aad)
+}
+
+impl<K: DeriveKey + Clone, C: ObjectSafeCipher + Clone> PwBoxInner<K, C> {
+    fn change_password<R: RngCore + ?Sized>(
+        &self,
+        rng: &mut R,
+        old_password: impl AsRef<[u8]>,
+        new_password: impl AsRef<[u8]>,
+    ) -> Result<Self, Error> {
+        let plaintext = self.open(old_password, &[])?;
+        let new_password = self.password_policy.apply(new_password.as_ref());
+        let (kdf, cipher, metadata) =
+            (self.kdf.clone(), self.cipher.clone(), self.metadata.clone());
+        let mut inner = Self::seal(kdf, cipher, rng, new_password, plaintext, &[], metadata)?;
+        inner.password_policy = self.password_policy;
+        Ok(inner)
+    }
+
+    /// Seals a box with the same KDF, salt and metadata as this box, using the `key`
+    /// previously derived from the password.
+    fn seal_with_same_key<R: RngCore + ?Sized>(
+        &self,
+        rng: &mut R,
+        key: &[u8],
+        message: &[u8],
+    ) -> Result<Self, Error> {
+        self.check_key_len(key)?;
+        let mut nonce = vec![0; self.cipher.nonce_len()];
+        rng.fill_bytes(&mut nonce);
+        let (kdf, cipher, salt) = (self.kdf.clone(), self.cipher.clone(), self.salt.clone());
+        let aad = (&[][..], self.metadata.clone());
+        let mut inner = Self::seal_with_key(kdf, cipher, salt, nonce, key, message, aad);
+        inner.password_policy = self.password_policy;
+        Ok(inner)
     }
 }
 
 /// Password-encrypted data.
 ///
+/// # Zeroization
+///
+/// Secret data created during sealing and opening the box (derived keys, intermediate
+/// plaintexts) is stored in containers zeroed on drop, such as [`SensitiveData`]
+/// and [`SecretVec`]; this includes scratch buffers in the cipher and KDF implementations
+/// provided by the crate. Buffers allocated internally by the underlying cryptographic
+/// libraries (e.g., the working memory of `scrypt`) are outside of the crate's control.
+/// The box itself only stores public data (salt, nonce, ciphertext and MAC); its buffers
+/// are nevertheless zeroed on drop.
+///
 /// # See also
 ///
 /// See the crate docs for an example of usage. See [`ErasedPwBox`] for serialization details.
@@ -306,42 +888,306 @@ pub struct PwBox<K, C> {
     inner: PwBoxInner<K, CipherObject<C>>,
 }
 
+// Cannot be derived: the derived impl would require `C: Clone`.
+impl<K: Clone, C> Clone for PwBox<K, C> {
+    fn clone(&self) -> Self {
+        PwBox {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
 impl<K: DeriveKey + Default, C: Cipher> PwBox<K, C> {
     /// Creates a new box by using default settings of the supplied KDF.
     pub fn new<R: RngCore + CryptoRng>(
         rng: &mut R,
         password: impl AsRef<[u8]>,
         message: impl AsRef<[u8]>,
-    ) -> anyhow::Result<Self> {
+    ) -> Result<Self, Error> {
         let (kdf, cipher) = (K::default(), CipherObject::default());
-        PwBoxInner::seal(kdf, cipher, rng, password, message).map(|inner| PwBox { inner })
+        PwBoxInner::seal(kdf, cipher, rng, password, message, &[], Vec::new())
+            .map(|inner| PwBox { inner })
+    }
+}
+
+impl<C: Cipher> PwBox<NoKdf, C> {
+    /// Creates a new box with the specified `key` used as the cipher key directly,
+    /// bypassing key derivation. The box can be opened with the same `key` via
+    /// [`Self::open_with_key()`] or [`Self::open()`].
+    ///
+    /// This is equivalent to [`PwBoxBuilder::seal_with_key()`] with the default builder
+    /// settings. To seal a box with a key derived from the password, which can be later
+    /// opened with this password, use [`Self::seal_with_derived_key()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::KeyLen`] if the `key` length is not equal to [`Cipher::KEY_LEN`].
+    pub fn seal_with_key<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        key: &[u8],
+        message: impl AsRef<[u8]>,
+    ) -> Result<Self, Error> {
+        if key.len() != C::KEY_LEN {
+            return Err(Error::KeyLen {
+                expected: C::KEY_LEN,
+                actual: key.len(),
+            });
+        }
+        let mut nonce = vec![0; C::NONCE_LEN];
+        rng.fill_bytes(&mut nonce);
+        let (kdf, cipher) = (NoKdf {}, CipherObject::default());
+        let aad = (&[][..], Vec::new());
+        let inner =
+            PwBoxInner::seal_with_key(kdf, cipher, Vec::new(), nonce, key, message.as_ref(), aad);
+        Ok(PwBox { inner })
     }
 }
 
-// `is_empty()` method wouldn't make much sense; in *all* valid use cases, `len() > 0`.
-#[allow(clippy::len_without_is_empty)]
 impl<K: DeriveKey, C: Cipher> PwBox<K, C> {
     /// Returns the byte size of the encrypted data stored in this box.
     pub fn len(&self) -> usize {
         self.inner.len()
     }
 
+    /// Checks whether this box contains an empty message.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Checks whether this box is the same sealed object as `other`, i.e., the salt, nonce,
+    /// ciphertext, MAC and the metadata bound to the boxes are equal. KDF params
+    /// are not compared, since `K` is not required to implement `PartialEq`; however,
+    /// the MAC depends on the derived key, so boxes with equal contents were sealed with
+    /// the same key with overwhelming probability.
+    ///
+    /// The comparison is not constant-time. This is fine, since all compared fields
+    /// are public data stored in the box; in particular, the MAC is not compared
+    /// with a value computed from a secret key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rand::thread_rng;
+    /// # use pwbox::{sodium::{Scrypt, Sodium}, Suite};
+    /// let pwbox = Sodium::build_box(&mut thread_rng())
+    /// #   .kdf(Scrypt::light())
+    ///     .seal("correct horse", b"battery staple")?;
+    /// assert!(pwbox.content_eq(&pwbox.clone()));
+    /// let other_box = Sodium::build_box(&mut thread_rng())
+    /// #   .kdf(Scrypt::light())
+    ///     .seal("correct horse", b"battery staple")?;
+    /// // The boxes have different salts and nonces.
+    /// assert!(!pwbox.content_eq(&other_box));
+    /// # Ok::<_, pwbox::Error>(())
+    /// ```
+    pub fn content_eq(&self, other: &Self) -> bool {
+        self.inner.content_eq(&other.inner)
+    }
+
     /// Decrypts the box into the specified container.
     ///
     /// This method should be preferred to `open()` if the `output` type implements
     /// zeroing on drop (e.g., cryptographic secrets from `sodiumoxide`).
+    ///
+    /// The `output` may be larger than [`Self::len()`]; in this case, the decrypted data
+    /// is written to its prefix. Returns the number of bytes written, which is always equal
+    /// to `len()`.
+    ///
+    /// # Errors
+    ///
+    /// If the `output` is too small, returns [`Error::OutputLen`] without deriving the key.
     pub fn open_into(
         &self,
         output: impl AsMut<[u8]>,
         password: impl AsRef<[u8]>,
-    ) -> Result<(), Error> {
-        self.inner.open_into(output, password)
+    ) -> Result<usize, Error> {
+        self.inner.open_into(output, password, &[])
     }
 
     /// Decrypts the box and returns its contents. The returned container is zeroed on drop
     /// and derefs to a byte slice.
     pub fn open(&self, password: impl AsRef<[u8]>) -> Result<SensitiveData, Error> {
-        self.inner.open(password)
+        self.inner.open(password, &[])
+    }
+
+    /// Decrypts the box consuming it. Unlike [`Self::open()`], the ciphertext buffer
+    /// of the box is decrypted in place and returned, so that opening a large box requires
+    /// memory for a single copy of the data. (This holds if the cipher supports in-place
+    /// decryption, which is the case for all ciphers provided by the crate except for
+    /// AES-GCM; otherwise, the ciphertext is copied transparently,
+    /// see [`Cipher::open_in_place()`].)
+    ///
+    /// The remaining buffers of the box are zeroed, both on success and on failure.
+    pub fn open_in_place(self, password: impl AsRef<[u8]>) -> Result<SensitiveData, Error> {
+        self.inner.open_in_place(password.as_ref())
+    }
+
+    /// Decrypts the box, reporting progress to the `progress` callback. The callback
+    /// receives [`Progress::DerivingKey`], [`Progress::KeyDerived`] and [`Progress::Verifying`]
+    /// events; the latter is emitted before the box is verified and decrypted.
+    pub fn open_with_progress(
+        &self,
+        password: impl AsRef<[u8]>,
+        mut progress: impl FnMut(Progress),
+    ) -> Result<SensitiveData, Error> {
+        self.inner
+            .open_with_progress(password.as_ref(), &[], &mut progress)
+    }
+
+    /// Checks whether the password is correct without decrypting the box. This is faster
+    /// than [`Self::open()`] for some ciphers (e.g., [`CipherWithMac`]), and does not expose
+    /// the plaintext in any case.
+    ///
+    /// Returns `Ok(false)` if the MAC does not verify. Errors are only returned if the key
+    /// cannot be derived. Boxes with associated data (see [`Self::has_associated_data()`])
+    /// cannot be checked this way; the method returns `Ok(false)` for them.
+    /// Use [`Self::verify_with_aad()`] for such boxes.
+    pub fn check_password(&self, password: impl AsRef<[u8]>) -> Result<bool, Error> {
+        self.inner.check_password(password)
+    }
+
+    /// Derives the key from the password and verifies the MAC without returning the plaintext,
+    /// similar to [`Self::check_password()`]. If the password is incorrect,
+    /// [`Error::MacMismatch`] is returned.
+    pub fn verify(&self, password: impl AsRef<[u8]>) -> Result<(), Error> {
+        self.inner.verify(password, &[])
+    }
+
+    /// Verifies the box sealed with the specified associated data. If the password
+    /// or associated data are incorrect, [`Error::MacMismatch`] is returned.
+    pub fn verify_with_aad(
+        &self,
+        password: impl AsRef<[u8]>,
+        associated_data: impl AsRef<[u8]>,
+    ) -> Result<(), Error> {
+        self.inner.verify(password, associated_data.as_ref())
+    }
+
+    /// Checks whether the box was sealed with non-empty associated data.
+    pub fn has_associated_data(&self) -> bool {
+        self.inner.associated_data
+    }
+
+    /// Decrypts the box sealed with the specified associated data. If the associated data
+    /// does not match, [`Error::MacMismatch`] is returned.
+    pub fn open_with_aad(
+        &self,
+        password: impl AsRef<[u8]>,
+        associated_data: impl AsRef<[u8]>,
+    ) -> Result<SensitiveData, Error> {
+        self.inner.open(password, associated_data.as_ref())
+    }
+
+    /// Decrypts the box sealed with the specified associated data into the specified
+    /// container. See [`Self::open_into()`] and [`Self::open_with_aad()`] for details.
+    pub fn open_into_with_aad(
+        &self,
+        output: impl AsMut<[u8]>,
+        password: impl AsRef<[u8]>,
+        associated_data: impl AsRef<[u8]>,
+    ) -> Result<usize, Error> {
+        self.inner
+            .open_into(output, password, associated_data.as_ref())
+    }
+
+    /// Derives the cipher key from the password and the salt of this box. The key
+    /// can be used to open this box and boxes sealed with [`Self::seal_with_derived_key()`]
+    /// without re-running the KDF. The returned key is zeroed on drop.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DeriveKey`] if the KDF fails. The password is not checked;
+    /// an incorrect password results in a key failing to open the box.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rand::thread_rng;
+    /// # use pwbox::{sodium::{Scrypt, Sodium}, Suite};
+    /// let mut rng = thread_rng();
+    /// let pwbox = Sodium::build_box(&mut rng)
+    /// #   .kdf(Scrypt::light())
+    ///     .seal("correct horse", b"battery staple")?;
+    /// let key = pwbox.derive_key("correct horse")?;
+    /// let other_box = pwbox.seal_with_derived_key(&mut rng, &key, b"Tr0ub4dor&3")?;
+    /// // Both boxes can be opened without re-running the KDF...
+    /// assert_eq!(&*pwbox.open_with_key(&key)?, b"battery staple");
+    /// assert_eq!(&*other_box.open_with_key(&key)?, b"Tr0ub4dor&3");
+    /// // ...or with the password.
+    /// assert_eq!(&*other_box.open("correct horse")?, b"Tr0ub4dor&3");
+    /// # Ok::<_, anyhow::Error>(())
+    /// ```
+    pub fn derive_key(&self, password: impl AsRef<[u8]>) -> Result<SensitiveData, Error> {
+        self.inner.derive_key(password)
+    }
+
+    /// Decrypts the box with the key previously obtained from [`Self::derive_key()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::KeyLen`] if the `key` length is not equal to [`Cipher::KEY_LEN`],
+    /// and [`Error::MacMismatch`] if the key is incorrect.
+    pub fn open_with_key(&self, key: &[u8]) -> Result<SensitiveData, Error> {
+        self.inner.check_key_len(key)?;
+        self.inner.open_with_key(key, &[])
+    }
+
+    /// Decrypts the box deriving the key with the caller-supplied `kdf` instead
+    /// of the KDF stored in the box. This is used for boxes sealed with
+    /// [`PwBoxBuilder::seal_with_external_kdf()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SaltLen`] if the `kdf` does not accept the salt of this box;
+    /// the `kdf` is not invoked in this case. Returns [`Error::DeriveKey`] if the `kdf`
+    /// fails, and [`Error::MacMismatch`] if the password is incorrect.
+    pub fn open_with_kdf(
+        &self,
+        kdf: &mut impl DeriveKeyMut,
+        password: impl AsRef<[u8]>,
+    ) -> Result<SensitiveData, Error> {
+        self.inner.open_with_kdf(kdf, password.as_ref())
+    }
+}
+
+impl<K: DeriveKey + Clone, C: Cipher> PwBox<K, C> {
+    /// Re-encrypts the contents of this box under a new password. The new box uses
+    /// the same KDF params and cipher, but a fresh salt and nonce.
+    ///
+    /// The plaintext never leaves the crate; it is kept in a container zeroed on drop.
+    /// If the `old_password` is incorrect, [`Error::MacMismatch`] is returned.
+    pub fn change_password<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        old_password: impl AsRef<[u8]>,
+        new_password: impl AsRef<[u8]>,
+    ) -> Result<Self, Error> {
+        self.inner
+            .change_password(rng, old_password, new_password)
+            .map(|inner| PwBox { inner })
+    }
+
+    /// Seals the `message` into a new box with the `key` obtained from [`Self::derive_key()`],
+    /// bypassing key derivation. The new box shares the KDF params and the salt with this box
+    /// and has a fresh random nonce; thus, it can be opened both with the `key`
+    /// and with the password the key was derived from.
+    ///
+    /// Since all boxes sealed this way share the key, the cipher should have nonces long
+    /// enough to be safely generated at random (e.g., 24-byte nonces of `XSalsa20Poly1305`
+    /// or `XChaCha20Poly1305`).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::KeyLen`] if the `key` length is not equal to [`Cipher::KEY_LEN`].
+    pub fn seal_with_derived_key<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        key: &[u8],
+        message: impl AsRef<[u8]>,
+    ) -> Result<Self, Error> {
+        self.inner
+            .seal_with_same_key(rng, key, message.as_ref())
+            .map(|inner| PwBox { inner })
     }
 }
 
@@ -349,69 +1195,546 @@ impl<K: DeriveKey, C: Cipher> PwBox<K, C> {
 ///
 /// If the box may be corrupted, it may make sense to check its length
 /// with the [`Self::len()`] method before `open`ing the box.
+///
+/// The box retains the KDF and cipher names and the KDF params it was restored with,
+/// so it can be converted back into the serializable form with [`Eraser::erase_restored()`].
+/// Cloning the box is cheap: the KDF and cipher are shared among the clones.
+///
+/// The box is `Send + Sync`, so it can be restored once and shared among threads
+/// (e.g., behind an `Arc`), which may open it concurrently.
+#[derive(Clone)]
 pub struct RestoredPwBox {
-    inner: PwBoxInner<Box<dyn DeriveKey>, Box<dyn ObjectSafeCipher>>,
+    inner: PwBoxInner<Arc<dyn DeriveKey>, Arc<dyn ObjectSafeCipher>>,
+    kdf_name: String,
+    cipher_name: String,
+    kdf_params: JsonValue,
 }
 
 impl fmt::Debug for RestoredPwBox {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-        formatter.debug_struct("RestoredPwBox").finish()
+        formatter
+            .debug_struct("RestoredPwBox")
+            .field("kdf", &self.kdf_name)
+            .field("cipher", &self.cipher_name)
+            .finish()
     }
 }
 
-// `is_empty()` method wouldn't make much sense; in *all* valid use cases, `len() > 0`.
-#[allow(clippy::len_without_is_empty)]
 impl RestoredPwBox {
     /// Returns the byte size of the encrypted data stored in this box.
     pub fn len(&self) -> usize {
         self.inner.len()
     }
 
+    /// Checks whether this box contains an empty message.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Checks whether this box is the same sealed object as `other`. Unlike
+    /// [`PwBox::content_eq()`], the KDF and cipher names and the KDF params
+    /// are compared as well. As with `PwBox`, the comparison is not constant-time,
+    /// since all compared fields are public.
+    pub fn content_eq(&self, other: &Self) -> bool {
+        self.kdf_name == other.kdf_name
+            && self.cipher_name == other.cipher_name
+            && self.kdf_params == other.kdf_params
+            && self.inner.content_eq(&other.inner)
+    }
+
+    /// Returns the name of the KDF this box was restored with, as recorded in the erased box.
+    pub fn kdf_name(&self) -> &str {
+        &self.kdf_name
+    }
+
+    /// Returns the name of the cipher this box was restored with, as recorded
+    /// in the erased box.
+    pub fn cipher_name(&self) -> &str {
+        &self.cipher_name
+    }
+
+    /// Returns the KDF params of this box, excluding the salt. Params recorded
+    /// as a PHC string are returned in the same form as for other boxes.
+    ///
+    /// The params are available for any KDF registered in the [`Eraser`]. For KDFs known
+    /// at compile time, the params can be parsed into the KDF type, e.g.,
+    /// with `serde_json::from_value()`.
+    pub fn kdf_params(&self) -> &JsonValue {
+        &self.kdf_params
+    }
+
+    /// Returns the estimated resources required to derive the key for this box, or `None`
+    /// if the KDF does not provide an estimate. This can be used to reject boxes
+    /// with too weak or too expensive KDF params before opening them.
+    /// See also [`Eraser::set_kdf_limits()`], which allows to reject boxes
+    /// with too expensive params on restoration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rand::thread_rng;
+    /// # use pwbox::{sodium::{Scrypt, Sodium}, Eraser, Suite};
+    /// let eraser = Eraser::with_suites::<(Sodium,)>()?;
+    /// let pwbox = Sodium::build_box(&mut thread_rng())
+    ///     .kdf(Scrypt::light())
+    ///     .seal("correct horse", b"battery staple")?;
+    /// let restored = eraser.restore(&eraser.erase(&pwbox)?)?;
+    /// assert_eq!(restored.kdf_name(), "scrypt-nacl");
+    /// assert!(restored.kdf_params().get("opslimit").is_some());
+    /// let cost = restored.kdf_cost().unwrap();
+    /// assert!(cost.memory_bytes >= 1 << 20);
+    /// # Ok::<_, anyhow::Error>(())
+    /// ```
+    pub fn kdf_cost(&self) -> Option<KdfCost> {
+        self.inner.kdf.cost()
+    }
+
     /// Decrypts the box into the specified container.
     ///
     /// This method should be preferred to `open()` if the `output` type implements
     /// zeroing on drop (e.g., cryptographic secrets from `sodiumoxide`).
+    ///
+    /// The `output` may be larger than [`Self::len()`]; in this case, the decrypted data
+    /// is written to its prefix. Returns the number of bytes written, which is always equal
+    /// to `len()`.
+    ///
+    /// # Errors
+    ///
+    /// If the `output` is too small, returns [`Error::OutputLen`] without deriving the key.
     pub fn open_into(
         &self,
         output: impl AsMut<[u8]>,
         password: impl AsRef<[u8]>,
-    ) -> Result<(), Error> {
-        self.inner.open_into(output, password)
+    ) -> Result<usize, Error> {
+        self.inner.open_into(output, password, &[])
     }
 
     /// Decrypts the box and returns its contents. The returned container is zeroed on drop
     /// and derefs to a byte slice.
     pub fn open(&self, password: impl AsRef<[u8]>) -> Result<SensitiveData, Error> {
-        self.inner.open(password)
+        self.inner.open(password, &[])
     }
-}
 
-/// Builder for `PwBox`es.
-pub struct PwBoxBuilder<'a, K, C> {
-    kdf: Option<K>,
-    rng: &'a mut dyn RngCore,
-    _cipher: PhantomData<C>,
-}
+    /// Decrypts the box consuming it. Unlike [`Self::open()`], the ciphertext buffer
+    /// of the box is decrypted in place and returned, so that opening a large box requires
+    /// memory for a single copy of the data. (This holds if the cipher supports in-place
+    /// decryption, which is the case for all ciphers provided by the crate except for
+    /// AES-GCM; otherwise, the ciphertext is copied transparently,
+    /// see [`Cipher::open_in_place()`].)
+    ///
+    /// The remaining buffers of the box are zeroed, both on success and on failure.
+    pub fn open_in_place(self, password: impl AsRef<[u8]>) -> Result<SensitiveData, Error> {
+        self.inner.open_in_place(password.as_ref())
+    }
 
-impl<'a, K, C> fmt::Debug for PwBoxBuilder<'a, K, C> {
-    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-        formatter
-            .debug_struct("PwBoxBuilder")
-            .field("custom_kdf", &self.kdf.is_some())
-            .finish()
+    /// Decrypts the box trying the candidate `passwords` in order, e.g., variants
+    /// of the password under different normalization schemes. Returns the index
+    /// of the first password that fits together with the decrypted contents.
+    ///
+    /// The restored KDF and cipher are reused for all attempts; note that the key
+    /// is still derived anew for each candidate.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MacMismatch`] if none of the passwords fits (including the case
+    /// when `passwords` is empty). Other errors (e.g., a KDF failure) are returned
+    /// immediately without trying the remaining candidates.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rand::thread_rng;
+    /// # use pwbox::{sodium::{Scrypt, Sodium}, Eraser, Suite};
+    /// # let eraser = Eraser::with_suites::<(Sodium,)>()?;
+    /// # let pwbox = Sodium::build_box(&mut thread_rng())
+    /// #     .kdf(Scrypt::light())
+    /// #     .seal("correct horse", b"battery staple")?;
+    /// let restored = eraser.restore(&eraser.erase(&pwbox)?)?;
+    /// let password = " correct horse ";
+    /// let candidates = [password, password.trim()];
+    /// let (i, plaintext) = restored.open_any(candidates.iter().map(|s| s.as_bytes()))?;
+    /// assert_eq!(i, 1);
+    /// assert_eq!(&*plaintext, b"battery staple");
+    /// # Ok::<_, anyhow::Error>(())
+    /// ```
+    pub fn open_any<'a>(
+        &self,
+        passwords: impl IntoIterator<Item = &'a [u8]>,
+    ) -> Result<(usize, SensitiveData), Error> {
+        self.inner.open_any(passwords)
     }
-}
 
-impl<'a, K, C> PwBoxBuilder<'a, K, C>
-where
-    K: DeriveKey + Clone + Default,
-    C: Cipher,
-{
-    /// Initializes the builder with a random number generator.
-    pub fn new<R: RngCore + CryptoRng>(rng: &'a mut R) -> Self {
-        PwBoxBuilder {
-            kdf: None,
-            rng,
+    /// Decrypts the box, reporting progress to the `progress` callback. The callback
+    /// receives [`Progress::DerivingKey`], [`Progress::KeyDerived`] and [`Progress::Verifying`]
+    /// events; the latter is emitted before the box is verified and decrypted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rand::thread_rng;
+    /// # use pwbox::{sodium::{Scrypt, Sodium}, Eraser, Progress, Suite};
+    /// # let mut eraser = Eraser::new();
+    /// # eraser.add_suite::<Sodium>()?;
+    /// # let pwbox = Sodium::build_box(&mut thread_rng())
+    /// #     .kdf(Scrypt::light())
+    /// #     .seal("correct horse", b"battery staple")?;
+    /// # let erased = eraser.erase(&pwbox)?;
+    /// let restored = eraser.restore(&erased)?;
+    /// let mut events = vec![];
+    /// let plaintext = restored.open_with_progress("correct horse", |event| events.push(event))?;
+    /// assert_eq!(&*plaintext, b"battery staple");
+    /// assert_eq!(
+    ///     events,
+    ///     [Progress::DerivingKey, Progress::KeyDerived, Progress::Verifying]
+    /// );
+    /// # Ok::<_, anyhow::Error>(())
+    /// ```
+    pub fn open_with_progress(
+        &self,
+        password: impl AsRef<[u8]>,
+        mut progress: impl FnMut(Progress),
+    ) -> Result<SensitiveData, Error> {
+        self.inner
+            .open_with_progress(password.as_ref(), &[], &mut progress)
+    }
+
+    /// Checks whether the password is correct without decrypting the box. This is faster
+    /// than [`Self::open()`] for some ciphers (e.g., [`CipherWithMac`]), and does not expose
+    /// the plaintext in any case.
+    ///
+    /// Returns `Ok(false)` if the MAC does not verify. Errors are only returned if the key
+    /// cannot be derived. Boxes with associated data (see [`Self::has_associated_data()`])
+    /// cannot be checked this way; the method returns `Ok(false)` for them.
+    /// Use [`Self::verify_with_aad()`] for such boxes.
+    pub fn check_password(&self, password: impl AsRef<[u8]>) -> Result<bool, Error> {
+        self.inner.check_password(password)
+    }
+
+    /// Derives the key from the password and verifies the MAC without returning the plaintext,
+    /// similar to [`Self::check_password()`]. If the password is incorrect,
+    /// [`Error::MacMismatch`] is returned.
+    pub fn verify(&self, password: impl AsRef<[u8]>) -> Result<(), Error> {
+        self.inner.verify(password, &[])
+    }
+
+    /// Verifies the box sealed with the specified associated data. If the password
+    /// or associated data are incorrect, [`Error::MacMismatch`] is returned.
+    pub fn verify_with_aad(
+        &self,
+        password: impl AsRef<[u8]>,
+        associated_data: impl AsRef<[u8]>,
+    ) -> Result<(), Error> {
+        self.inner.verify(password, associated_data.as_ref())
+    }
+
+    /// Checks whether the box was sealed with non-empty associated data.
+    pub fn has_associated_data(&self) -> bool {
+        self.inner.associated_data
+    }
+
+    /// Decrypts the box sealed with the specified associated data. If the associated data
+    /// does not match, [`Error::MacMismatch`] is returned.
+    pub fn open_with_aad(
+        &self,
+        password: impl AsRef<[u8]>,
+        associated_data: impl AsRef<[u8]>,
+    ) -> Result<SensitiveData, Error> {
+        self.inner.open(password, associated_data.as_ref())
+    }
+
+    /// Decrypts the box sealed with the specified associated data into the specified
+    /// container. See [`Self::open_into()`] and [`Self::open_with_aad()`] for details.
+    pub fn open_into_with_aad(
+        &self,
+        output: impl AsMut<[u8]>,
+        password: impl AsRef<[u8]>,
+        associated_data: impl AsRef<[u8]>,
+    ) -> Result<usize, Error> {
+        self.inner
+            .open_into(output, password, associated_data.as_ref())
+    }
+
+    /// Derives the cipher key from the password and the salt of this box.
+    /// See [`PwBox::derive_key()`] for details.
+    pub fn derive_key(&self, password: impl AsRef<[u8]>) -> Result<SensitiveData, Error> {
+        self.inner.derive_key(password)
+    }
+
+    /// Decrypts the box with the key previously obtained from [`Self::derive_key()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::KeyLen`] if the `key` has an incorrect length,
+    /// and [`Error::MacMismatch`] if the key is incorrect.
+    pub fn open_with_key(&self, key: &[u8]) -> Result<SensitiveData, Error> {
+        self.inner.check_key_len(key)?;
+        self.inner.open_with_key(key, &[])
+    }
+
+    /// Decrypts the box deriving the key with the caller-supplied `kdf` instead
+    /// of the restored KDF. See [`PwBox::open_with_kdf()`] for details.
+    pub fn open_with_kdf(
+        &self,
+        kdf: &mut impl DeriveKeyMut,
+        password: impl AsRef<[u8]>,
+    ) -> Result<SensitiveData, Error> {
+        self.inner.open_with_kdf(kdf, password.as_ref())
+    }
+
+    /// Re-encrypts the contents of this box under a new password. The new box uses
+    /// the same KDF params and cipher, but a fresh salt and nonce.
+    ///
+    /// The plaintext never leaves the crate; it is kept in a container zeroed on drop.
+    /// If the `old_password` is incorrect, [`Error::MacMismatch`] is returned.
+    pub fn change_password<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        old_password: impl AsRef<[u8]>,
+        new_password: impl AsRef<[u8]>,
+    ) -> Result<Self, Error> {
+        self.inner
+            .change_password(rng, old_password, new_password)
+            .map(|inner| RestoredPwBox {
+                inner,
+                kdf_name: self.kdf_name.clone(),
+                cipher_name: self.cipher_name.clone(),
+                kdf_params: self.kdf_params.clone(),
+            })
+    }
+
+    /// Re-encrypts the contents of this box with the default params of the KDF `K`
+    /// and the cipher `C`, using a fresh salt and nonce. This can be used to upgrade boxes
+    /// sealed with weak KDF params (e.g., when the user logs in); the password
+    /// and the password policy of the box are retained. This box is left intact.
+    ///
+    /// The plaintext never leaves the crate; it is kept in a container zeroed on drop.
+    /// See also [`Eraser::migrate()`], which re-seals erased boxes with a custom builder.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MacMismatch`] if the `password` is incorrect, or if the box
+    /// was sealed with associated data. Otherwise, an error is returned under the same
+    /// conditions as for [`Self::open()`] and [`PwBoxBuilder::seal()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rand::thread_rng;
+    /// # use pwbox::{sodium::{Scrypt, Sodium, XChaCha20Poly1305}, Eraser, PwBox, Suite};
+    /// let eraser = Eraser::with_suites::<(Sodium,)>()?;
+    /// let weak_box = Sodium::build_box(&mut thread_rng())
+    ///     .kdf(Scrypt::light())
+    ///     .seal("correct horse", b"battery staple")?;
+    /// let restored = eraser.restore(&eraser.erase(&weak_box)?)?;
+    ///
+    /// let pwbox: PwBox<Scrypt, XChaCha20Poly1305> =
+    ///     restored.reseal(&mut thread_rng(), "correct horse")?;
+    /// let upgraded = eraser.restore(&eraser.erase(&pwbox)?)?;
+    /// let cost = upgraded.kdf_cost().unwrap();
+    /// assert!(cost.memory_bytes > restored.kdf_cost().unwrap().memory_bytes);
+    /// assert_eq!(&*upgraded.open("correct horse")?, b"battery staple");
+    /// # Ok::<_, anyhow::Error>(())
+    /// ```
+    pub fn reseal<K, C, R>(
+        &self,
+        rng: &mut R,
+        password: impl AsRef<[u8]>,
+    ) -> Result<PwBox<K, C>, Error>
+    where
+        K: DeriveKey + Clone + Default,
+        C: Cipher,
+        R: RngCore + CryptoRng,
+    {
+        let password = password.as_ref();
+        let contents = self.open(password)?;
+        PwBoxBuilder::new(rng)
+            .password_policy(self.inner.password_policy)
+            .seal(password, &*contents)
+    }
+}
+
+/// Strategy of generating cipher nonces in a [`PwBoxBuilder`].
+///
+/// Reusing a nonce with the same key breaks the security of most ciphers. Since the key
+/// is derived from the password and a random salt, nonce reuse is only possible if
+/// the salt is reused as well, e.g., if the RNG supplied to the builder has low quality
+/// (say, is a seeded RNG used for reproducibility). Non-random strategies guarantee that
+/// nonces of boxes sealed by the same builder are unique even in this case. These strategies
+/// require the cipher nonce to have at least 8 bytes.
+///
+/// The strategy does not affect streaming boxes, which always use random nonces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonceStrategy {
+    /// Nonces are generated by the RNG. This is the default strategy.
+    Random,
+    /// A nonce consists of a random prefix generated once per builder, followed by
+    /// a big-endian `u64` counter incremented with each sealed box.
+    Counter,
+    /// A nonce is derived from the box salt and the index of the box sealed by the builder.
+    /// The nonce is the SHA-256 digest of the salt followed by the little-endian `u64` index,
+    /// truncated to the nonce size. This strategy requires the cipher nonce to have
+    /// at most 32 bytes.
+    Derived,
+}
+
+// `#[default]` enum variants are not supported by the minimum supported Rust version.
+#[allow(clippy::derivable_impls)]
+impl Default for NonceStrategy {
+    fn default() -> Self {
+        NonceStrategy::Random
+    }
+}
+
+/// Policy of transforming passwords before they are supplied to the KDF.
+///
+/// The same visual password may be encoded in different ways depending on the OS or
+/// the input method; e.g., `é` may be a single code point (NFC), or `e` followed by
+/// a combining acute accent (NFD). With the [`Raw`](Self::Raw) policy, such passwords
+/// produce different keys. The policy is set with [`PwBoxBuilder::password_policy()`]
+/// and is recorded in the box, so that the same transform is applied when opening it.
+///
+/// # Examples
+///
+/// ```
+/// # use rand::thread_rng;
+/// # use pwbox::{sodium::{Scrypt, Sodium}, PasswordPolicy, Suite};
+/// let mut rng = thread_rng();
+/// let mut builder = Sodium::build_box(&mut rng);
+/// builder.password_policy(PasswordPolicy::Nfkd);
+/// # builder.kdf(Scrypt::light());
+/// let pwbox = builder.seal("caf\u{e9}", b"battery staple")?;
+/// // The box can be opened with the decomposed form of the password.
+/// assert_eq!(&*pwbox.open("cafe\u{301}")?, b"battery staple");
+/// # Ok::<_, anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PasswordPolicy {
+    /// Password bytes are supplied to the KDF as is. This is the default policy.
+    Raw,
+    /// Password is normalized according to the Unicode NFKD form. Passwords that are
+    /// not valid UTF-8 are supplied to the KDF as is.
+    Nfkd,
+}
+
+// `#[default]` enum variants are not supported by the minimum supported Rust version.
+#[allow(clippy::derivable_impls)]
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy::Raw
+    }
+}
+
+impl PasswordPolicy {
+    #[allow(clippy::trivially_copy_pass_by_ref)] // required by `serde`
+    fn is_raw(&self) -> bool {
+        *self == PasswordPolicy::Raw
+    }
+
+    /// Transforms the password according to this policy. The transformed password
+    /// is stored in a buffer zeroed on drop.
+    fn apply(self, password: &[u8]) -> SecretVec {
+        if let (PasswordPolicy::Nfkd, Ok(normalized)) = (self, str::from_utf8(password)) {
+            // Compute the length in advance, so that the buffer is not reallocated
+            // (which would leave copies of the password in memory).
+            let len = normalized.nfkd().map(char::len_utf8).sum();
+            let mut buffer = SecretVec::zeros(len);
+            let mut pos = 0;
+            for ch in normalized.nfkd() {
+                pos += ch.encode_utf8(&mut buffer[pos..]).len();
+            }
+            buffer
+        } else {
+            SecretVec::from(password.to_vec())
+        }
+    }
+}
+
+/// Progress of sealing or opening a box reported to callbacks, e.g., in order to display
+/// a progress bar while a KDF with high difficulty params is running.
+///
+/// Progress events never contain secret data (passwords, keys or plaintexts).
+/// See [`PwBoxBuilder::on_progress()`] and [`RestoredPwBox::open_with_progress()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    /// Key derivation has started. KDFs do not report granular progress, so the next event
+    /// is emitted after the key is derived.
+    DerivingKey,
+    /// Key derivation has finished.
+    KeyDerived,
+    /// Data is being encrypted. Boxes are sealed with a single cipher invocation,
+    /// so the event is emitted before and after encryption; streams report progress
+    /// after each chunk.
+    Encrypting {
+        /// Number of encrypted bytes.
+        bytes_done: u64,
+        /// Total number of bytes to encrypt, or `None` if it is not known in advance
+        /// (e.g., for streams, the total is only reported with the last chunk).
+        bytes_total: Option<u64>,
+    },
+    /// The box is being verified and decrypted.
+    Verifying,
+}
+
+/// Builder for `PwBox`es.
+///
+/// The builder may be used to seal multiple boxes; it retains its settings
+/// and the state of the [`NonceStrategy`] between `seal*` calls.
+pub struct PwBoxBuilder<'a, K, C> {
+    kdf: Option<K>,
+    associated_data: Vec<u8>,
+    chunk_size: Option<u32>,
+    salt: Option<Vec<u8>>,
+    nonce: Option<Vec<u8>>,
+    nonce_strategy: NonceStrategy,
+    /// Random nonce prefix for `NonceStrategy::Counter`, generated on first use.
+    nonce_prefix: Option<Vec<u8>>,
+    /// Index of the next nonce for non-random nonce strategies.
+    nonce_index: u64,
+    /// Context for deterministic sealing; `None` if the deterministic mode is off.
+    deterministic_context: Option<Vec<u8>>,
+    password_policy: PasswordPolicy,
+    progress: Option<Box<dyn FnMut(Progress)>>,
+    rng: &'a mut dyn RngCore,
+    _cipher: PhantomData<C>,
+}
+
+impl<'a, K, C> fmt::Debug for PwBoxBuilder<'a, K, C> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("PwBoxBuilder")
+            .field("custom_kdf", &self.kdf.is_some())
+            .field("associated_data", &self.associated_data)
+            .field("custom_salt", &self.salt.is_some())
+            .field("custom_nonce", &self.nonce.is_some())
+            .field("nonce_strategy", &self.nonce_strategy)
+            .field("deterministic", &self.deterministic_context.is_some())
+            .field("password_policy", &self.password_policy)
+            .field("progress_callback", &self.progress.is_some())
+            .finish()
+    }
+}
+
+impl<'a, K, C> PwBoxBuilder<'a, K, C>
+where
+    K: DeriveKey + Clone + Default,
+    C: Cipher,
+{
+    /// Initializes the builder with a random number generator.
+    pub fn new<R: RngCore + CryptoRng>(rng: &'a mut R) -> Self {
+        PwBoxBuilder {
+            kdf: None,
+            associated_data: Vec::new(),
+            chunk_size: None,
+            salt: None,
+            nonce: None,
+            nonce_strategy: NonceStrategy::Random,
+            nonce_prefix: None,
+            nonce_index: 0,
+            deterministic_context: None,
+            password_policy: PasswordPolicy::Raw,
+            progress: None,
+            rng,
             _cipher: PhantomData,
         }
     }
@@ -422,16 +1745,600 @@ where
         self
     }
 
+    /// Switches the cipher used by the builder, retaining other settings. This allows to use
+    /// a non-default cipher with [`Suite::build_box()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rand::thread_rng;
+    /// # use pwbox::{sodium::{ChaCha20Poly1305Ietf, Scrypt, Sodium}, Eraser, Suite};
+    /// let pwbox = Sodium::build_box(&mut thread_rng())
+    ///     .cipher::<ChaCha20Poly1305Ietf>()
+    /// #   .kdf(Scrypt::light())
+    ///     .seal("correct horse", b"battery staple")
+    ///     .unwrap();
+    ///
+    /// // The box can be erased and restored as usual.
+    /// let mut eraser = Eraser::new();
+    /// eraser.add_suite::<Sodium>().unwrap();
+    /// let erased = eraser.erase(&pwbox).unwrap();
+    /// let restored = eraser.restore(&erased).unwrap();
+    /// assert_eq!(&*restored.open("correct horse").unwrap(), b"battery staple");
+    /// ```
+    pub fn cipher<C2: Cipher>(self) -> PwBoxBuilder<'a, K, C2> {
+        PwBoxBuilder {
+            kdf: self.kdf,
+            associated_data: self.associated_data,
+            chunk_size: self.chunk_size,
+            salt: self.salt,
+            nonce: self.nonce,
+            nonce_strategy: self.nonce_strategy,
+            // The prefix length depends on the cipher nonce length.
+            nonce_prefix: None,
+            nonce_index: self.nonce_index,
+            deterministic_context: self.deterministic_context,
+            password_policy: self.password_policy,
+            progress: self.progress,
+            rng: self.rng,
+            _cipher: PhantomData,
+        }
+    }
+
+    /// Sets associated data for the box. The data is authenticated, but is not stored
+    /// in the box; the same data needs to be supplied to [`PwBox::open_with_aad()`]
+    /// in order to open the box.
+    ///
+    /// Not all ciphers support associated data; sealing will fail if a cipher
+    /// does not support it.
+    pub fn associated_data(&mut self, data: impl AsRef<[u8]>) -> &mut Self {
+        self.associated_data = data.as_ref().to_vec();
+        self
+    }
+
+    /// Sets the strategy of generating nonces. See [`NonceStrategy`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rand::thread_rng;
+    /// # use pwbox::{sodium::{Scrypt, Sodium}, NonceStrategy, Suite};
+    /// let mut rng = thread_rng();
+    /// let mut builder = Sodium::build_box(&mut rng);
+    /// builder.nonce_strategy(NonceStrategy::Counter);
+    /// # builder.kdf(Scrypt::light());
+    /// let boxes: Vec<_> = (0..3)
+    ///     .map(|i| builder.seal("correct horse", [i; 32]).unwrap())
+    ///     .collect();
+    /// assert_eq!(&*boxes[2].open("correct horse").unwrap(), &[2; 32]);
+    /// ```
+    pub fn nonce_strategy(&mut self, strategy: NonceStrategy) -> &mut Self {
+        self.nonce_strategy = strategy;
+        self
+    }
+
+    /// Enables deterministic (aka convergent) sealing: the salt and nonce of each box
+    /// are derived from the password, the sealed data and the `context` rather than
+    /// generated by the RNG. Thus, sealing the same data with the same password, context,
+    /// KDF and associated data always produces the same box, which allows to deduplicate
+    /// encrypted data (e.g., in backups). Boxes sealed in this mode are erased, restored
+    /// and opened as usual.
+    ///
+    /// The salt and nonce are obtained by expanding
+    /// `HKDF-SHA-256(ikm = password, salt = digest, info = context)`, where `digest`
+    /// is SHA-256 of the associated data and the sealed data (each prefixed
+    /// with its length). The mode overrides the [`NonceStrategy`]. It is not supported
+    /// by [`Self::seal_from_reader()`] and for boxes sharing the salt (see
+    /// [`PwBoxCollection::seal()`]).
+    ///
+    /// # Security
+    ///
+    /// **This mode trades semantic security for convergence.** Use it only if
+    /// deduplication is required, and be aware of the following:
+    ///
+    /// - Anyone can tell whether two boxes sealed with the same password and context
+    ///   contain the same data.
+    /// - The salt is derived from the password without the KDF. An attacker who knows
+    ///   or can guess the sealed data may check password guesses against the salt without
+    ///   paying the KDF cost. Thus, the password should have high entropy (e.g., be
+    ///   a randomly generated key).
+    ///
+    /// Different `context`s produce unrelated boxes for the same inputs, so the context
+    /// should be specific to the application (and, e.g., to the backup set).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rand::thread_rng;
+    /// # use pwbox::{sodium::{Scrypt, Sodium}, Suite};
+    /// let mut rng = thread_rng();
+    /// let mut builder = Sodium::build_box(&mut rng);
+    /// builder.deterministic(b"backup-v1");
+    /// # builder.kdf(Scrypt::light());
+    /// let pwbox = builder.seal("correct horse", b"battery staple")?;
+    /// let other_box = builder.seal("correct horse", b"battery staple")?;
+    /// assert_eq!(
+    ///     serde_json::to_string(&pwbox)?,
+    ///     serde_json::to_string(&other_box)?
+    /// );
+    /// # Ok::<_, anyhow::Error>(())
+    /// ```
+    pub fn deterministic(&mut self, context: impl AsRef<[u8]>) -> &mut Self {
+        self.deterministic_context = Some(context.as_ref().to_vec());
+        self
+    }
+
+    /// Sets the policy of transforming passwords before key derivation. The policy
+    /// is recorded in sealed boxes and is applied to passwords when opening them.
+    /// See [`PasswordPolicy`] for details.
+    ///
+    /// The policy is not supported by [`Self::seal_from_reader()`], and is ignored
+    /// by [`Self::seal_with_key()`].
+    pub fn password_policy(&mut self, policy: PasswordPolicy) -> &mut Self {
+        self.password_policy = policy;
+        self
+    }
+
+    /// Sets a callback invoked to report progress of sealing boxes. The callback receives
+    /// [`Progress::DerivingKey`] and [`Progress::KeyDerived`] events around key derivation,
+    /// followed by [`Progress::Encrypting`] events. Streams sealed with
+    /// [`Self::seal_from_reader()`] report encryption progress after each chunk.
+    ///
+    /// The callback must be `'static`, so that it does not borrow data for the lifetime
+    /// of the builder. To collect events into a local variable, share it with the callback
+    /// via `Rc<RefCell<_>>`, or send events over a channel.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rand::thread_rng;
+    /// # use pwbox::{sodium::{Scrypt, Sodium}, Progress, Suite};
+    /// let mut rng = thread_rng();
+    /// let mut builder = Sodium::build_box(&mut rng);
+    /// # builder.kdf(Scrypt::light());
+    /// builder.on_progress(|progress| {
+    ///     if progress == Progress::DerivingKey {
+    ///         println!("Deriving key...");
+    ///     }
+    /// });
+    /// let pwbox = builder.seal("correct horse", b"battery staple")?;
+    /// # Ok::<_, anyhow::Error>(())
+    /// ```
+    pub fn on_progress(&mut self, callback: impl FnMut(Progress) + 'static) -> &mut Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    fn report(&mut self, progress: Progress) {
+        if let Some(callback) = &mut self.progress {
+            callback(progress);
+        }
+    }
+
+    /// Sets the byte size of plaintext chunks for [`Self::seal_from_reader()`].
+    /// If not set, [`StreamPwBox::DEFAULT_CHUNK_SIZE`] is used.
+    pub fn chunk_size(&mut self, chunk_size: u32) -> &mut Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    /// Sets the KDF salt instead of generating it randomly. The salt must have a size
+    /// accepted by the KDF; otherwise, sealing will fail with [`Error::SaltLen`].
+    ///
+    /// This method is intended to reproduce test vectors and is only available with
+    /// the `testing` crate feature.
+    #[cfg(any(test, feature = "testing"))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
+    pub fn salt(&mut self, salt: impl AsRef<[u8]>) -> &mut Self {
+        self.salt = Some(salt.as_ref().to_vec());
+        self
+    }
+
+    /// Sets the cipher nonce instead of generating it randomly. The nonce must have
+    /// [`Cipher::NONCE_LEN`] bytes; otherwise, sealing will fail with [`Error::NonceLen`].
+    ///
+    /// This method is intended to reproduce test vectors and is only available with
+    /// the `testing` crate feature. **Never** reuse the nonce with the same key
+    /// for real data; this compromises the cipher security.
+    #[cfg(any(test, feature = "testing"))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
+    pub fn nonce(&mut self, nonce: impl AsRef<[u8]>) -> &mut Self {
+        self.nonce = Some(nonce.as_ref().to_vec());
+        self
+    }
+
     /// Creates a new `PwBox` with the specified password and contents.
     pub fn seal(
         &mut self,
         password: impl AsRef<[u8]>,
         data: impl AsRef<[u8]>,
-    ) -> anyhow::Result<PwBox<K, C>> {
+    ) -> Result<PwBox<K, C>, Error> {
+        let kdf = self.kdf.clone().unwrap_or_default();
+        let policy = self.password_policy;
+        self.seal_with_kdf(kdf, policy, password.as_ref(), data.as_ref(), Vec::new())
+    }
+
+    /// Seals a box with the specified KDF, password policy and the `metadata` bound
+    /// to the box.
+    fn seal_with_kdf<K2: DeriveKey>(
+        &mut self,
+        kdf: K2,
+        password_policy: PasswordPolicy,
+        password: &[u8],
+        data: &[u8],
+        metadata: Vec<u8>,
+    ) -> Result<PwBox<K2, C>, Error> {
+        self.seal_with_kdfs((kdf, None), password_policy, password, data, metadata)
+    }
+
+    /// Seals a box with the specified KDF stored in the box. If `external_kdf` is specified,
+    /// it is used to derive the key instead of the stored KDF.
+    fn seal_with_kdfs<K2: DeriveKey>(
+        &mut self,
+        (kdf, external_kdf): (K2, Option<&mut dyn DeriveKeyMut>),
+        password_policy: PasswordPolicy,
+        password: &[u8],
+        data: &[u8],
+        metadata: Vec<u8>,
+    ) -> Result<PwBox<K2, C>, Error> {
+        let mut kdf_ref;
+        let deriving_kdf: &mut dyn DeriveKeyMut = if let Some(external_kdf) = external_kdf {
+            external_kdf
+        } else {
+            kdf_ref = KdfRef(&kdf);
+            &mut kdf_ref
+        };
+
+        let password = &*password_policy.apply(password);
+        let cipher = CipherObject::<C>::default();
+        let (salt, nonce) = if let Some(context) = &self.deterministic_context {
+            let associated_data = (self.associated_data.as_slice(), metadata.as_slice());
+            let lengths = (deriving_kdf.salt_len(), C::NONCE_LEN);
+            derive_salt_and_nonce(password, context, associated_data, data, lengths)?
+        } else {
+            let salt = self.generate_salt(deriving_kdf)?;
+            let nonce = self.generate_nonce(&salt)?;
+            (salt, nonce)
+        };
+        check_aad_support(&cipher, &self.associated_data, &metadata)?;
+        enter_span!("seal", cipher = %cipher.name(), len = data.len());
+
+        let mut key = SensitiveData::zeros(C::KEY_LEN);
+        self.report(Progress::DerivingKey);
+        trace_kdf!(
+            salt_len = salt.len(),
+            deriving_kdf.derive_key(key.bytes_mut(), password, &salt)
+        )
+        .map_err(Error::DeriveKey)?;
+        self.report(Progress::KeyDerived);
+
+        let bytes_total = data.len() as u64;
+        self.report(Progress::Encrypting {
+            bytes_done: 0,
+            bytes_total: Some(bytes_total),
+        });
+        let aad = (self.associated_data.as_slice(), metadata);
+        let mut inner = PwBoxInner::seal_with_key(kdf, cipher, salt, nonce, &key, data, aad);
+        inner.password_policy = password_policy;
+        self.report(Progress::Encrypting {
+            bytes_done: bytes_total,
+            bytes_total: Some(bytes_total),
+        });
+        Ok(PwBox { inner })
+    }
+
+    /// Seals several boxes with the specified KDF and `metadata`. The boxes share the salt,
+    /// so the key is derived only once; each box has a separate random nonce.
+    fn seal_shared_with_kdf<K2: DeriveKey + Clone>(
+        &mut self,
+        kdf: &K2,
+        password: &[u8],
+        messages: &[&[u8]],
+        metadata: &[u8],
+    ) -> Result<Vec<PwBox<K2, C>>, Error> {
+        if self.nonce.is_some() && messages.len() > 1 {
+            let reason = "fixed nonce cannot be used for several boxes sealed with the same key";
+            return Err(Error::SealOptions(reason.into()));
+        }
+        if self.deterministic_context.is_some() {
+            let reason = "deterministic sealing is not supported for boxes with shared salt";
+            return Err(Error::SealOptions(reason.into()));
+        }
+        let cipher = CipherObject::<C>::default();
+        check_aad_support(&cipher, &self.associated_data, metadata)?;
+        enter_span!("seal_shared", cipher = %cipher.name(), count = messages.len());
+        let salt = self.generate_salt(&KdfRef(kdf))?;
+        let password = self.password_policy.apply(password);
+        let mut key = SensitiveData::zeros(C::KEY_LEN);
+        self.report(Progress::DerivingKey);
+        trace_kdf!(
+            salt_len = salt.len(),
+            kdf.derive_key(key.bytes_mut(), &password, &salt)
+        )
+        .map_err(Error::DeriveKey)?;
+        self.report(Progress::KeyDerived);
+
+        let bytes_total = messages.iter().map(|message| message.len() as u64).sum();
+        let mut bytes_done = 0;
+        let mut boxes = Vec::with_capacity(messages.len());
+        for &message in messages {
+            let nonce = self.generate_nonce(&salt)?;
+            let aad = (self.associated_data.as_slice(), metadata.to_vec());
+            let (kdf, cipher, salt) = (kdf.clone(), cipher.clone(), salt.clone());
+            let mut inner = PwBoxInner::seal_with_key(kdf, cipher, salt, nonce, &key, message, aad);
+            inner.password_policy = self.password_policy;
+            boxes.push(PwBox { inner });
+
+            bytes_done += message.len() as u64;
+            self.report(Progress::Encrypting {
+                bytes_done,
+                bytes_total: Some(bytes_total),
+            });
+        }
+        Ok(boxes)
+    }
+
+    fn generate_salt(&mut self, kdf: &dyn DeriveKeyMut) -> Result<Vec<u8>, Error> {
+        Ok(if let Some(salt) = &self.salt {
+            if !kdf.valid_salt_len(salt.len()) {
+                return Err(Error::SaltLen);
+            }
+            salt.clone()
+        } else {
+            let mut salt = vec![0_u8; kdf.salt_len()];
+            self.rng.fill_bytes(&mut salt);
+            salt
+        })
+    }
+
+    /// Generates a nonce for a box with the specified `salt` according to the nonce strategy.
+    fn generate_nonce(&mut self, salt: &[u8]) -> Result<Vec<u8>, Error> {
+        if let Some(nonce) = &self.nonce {
+            if nonce.len() != C::NONCE_LEN {
+                return Err(Error::NonceLen);
+            }
+            return Ok(nonce.clone());
+        }
+
+        let mut nonce = vec![0_u8; C::NONCE_LEN];
+        if self.nonce_strategy == NonceStrategy::Random {
+            self.rng.fill_bytes(&mut nonce);
+            return Ok(nonce);
+        }
+
+        let counter_len = mem::size_of::<u64>();
+        if C::NONCE_LEN < counter_len {
+            let reason = "cipher nonce is too short for the nonce strategy";
+            return Err(Error::SealOptions(reason.into()));
+        }
+        if self.nonce_strategy == NonceStrategy::Derived && C::NONCE_LEN > Sha256::output_size() {
+            let reason = "cipher nonce is too long for the nonce strategy";
+            return Err(Error::SealOptions(reason.into()));
+        }
+        let index = self.nonce_index;
+        self.nonce_index = index
+            .checked_add(1)
+            .ok_or_else(|| Error::SealOptions("nonce counter is exhausted".into()))?;
+
+        let prefix_len = C::NONCE_LEN - counter_len;
+        if self.nonce_strategy == NonceStrategy::Counter {
+            let rng = &mut self.rng;
+            let prefix = self.nonce_prefix.get_or_insert_with(|| {
+                let mut prefix = vec![0_u8; prefix_len];
+                rng.fill_bytes(&mut prefix);
+                prefix
+            });
+            nonce[..prefix_len].copy_from_slice(prefix);
+            nonce[prefix_len..].copy_from_slice(&index.to_be_bytes());
+        } else {
+            let mut hasher = Sha256::new();
+            hasher.update(salt);
+            hasher.update(index.to_le_bytes());
+            nonce.copy_from_slice(&hasher.finalize()[..C::NONCE_LEN]);
+        }
+        Ok(nonce)
+    }
+
+    /// Creates a new `PwBox` with the specified key and contents, bypassing key derivation.
+    /// The key must have the same byte size as the cipher key (i.e., [`Cipher::KEY_LEN`]);
+    /// otherwise, an error is returned. The KDF and the password policy set up in the builder
+    /// are ignored.
+    ///
+    /// The key is used as the cipher key directly, so it should be generated randomly
+    /// rather than be a human-readable password. See [`NoKdf`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rand::{thread_rng, RngCore};
+    /// # use pwbox::{sodium::Sodium, Eraser, Suite};
+    /// let mut rng = thread_rng();
+    /// let mut key = [0_u8; 32];
+    /// rng.fill_bytes(&mut key);
+    /// let pwbox = Sodium::build_box(&mut rng)
+    ///     .seal_with_key(&key, b"battery staple")
+    ///     .unwrap();
+    ///
+    /// let mut eraser = Eraser::new();
+    /// eraser.add_suite::<Sodium>().unwrap();
+    /// let erased = eraser.erase(&pwbox).unwrap();
+    /// assert_eq!(erased.kdf_name(), "none");
+    /// let restored = eraser.restore(&erased).unwrap();
+    /// assert_eq!(&*restored.open(&key).unwrap(), b"battery staple");
+    /// ```
+    pub fn seal_with_key(
+        &mut self,
+        key: &[u8],
+        data: impl AsRef<[u8]>,
+    ) -> Result<PwBox<NoKdf, C>, Error> {
+        self.seal_with_kdf(
+            NoKdf {},
+            PasswordPolicy::Raw,
+            key,
+            data.as_ref(),
+            Vec::new(),
+        )
+    }
+
+    /// Creates a new `PwBox` with the key derived by the `external_kdf`, such as
+    /// a hardware-backed KDF. Instead of the KDF, the box stores the `placeholder`
+    /// with opaque params, and should be opened with [`PwBox::open_with_kdf()`].
+    /// The KDF set up in the builder is ignored.
+    ///
+    /// See [`ExternalKdf`] for an example of usage.
+    pub fn seal_with_external_kdf(
+        &mut self,
+        external_kdf: &mut impl DeriveKeyMut,
+        placeholder: ExternalKdf,
+        password: impl AsRef<[u8]>,
+        data: impl AsRef<[u8]>,
+    ) -> Result<PwBox<ExternalKdf, C>, Error> {
+        let kdfs = (placeholder, Some(external_kdf as &mut dyn DeriveKeyMut));
+        let policy = self.password_policy;
+        self.seal_with_kdfs(kdfs, policy, password.as_ref(), data.as_ref(), Vec::new())
+    }
+
+    /// Encrypts data from `reader` in chunks, writing the encrypted stream to `writer`.
+    /// The returned box contains the information necessary to decrypt the stream,
+    /// but not the stream itself.
+    ///
+    /// The cipher must have nonces of at least 9 bytes. Associated data is not supported.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn seal_from_reader(
+        &mut self,
+        password: impl AsRef<[u8]>,
+        reader: impl std::io::Read,
+        writer: impl std::io::Write,
+    ) -> Result<StreamPwBox<K, C>, Error> {
+        self.check_stream_support()?;
         let cipher = CipherObject::<C>::default();
         let kdf = self.kdf.clone().unwrap_or_default();
-        PwBoxInner::seal(kdf, cipher, self.rng, password, data).map(|inner| PwBox { inner })
+        let chunk_size = self
+            .chunk_size
+            .unwrap_or(StreamPwBox::<K, C>::DEFAULT_CHUNK_SIZE);
+        let password = password.as_ref();
+        let callback = &mut self.progress;
+        let mut progress = |event| {
+            if let Some(callback) = callback {
+                callback(event);
+            }
+        };
+        stream::StreamInner::seal(
+            kdf,
+            cipher,
+            self.rng,
+            chunk_size,
+            password,
+            (reader, writer),
+            &mut progress,
+        )
+        .map(|inner| StreamPwBox { inner })
+    }
+
+    /// Derives the key from the password and returns a writer, which encrypts the data
+    /// written to it in chunks and writes the encrypted stream to `writer`. Unlike
+    /// [`Self::seal_from_reader()`], this allows to encrypt data produced incrementally
+    /// (e.g., by a serializer). The stream is compatible with `seal_from_reader()`.
+    ///
+    /// The stream must be completed with [`PwBoxWriter::finish()`], which returns the box
+    /// necessary to decrypt the stream. The progress callback (if any) only receives
+    /// key derivation events.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rand::thread_rng;
+    /// # use pwbox::{sodium::{Scrypt, Sodium}, Suite};
+    /// use std::io::{Read, Write};
+    ///
+    /// let mut writer = Sodium::build_box(&mut thread_rng())
+    /// #   .kdf(Scrypt::light())
+    ///     .chunk_size(1_024)
+    ///     .seal_to_writer("correct horse", vec![])?;
+    /// for i in 0..100 {
+    ///     write!(writer, "line #{}\n", i)?;
+    /// }
+    /// let (pwbox, encrypted) = writer.finish()?;
+    ///
+    /// let mut decrypted = String::new();
+    /// pwbox
+    ///     .open_reader("correct horse", &*encrypted)?
+    ///     .read_to_string(&mut decrypted)?;
+    /// assert_eq!(decrypted.lines().count(), 100);
+    /// # Ok::<_, anyhow::Error>(())
+    /// ```
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn seal_to_writer<W: std::io::Write>(
+        &mut self,
+        password: impl AsRef<[u8]>,
+        writer: W,
+    ) -> Result<PwBoxWriter<K, C, W>, Error> {
+        self.check_stream_support()?;
+        let kdf = self.kdf.clone().unwrap_or_default();
+        let chunk_size = self
+            .chunk_size
+            .unwrap_or(StreamPwBox::<K, C>::DEFAULT_CHUNK_SIZE);
+        let callback = &mut self.progress;
+        let mut progress = |event| {
+            if let Some(callback) = callback {
+                callback(event);
+            }
+        };
+        stream::StreamInner::seal_to_writer(
+            kdf,
+            self.rng,
+            chunk_size,
+            password.as_ref(),
+            writer,
+            &mut progress,
+        )
     }
+
+    /// Checks that the builder params are supported for streaming encryption.
+    #[cfg(feature = "std")]
+    fn check_stream_support(&self) -> Result<(), Error> {
+        let reason = if !self.associated_data.is_empty() {
+            "associated data is not supported for streams"
+        } else if self.salt.is_some() || self.nonce.is_some() {
+            "custom salt and nonce are not supported for streams"
+        } else if self.deterministic_context.is_some() {
+            "deterministic sealing is not supported for streams"
+        } else if !self.password_policy.is_raw() {
+            "password policies are not supported for streams"
+        } else {
+            return Ok(());
+        };
+        Err(Error::SealOptions(reason.into()))
+    }
+}
+
+/// Derives the salt and nonce for deterministic sealing (see [`PwBoxBuilder::deterministic()`]).
+/// `associated_data` consists of the associated data and the metadata bound to the box.
+fn derive_salt_and_nonce(
+    password: &[u8],
+    context: &[u8],
+    (associated_data, metadata): (&[u8], &[u8]),
+    data: &[u8],
+    (salt_len, nonce_len): (usize, usize),
+) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let mut hasher = Sha256::new();
+    for &part in &[associated_data, metadata, data] {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    let digest = hasher.finalize();
+
+    let mut salt = vec![0_u8; salt_len + nonce_len];
+    Hkdf::<Sha256>::new(Some(&digest), password)
+        .expand(context, &mut salt)
+        .map_err(|_| {
+            let reason = "salt and nonce are too long for deterministic sealing";
+            Error::SealOptions(reason.into())
+        })?;
+    let nonce = salt.split_off(salt_len);
+    Ok((salt, nonce))
 }
 
 // This function is used in testing cryptographic backends, so it's intentionally kept public.
@@ -463,4 +2370,1048 @@ where
     // the stopgap threshold 32), we need to index it explicitly.
     pwbox.open_into(&mut buffer[..], PASSWORD).unwrap();
     assert_eq!(buffer[..], *message);
+
+    assert!(pwbox.check_password(PASSWORD).unwrap());
+    assert!(!pwbox.check_password("Tr0ub4dor&3").unwrap());
+    pwbox.verify(PASSWORD).unwrap();
+    assert_matches::assert_matches!(pwbox.verify("Tr0ub4dor&3").unwrap_err(), Error::MacMismatch);
+
+    // `seal_into()` must produce the same output as `seal()`.
+    let (mut key, mut nonce) = (vec![0_u8; C::KEY_LEN], vec![0_u8; C::NONCE_LEN]);
+    rng.fill_bytes(&mut key);
+    rng.fill_bytes(&mut nonce);
+    let encrypted = C::seal(&message, &[], &nonce, &key);
+    let mut buffers = CipherOutput {
+        ciphertext: vec![0; message.len()],
+        mac: vec![0; C::MAC_LEN],
+    };
+    C::seal_into(&message, &[], &nonce, &key, &mut buffers.as_mut_ref());
+    assert_eq!(buffers, encrypted);
+
+    // `open_in_place()` must be consistent with `open()`.
+    let mut buffer = encrypted.ciphertext.clone();
+    C::open_in_place(&mut buffer, &encrypted.mac, &[], &nonce, &key).unwrap();
+    assert_eq!(buffer, message);
+    let mut buffer = encrypted.ciphertext.clone();
+    buffer[0] ^= 1;
+    assert!(C::open_in_place(&mut buffer, &encrypted.mac, &[], &nonce, &key).is_err());
+    assert_eq!(message, &*pwbox.open_in_place(PASSWORD).unwrap());
+}
+
+#[cfg(all(test, feature = "exonum_sodiumoxide"))]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::thread_rng;
+
+    use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+    use super::*;
+    use crate::sodium::{Scrypt, Sodium, XChaCha20Poly1305 as XChaCha};
+
+    const OLD_PASSWORD: &str = "correct horse battery staple";
+    const NEW_PASSWORD: &str = "Tr0ub4dor&3";
+    const MESSAGE: &[u8] = b"1234567890";
+
+    /// Cipher relying on the default `seal_into()` and `open_in_place()` implementations.
+    struct SealOnly;
+
+    impl Cipher for SealOnly {
+        const KEY_LEN: usize = XChaCha::KEY_LEN;
+        const NONCE_LEN: usize = XChaCha::NONCE_LEN;
+        const MAC_LEN: usize = XChaCha::MAC_LEN;
+
+        fn seal(message: &[u8], aad: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
+            XChaCha::seal(message, aad, nonce, key)
+        }
+
+        fn open(
+            output: &mut [u8],
+            enc: &CipherOutput,
+            aad: &[u8],
+            nonce: &[u8],
+            key: &[u8],
+        ) -> Result<(), MacMismatch> {
+            XChaCha::open(output, enc, aad, nonce, key)
+        }
+    }
+
+    #[test]
+    fn public_types_are_thread_safe() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<PwBox<Scrypt, XChaCha>>();
+        assert_send_sync::<RestoredPwBox>();
+        assert_send_sync::<SensitiveData>();
+        assert_send_sync::<ErasedPwBox>();
+        assert_send_sync::<Eraser>();
+        assert_send_sync::<StreamPwBox<Scrypt, XChaCha>>();
+        assert_send_sync::<RestoredStreamPwBox>();
+        assert_send_sync::<PwBoxCollection>();
+        assert_send_sync::<cache::KeyCache>();
+        assert_send_sync::<KeyDerivationJob>();
+        assert_send_sync::<Error>();
+    }
+
+    #[test]
+    fn restored_box_can_be_shared_among_threads() {
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let pwbox = Sodium::build_box(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .seal(OLD_PASSWORD, MESSAGE)
+            .unwrap();
+        let restored = Arc::new(eraser.restore(&eraser.erase(&pwbox).unwrap()).unwrap());
+
+        let handles: Vec<_> = [OLD_PASSWORD, NEW_PASSWORD]
+            .iter()
+            .map(|&password| {
+                let restored = Arc::clone(&restored);
+                std::thread::spawn(move || {
+                    let mut output = vec![0_u8; restored.len()];
+                    let len = restored.open_into(&mut output, password)?;
+                    output.truncate(len);
+                    Ok::<_, Error>(output)
+                })
+            })
+            .collect();
+        let results: Vec<_> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+        assert_eq!(results[0].as_ref().unwrap(), MESSAGE);
+        assert_matches!(results[1], Err(Error::MacMismatch));
+    }
+
+    #[test]
+    fn default_seal_into() {
+        test_kdf_and_cipher::<_, SealOnly>(Scrypt::light());
+    }
+
+    /// RNG stub always returning zeros.
+    struct ConstantRng;
+
+    impl RngCore for ConstantRng {
+        fn next_u32(&mut self) -> u32 {
+            0
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            0
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for byte in dest {
+                *byte = 0;
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    impl CryptoRng for ConstantRng {}
+
+    fn seal_with_constant_rng(strategy: NonceStrategy, count: u32) -> Vec<PwBox<NoKdf, XChaCha>> {
+        const KEY: &[u8] = &[5; 32];
+
+        let mut rng = ConstantRng;
+        let mut builder = PwBoxBuilder::<Scrypt, XChaCha>::new(&mut rng);
+        builder.nonce_strategy(strategy);
+        (0..count)
+            .map(|i| builder.seal_with_key(KEY, i.to_le_bytes()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn counter_nonce_strategy() {
+        let boxes = seal_with_constant_rng(NonceStrategy::Counter, 1_000);
+        let nonces: HashSet<_> = boxes.iter().map(|pwbox| &pwbox.inner.nonce).collect();
+        assert_eq!(nonces.len(), boxes.len());
+        for (i, pwbox) in (0_u32..).zip(&boxes) {
+            assert_eq!(&*pwbox.open([5; 32]).unwrap(), i.to_le_bytes());
+            // The prefix is generated by the constant RNG.
+            let mut expected_nonce = [0_u8; 24];
+            expected_nonce[16..].copy_from_slice(&u64::from(i).to_be_bytes());
+            assert_eq!(pwbox.inner.nonce, expected_nonce);
+        }
+
+        // With the random strategy, all nonces are the same.
+        let boxes = seal_with_constant_rng(NonceStrategy::Random, 10);
+        let nonces: HashSet<_> = boxes.iter().map(|pwbox| &pwbox.inner.nonce).collect();
+        assert_eq!(nonces.len(), 1);
+    }
+
+    #[test]
+    fn derived_nonce_strategy() {
+        let boxes = seal_with_constant_rng(NonceStrategy::Derived, 1_000);
+        let nonces: HashSet<_> = boxes.iter().map(|pwbox| &pwbox.inner.nonce).collect();
+        assert_eq!(nonces.len(), boxes.len());
+        for (i, pwbox) in (0_u64..).zip(&boxes) {
+            let mut hasher = Sha256::new();
+            hasher.update(&pwbox.inner.salt);
+            hasher.update(i.to_le_bytes());
+            assert_eq!(pwbox.inner.nonce, hasher.finalize()[..24]);
+        }
+
+        // Salts are identical since they are generated by the constant RNG.
+        let mut rng = ConstantRng;
+        let mut builder = Sodium::build_box(&mut rng);
+        builder
+            .kdf(Scrypt::light())
+            .nonce_strategy(NonceStrategy::Derived);
+        let boxes: Vec<_> = (0..3)
+            .map(|i| builder.seal(OLD_PASSWORD, [i; 16]).unwrap())
+            .collect();
+        assert_eq!(boxes[0].inner.salt, boxes[1].inner.salt);
+        let nonces: HashSet<_> = boxes.iter().map(|pwbox| &pwbox.inner.nonce).collect();
+        assert_eq!(nonces.len(), 3);
+        for (i, pwbox) in (0..).zip(&boxes) {
+            assert_eq!(&*pwbox.open(OLD_PASSWORD).unwrap(), [i; 16]);
+        }
+    }
+
+    /// Cipher with a nonce too short for non-random nonce strategies.
+    #[derive(Debug)]
+    struct ShortNonce;
+
+    impl ShortNonce {
+        fn extend_nonce(nonce: &[u8]) -> [u8; 24] {
+            let mut extended_nonce = [0; 24];
+            extended_nonce[..4].copy_from_slice(nonce);
+            extended_nonce
+        }
+    }
+
+    impl Cipher for ShortNonce {
+        const KEY_LEN: usize = 32;
+        const NONCE_LEN: usize = 4;
+        const MAC_LEN: usize = 16;
+
+        fn seal(message: &[u8], aad: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
+            let nonce = Self::extend_nonce(nonce);
+            XChaCha::seal(message, aad, &nonce, key)
+        }
+
+        fn open(
+            output: &mut [u8],
+            enc: &CipherOutput,
+            aad: &[u8],
+            nonce: &[u8],
+            key: &[u8],
+        ) -> Result<(), MacMismatch> {
+            let nonce = Self::extend_nonce(nonce);
+            XChaCha::open(output, enc, aad, &nonce, key)
+        }
+    }
+
+    #[test]
+    fn nonce_strategy_with_short_nonce() {
+        let mut rng = ConstantRng;
+        let mut builder = PwBoxBuilder::<NoKdf, ShortNonce>::new(&mut rng);
+        builder.seal_with_key(&[0; 32], MESSAGE).unwrap();
+        let err = builder
+            .nonce_strategy(NonceStrategy::Counter)
+            .seal_with_key(&[0; 32], MESSAGE)
+            .unwrap_err();
+        assert_matches!(err, Error::SealOptions(ref reason) if reason.contains("too short"));
+    }
+
+    #[test]
+    fn changing_password() {
+        let mut rng = thread_rng();
+        let pwbox = Sodium::build_box(&mut rng)
+            .kdf(Scrypt::light())
+            .seal(OLD_PASSWORD, MESSAGE)
+            .unwrap();
+
+        let new_box = pwbox
+            .change_password(&mut rng, OLD_PASSWORD, NEW_PASSWORD)
+            .unwrap();
+        assert_ne!(new_box.inner.salt, pwbox.inner.salt);
+        assert_ne!(new_box.inner.nonce, pwbox.inner.nonce);
+        assert_eq!(&*new_box.open(NEW_PASSWORD).unwrap(), MESSAGE);
+        assert_matches!(new_box.open(OLD_PASSWORD).unwrap_err(), Error::MacMismatch);
+
+        // The original box should be intact.
+        assert_eq!(&*pwbox.open(OLD_PASSWORD).unwrap(), MESSAGE);
+    }
+
+    #[test]
+    fn sensitive_data_as_box_input_and_output() {
+        let mut rng = thread_rng();
+        let password = SensitiveData::from(OLD_PASSWORD.as_bytes().to_vec());
+        let message = SensitiveData::from(MESSAGE.to_vec());
+        let pwbox = Sodium::build_box(&mut rng)
+            .kdf(Scrypt::light())
+            .seal(&password, &message)
+            .unwrap();
+
+        let mut output = SensitiveData::zeros(pwbox.len());
+        pwbox.open_into(&mut output, &password).unwrap();
+        assert_eq!(output.as_ref(), MESSAGE);
+    }
+
+    #[test]
+    fn open_into_buffers_of_different_sizes() {
+        let pwbox = Sodium::build_box(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .seal(OLD_PASSWORD, MESSAGE)
+            .unwrap();
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let restored = eraser.restore(&eraser.erase(&pwbox).unwrap()).unwrap();
+
+        let mut small_buffer = [0_u8; 5];
+        assert_matches!(
+            pwbox
+                .open_into(&mut small_buffer, OLD_PASSWORD)
+                .unwrap_err(),
+            Error::OutputLen {
+                expected: 10,
+                actual: 5
+            }
+        );
+        assert_matches!(
+            restored
+                .open_into(&mut small_buffer, OLD_PASSWORD)
+                .unwrap_err(),
+            Error::OutputLen {
+                expected: 10,
+                actual: 5
+            }
+        );
+
+        let mut empty_buffer = [0_u8; 0];
+        assert_matches!(
+            pwbox
+                .open_into(&mut empty_buffer, OLD_PASSWORD)
+                .unwrap_err(),
+            Error::OutputLen {
+                expected: 10,
+                actual: 0
+            }
+        );
+        assert_matches!(
+            restored
+                .open_into(&mut empty_buffer, OLD_PASSWORD)
+                .unwrap_err(),
+            Error::OutputLen {
+                expected: 10,
+                actual: 0
+            }
+        );
+
+        let mut buffer = [0_u8; 10];
+        assert_eq!(pwbox.open_into(&mut buffer, OLD_PASSWORD).unwrap(), 10);
+        assert_eq!(buffer, MESSAGE);
+        let mut buffer = [0_u8; 10];
+        assert_eq!(restored.open_into(&mut buffer, OLD_PASSWORD).unwrap(), 10);
+        assert_eq!(buffer, MESSAGE);
+
+        let mut large_buffer = [0xff_u8; 16];
+        assert_eq!(
+            pwbox.open_into(&mut large_buffer, OLD_PASSWORD).unwrap(),
+            10
+        );
+        assert_eq!(large_buffer[..10], *MESSAGE);
+        assert_eq!(large_buffer[10..], [0xff; 6]);
+        let mut large_buffer = [0xff_u8; 16];
+        assert_eq!(
+            restored.open_into(&mut large_buffer, OLD_PASSWORD).unwrap(),
+            10
+        );
+        assert_eq!(large_buffer[..10], *MESSAGE);
+        assert_eq!(large_buffer[10..], [0xff; 6]);
+    }
+
+    #[test]
+    fn opening_box_in_place() {
+        // The message is larger than the stack buffer of `SensitiveData`.
+        let message = vec![42_u8; 1_024];
+        let pwbox = Sodium::build_box(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .seal(OLD_PASSWORD, &message)
+            .unwrap();
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let restored = eraser.restore(&eraser.erase(&pwbox).unwrap()).unwrap();
+
+        let err = pwbox.clone().open_in_place(NEW_PASSWORD).unwrap_err();
+        assert_matches!(err, Error::MacMismatch);
+        let ciphertext_ptr = pwbox.inner.encrypted.ciphertext.as_ptr();
+        let opened = pwbox.open_in_place(OLD_PASSWORD).unwrap();
+        assert_eq!(*opened, *message);
+        assert_eq!(opened.as_ptr(), ciphertext_ptr);
+
+        let err = restored.clone().open_in_place(NEW_PASSWORD).unwrap_err();
+        assert_matches!(err, Error::MacMismatch);
+        let ciphertext_ptr = restored.inner.encrypted.ciphertext.as_ptr();
+        let opened = restored.open_in_place(OLD_PASSWORD).unwrap();
+        assert_eq!(*opened, *message);
+        assert_eq!(opened.as_ptr(), ciphertext_ptr);
+
+        // Small boxes are opened into the stack buffer of `SensitiveData`.
+        let pwbox = PwBoxBuilder::<_, SealOnly>::new(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .seal(OLD_PASSWORD, MESSAGE)
+            .unwrap();
+        assert_eq!(&*pwbox.open_in_place(OLD_PASSWORD).unwrap(), MESSAGE);
+    }
+
+    #[test]
+    fn box_buffers_are_zeroed() {
+        let pwbox = Sodium::build_box(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .seal(OLD_PASSWORD, MESSAGE)
+            .unwrap();
+        assert!(mem::needs_drop::<PwBoxInner<Scrypt, CipherObject<XChaCha>>>());
+
+        // `Drop` delegates to `zeroize()`, which we call directly to inspect the buffers.
+        let mut inner = pwbox.inner.clone();
+        inner.zeroize();
+        assert!(inner.salt.is_empty());
+        assert!(inner.nonce.is_empty());
+        assert!(inner.encrypted.ciphertext.is_empty());
+        assert!(inner.encrypted.mac.is_empty());
+        assert!(inner.metadata.is_empty());
+        assert_eq!(&*pwbox.open(OLD_PASSWORD).unwrap(), MESSAGE);
+    }
+
+    #[test]
+    fn sealing_with_raw_key() {
+        let mut rng = thread_rng();
+        let mut key = [0_u8; 32];
+        rng.fill_bytes(&mut key);
+        let pwbox = Sodium::build_box(&mut rng)
+            .seal_with_key(&key, MESSAGE)
+            .unwrap();
+        assert!(pwbox.inner.salt.is_empty());
+        assert_eq!(&*pwbox.open(&key).unwrap(), MESSAGE);
+        assert_matches!(pwbox.open(&[0; 32]).unwrap_err(), Error::MacMismatch);
+        assert_matches!(pwbox.open(&key[..16]).unwrap_err(), Error::DeriveKey(_));
+        assert_matches!(pwbox.open(OLD_PASSWORD).unwrap_err(), Error::DeriveKey(_));
+
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let erased_box = eraser.erase(&pwbox).unwrap();
+        assert_eq!(erased_box.kdf_name(), "none");
+        assert_eq!(*erased_box.kdf_params(), serde_json::json!({}));
+        let json = serde_json::to_string(&erased_box).unwrap();
+        let erased_box: ErasedPwBox = serde_json::from_str(&json).unwrap();
+        let restored = eraser.restore(&erased_box).unwrap();
+        assert_eq!(&*restored.open(&key).unwrap(), MESSAGE);
+        assert_matches!(restored.open(&key[1..]).unwrap_err(), Error::DeriveKey(_));
+
+        // Boxes recording the KDF under its legacy name can be restored as well.
+        let legacy_json = json.replace(r#""kdf":"none""#, r#""kdf":"raw""#);
+        assert_ne!(legacy_json, json);
+        let erased_box: ErasedPwBox = serde_json::from_str(&legacy_json).unwrap();
+        let restored = eraser.restore(&erased_box).unwrap();
+        assert_eq!(&*restored.open(&key).unwrap(), MESSAGE);
+
+        // Keys must match the cipher key size.
+        let err = Sodium::build_box(&mut rng)
+            .seal_with_key(&key[..16], MESSAGE)
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid key length"));
+    }
+
+    #[test]
+    fn reporting_progress() {
+        let mut rng = thread_rng();
+        let events = Rc::new(RefCell::new(vec![]));
+        let events_ = Rc::clone(&events);
+        let mut builder = Sodium::build_box(&mut rng);
+        builder
+            .kdf(Scrypt::light())
+            .on_progress(move |event| events_.borrow_mut().push(event));
+        let pwbox = builder.seal(OLD_PASSWORD, MESSAGE).unwrap();
+        let bytes_total = Some(MESSAGE.len() as u64);
+        let expected_events = [
+            Progress::DerivingKey,
+            Progress::KeyDerived,
+            Progress::Encrypting {
+                bytes_done: 0,
+                bytes_total,
+            },
+            Progress::Encrypting {
+                bytes_done: 10,
+                bytes_total,
+            },
+        ];
+        assert_eq!(*events.borrow(), expected_events);
+
+        // Restored boxes report progress through the type-erased KDF.
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let restored = eraser.restore(&eraser.erase(&pwbox).unwrap()).unwrap();
+        let mut open_events = vec![];
+        let plaintext = restored
+            .open_with_progress(OLD_PASSWORD, |event| open_events.push(event))
+            .unwrap();
+        assert_eq!(&*plaintext, MESSAGE);
+        let expected_events = [
+            Progress::DerivingKey,
+            Progress::KeyDerived,
+            Progress::Verifying,
+        ];
+        assert_eq!(open_events, expected_events);
+        open_events.clear();
+        let err = pwbox
+            .open_with_progress(NEW_PASSWORD, |event| open_events.push(event))
+            .unwrap_err();
+        assert_matches!(err, Error::MacMismatch);
+        assert_eq!(open_events, expected_events);
+
+        // Streams report progress after each chunk.
+        events.borrow_mut().clear();
+        builder.chunk_size(4);
+        builder
+            .seal_from_reader(OLD_PASSWORD, MESSAGE, vec![])
+            .unwrap();
+        let expected_events = [
+            Progress::DerivingKey,
+            Progress::KeyDerived,
+            Progress::Encrypting {
+                bytes_done: 4,
+                bytes_total: None,
+            },
+            Progress::Encrypting {
+                bytes_done: 8,
+                bytes_total: None,
+            },
+            Progress::Encrypting {
+                bytes_done: 10,
+                bytes_total,
+            },
+        ];
+        assert_eq!(*events.borrow(), expected_events);
+    }
+
+    #[test]
+    fn normalizing_passwords() {
+        const NFC_PASSWORD: &str = "caf\u{e9}";
+        const NFD_PASSWORD: &str = "cafe\u{301}";
+
+        let mut rng = thread_rng();
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let mut builder = Sodium::build_box(&mut rng);
+        builder
+            .kdf(Scrypt::light())
+            .password_policy(PasswordPolicy::Nfkd);
+        let pwbox = builder.seal(NFC_PASSWORD, MESSAGE).unwrap();
+        assert_eq!(&*pwbox.open(NFD_PASSWORD).unwrap(), MESSAGE);
+        let err = builder
+            .seal_from_reader(NFC_PASSWORD, MESSAGE, vec![])
+            .unwrap_err();
+        assert!(err.to_string().contains("not supported for streams"));
+
+        // The policy is recorded in the erased box and applied by restored boxes.
+        let erased_box = eraser.erase(&pwbox).unwrap();
+        assert_eq!(erased_box.password_policy(), PasswordPolicy::Nfkd);
+        let json = serde_json::to_value(&erased_box).unwrap();
+        assert_eq!(json["passwordpolicy"], "nfkd");
+        let bytes = bincode::serialize(&erased_box).unwrap();
+        let erased_copy: ErasedPwBox = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(erased_copy, erased_box);
+        let restored = eraser.restore(&erased_copy).unwrap();
+        assert_eq!(&*restored.open(NFD_PASSWORD).unwrap(), MESSAGE);
+        let restored = restored
+            .change_password(&mut thread_rng(), NFD_PASSWORD, NFC_PASSWORD)
+            .unwrap();
+        assert_eq!(&*restored.open(NFD_PASSWORD).unwrap(), MESSAGE);
+
+        // With the raw policy, the encodings of the password are distinct.
+        let mut builder = Sodium::build_box(&mut rng);
+        builder.kdf(Scrypt::light());
+        let pwbox = builder.seal(NFC_PASSWORD, MESSAGE).unwrap();
+        assert_matches!(pwbox.open(NFD_PASSWORD).unwrap_err(), Error::MacMismatch);
+        let erased_box = eraser.erase(&pwbox).unwrap();
+        assert_eq!(erased_box.password_policy(), PasswordPolicy::Raw);
+        let json = serde_json::to_value(&erased_box).unwrap();
+        assert!(json.get("passwordpolicy").is_none());
+        let restored = eraser.restore(&erased_box).unwrap();
+        assert_matches!(restored.open(NFD_PASSWORD).unwrap_err(), Error::MacMismatch);
+    }
+
+    #[test]
+    fn opening_restored_box_with_candidate_passwords() {
+        const NFC_PASSWORD: &str = "caf\u{e9}";
+        const NFD_PASSWORD: &str = "cafe\u{301}";
+
+        let pwbox = Sodium::build_box(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .seal(NFC_PASSWORD, MESSAGE)
+            .unwrap();
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let restored = eraser.restore(&eraser.erase(&pwbox).unwrap()).unwrap();
+
+        let candidates = [NFD_PASSWORD, " caf\u{e9} ", NFC_PASSWORD, "wrong"];
+        let (i, plaintext) = restored
+            .open_any(candidates.iter().map(|s| s.as_bytes()))
+            .unwrap();
+        assert_eq!(i, 2);
+        assert_eq!(&*plaintext, MESSAGE);
+        let (i, _) = restored.open_any(vec![NFC_PASSWORD.as_bytes(); 2]).unwrap();
+        assert_eq!(i, 0);
+
+        let err = restored
+            .open_any(candidates[..2].iter().map(|s| s.as_bytes()))
+            .unwrap_err();
+        assert_matches!(err, Error::MacMismatch);
+        let err = restored.open_any(vec![]).unwrap_err();
+        assert_matches!(err, Error::MacMismatch);
+    }
+
+    #[test]
+    fn deterministic_sealing() {
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let mut rng = thread_rng();
+        let mut builder = Sodium::build_box(&mut rng);
+        builder.kdf(Scrypt::light()).deterministic(b"backup");
+        let pwbox = builder.seal(OLD_PASSWORD, MESSAGE).unwrap();
+        let erased_box = eraser.erase(&pwbox).unwrap();
+        let same_box = builder.seal(OLD_PASSWORD, MESSAGE).unwrap();
+        assert_eq!(eraser.erase(&same_box).unwrap(), erased_box);
+
+        let restored = eraser.restore(&erased_box).unwrap();
+        assert_eq!(&*restored.open(OLD_PASSWORD).unwrap(), MESSAGE);
+        assert_matches!(restored.open(NEW_PASSWORD).unwrap_err(), Error::MacMismatch);
+
+        // Deterministic sealing is not supported for streams.
+        let err = builder
+            .seal_from_reader(OLD_PASSWORD, MESSAGE, vec![])
+            .unwrap_err();
+        assert!(err.to_string().contains("deterministic"));
+
+        // Changing any of the inputs changes the salt and nonce.
+        let other_message = builder.seal(OLD_PASSWORD, b"other message").unwrap();
+        let other_password = builder.seal(NEW_PASSWORD, MESSAGE).unwrap();
+        builder.deterministic(b"other backup");
+        let other_context = builder.seal(OLD_PASSWORD, MESSAGE).unwrap();
+        builder.deterministic(b"backup").associated_data(b"aad");
+        let other_aad = builder.seal(OLD_PASSWORD, MESSAGE).unwrap();
+        let other_boxes = [&other_message, &other_password, &other_context, &other_aad];
+        for other_box in &other_boxes {
+            assert_ne!(other_box.inner.salt, pwbox.inner.salt);
+            assert_ne!(other_box.inner.nonce, pwbox.inner.nonce);
+        }
+        assert_eq!(
+            &*other_aad.open_with_aad(OLD_PASSWORD, b"aad").unwrap(),
+            MESSAGE
+        );
+    }
+
+    #[test]
+    fn opening_with_derived_key() {
+        let mut rng = thread_rng();
+        let pwbox = Sodium::build_box(&mut rng)
+            .kdf(Scrypt::light())
+            .seal(OLD_PASSWORD, MESSAGE)
+            .unwrap();
+        let key = pwbox.derive_key(OLD_PASSWORD).unwrap();
+        assert_eq!(key.len(), XChaCha::KEY_LEN);
+        assert_eq!(
+            *pwbox.open_with_key(&key).unwrap(),
+            *pwbox.open(OLD_PASSWORD).unwrap()
+        );
+        assert_matches!(
+            pwbox.open_with_key(&key[1..]).unwrap_err(),
+            Error::KeyLen {
+                expected: 32,
+                actual: 31
+            }
+        );
+        let wrong_key = pwbox.derive_key(NEW_PASSWORD).unwrap();
+        assert_matches!(
+            pwbox.open_with_key(&wrong_key).unwrap_err(),
+            Error::MacMismatch
+        );
+
+        // Boxes sealed with the derived key can be opened with the key or the password.
+        let other_box = pwbox
+            .seal_with_derived_key(&mut rng, &key, b"other message")
+            .unwrap();
+        assert_eq!(other_box.inner.salt, pwbox.inner.salt);
+        assert_ne!(other_box.inner.nonce, pwbox.inner.nonce);
+        assert_eq!(&*other_box.open_with_key(&key).unwrap(), b"other message");
+        assert_eq!(&*other_box.open(OLD_PASSWORD).unwrap(), b"other message");
+        assert_matches!(
+            pwbox
+                .seal_with_derived_key(&mut rng, &key[..16], MESSAGE)
+                .unwrap_err(),
+            Error::KeyLen {
+                expected: 32,
+                actual: 16
+            }
+        );
+
+        // Restored boxes support the same workflow.
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let restored = eraser.restore(&eraser.erase(&other_box).unwrap()).unwrap();
+        assert_eq!(*restored.derive_key(OLD_PASSWORD).unwrap(), *key);
+        assert_eq!(&*restored.open_with_key(&key).unwrap(), b"other message");
+        assert_matches!(
+            restored.open_with_key(&[0; 16]).unwrap_err(),
+            Error::KeyLen { .. }
+        );
+
+        // Raw-key boxes do not record KDF params.
+        let raw_box = PwBox::<NoKdf, XChaCha>::seal_with_key(&mut rng, &key, MESSAGE).unwrap();
+        assert!(raw_box.inner.salt.is_empty());
+        assert_eq!(&*raw_box.open_with_key(&key).unwrap(), MESSAGE);
+        assert_eq!(&*raw_box.open(&key).unwrap(), MESSAGE);
+        let err = PwBox::<NoKdf, XChaCha>::seal_with_key(&mut rng, &[0; 16], MESSAGE).unwrap_err();
+        assert_matches!(
+            err,
+            Error::KeyLen {
+                expected: 32,
+                actual: 16
+            }
+        );
+    }
+
+    #[test]
+    fn reproducing_libsodium_test_vector() {
+        use crate::sodium::XSalsa20Poly1305;
+        use serde_json::json;
+
+        // Box produced with `crypto_pwhash_scryptsalsa208sha256` and `crypto_secretbox`.
+        let expected_json = json!({
+            "ciphertext": "cd9d2fb2355d8c60d92dcc860abc0c4b20ddd12dd52a4dd53caca0a2f87f7f5f",
+            "mac": "83ae22646d7834f254caea78862eafda",
+            "kdf": "scrypt-nacl",
+            "cipher": "xsalsa20-poly1305",
+            "kdfparams": {
+                "salt": "87d68fb57d9c2331cf2bd9fdd7551057798bd36d0d2999481311cfae39863691",
+                "memlimit": 16_777_216,
+                "opslimit": 524_288,
+            },
+            "cipherparams": {
+                "iv": "db39c466e2f8ae7fbbc857df48d99254017b059624af7106",
+            },
+        });
+        let message =
+            hex::decode("81eadef2506c61acd04d35c7cad820349dc00a68fdf5bc1a016788cd6fe05816")
+                .unwrap();
+
+        let kdf = Scrypt {
+            opslimit: 524_288,
+            memlimit: 16_777_216,
+        };
+        let salt = hex::decode(expected_json["kdfparams"]["salt"].as_str().unwrap()).unwrap();
+        let nonce = hex::decode(expected_json["cipherparams"]["iv"].as_str().unwrap()).unwrap();
+        let pwbox = Sodium::build_box(&mut thread_rng())
+            .cipher::<XSalsa20Poly1305>()
+            .kdf(kdf)
+            .salt(&salt)
+            .nonce(&nonce)
+            .seal(OLD_PASSWORD, &message)
+            .unwrap();
+
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let erased_box = eraser.erase(&pwbox).unwrap();
+        assert_eq!(serde_json::to_value(&erased_box).unwrap(), expected_json);
+        assert_eq!(*pwbox.open(OLD_PASSWORD).unwrap(), *message);
+
+        // Invalid salt and nonce lengths.
+        let err = Sodium::build_box(&mut thread_rng())
+            .kdf(kdf)
+            .salt(&salt[1..])
+            .seal(OLD_PASSWORD, &message)
+            .unwrap_err();
+        assert_matches!(err, Error::SaltLen);
+        let err = Sodium::build_box(&mut thread_rng())
+            .kdf(kdf)
+            .nonce(&nonce[..12])
+            .seal(OLD_PASSWORD, &message)
+            .unwrap_err();
+        assert_matches!(err, Error::NonceLen);
+
+        // Without the overrides, salt and nonce are random.
+        let other_box = Sodium::build_box(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .seal(OLD_PASSWORD, &message)
+            .unwrap();
+        assert_ne!(other_box.inner.salt, salt);
+        assert_ne!(other_box.inner.nonce, nonce);
+    }
+
+    #[test]
+    fn changing_password_with_incorrect_old_password() {
+        let mut rng = thread_rng();
+        let pwbox = Sodium::build_box(&mut rng)
+            .kdf(Scrypt::light())
+            .seal(OLD_PASSWORD, MESSAGE)
+            .unwrap();
+
+        let err = pwbox
+            .change_password(&mut rng, NEW_PASSWORD, NEW_PASSWORD)
+            .unwrap_err();
+        assert_matches!(err, Error::MacMismatch);
+        assert_eq!(&*pwbox.open(OLD_PASSWORD).unwrap(), MESSAGE);
+    }
+
+    #[test]
+    fn changing_password_for_restored_box() {
+        let mut rng = thread_rng();
+        let pwbox = Sodium::build_box(&mut rng)
+            .kdf(Scrypt::light())
+            .seal(OLD_PASSWORD, MESSAGE)
+            .unwrap();
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let restored = eraser.restore(&eraser.erase(&pwbox).unwrap()).unwrap();
+
+        let new_box = restored
+            .change_password(&mut rng, OLD_PASSWORD, NEW_PASSWORD)
+            .unwrap();
+        assert_ne!(new_box.inner.salt, restored.inner.salt);
+        assert_ne!(new_box.inner.nonce, restored.inner.nonce);
+        assert_eq!(&*new_box.open(NEW_PASSWORD).unwrap(), MESSAGE);
+        assert_matches!(new_box.open(OLD_PASSWORD).unwrap_err(), Error::MacMismatch);
+        assert_matches!(
+            restored
+                .change_password(&mut rng, NEW_PASSWORD, OLD_PASSWORD)
+                .unwrap_err(),
+            Error::MacMismatch
+        );
+    }
+
+    #[test]
+    fn resealing_restored_box() {
+        let mut rng = thread_rng();
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let mut builder = Sodium::build_box(&mut rng);
+        builder
+            .kdf(Scrypt::light())
+            .password_policy(PasswordPolicy::Nfkd);
+        let pwbox = builder.seal(OLD_PASSWORD, MESSAGE).unwrap();
+        let restored = eraser.restore(&eraser.erase(&pwbox).unwrap()).unwrap();
+
+        let resealed: PwBox<Scrypt, XChaCha> = restored.reseal(&mut rng, OLD_PASSWORD).unwrap();
+        assert_ne!(resealed.inner.salt, restored.inner.salt);
+        assert_ne!(resealed.inner.nonce, restored.inner.nonce);
+        assert_eq!(resealed.inner.kdf.opslimit, Scrypt::default().opslimit);
+        assert_eq!(resealed.inner.password_policy, PasswordPolicy::Nfkd);
+        assert_eq!(&*resealed.open(OLD_PASSWORD).unwrap(), MESSAGE);
+        // The original box is left intact.
+        assert_eq!(&*restored.open(OLD_PASSWORD).unwrap(), MESSAGE);
+
+        let erased_box = eraser.erase(&resealed).unwrap();
+        assert_eq!(erased_box.password_policy(), PasswordPolicy::Nfkd);
+        let restored_again = eraser.restore(&erased_box).unwrap();
+        assert_eq!(&*restored_again.open(OLD_PASSWORD).unwrap(), MESSAGE);
+
+        let err = restored
+            .reseal::<Scrypt, XChaCha, _>(&mut rng, NEW_PASSWORD)
+            .unwrap_err();
+        assert_matches!(err, Error::MacMismatch);
+    }
+
+    #[test]
+    fn comparing_box_contents() {
+        let mut rng = thread_rng();
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let mut builder = Sodium::build_box(&mut rng);
+        builder.kdf(Scrypt::light());
+        let pwbox = builder.seal(OLD_PASSWORD, MESSAGE).unwrap();
+        assert!(pwbox.content_eq(&pwbox.clone()));
+        let other_box = builder.seal(OLD_PASSWORD, MESSAGE).unwrap();
+        assert!(!pwbox.content_eq(&other_box));
+        let changed_box = pwbox
+            .change_password(&mut rng, OLD_PASSWORD, NEW_PASSWORD)
+            .unwrap();
+        assert!(!pwbox.content_eq(&changed_box));
+
+        let erased_box = eraser.erase(&pwbox).unwrap();
+        let restored = eraser.restore(&erased_box).unwrap();
+        assert!(restored.content_eq(&eraser.restore(&erased_box).unwrap()));
+        let typed_box = eraser.restore_as::<Sodium>(&erased_box).unwrap();
+        assert!(pwbox.content_eq(&typed_box));
+
+        let json = serde_json::to_value(&erased_box).unwrap();
+        let mut tampered_json = json.clone();
+        tampered_json["kdfparams"]["unknown"] = true.into();
+        let mut tampered_boxes = vec![tampered_json];
+        let mut tampered_json = json;
+        tampered_json["mac"] = hex::encode([0_u8; 16]).into();
+        tampered_boxes.push(tampered_json);
+        for tampered_json in tampered_boxes {
+            let tampered_box: ErasedPwBox = serde_json::from_value(tampered_json).unwrap();
+            assert!(!restored.content_eq(&eraser.restore(&tampered_box).unwrap()));
+        }
+    }
+
+    /// Mock hardware KDF with a mutable state borrowed from the test.
+    struct MockDevice<'a> {
+        secret: u8,
+        invocations: &'a mut usize,
+        is_broken: bool,
+    }
+
+    impl DeriveKeyMut for MockDevice<'_> {
+        fn salt_len(&self) -> usize {
+            16
+        }
+
+        fn derive_key(
+            &mut self,
+            buf: &mut [u8],
+            password: &[u8],
+            salt: &[u8],
+        ) -> anyhow::Result<()> {
+            *self.invocations += 1;
+            anyhow::ensure!(!self.is_broken, "device is disconnected");
+            let mut hasher = Sha256::new();
+            hasher.update(&[self.secret]);
+            hasher.update(salt);
+            hasher.update(password);
+            buf.copy_from_slice(&hasher.finalize());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn sealing_with_external_kdf() {
+        let mut invocations = 0;
+        let mut device = MockDevice {
+            secret: 42,
+            invocations: &mut invocations,
+            is_broken: false,
+        };
+        let mut params = ExternalKdf::default();
+        params.0.insert("serial".to_owned(), "0123".into());
+        let pwbox = Sodium::build_box(&mut thread_rng())
+            .seal_with_external_kdf(&mut device, params.clone(), OLD_PASSWORD, MESSAGE)
+            .unwrap();
+        assert_eq!(pwbox.inner.salt.len(), 16);
+        assert_eq!(*device.invocations, 1);
+        let opened = pwbox.open_with_kdf(&mut device, OLD_PASSWORD).unwrap();
+        assert_eq!(&*opened, MESSAGE);
+        assert_eq!(*device.invocations, 2);
+
+        let eraser = Eraser::with_suites::<(Sodium,)>().unwrap();
+        let erased_box = eraser.erase(&pwbox).unwrap();
+        assert_eq!(erased_box.kdf_name(), names::EXTERNAL);
+        assert_eq!(
+            *erased_box.kdf_params(),
+            serde_json::json!({ "serial": "0123" })
+        );
+        let restored = eraser.restore(&erased_box).unwrap();
+        let err = restored.open(OLD_PASSWORD).unwrap_err();
+        assert_matches!(err, Error::DeriveKey(ref e) if e.to_string().contains("open_with_kdf"));
+        let opened = restored.open_with_kdf(&mut device, OLD_PASSWORD).unwrap();
+        assert_eq!(&*opened, MESSAGE);
+        assert_eq!(*device.invocations, 3);
+        let err = restored
+            .open_with_kdf(&mut device, NEW_PASSWORD)
+            .unwrap_err();
+        assert_matches!(err, Error::MacMismatch);
+        assert_eq!(*device.invocations, 4);
+        let typed = eraser
+            .restore_typed::<ExternalKdf, XChaCha>(&erased_box)
+            .unwrap();
+        assert_eq!(typed.inner.kdf, params);
+    }
+
+    #[test]
+    fn external_kdf_failures() {
+        let mut invocations = 0;
+        let mut device = MockDevice {
+            secret: 42,
+            invocations: &mut invocations,
+            is_broken: true,
+        };
+        let err = Sodium::build_box(&mut thread_rng())
+            .seal_with_external_kdf(&mut device, ExternalKdf::default(), OLD_PASSWORD, MESSAGE)
+            .unwrap_err();
+        assert!(err.to_string().contains("disconnected"));
+
+        device.is_broken = false;
+        let pwbox = Sodium::build_box(&mut thread_rng())
+            .seal_with_external_kdf(&mut device, ExternalKdf::default(), OLD_PASSWORD, MESSAGE)
+            .unwrap();
+        device.is_broken = true;
+        let err = pwbox.open_with_kdf(&mut device, OLD_PASSWORD).unwrap_err();
+        assert_matches!(err, Error::DeriveKey(ref e) if e.to_string().contains("disconnected"));
+        assert_eq!(*device.invocations, 3);
+
+        // The device is not invoked with salt of unexpected length.
+        let other_box = Sodium::build_box(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .seal(OLD_PASSWORD, MESSAGE)
+            .unwrap();
+        let err = other_box
+            .open_with_kdf(&mut device, OLD_PASSWORD)
+            .unwrap_err();
+        assert_matches!(err, Error::SaltLen);
+        assert_eq!(*device.invocations, 3);
+    }
+
+    #[test]
+    fn error_sources() {
+        use std::error::Error as StdError;
+
+        let mut rng = thread_rng();
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let pwbox = Sodium::build_box(&mut rng)
+            .kdf(Scrypt::light())
+            .seal(OLD_PASSWORD, MESSAGE)
+            .unwrap();
+        let mut json = serde_json::to_value(eraser.erase(&pwbox).unwrap()).unwrap();
+        json["kdfparams"]["opslimit"] = "many".into();
+        let erased_box: ErasedPwBox = serde_json::from_value(json).unwrap();
+        let err = eraser.restore(&erased_box).unwrap_err();
+        assert_matches!(err, Error::KdfParams(_));
+        let source = err.source().unwrap();
+        assert!(source.downcast_ref::<serde_json::Error>().is_some());
+
+        let key = [0_u8; 32];
+        let pwbox = Sodium::build_box(&mut rng)
+            .seal_with_key(&key, MESSAGE)
+            .unwrap();
+        let err = pwbox.open(&key[1..]).unwrap_err();
+        assert_matches!(err, Error::DeriveKey(_));
+        assert!(err
+            .source()
+            .unwrap()
+            .to_string()
+            .contains("invalid key length"));
+
+        let err = pwbox.open(&[1; 32]).unwrap_err();
+        assert!(err.source().is_none());
+        // The error can be used with generic error handling.
+        let boxed: Box<dyn StdError + Send + Sync> = err.into();
+        assert_eq!(boxed.to_string(), "incorrect password or corrupted box");
+        let err = anyhow::Error::from(pwbox.open(&[1; 32]).unwrap_err());
+        assert_matches!(err.downcast_ref::<Error>(), Some(Error::MacMismatch));
+    }
+
+    #[test]
+    fn sealing_and_opening_errors_with_question_mark() {
+        use std::error::Error as StdError;
+
+        fn roundtrip(key: &[u8]) -> Result<SensitiveData, Box<dyn StdError + Send + Sync>> {
+            let pwbox = Sodium::build_box(&mut thread_rng()).seal_with_key(key, MESSAGE)?;
+            Ok(pwbox.open(key)?)
+        }
+
+        assert_eq!(&*roundtrip(&[0; 32]).unwrap(), MESSAGE);
+        let err = roundtrip(&[0; 16]).unwrap_err();
+        let err = err.downcast_ref::<Error>().unwrap();
+        assert_matches!(err, Error::DeriveKey(_));
+        let source = err.source().unwrap();
+        assert!(source.to_string().contains("invalid key length"));
+    }
 }
@@ -0,0 +1,88 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sealing boxes with the random number generator of the operating system.
+//!
+//! This module is useful in environments where `rand::thread_rng()` is not available,
+//! such as WASM. Randomness is provided by the [`getrandom`] crate, which works without
+//! the standard library.
+//!
+//! To use the crate in the browser (the `wasm32-unknown-unknown` target), disable default
+//! features and enable `pure` and `os-rng`; the `exonum_sodiumoxide` and `rust-crypto`
+//! backends depend on native code and cannot be compiled into WASM. Additionally,
+//! the `js` feature of `getrandom` needs to be enabled in the final crate.
+//!
+//! [`getrandom`]: https://docs.rs/getrandom/
+//!
+//! # Examples
+//!
+//! ```
+//! use pwbox::{os_rng::seal_with_os_rng, Eraser, FastInsecureSuite};
+//!
+//! let mut eraser = Eraser::new();
+//! eraser.add_suite::<FastInsecureSuite>()?;
+//! let erased = seal_with_os_rng::<FastInsecureSuite>(&eraser, "correct horse", "battery staple")?;
+//! let restored = eraser.restore(&erased)?;
+//! assert_eq!(&*restored.open("correct horse")?, b"battery staple");
+//! # Ok::<_, pwbox::Error>(())
+//! ```
+
+use serde::Serialize;
+
+pub use rand_core::OsRng;
+
+use crate::{ErasedPwBox, Eraser, Error, Suite};
+
+/// Seals a box with the recommended cipher and KDF of the suite `S`, using [`OsRng`]
+/// to generate the salt and nonce. The box is converted into serializable form
+/// with the provided `eraser` as per [`Eraser::seal()`].
+///
+/// # Errors
+///
+/// Returns an error under the same conditions as [`Eraser::seal()`].
+pub fn seal_with_os_rng<S>(
+    eraser: &Eraser,
+    password: impl AsRef<[u8]>,
+    message: impl AsRef<[u8]>,
+) -> Result<ErasedPwBox, Error>
+where
+    S: Suite,
+    S::DeriveKey: Serialize,
+{
+    let mut rng = OsRng;
+    let mut builder = S::build_box(&mut rng);
+    eraser.seal(&mut builder, password, message)
+}
+
+#[cfg(all(test, feature = "exonum_sodiumoxide"))]
+mod tests {
+    use super::*;
+    use crate::FastInsecureSuite;
+
+    #[test]
+    fn sealing_boxes_with_os_rng() {
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<FastInsecureSuite>().unwrap();
+        let boxes: Vec<_> = (0..2)
+            .map(|_| seal_with_os_rng::<FastInsecureSuite>(&eraser, "password", b"message"))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_ne!(boxes[0].salt(), boxes[1].salt());
+        assert_ne!(boxes[0].nonce(), boxes[1].nonce());
+        for erased_box in &boxes {
+            let restored = eraser.restore(erased_box).unwrap();
+            assert_eq!(&*restored.open("password").unwrap(), b"message");
+        }
+    }
+}
@@ -0,0 +1,509 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversion of KDF params to and from the PHC string format.
+
+use serde::de::Error as _;
+use serde_json::Error as JsonError;
+
+use core::{convert::TryFrom, fmt::Write as _};
+
+use crate::{
+    alloc::{format, String, Vec},
+    utils::decode_base64,
+    DeriveKey, Error, ScryptParams,
+};
+#[cfg(any(feature = "exonum_sodiumoxide", feature = "rust-crypto"))]
+use crate::{Argon2Params, Argon2Variant};
+
+#[cfg(any(feature = "exonum_sodiumoxide", feature = "rust-crypto"))]
+/// The only Argon2 version supported by the crate (0x13).
+const ARGON2_VERSION: u32 = 19;
+
+/// KDF with params representable in the [PHC string format], which is used to exchange
+/// password hashing params among different ecosystems. E.g., `scrypt` params are
+/// represented as `$scrypt$ln=15,r=8,p=1$<salt>`, where `<salt>` is encoded with base64
+/// without padding.
+///
+/// The trait is implemented for `scrypt` and Argon2 KDFs in all crypto backends.
+/// KDFs implementing the trait can be registered in an [`Eraser`] with
+/// [`Eraser::add_phc_kdf()`], so that boxes with KDF params given as a PHC string
+/// can be restored.
+///
+/// [PHC string format]: https://github.com/P-H-C/phc-string-format/blob/master/phc-sf-spec.md
+/// [`Eraser`]: crate::Eraser
+/// [`Eraser::add_phc_kdf()`]: crate::Eraser::add_phc_kdf()
+///
+/// # Examples
+///
+/// ```
+/// # use pwbox::{sodium::ScryptCompat, PhcString};
+/// let phc = "$scrypt$ln=15,r=8,p=1$c29tZXNhbHQ";
+/// let (scrypt, salt) = ScryptCompat::from_phc_string(phc)?;
+/// assert_eq!(salt, b"somesalt");
+/// assert_eq!(scrypt.0.memory_cost(), 32 << 20);
+/// assert_eq!(scrypt.to_phc_string(&salt), phc);
+/// # Ok::<_, pwbox::Error>(())
+/// ```
+pub trait PhcString: DeriveKey + Sized {
+    /// Converts the KDF params together with the `salt` into a PHC string. The string
+    /// does not contain a hash.
+    fn to_phc_string(&self, salt: &[u8]) -> String;
+
+    /// Parses the KDF params and the salt from a PHC string. The hash, if present,
+    /// is checked to be valid base64, but is otherwise ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::KdfParams`] if the string is malformed, or if the params
+    /// are not supported by the KDF.
+    fn from_phc_string(phc: &str) -> Result<(Self, Vec<u8>), Error>;
+}
+
+fn phc_error(message: &str) -> Error {
+    Error::KdfParams(JsonError::custom(format!(
+        "invalid PHC string: {}",
+        message
+    )))
+}
+
+/// Parses a decimal param value. Signs and leading zeros are not allowed.
+fn parse_decimal(name: &str, value: &str) -> Result<u32, Error> {
+    let is_canonical = !value.is_empty()
+        && value.bytes().all(|byte| byte.is_ascii_digit())
+        && (value == "0" || !value.starts_with('0'));
+    if !is_canonical {
+        return Err(phc_error(&format!(
+            "param `{}` is not a decimal number",
+            name
+        )));
+    }
+    value
+        .parse()
+        .map_err(|_| phc_error(&format!("param `{}` is too large", name)))
+}
+
+/// Structure of a PHC string: `$<id>[$v=<version>][$<params>][$<salt>[$<hash>]]`.
+#[derive(Debug)]
+struct ParsedPhc<'a> {
+    id: &'a str,
+    version: Option<u32>,
+    params: Vec<(&'a str, &'a str)>,
+    salt: Vec<u8>,
+}
+
+impl<'a> ParsedPhc<'a> {
+    fn parse(phc: &'a str) -> Result<Self, Error> {
+        if !phc.starts_with('$') {
+            return Err(phc_error("string must start with `$`"));
+        }
+        let mut parts = phc[1..].split('$').peekable();
+
+        let id = parts.next().unwrap_or_default();
+        let is_valid_id = !id.is_empty()
+            && id.len() <= 32
+            && id
+                .bytes()
+                .all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'-');
+        if !is_valid_id {
+            return Err(phc_error("invalid algorithm identifier"));
+        }
+
+        let mut version = None;
+        if let Some(part) = parts.peek().filter(|part| part.starts_with("v=")) {
+            version = Some(parse_decimal("v", &part[2..])?);
+            parts.next();
+        }
+
+        let mut params = Vec::new();
+        if parts.peek().map_or(false, |part| part.contains('=')) {
+            for param in parts.next().unwrap_or_default().split(',') {
+                let pos = param
+                    .find('=')
+                    .ok_or_else(|| phc_error("param must have `name=value` form"))?;
+                let (name, value) = (&param[..pos], &param[(pos + 1)..]);
+                if params.iter().any(|&(other_name, _)| other_name == name) {
+                    return Err(phc_error(&format!("duplicate param `{}`", name)));
+                }
+                params.push((name, value));
+            }
+        }
+
+        let salt = parts.next().ok_or_else(|| phc_error("salt is missing"))?;
+        let salt = decode_base64(salt).ok_or_else(|| phc_error("salt is not valid base64"))?;
+        if let Some(hash) = parts.next() {
+            decode_base64(hash).ok_or_else(|| phc_error("hash is not valid base64"))?;
+        }
+        if parts.next().is_some() {
+            return Err(phc_error("unexpected trailing fields"));
+        }
+
+        Ok(ParsedPhc {
+            id,
+            version,
+            params,
+            salt,
+        })
+    }
+
+    /// Returns values of the specified params in the same order. All params in the string
+    /// must be among `names`.
+    fn take_params(&self, names: &[&str]) -> Result<Vec<u32>, Error> {
+        if let Some(&(name, _)) = self.params.iter().find(|(name, _)| !names.contains(name)) {
+            return Err(phc_error(&format!("unsupported param `{}`", name)));
+        }
+        names
+            .iter()
+            .map(|&name| {
+                let value = self
+                    .params
+                    .iter()
+                    .find(|&&(other_name, _)| other_name == name);
+                let value = value.ok_or_else(|| phc_error(&format!("param `{}` is missing", name)));
+                parse_decimal(name, value?.1)
+            })
+            .collect()
+    }
+}
+
+/// Encodes the salt and appends it to the PHC string.
+fn push_salt(phc: &mut String, salt: &[u8]) {
+    phc.push('$');
+    phc.push_str(&base64::encode_config(salt, base64::STANDARD_NO_PAD));
+}
+
+pub(crate) fn scrypt_to_phc(params: ScryptParams, salt: &[u8]) -> String {
+    let mut phc = String::new();
+    let (log_n, r, p) = (params.log_n, params.r, params.p);
+    write!(phc, "$scrypt$ln={},r={},p={}", log_n, r, p).expect("writing to string");
+    push_salt(&mut phc, salt);
+    phc
+}
+
+pub(crate) fn scrypt_from_phc(phc: &str) -> Result<(ScryptParams, Vec<u8>), Error> {
+    let parsed = ParsedPhc::parse(phc)?;
+    if parsed.id != "scrypt" {
+        return Err(phc_error("algorithm identifier must be `scrypt`"));
+    }
+    if parsed.version.is_some() {
+        return Err(phc_error("scrypt does not have versions"));
+    }
+    let values = parsed.take_params(&["ln", "r", "p"])?;
+    let log_n = u8::try_from(values[0])
+        .ok()
+        .filter(|&log_n| log_n < 32)
+        .ok_or_else(|| phc_error("param `ln` must be less than 32"))?;
+    let params = ScryptParams {
+        log_n,
+        r: values[1],
+        p: values[2],
+    };
+    Ok((params, parsed.salt))
+}
+
+#[cfg(any(feature = "exonum_sodiumoxide", feature = "rust-crypto"))]
+/// Converts scrypt params from the PHC string into `libsodium` limits. The conversion
+/// fails if the limits would be converted back into other params.
+pub(crate) fn nacl_scrypt_from_phc(phc: &str) -> Result<((u32, u32), Vec<u8>), Error> {
+    let (params, salt) = scrypt_from_phc(phc)?;
+    let limits = match (
+        u32::try_from(params.ops_cost()),
+        u32::try_from(params.memory_cost()),
+    ) {
+        (Ok(opslimit), Ok(memlimit)) => Some((opslimit, memlimit)),
+        _ => None,
+    };
+    let limits = limits.filter(|&(opslimit, memlimit)| {
+        let converted = ScryptParams::from_nacl_limits(opslimit, memlimit);
+        (converted.log_n, converted.r, converted.p) == (params.log_n, params.r, params.p)
+    });
+    let limits = limits
+        .ok_or_else(|| phc_error("scrypt params are not representable as libsodium limits"))?;
+    Ok((limits, salt))
+}
+
+#[cfg(any(feature = "exonum_sodiumoxide", feature = "rust-crypto"))]
+pub(crate) fn argon2_to_phc(params: Argon2Params, salt: &[u8]) -> String {
+    let id = match params.variant {
+        Argon2Variant::Argon2i => "argon2i",
+        Argon2Variant::Argon2id => "argon2id",
+    };
+    let mut phc = String::new();
+    let (m, t, p) = (params.mem_cost, params.time_cost, params.parallelism);
+    write!(phc, "${}$v={}$m={},t={},p={}", id, ARGON2_VERSION, m, t, p).expect("writing to string");
+    push_salt(&mut phc, salt);
+    phc
+}
+
+#[cfg(any(feature = "exonum_sodiumoxide", feature = "rust-crypto"))]
+pub(crate) fn argon2_from_phc(phc: &str) -> Result<(Argon2Params, Vec<u8>), Error> {
+    let parsed = ParsedPhc::parse(phc)?;
+    let variant = match parsed.id {
+        "argon2i" => Argon2Variant::Argon2i,
+        "argon2id" => Argon2Variant::Argon2id,
+        _ => {
+            return Err(phc_error(
+                "algorithm identifier must be `argon2i` or `argon2id`",
+            ))
+        }
+    };
+    // A missing version corresponds to the legacy version 0x10.
+    if parsed.version != Some(ARGON2_VERSION) {
+        return Err(phc_error("only Argon2 version 19 is supported"));
+    }
+    let values = parsed.take_params(&["m", "t", "p"])?;
+    let params = Argon2Params {
+        variant,
+        mem_cost: values[0],
+        time_cost: values[1],
+        parallelism: values[2],
+    };
+    Ok((params, parsed.salt))
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use super::*;
+
+    #[test]
+    fn parsing_scrypt_params() {
+        let phc = "$scrypt$ln=15,r=8,p=1$c29tZXNhbHQ";
+        let (params, salt) = scrypt_from_phc(phc).unwrap();
+        assert_eq!((params.log_n, params.r, params.p), (15, 8, 1));
+        assert_eq!(salt, b"somesalt");
+        assert_eq!(scrypt_to_phc(params, &salt), phc);
+
+        // The hash is ignored.
+        let phc_with_hash = "$scrypt$ln=15,r=8,p=1$c29tZXNhbHQ$aGFzaA";
+        let (other_params, _) = scrypt_from_phc(phc_with_hash).unwrap();
+        assert_eq!(other_params.log_n, params.log_n);
+        // Params may go in any order.
+        let (other_params, _) = scrypt_from_phc("$scrypt$p=2,ln=10,r=4$c29tZXNhbHQ").unwrap();
+        assert_eq!(
+            (other_params.log_n, other_params.r, other_params.p),
+            (10, 4, 2)
+        );
+    }
+
+    #[cfg(any(feature = "exonum_sodiumoxide", feature = "rust-crypto"))]
+    #[test]
+    fn parsing_argon2_params() {
+        let phc = "$argon2id$v=19$m=65536,t=2,p=1$c29tZXNhbHQ";
+        let (params, salt) = argon2_from_phc(phc).unwrap();
+        assert_eq!(params.variant, Argon2Variant::Argon2id);
+        assert_eq!(
+            (params.mem_cost, params.time_cost, params.parallelism),
+            (65_536, 2, 1)
+        );
+        assert_eq!(salt, b"somesalt");
+        assert_eq!(argon2_to_phc(params, &salt), phc);
+
+        let (params, _) = argon2_from_phc("$argon2i$v=19$m=256,t=3,p=4$c29tZXNhbHQ").unwrap();
+        assert_eq!(params.variant, Argon2Variant::Argon2i);
+        assert_eq!(
+            argon2_to_phc(params, b"salt"),
+            "$argon2i$v=19$m=256,t=3,p=4$c2FsdA"
+        );
+    }
+
+    #[test]
+    fn malformed_phc_strings() {
+        const SCRYPT_STRINGS: &[(&str, &str)] = &[
+            ("", "must start with `$`"),
+            ("scrypt$ln=15,r=8,p=1$c29tZXNhbHQ", "must start with `$`"),
+            (
+                "$Scrypt$ln=15,r=8,p=1$c29tZXNhbHQ",
+                "invalid algorithm identifier",
+            ),
+            ("$pbkdf2$ln=15,r=8,p=1$c29tZXNhbHQ", "must be `scrypt`"),
+            (
+                "$scrypt$v=1$ln=15,r=8,p=1$c29tZXNhbHQ",
+                "does not have versions",
+            ),
+            ("$scrypt$ln=15,r=8,p=1", "salt is missing"),
+            (
+                "$scrypt$ln=15,r=8,p=1$c29tZXNhbHQ=",
+                "salt is not valid base64",
+            ),
+            (
+                "$scrypt$ln=15,r=8,p=1$c29tZXNhbHR",
+                "salt is not valid base64",
+            ),
+            (
+                "$scrypt$ln=15,r=8,p=1$c29tZXNhbHQ$!",
+                "hash is not valid base64",
+            ),
+            (
+                "$scrypt$ln=15,r=8,p=1$c29tZXNhbHQ$aGFzaA$",
+                "unexpected trailing fields",
+            ),
+            ("$scrypt$ln=15,r=8$c29tZXNhbHQ", "param `p` is missing"),
+            (
+                "$scrypt$ln=15,r=8,p=1,x=1$c29tZXNhbHQ",
+                "unsupported param `x`",
+            ),
+            (
+                "$scrypt$ln=15,r=8,p=1,p=1$c29tZXNhbHQ",
+                "duplicate param `p`",
+            ),
+            ("$scrypt$ln=15,r=8,p$c29tZXNhbHQ", "`name=value` form"),
+            (
+                "$scrypt$ln=015,r=8,p=1$c29tZXNhbHQ",
+                "param `ln` is not a decimal number",
+            ),
+            (
+                "$scrypt$ln=15,r=+8,p=1$c29tZXNhbHQ",
+                "param `r` is not a decimal number",
+            ),
+            (
+                "$scrypt$ln=15,r=8,p=$c29tZXNhbHQ",
+                "param `p` is not a decimal number",
+            ),
+            (
+                "$scrypt$ln=15,r=8,p=4294967296$c29tZXNhbHQ",
+                "param `p` is too large",
+            ),
+            (
+                "$scrypt$ln=32,r=8,p=1$c29tZXNhbHQ",
+                "param `ln` must be less than 32",
+            ),
+        ];
+
+        for &(phc, expected_message) in SCRYPT_STRINGS {
+            let err = scrypt_from_phc(phc).unwrap_err();
+            assert_matches!(
+                err,
+                Error::KdfParams(ref e) if e.to_string().contains(expected_message),
+                "{}",
+                phc
+            );
+        }
+    }
+
+    #[cfg(any(feature = "exonum_sodiumoxide", feature = "rust-crypto"))]
+    #[test]
+    fn malformed_argon2_phc_strings() {
+        const ARGON2_STRINGS: &[(&str, &str)] = &[
+            (
+                "$argon2d$v=19$m=256,t=2,p=1$c29tZXNhbHQ",
+                "must be `argon2i` or `argon2id`",
+            ),
+            (
+                "$argon2id$m=256,t=2,p=1$c29tZXNhbHQ",
+                "only Argon2 version 19",
+            ),
+            (
+                "$argon2id$v=16$m=256,t=2,p=1$c29tZXNhbHQ",
+                "only Argon2 version 19",
+            ),
+            (
+                "$argon2id$v=19$m=256,t=2,p=1,keyid=AA$c29tZXNhbHQ",
+                "unsupported param `keyid`",
+            ),
+            (
+                "$argon2id$v=19$m=256,p=1$c29tZXNhbHQ",
+                "param `t` is missing",
+            ),
+        ];
+
+        for &(phc, expected_message) in ARGON2_STRINGS {
+            let err = argon2_from_phc(phc).unwrap_err();
+            assert_matches!(
+                err,
+                Error::KdfParams(ref e) if e.to_string().contains(expected_message),
+                "{}",
+                phc
+            );
+        }
+    }
+
+    #[cfg(any(feature = "exonum_sodiumoxide", feature = "rust-crypto"))]
+    #[test]
+    fn converting_scrypt_params_to_nacl_limits() {
+        let light = ScryptParams::light();
+        let phc = scrypt_to_phc(light, b"somesalt");
+        let ((opslimit, memlimit), _) = nacl_scrypt_from_phc(&phc).unwrap();
+        let converted = ScryptParams::from_nacl_limits(opslimit, memlimit);
+        assert_eq!((converted.log_n, converted.r, converted.p), (12, 8, 6));
+
+        for &phc in &[
+            "$scrypt$ln=14,r=4,p=1$c29tZXNhbHQ",
+            "$scrypt$ln=30,r=8,p=1$c29tZXNhbHQ",
+        ] {
+            let err = nacl_scrypt_from_phc(phc).unwrap_err();
+            assert_matches!(err, Error::KdfParams(ref e) if e.to_string().contains("libsodium"));
+        }
+    }
+
+    /// Checks that the KDF params and the salt encoded in a PHC string with a hash
+    /// produce the hash.
+    #[cfg(any(feature = "pure", feature = "rust-crypto"))]
+    fn check_test_vector<K: PhcString>(phc: &str, password: &[u8]) {
+        let (kdf, salt) = K::from_phc_string(phc).unwrap();
+        let hash_start = phc.rfind('$').unwrap();
+        let expected_hash = decode_base64(&phc[(hash_start + 1)..]).unwrap();
+        let mut hash = vec![0; expected_hash.len()];
+        kdf.derive_key(&mut hash, password, &salt).unwrap();
+        assert_eq!(hash, expected_hash);
+        assert_eq!(kdf.to_phc_string(&salt), phc[..hash_start]);
+    }
+
+    // From RFC 7914.
+    #[cfg(any(feature = "pure", feature = "rust-crypto"))]
+    const SCRYPT_VECTOR: &str = "$scrypt$ln=10,r=8,p=16$TmFDbA\
+        $/bq+HJ00cgB4VucZDQHp/nxq18vII3gw53N2Y0s3MWIurzDZLiKjiG/xCSedmDDaxyevuUqD7m2DYMvfoswGQA";
+
+    #[cfg(feature = "pure")]
+    #[test]
+    fn pure_scrypt_test_vector() {
+        check_test_vector::<crate::pure::Scrypt>(SCRYPT_VECTOR, b"password");
+    }
+
+    // Argon2 vectors are from the reference implementation.
+    #[cfg(feature = "rust-crypto")]
+    #[test]
+    fn rust_crypto_test_vectors() {
+        use crate::rcrypto::{Argon2, Scrypt};
+
+        check_test_vector::<Scrypt>(SCRYPT_VECTOR, b"password");
+        check_test_vector::<Argon2>(
+            "$argon2i$v=19$m=256,t=2,p=1$c29tZXNhbHQ$iekCn0Y3spW+sCcFanM2xBT63UP2sghkUoHLIUpWRS8",
+            b"password",
+        );
+        check_test_vector::<Argon2>(
+            "$argon2id$v=19$m=256,t=2,p=1$c29tZXNhbHQ$nf65EOgLrQMR/uIPnA4rEsF5h7TKyQwu9U1bMCHGi/4",
+            b"password",
+        );
+    }
+
+    #[cfg(all(feature = "exonum_sodiumoxide", feature = "rust-crypto"))]
+    #[test]
+    fn sodium_and_rust_crypto_conversions_agree() {
+        use crate::{rcrypto, sodium};
+
+        let salt = [0; 32];
+        let phc = sodium::Scrypt::light().to_phc_string(&salt);
+        assert_eq!(phc, scrypt_to_phc(ScryptParams::light(), &salt));
+        let (kdf, _) = rcrypto::ScryptNacl::from_phc_string(&phc).unwrap();
+        assert_eq!(kdf.to_phc_string(&salt), phc);
+        let (kdf, _) = sodium::Scrypt::from_phc_string(&phc).unwrap();
+        let (other_kdf, _) = rcrypto::ScryptNacl::from_phc_string(&phc).unwrap();
+        assert_eq!(
+            (kdf.opslimit, kdf.memlimit),
+            (other_kdf.opslimit, other_kdf.memlimit)
+        );
+    }
+}
@@ -17,14 +17,19 @@
 
 use anyhow::Error;
 use chacha20poly1305::{
-    aead::{generic_array::GenericArray, Aead, NewAead},
+    aead::{generic_array::GenericArray, AeadInPlace, NewAead},
     ChaCha20Poly1305,
 };
 use scrypt::{scrypt, ScryptParams as Params};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    alloc::Vec, Cipher, CipherOutput, DeriveKey, Eraser, MacMismatch, ScryptParams, Suite,
+    alloc::{String, Vec},
+    names, phc,
+    tune::TunableKdf,
+    utils::{MAX_SALT_LEN, MIN_SALT_LEN},
+    Cipher, CipherOutput, CipherOutputRef, DeriveKey, Eraser, ExternalKdf, KdfCost, KdfDifficulty,
+    KdfPreset, MacMismatch, NoKdf, PhcString, ScryptParams, Suite,
 };
 
 impl Cipher for ChaCha20Poly1305 {
@@ -32,38 +37,55 @@ impl Cipher for ChaCha20Poly1305 {
     const NONCE_LEN: usize = 12;
     const MAC_LEN: usize = 16;
 
-    fn seal(message: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
-        let mut buffer = Self::new(GenericArray::from_slice(key))
-            .encrypt(GenericArray::from_slice(nonce), message)
+    fn seal(message: &[u8], associated_data: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
+        CipherOutput::seal_with::<Self>(message, associated_data, nonce, key)
+    }
+
+    fn seal_into(
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        out: &mut CipherOutputRef<'_>,
+    ) {
+        out.ciphertext.copy_from_slice(message);
+        let mac = Self::new(GenericArray::from_slice(key))
+            .encrypt_in_place_detached(
+                GenericArray::from_slice(nonce),
+                associated_data,
+                out.ciphertext,
+            )
             .expect("Cannot encrypt with ChaCha20Poly1305");
-        assert!(
-            buffer.len() > Self::MAC_LEN,
-            "Insufficient ciphertext length"
-        );
-        let mac = buffer.split_off(buffer.len() - Self::MAC_LEN);
-        CipherOutput {
-            ciphertext: buffer,
-            mac,
-        }
+        out.mac.copy_from_slice(&mac);
     }
 
-    #[allow(clippy::unknown_clippy_lints, clippy::map_err_ignore)]
-    // ^-- The error returned by `ChaCha20Poly1305` is opaque, so ignoring it doesn't lose info.
     fn open(
         output: &mut [u8],
         encrypted: &CipherOutput,
+        associated_data: &[u8],
         nonce: &[u8],
         key: &[u8],
     ) -> Result<(), MacMismatch> {
-        let mut encryption = Vec::with_capacity(encrypted.ciphertext.len() + Self::MAC_LEN);
-        encryption.extend_from_slice(&encrypted.ciphertext);
-        encryption.extend_from_slice(&encrypted.mac);
+        output.copy_from_slice(&encrypted.ciphertext);
+        Self::open_in_place(output, &encrypted.mac, associated_data, nonce, key)
+    }
 
+    #[allow(clippy::map_err_ignore)]
+    // ^-- The error returned by `ChaCha20Poly1305` is opaque, so ignoring it doesn't lose info.
+    fn open_in_place(
+        buffer: &mut [u8],
+        mac: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
         Self::new(GenericArray::from_slice(key))
-            .decrypt(GenericArray::from_slice(nonce), &*encryption)
-            .map(|plaintext| {
-                output.copy_from_slice(&plaintext);
-            })
+            .decrypt_in_place_detached(
+                GenericArray::from_slice(nonce),
+                associated_data,
+                buffer,
+                GenericArray::from_slice(mac),
+            )
             .map_err(|_| MacMismatch)
     }
 }
@@ -78,8 +100,13 @@ impl DeriveKey for Scrypt {
         32
     }
 
+    fn valid_salt_len(&self, len: usize) -> bool {
+        (MIN_SALT_LEN..=MAX_SALT_LEN).contains(&len)
+    }
+
     #[cfg(feature = "std")]
     fn derive_key(&self, buf: &mut [u8], password: &[u8], salt: &[u8]) -> Result<(), Error> {
+        self.0.validate()?;
         let params = Params::new(self.0.log_n, self.0.r, self.0.p)?;
         scrypt(password, salt, &params, buf).map_err(Error::new)
     }
@@ -87,9 +114,56 @@ impl DeriveKey for Scrypt {
     #[cfg(not(feature = "std"))]
     fn derive_key(&self, buf: &mut [u8], password: &[u8], salt: &[u8]) -> Result<(), Error> {
         // Without `std`, we need to use more dumb conversions to `anyhow::Error`.
+        self.0.validate()?;
         let params = Params::new(self.0.log_n, self.0.r, self.0.p).map_err(Error::msg)?;
         scrypt(password, salt, &params, buf).map_err(Error::msg)
     }
+
+    fn validate_params(&self) -> Result<(), Error> {
+        self.0.validate()
+    }
+
+    fn cost(&self) -> Option<KdfCost> {
+        Some(self.0.cost())
+    }
+}
+
+impl KdfDifficulty for Scrypt {
+    fn preset(preset: KdfPreset) -> Self {
+        Scrypt(ScryptParams::preset(preset))
+    }
+
+    fn memory_cost(&self) -> u64 {
+        self.0.memory_cost()
+    }
+
+    fn ops_cost(&self) -> u64 {
+        self.0.ops_cost()
+    }
+}
+
+impl TunableKdf for Scrypt {
+    fn min_difficulty() -> Self {
+        Scrypt(ScryptParams::min_tunable())
+    }
+
+    fn double_memory(&self) -> Option<Self> {
+        self.0.double_memory().map(Scrypt)
+    }
+
+    fn multiply_time(&self, multiplier: u32) -> Option<Self> {
+        self.0.multiply_time(multiplier).map(Scrypt)
+    }
+}
+
+impl PhcString for Scrypt {
+    fn to_phc_string(&self, salt: &[u8]) -> String {
+        phc::scrypt_to_phc(self.0, salt)
+    }
+
+    fn from_phc_string(phc: &str) -> Result<(Self, Vec<u8>), crate::Error> {
+        phc::scrypt_from_phc(phc).map(|(params, salt)| (Scrypt(params), salt))
+    }
 }
 
 /// Suite for password-based encryption provided by pure-Rust crypto primitives.
@@ -101,6 +175,8 @@ impl DeriveKey for Scrypt {
 /// # KDFs
 ///
 /// - `scrypt`: `scrypt` KDF with the original paper parametrization.
+/// - `none`: [`NoKdf`](crate::NoKdf), i.e., using the password as the key
+///   (`raw` is accepted as an alias).
 ///
 /// # Examples
 ///
@@ -120,7 +196,7 @@ impl DeriveKey for Scrypt {
 ///
 /// // Read from existing box.
 /// let mut eraser = Eraser::new();
-/// eraser.add_suite::<PureCrypto>();
+/// eraser.add_suite::<PureCrypto>().unwrap();
 /// let erased: ErasedPwBox = // deserialized from some format
 /// #   eraser.erase(&pwbox).unwrap();
 /// let plaintext = eraser.restore(&erased)?.open(b"correct horse")?;
@@ -135,17 +211,27 @@ impl Suite for PureCrypto {
     type Cipher = ChaCha20Poly1305;
     type DeriveKey = Scrypt;
 
-    fn add_ciphers_and_kdfs(eraser: &mut Eraser) {
+    fn add_ciphers_and_kdfs(eraser: &mut Eraser) -> Result<(), crate::Error> {
         eraser
-            .add_kdf::<Scrypt>("scrypt")
-            .add_cipher::<ChaCha20Poly1305>("chacha20-poly1305");
+            .add_phc_kdf::<Scrypt>(names::SCRYPT)?
+            .add_kdf::<NoKdf>(names::NONE)?
+            .add_alias(names::RAW, names::NONE)?
+            .add_kdf::<ExternalKdf>(names::EXTERNAL)?
+            .add_cipher::<ChaCha20Poly1305>(names::CHACHA20_POLY1305)?;
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{erased::test_kdf_and_cipher_corruption, test_kdf_and_cipher};
+    use crate::{
+        erased::{
+            test_kdf_and_cipher_corruption, test_kdf_and_cipher_message_sizes,
+            test_kdf_and_cipher_with_aad, test_kdf_presets,
+        },
+        test_kdf_and_cipher,
+    };
     use rand::{thread_rng, Rng};
 
     #[test]
@@ -153,20 +239,27 @@ mod tests {
         let nonce = [0; ChaCha20Poly1305::NONCE_LEN];
         let mut rng = thread_rng();
         let key: [u8; ChaCha20Poly1305::KEY_LEN] = rng.gen();
-        let mut encrypted = ChaCha20Poly1305::seal(b"Foobar", &nonce, &key);
+        let mut encrypted = ChaCha20Poly1305::seal(b"Foobar", b"context", &nonce, &key);
         assert_eq!(encrypted.ciphertext.len(), 6);
         let mut decrypted = [0_u8; 6];
-        ChaCha20Poly1305::open(&mut decrypted, &encrypted, &nonce, &key).unwrap();
+        ChaCha20Poly1305::open(&mut decrypted, &encrypted, b"context", &nonce, &key).unwrap();
         assert_eq!(decrypted, *b"Foobar");
 
+        // Use different associated data.
+        assert!(ChaCha20Poly1305::open(&mut decrypted, &encrypted, &[], &nonce, &key).is_err());
+
         // Maul the MAC.
         encrypted.mac[11] ^= 1;
-        assert!(ChaCha20Poly1305::open(&mut decrypted, &encrypted, &nonce, &key).is_err());
+        assert!(
+            ChaCha20Poly1305::open(&mut decrypted, &encrypted, b"context", &nonce, &key).is_err()
+        );
         encrypted.mac[11] ^= 1;
 
         // Maul the ciphertext.
         encrypted.ciphertext[2] ^= 16;
-        assert!(ChaCha20Poly1305::open(&mut decrypted, &encrypted, &nonce, &key).is_err());
+        assert!(
+            ChaCha20Poly1305::open(&mut decrypted, &encrypted, b"context", &nonce, &key).is_err()
+        );
     }
 
     #[test]
@@ -181,6 +274,23 @@ mod tests {
         test_kdf_and_cipher_corruption::<_, ChaCha20Poly1305>(scrypt);
     }
 
+    #[test]
+    fn scrypt_and_chacha_with_aad() {
+        let scrypt = Scrypt(ScryptParams::light());
+        test_kdf_and_cipher_with_aad::<_, ChaCha20Poly1305>(scrypt);
+    }
+
+    #[test]
+    fn scrypt_and_chacha_message_sizes() {
+        let scrypt = Scrypt(ScryptParams::light());
+        test_kdf_and_cipher_message_sizes::<_, ChaCha20Poly1305>(scrypt);
+    }
+
+    #[test]
+    fn scrypt_presets() {
+        test_kdf_presets::<Scrypt>();
+    }
+
     #[test]
     #[cfg(feature = "exonum_sodiumoxide")]
     fn compatibility_with_sodium() {
@@ -191,11 +301,11 @@ mod tests {
             .seal(b"correct horse", b"battery staple")
             .unwrap();
         let mut eraser = Eraser::new();
-        eraser.add_suite::<PureCrypto>();
+        eraser.add_suite::<PureCrypto>().unwrap();
         let encrypted = eraser.erase(&encrypted).unwrap();
 
         let mut eraser = Eraser::new();
-        eraser.add_suite::<Sodium>();
+        eraser.add_suite::<Sodium>().unwrap();
         let encrypted = eraser.restore(&encrypted).unwrap();
         assert_eq!(
             encrypted.open(b"correct horse").unwrap().as_ref(),
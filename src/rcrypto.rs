@@ -14,23 +14,53 @@
 
 //! `rust-crypto` cryptographic backend.
 
-use anyhow::Error;
+use anyhow::{bail, Error};
+use argon2::{Config, ThreadMode, Variant, Version};
+use constant_time_eq::constant_time_eq;
 use crypto::{
     aead::{AeadDecryptor, AeadEncryptor},
     aes, aes_gcm,
+    chacha20::ChaCha20,
     digest::Digest,
+    hmac::Hmac,
+    mac::{Mac as _, MacResult},
+    pbkdf2::pbkdf2,
+    poly1305::Poly1305,
+    salsa20::Salsa20,
     scrypt::{scrypt, ScryptParams as Params},
+    sha2::{Sha256, Sha512},
     sha3::Sha3,
+    symmetriccipher::SynchronousStreamCipher,
 };
 use serde::{Deserialize, Serialize};
-use zeroize::Zeroizing;
+
+use core::convert::TryFrom;
 
 use crate::{
-    alloc::{vec, Vec},
-    Cipher, CipherOutput, CipherWithMac, DeriveKey, Eraser, Mac, MacMismatch, ScryptParams, Suite,
-    UnauthenticatedCipher,
+    alloc::{vec, String, Vec},
+    names, phc,
+    tune::TunableKdf,
+    utils::{MAX_SALT_LEN, MIN_SALT_LEN},
+    Argon2Params, Argon2Variant, Cipher, CipherOutput, CipherOutputRef, CipherWithMac, DeriveKey,
+    Eraser, ExternalKdf, KdfCost, KdfDifficulty, KdfPreset, Mac, MacMismatch, NoKdf, PhcString,
+    ScryptParams, SecretVec, Suite, UnauthenticatedCipher,
 };
 
+/// Byte size of the scratch buffer used by [`process_in_place()`].
+const IN_PLACE_CHUNK_LEN: usize = 4_096;
+
+/// Applies the stream `cipher` to the `buffer` in place. `rust-crypto` stream ciphers
+/// need distinct input and output buffers, so the buffer is processed chunk by chunk
+/// via a small scratch buffer rather than copied as a whole.
+fn process_in_place(cipher: &mut dyn SynchronousStreamCipher, buffer: &mut [u8]) {
+    let mut scratch = SecretVec::zeros(IN_PLACE_CHUNK_LEN.min(buffer.len()));
+    for chunk in buffer.chunks_mut(IN_PLACE_CHUNK_LEN) {
+        let scratch = &mut scratch[..chunk.len()];
+        cipher.process(chunk, scratch);
+        chunk.copy_from_slice(scratch);
+    }
+}
+
 /// AES-128 cipher in CTR mode.
 ///
 /// This cipher is used as a part of Ethereum keystores. Note that as this cipher
@@ -44,9 +74,26 @@ impl UnauthenticatedCipher for Aes128Ctr {
     const NONCE_LEN: usize = 16;
 
     fn seal_or_open(message: &mut [u8], nonce: &[u8], key: &[u8]) {
-        let mut output = Zeroizing::new(vec![0; message.len()]);
-        aes::ctr(aes::KeySize::KeySize128, key, nonce).process(message, &mut *output);
-        message.copy_from_slice(&output);
+        let mut cipher = aes::ctr(aes::KeySize::KeySize128, key, nonce);
+        process_in_place(&mut *cipher, message);
+    }
+}
+
+/// AES-256 cipher in CTR mode.
+///
+/// This cipher is emitted by some Ethereum keystore tooling. As with [`Aes128Ctr`], the cipher
+/// is not authenticated, so it should be paired with a MAC construction (e.g., `Keccak256`)
+/// in order to create a `Cipher`.
+#[derive(Debug)]
+pub struct Aes256Ctr(());
+
+impl UnauthenticatedCipher for Aes256Ctr {
+    const KEY_LEN: usize = 32;
+    const NONCE_LEN: usize = 16;
+
+    fn seal_or_open(message: &mut [u8], nonce: &[u8], key: &[u8]) {
+        let mut cipher = aes::ctr(aes::KeySize::KeySize256, key, nonce);
+        process_in_place(&mut *cipher, message);
     }
 }
 
@@ -88,7 +135,51 @@ impl Mac for Keccak256 {
     }
 }
 
+/// HMAC construction based on SHA-512 hash function, as specified in [RFC 2104].
+///
+/// HMAC accepts keys of any length; the key length used within [`CipherWithMac`]
+/// is 32 bytes.
+///
+/// [RFC 2104]: https://tools.ietf.org/html/rfc2104
+#[derive(Debug)]
+pub struct HmacSha512(());
+
+impl Mac for HmacSha512 {
+    const KEY_LEN: usize = 32;
+    const MAC_LEN: usize = 64;
+
+    fn digest(key: &[u8], message: &[u8]) -> Vec<u8> {
+        let mut hmac = Hmac::new(Sha512::new(), key);
+        hmac.input(message);
+        let mut output = vec![0_u8; Self::MAC_LEN];
+        hmac.raw_result(&mut output);
+        output
+    }
+
+    /// Uses the `rust-crypto` MAC comparison, which is constant-time.
+    fn verify(key: &[u8], message: &[u8], mac: &[u8]) -> Result<(), MacMismatch> {
+        let mut hmac = Hmac::new(Sha512::new(), key);
+        hmac.input(message);
+        if hmac.result() == MacResult::new(mac) {
+            Ok(())
+        } else {
+            Err(MacMismatch)
+        }
+    }
+}
+
+/// AES-128 cipher in CTR mode authenticated with HMAC-SHA-512.
+pub type Aes128CtrHmacSha512 = CipherWithMac<Aes128Ctr, HmacSha512>;
+
+/// AES-256 cipher in CTR mode authenticated with HMAC-SHA-512.
+pub type Aes256CtrHmacSha512 = CipherWithMac<Aes256Ctr, HmacSha512>;
+
 /// RustCrypto wrapper around scrypt.
+///
+/// The implementation requires params to satisfy `n < 2^(16 * r)` as per the scrypt
+/// specification; deriving a key with params violating this requirement returns an error.
+/// Note that some Ethereum keystores (e.g., the scrypt test vector from the Web3 Secret Storage
+/// definition) use such params.
 #[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
 #[serde(transparent)]
 pub struct Scrypt(pub ScryptParams);
@@ -98,213 +189,1626 @@ impl DeriveKey for Scrypt {
         32
     }
 
+    fn valid_salt_len(&self, len: usize) -> bool {
+        (MIN_SALT_LEN..=MAX_SALT_LEN).contains(&len)
+    }
+
     fn derive_key(&self, buf: &mut [u8], password: &[u8], salt: &[u8]) -> Result<(), Error> {
+        // `rust-crypto` panics on invalid params, so we check them beforehand.
+        self.0.validate()?;
         let params = Params::new(self.0.log_n, self.0.r, self.0.p);
         scrypt(password, salt, &params, buf);
         Ok(())
     }
+
+    fn validate_params(&self) -> Result<(), Error> {
+        self.0.validate()
+    }
+
+    fn cost(&self) -> Option<KdfCost> {
+        Some(self.0.cost())
+    }
 }
 
-/// AES-128 cipher in GCM mode.
-///
-/// # Implementation note
-///
-/// The GCM mode allows authenticating public data in addition to the ciphertext;
-/// for this application, this additional data is an empty slice `&[]`.
-#[derive(Debug, Clone, Copy, Default)]
-pub struct Aes128Gcm;
+impl KdfDifficulty for Scrypt {
+    fn preset(preset: KdfPreset) -> Self {
+        Scrypt(ScryptParams::preset(preset))
+    }
 
-impl Cipher for Aes128Gcm {
-    const KEY_LEN: usize = 16;
-    const NONCE_LEN: usize = 12;
-    const MAC_LEN: usize = 16;
+    fn memory_cost(&self) -> u64 {
+        self.0.memory_cost()
+    }
 
-    fn seal(message: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
-        // We don't use additional data (the last parameter to the constructor).
-        let mut cipher = aes_gcm::AesGcm::new(aes::KeySize::KeySize128, key, nonce, &[]);
-        let mut ciphertext = vec![0_u8; message.len()];
-        let mut mac = vec![0_u8; Self::MAC_LEN];
-        cipher.encrypt(message, &mut ciphertext, &mut mac);
-        CipherOutput { ciphertext, mac }
+    fn ops_cost(&self) -> u64 {
+        self.0.ops_cost()
     }
+}
 
-    fn open(
-        output: &mut [u8],
-        enc: &CipherOutput,
-        nonce: &[u8],
-        key: &[u8],
-    ) -> Result<(), MacMismatch> {
-        let mut cipher = aes_gcm::AesGcm::new(aes::KeySize::KeySize128, key, nonce, &[]);
+impl TunableKdf for Scrypt {
+    fn min_difficulty() -> Self {
+        Scrypt(ScryptParams::min_tunable())
+    }
 
-        if cipher.decrypt(&enc.ciphertext, output, &enc.mac) {
-            Ok(())
-        } else {
-            Err(MacMismatch)
-        }
+    fn double_memory(&self) -> Option<Self> {
+        self.0.double_memory().map(Scrypt)
+    }
+
+    fn multiply_time(&self, multiplier: u32) -> Option<Self> {
+        self.0.multiply_time(multiplier).map(Scrypt)
     }
 }
 
-/// Suite for password-based encryption provided by `rust-crypto`.
-///
-/// # Ciphers
-///
-/// - `aes-128-ctr`: AES-128 cipher in CTR mode with Keccak256-based MAC
-/// - `aes-128-gcm`: AES-128 cipher in GCM mode
+impl PhcString for Scrypt {
+    fn to_phc_string(&self, salt: &[u8]) -> String {
+        phc::scrypt_to_phc(self.0, salt)
+    }
+
+    fn from_phc_string(phc: &str) -> Result<(Self, Vec<u8>), crate::Error> {
+        phc::scrypt_from_phc(phc).map(|(params, salt)| (Scrypt(params), salt))
+    }
+}
+
+/// RustCrypto wrapper around scrypt with the `libsodium` parametrization, i.e., via
+/// `opslimit` and `memlimit`.
 ///
-/// # KDFs
+/// The KDF is compatible with [the `libsodium` implementation](crate::sodium::Scrypt):
+/// the params are serialized in the same way, and are converted to the original
+/// parametrization using the same algorithm as in `libsodium`.
+/// Thus, boxes sealed with one implementation can be opened with the other one.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ScryptNacl {
+    /// Parameter determining the computational hardness of the KDF.
+    ///
+    /// The default value is `1 << 19`.
+    pub opslimit: u32,
+
+    /// Parameter determining the RAM consumption of the KDF, in bytes.
+    ///
+    /// The default value is `1 << 24`.
+    pub memlimit: u32,
+}
+
+impl Default for ScryptNacl {
+    /// Returns the "interactive" `scrypt` parameters as defined in libsodium.
+    fn default() -> Self {
+        ScryptNacl {
+            opslimit: 1 << 19,
+            memlimit: 1 << 24,
+        }
+    }
+}
+
+impl DeriveKey for ScryptNacl {
+    fn salt_len(&self) -> usize {
+        32
+    }
+
+    fn derive_key(&self, buf: &mut [u8], password: &[u8], salt: &[u8]) -> Result<(), Error> {
+        let params = ScryptParams::from_nacl_limits(self.opslimit, self.memlimit);
+        Scrypt(params).derive_key(buf, password, salt)
+    }
+
+    fn validate_params(&self) -> Result<(), Error> {
+        ScryptParams::from_nacl_limits(self.opslimit, self.memlimit).validate()
+    }
+
+    fn cost(&self) -> Option<KdfCost> {
+        Some(ScryptParams::from_nacl_limits(self.opslimit, self.memlimit).cost())
+    }
+}
+
+/// The limits are converted to the original parametrization in the same way
+/// as for [the `libsodium` implementation](crate::sodium::Scrypt).
+impl PhcString for ScryptNacl {
+    fn to_phc_string(&self, salt: &[u8]) -> String {
+        let params = ScryptParams::from_nacl_limits(self.opslimit, self.memlimit);
+        phc::scrypt_to_phc(params, salt)
+    }
+
+    fn from_phc_string(phc: &str) -> Result<(Self, Vec<u8>), crate::Error> {
+        let ((opslimit, memlimit), salt) = phc::nacl_scrypt_from_phc(phc)?;
+        Ok((ScryptNacl { opslimit, memlimit }, salt))
+    }
+}
+
+/// Pseudo-random function used in [`Pbkdf2`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Pbkdf2Prf {
+    /// HMAC-SHA-256. This is the only PRF used in Ethereum keystores.
+    #[serde(rename = "hmac-sha256")]
+    HmacSha256,
+    /// HMAC-SHA-512.
+    #[serde(rename = "hmac-sha512")]
+    HmacSha512,
+}
+
+// `#[default]` enum variants are not supported by the minimum supported Rust version.
+#[allow(clippy::derivable_impls)]
+impl Default for Pbkdf2Prf {
+    fn default() -> Self {
+        Pbkdf2Prf::HmacSha256
+    }
+}
+
+/// PBKDF2 key derivation function with an HMAC-based pseudo-random function.
 ///
-/// - `scrypt`: `scrypt` KDF with the original parametrization (not the libsodium one)
+/// PBKDF2 is not memory-hard, so it is much more susceptible to brute-forcing
+/// on specialized hardware than `scrypt` or Argon2. It is provided for compatibility
+/// with existing Ethereum keystores and WebCrypto-based tools.
 ///
-/// # Examples
+/// # Serialization
 ///
-/// This suite can be used for compatibility with Ethereum keystores.
+/// The KDF is serialized as per the Ethereum keystore spec, with `c` (number of iterations),
+/// `prf` and optional `dklen` (derived key length) fields:
 ///
 /// ```
-/// use rand::thread_rng;
-/// use pwbox::{Eraser, ErasedPwBox, Suite, rcrypto::RustCrypto};
-/// # use pwbox::{rcrypto::Scrypt, Error, ScryptParams};
-///
-/// # fn main() -> Result<(), Error> {
-/// // Create a new box.
-/// let pwbox = RustCrypto::build_box(&mut thread_rng())
-/// #   .kdf(Scrypt(ScryptParams::custom(2, 1)))
-///     .seal(b"correct horse", b"battery staple")
-///     .unwrap();
+/// use serde_json::json;
+/// # use pwbox::rcrypto::Pbkdf2;
 ///
-/// // Read from existing box.
-/// let mut eraser = Eraser::new();
-/// eraser.add_suite::<RustCrypto>();
-/// let erased: ErasedPwBox = // deserialized from some format
-/// #   eraser.erase(&pwbox).unwrap();
-/// let plaintext = eraser.restore(&erased)?.open(b"correct horse")?;
-/// # assert_eq!(&*plaintext, b"battery staple");
-/// # Ok(())
-/// # }
+/// let pbkdf2 = Pbkdf2::default();
+/// assert_eq!(
+///     serde_json::to_value(pbkdf2).unwrap(),
+///     json!({ "c": 262_144, "prf": "hmac-sha256" })
+/// );
 /// ```
-#[derive(Debug)]
-pub struct RustCrypto(());
-
-impl Suite for RustCrypto {
-    type Cipher = CipherWithMac<Aes128Ctr, Keccak256>;
-    type DeriveKey = Scrypt;
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Pbkdf2 {
+    /// Number of iterations.
+    pub c: u32,
+    /// Pseudo-random function.
+    #[serde(default)]
+    pub prf: Pbkdf2Prf,
+    /// Length of the derived key. If set, it is checked to match the key length
+    /// of the cipher used with the KDF.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dklen: Option<u32>,
+}
 
-    fn add_ciphers_and_kdfs(eraser: &mut Eraser) {
-        // `aes-128-ctr` is the name used in Ethereum keystores. A more appropriate name
-        // would be something like `aes-128-ctr/keccak256`, but the shorter one is used here
-        // for compatibility.
-        eraser
-            .add_cipher::<Self::Cipher>("aes-128-ctr")
-            .add_cipher::<Aes128Gcm>("aes-128-gcm")
-            .add_kdf::<Scrypt>("scrypt");
+impl Default for Pbkdf2 {
+    /// Returns the parameters used by default in `geth`: 262,144 iterations of HMAC-SHA-256.
+    fn default() -> Self {
+        Pbkdf2 {
+            c: 1 << 18,
+            prf: Pbkdf2Prf::HmacSha256,
+            dklen: None,
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::{
-        erased::{test_kdf_and_cipher_corruption, ErasedPwBox},
-        test_kdf_and_cipher,
-    };
+impl DeriveKey for Pbkdf2 {
+    fn salt_len(&self) -> usize {
+        32
+    }
 
-    #[test]
-    fn aes_with_keccak_mac() {
-        use rand::{thread_rng, RngCore};
+    fn derive_key(&self, buf: &mut [u8], password: &[u8], salt: &[u8]) -> Result<(), Error> {
+        if self.c == 0 {
+            bail!("number of PBKDF2 iterations must be positive");
+        }
+        if let Some(dklen) = self.dklen {
+            if usize::try_from(dklen).map_or(true, |dklen| dklen != buf.len()) {
+                bail!("derived key length mismatch");
+            }
+        }
 
-        const MESSAGE: &[u8] = b"battery staple";
-        type Ci = CipherWithMac<Aes128Ctr, Keccak256>;
+        match self.prf {
+            Pbkdf2Prf::HmacSha256 => {
+                pbkdf2(&mut Hmac::new(Sha256::new(), password), salt, self.c, buf);
+            }
+            Pbkdf2Prf::HmacSha512 => {
+                pbkdf2(&mut Hmac::new(Sha512::new(), password), salt, self.c, buf);
+            }
+        }
+        Ok(())
+    }
 
-        let mut rng = thread_rng();
-        let mut key = vec![0; Ci::KEY_LEN];
-        rng.fill_bytes(&mut key);
-        let mut nonce = vec![0; Ci::NONCE_LEN];
-        rng.fill_bytes(&mut nonce);
+    fn validate_params(&self) -> Result<(), Error> {
+        if self.c == 0 {
+            bail!("number of PBKDF2 iterations must be positive");
+        }
+        Ok(())
+    }
 
-        let mut sealed = Ci::seal(MESSAGE, &nonce, &key);
-        let mut plaintext = vec![0; MESSAGE.len()];
-        Ci::open(&mut plaintext, &sealed, &nonce, &key).unwrap();
-        assert_eq!(&*plaintext, MESSAGE);
+    fn cost(&self) -> Option<KdfCost> {
+        // Each iteration hashes 2 blocks of the underlying hash function.
+        let block_len = match self.prf {
+            Pbkdf2Prf::HmacSha256 => 64,
+            Pbkdf2Prf::HmacSha512 => 128,
+        };
+        Some(KdfCost {
+            memory_bytes: 0,
+            time_cost: u64::from(self.c) * 2 * block_len,
+        })
+    }
+}
 
-        // Corrupt MAC.
-        sealed.mac[0] ^= 1;
-        let mut plaintext = vec![0; MESSAGE.len()];
-        assert!(Ci::open(&mut plaintext, &sealed, &nonce, &key).is_err());
+/// Pure Rust implementation of Argon2 (version 0x13).
+///
+/// The KDF is compatible with [the `libsodium` implementation](crate::sodium::Argon2);
+/// thus, boxes sealed with one implementation can be opened with the other one,
+/// provided that `parallelism == 1`.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Argon2(pub Argon2Params);
+
+impl DeriveKey for Argon2 {
+    fn salt_len(&self) -> usize {
+        16
     }
 
-    // `rust-crypto` is quite slow in debug mode, so we use *very* easy parameters here
-    // (much easier than even `Scrypt::light()`) for the sake of testing.
-    fn light_scrypt() -> Scrypt {
-        Scrypt(ScryptParams::custom(6, 16))
+    fn valid_salt_len(&self, len: usize) -> bool {
+        (MIN_SALT_LEN..=MAX_SALT_LEN).contains(&len)
     }
 
-    #[test]
-    fn scrypt_and_aes128ctr() {
-        test_kdf_and_cipher::<_, CipherWithMac<Aes128Ctr, Keccak256>>(light_scrypt());
+    fn derive_key(&self, buf: &mut [u8], password: &[u8], salt: &[u8]) -> Result<(), Error> {
+        let variant = match self.0.variant {
+            Argon2Variant::Argon2i => Variant::Argon2i,
+            Argon2Variant::Argon2id => Variant::Argon2id,
+        };
+        let config = Config {
+            variant,
+            version: Version::Version13,
+            mem_cost: self.0.mem_cost,
+            time_cost: self.0.time_cost,
+            lanes: self.0.parallelism,
+            thread_mode: ThreadMode::from_threads(self.0.parallelism),
+            secret: &[],
+            ad: &[],
+            hash_length: u32::try_from(buf.len()).map_err(Error::msg)?,
+        };
+
+        let key = SecretVec::from(argon2::hash_raw(password, salt, &config).map_err(Error::msg)?);
+        buf.copy_from_slice(&key);
+        Ok(())
     }
 
-    #[test]
-    fn scrypt_and_aes128ctr_corruption() {
-        test_kdf_and_cipher_corruption::<_, CipherWithMac<Aes128Ctr, Keccak256>>(light_scrypt());
+    fn validate_params(&self) -> Result<(), Error> {
+        self.0.validate()
     }
 
-    #[test]
-    fn scrypt_and_aes128gcm() {
-        test_kdf_and_cipher::<_, Aes128Gcm>(light_scrypt());
+    fn cost(&self) -> Option<KdfCost> {
+        Some(self.0.cost())
     }
+}
 
-    #[test]
-    fn scrypt_and_aes128gcm_corruption() {
-        test_kdf_and_cipher_corruption::<_, Aes128Gcm>(light_scrypt());
+impl KdfDifficulty for Argon2 {
+    fn preset(preset: KdfPreset) -> Self {
+        Argon2(Argon2Params::preset(preset))
     }
 
-    #[test]
-    fn ethstore_functionality() {
-        use rand::thread_rng;
+    fn memory_cost(&self) -> u64 {
+        self.0.memory_cost()
+    }
 
-        const PASSWORD: &str = "correct horse battery staple";
-        const MESSAGE: &[u8] = b"1234567890";
+    fn ops_cost(&self) -> u64 {
+        self.0.ops_cost()
+    }
+}
 
-        let mut eraser = Eraser::new();
-        let eraser = eraser.add_suite::<RustCrypto>();
+impl TunableKdf for Argon2 {
+    fn min_difficulty() -> Self {
+        Argon2(Argon2Params::min_tunable())
+    }
 
-        let mut rng = thread_rng();
-        let pwbox = RustCrypto::build_box(&mut rng)
-            .kdf(light_scrypt())
-            .seal(PASSWORD, MESSAGE)
-            .unwrap();
+    fn double_memory(&self) -> Option<Self> {
+        self.0.double_memory().map(Argon2)
+    }
 
-        let erased_box = eraser.erase(&pwbox).unwrap();
-        let pwbox_copy = eraser.restore(&erased_box).unwrap();
-        assert_eq!(MESSAGE, &*pwbox_copy.open(PASSWORD).unwrap());
+    fn multiply_time(&self, multiplier: u32) -> Option<Self> {
+        self.0.multiply_time(multiplier).map(Argon2)
     }
+}
 
-    #[test]
-    fn ethstore_compatibility() {
-        const PASSWORD: &str = "foo";
-        const MESSAGE_HEX: &str = "fa7b3db73dc7dfdf8c5fbdb796d741e4488628c41fc4febd9160a866ba0f35";
-        const PWBOX: &str = r#"{
-            "cipher" : "aes-128-ctr",
-            "cipherparams" : {
-                "iv" : "e0c41130a323adc1446fc82f724bca2f"
-            },
-            "ciphertext" : "9517cd5bdbe69076f9bf5057248c6c050141e970efa36ce53692d5d59a3984",
-            "kdf" : "scrypt",
-            "kdfparams" : {
-                "dklen" : 32,
-                "n" : 2,
-                "r" : 8,
-                "p" : 1,
-                "salt" : "711f816911c92d649fb4c84b047915679933555030b3552c1212609b38208c63"
-            },
-            "mac" : "d5e116151c6aa71470e67a7d42c9620c75c4d23229847dcc127794f0732b0db5"
-        }"#;
+impl PhcString for Argon2 {
+    fn to_phc_string(&self, salt: &[u8]) -> String {
+        phc::argon2_to_phc(self.0, salt)
+    }
 
-        let mut eraser = Eraser::new();
-        let eraser = eraser.add_suite::<RustCrypto>();
+    fn from_phc_string(phc: &str) -> Result<(Self, Vec<u8>), crate::Error> {
+        phc::argon2_from_phc(phc).map(|(params, salt)| (Argon2(params), salt))
+    }
+}
 
-        let message = hex::decode(MESSAGE_HEX).unwrap();
-        let erased_box: ErasedPwBox = serde_json::from_str(&PWBOX).unwrap();
-        let pwbox = eraser.restore(&erased_box).unwrap();
-        assert_eq!(message, &*pwbox.open(PASSWORD).unwrap());
+/// Encrypts `message` with AES in GCM mode with the specified key size.
+fn seal_aes_gcm(
+    key_size: aes::KeySize,
+    message: &[u8],
+    associated_data: &[u8],
+    nonce: &[u8],
+    key: &[u8],
+    out: &mut CipherOutputRef<'_>,
+) {
+    let mut cipher = aes_gcm::AesGcm::new(key_size, key, nonce, associated_data);
+    cipher.encrypt(message, out.ciphertext, out.mac);
+}
+
+/// Decrypts the ciphertext with AES in GCM mode with the specified key size.
+fn open_aes_gcm(
+    key_size: aes::KeySize,
+    output: &mut [u8],
+    enc: &CipherOutput,
+    associated_data: &[u8],
+    nonce: &[u8],
+    key: &[u8],
+) -> Result<(), MacMismatch> {
+    let mut cipher = aes_gcm::AesGcm::new(key_size, key, nonce, associated_data);
+    if cipher.decrypt(&enc.ciphertext, output, &enc.mac) {
+        Ok(())
+    } else {
+        Err(MacMismatch)
+    }
+}
+
+/// AES-128 cipher in GCM mode.
+///
+/// # Implementation note
+///
+/// The GCM mode allows authenticating public data in addition to the ciphertext;
+/// for this application, this additional data is an empty slice `&[]`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Aes128Gcm;
+
+impl Cipher for Aes128Gcm {
+    const KEY_LEN: usize = 16;
+    const NONCE_LEN: usize = 12;
+    const MAC_LEN: usize = 16;
+
+    fn seal(message: &[u8], associated_data: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
+        CipherOutput::seal_with::<Self>(message, associated_data, nonce, key)
+    }
+
+    fn seal_into(
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        out: &mut CipherOutputRef<'_>,
+    ) {
+        let key_size = aes::KeySize::KeySize128;
+        seal_aes_gcm(key_size, message, associated_data, nonce, key, out);
+    }
+
+    fn open(
+        output: &mut [u8],
+        enc: &CipherOutput,
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        let key_size = aes::KeySize::KeySize128;
+        open_aes_gcm(key_size, output, enc, associated_data, nonce, key)
+    }
+}
+
+/// AES-256 cipher in GCM mode as per [NIST SP 800-38D].
+///
+/// The cipher uses 96-bit nonces; since nonces are generated randomly for each box,
+/// the same key should not be used to seal more than 2^32 boxes (which is not a concern
+/// for keys derived from passwords with a random salt).
+///
+/// [NIST SP 800-38D]: https://csrc.nist.gov/publications/detail/sp/800-38d/final
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Aes256Gcm;
+
+impl Cipher for Aes256Gcm {
+    const KEY_LEN: usize = 32;
+    const NONCE_LEN: usize = 12;
+    const MAC_LEN: usize = 16;
+
+    fn seal(message: &[u8], associated_data: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
+        CipherOutput::seal_with::<Self>(message, associated_data, nonce, key)
+    }
+
+    fn seal_into(
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        out: &mut CipherOutputRef<'_>,
+    ) {
+        let key_size = aes::KeySize::KeySize256;
+        seal_aes_gcm(key_size, message, associated_data, nonce, key, out);
+    }
+
+    fn open(
+        output: &mut [u8],
+        enc: &CipherOutput,
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        let key_size = aes::KeySize::KeySize256;
+        open_aes_gcm(key_size, output, enc, associated_data, nonce, key)
+    }
+}
+
+/// `xsalsa20` symmetric cipher with `poly1305` MAC, i.e., the `secretbox` construction
+/// from NaCl.
+///
+/// The cipher is compatible with [the `libsodium` implementation](crate::sodium::XSalsa20Poly1305).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct XSalsa20Poly1305;
+
+impl XSalsa20Poly1305 {
+    /// Initializes the stream cipher and the MAC. As per the `secretbox` construction,
+    /// the one-time MAC key is the first 32 bytes of the keystream, and the remaining
+    /// keystream is used for encryption.
+    fn init(nonce: &[u8], key: &[u8]) -> (Salsa20, Poly1305) {
+        let mut cipher = Salsa20::new_xsalsa20(key, nonce);
+        let mut mac_key = SecretVec::zeros(32);
+        cipher.process(&[0; 32], &mut mac_key);
+        (cipher, Poly1305::new(&mac_key))
+    }
+}
+
+impl Cipher for XSalsa20Poly1305 {
+    const KEY_LEN: usize = 32;
+    const NONCE_LEN: usize = 24;
+    const MAC_LEN: usize = 16;
+    /// `secretbox` construction does not support associated data.
+    const SUPPORTS_ASSOCIATED_DATA: bool = false;
+
+    fn seal(message: &[u8], associated_data: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
+        CipherOutput::seal_with::<Self>(message, associated_data, nonce, key)
+    }
+
+    fn seal_into(
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        out: &mut CipherOutputRef<'_>,
+    ) {
+        assert!(
+            associated_data.is_empty(),
+            "XSalsa20Poly1305 does not support associated data"
+        );
+        let (mut cipher, mut poly1305) = Self::init(nonce, key);
+        cipher.process(message, out.ciphertext);
+        poly1305.input(out.ciphertext);
+        poly1305.raw_result(out.mac);
+    }
+
+    fn open(
+        output: &mut [u8],
+        enc: &CipherOutput,
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        output.copy_from_slice(&enc.ciphertext);
+        Self::open_in_place(output, &enc.mac, associated_data, nonce, key)
+    }
+
+    fn open_in_place(
+        buffer: &mut [u8],
+        mac: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        if !associated_data.is_empty() {
+            // The box could not have been sealed with this associated data.
+            return Err(MacMismatch);
+        }
+        let (mut cipher, mut poly1305) = Self::init(nonce, key);
+        poly1305.input(buffer);
+        let mut expected_mac = [0_u8; 16];
+        poly1305.raw_result(&mut expected_mac);
+        if !constant_time_eq(&expected_mac, mac) {
+            return Err(MacMismatch);
+        }
+        process_in_place(&mut cipher, buffer);
+        Ok(())
+    }
+}
+
+/// `chacha20` symmetric cipher with `poly1305` MAC, i.e., the AEAD construction
+/// from [RFC 8439] with 96-bit nonces.
+///
+/// The cipher is compatible with [the `libsodium` implementation](crate::sodium::ChaCha20Poly1305)
+/// and is registered under the same name.
+///
+/// [RFC 8439]: https://tools.ietf.org/html/rfc8439
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChaCha20Poly1305;
+
+impl ChaCha20Poly1305 {
+    /// Initializes the stream cipher and the MAC. As per RFC 8439, the one-time MAC key
+    /// is the first 32 bytes of the keystream block with counter 0, and encryption starts
+    /// from the block with counter 1.
+    fn init(nonce: &[u8], key: &[u8]) -> (ChaCha20, Poly1305) {
+        let mut cipher = ChaCha20::new(key, nonce);
+        let mut mac_key = SecretVec::zeros(64);
+        cipher.process(&[0; 64], &mut mac_key);
+        (cipher, Poly1305::new(&mac_key[..32]))
+    }
+
+    /// Computes the MAC over the associated data and the ciphertext.
+    fn compute_mac(mut poly1305: Poly1305, associated_data: &[u8], ciphertext: &[u8]) -> [u8; 16] {
+        fn pad16(poly1305: &mut Poly1305, len: usize) {
+            let padding = (16 - len % 16) % 16;
+            poly1305.input(&[0; 16][..padding]);
+        }
+
+        poly1305.input(associated_data);
+        pad16(&mut poly1305, associated_data.len());
+        poly1305.input(ciphertext);
+        pad16(&mut poly1305, ciphertext.len());
+        poly1305.input(&(associated_data.len() as u64).to_le_bytes());
+        poly1305.input(&(ciphertext.len() as u64).to_le_bytes());
+
+        let mut mac = [0_u8; 16];
+        poly1305.raw_result(&mut mac);
+        mac
+    }
+}
+
+impl Cipher for ChaCha20Poly1305 {
+    const KEY_LEN: usize = 32;
+    const NONCE_LEN: usize = 12;
+    const MAC_LEN: usize = 16;
+
+    fn seal(message: &[u8], associated_data: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
+        CipherOutput::seal_with::<Self>(message, associated_data, nonce, key)
+    }
+
+    fn seal_into(
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        out: &mut CipherOutputRef<'_>,
+    ) {
+        let (mut cipher, poly1305) = Self::init(nonce, key);
+        cipher.process(message, out.ciphertext);
+        let mac = Self::compute_mac(poly1305, associated_data, out.ciphertext);
+        out.mac.copy_from_slice(&mac);
+    }
+
+    fn open(
+        output: &mut [u8],
+        enc: &CipherOutput,
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        output.copy_from_slice(&enc.ciphertext);
+        Self::open_in_place(output, &enc.mac, associated_data, nonce, key)
+    }
+
+    fn open_in_place(
+        buffer: &mut [u8],
+        mac: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        let (mut cipher, poly1305) = Self::init(nonce, key);
+        let expected_mac = Self::compute_mac(poly1305, associated_data, buffer);
+        if !constant_time_eq(&expected_mac, mac) {
+            return Err(MacMismatch);
+        }
+        process_in_place(&mut cipher, buffer);
+        Ok(())
+    }
+}
+
+/// Suite for password-based encryption provided by `rust-crypto`.
+///
+/// # Ciphers
+///
+/// - `aes-128-ctr`: AES-128 cipher in CTR mode with Keccak256-based MAC
+/// - `aes-256-ctr`: AES-256 cipher in CTR mode with Keccak256-based MAC
+/// - `aes-128-gcm`: AES-128 cipher in GCM mode
+/// - `aes-256-gcm`: AES-256 cipher in GCM mode
+/// - `aes-128-ctr/hmac-sha512`: AES-128 cipher in CTR mode with HMAC-SHA-512
+/// - `aes-256-ctr/hmac-sha512`: AES-256 cipher in CTR mode with HMAC-SHA-512
+/// - `xsalsa20-poly1305`: XSalsa20 stream cipher with Poly1305 MAC (compatible with
+///   the cipher with the same name from the [`Sodium`] suite)
+/// - `chacha20-poly1305`: ChaCha20-Poly1305 AEAD as per RFC 8439 (compatible with
+///   the cipher with the same name from the [`Sodium`] suite)
+///
+/// # KDFs
+///
+/// - `scrypt`: `scrypt` KDF with the original parametrization (not the libsodium one)
+/// - `scrypt-nacl`: `scrypt` KDF with the libsodium parametrization (compatible with
+///   the KDF with the same name from the [`Sodium`] suite)
+/// - `argon2`: Argon2 KDF (compatible with the `argon2` KDF from the [`Sodium`] suite)
+/// - `pbkdf2`: PBKDF2 KDF with HMAC-SHA-256 or HMAC-SHA-512
+/// - `none`: [`NoKdf`](crate::NoKdf), i.e., using the password as the key
+///   (`raw` is accepted as an alias)
+///
+/// [`Sodium`]: crate::sodium::Sodium
+///
+/// # Examples
+///
+/// This suite can be used for compatibility with Ethereum keystores.
+///
+/// ```
+/// use rand::thread_rng;
+/// use pwbox::{Eraser, ErasedPwBox, Suite, rcrypto::RustCrypto};
+/// # use pwbox::{rcrypto::Scrypt, Error, ScryptParams};
+///
+/// # fn main() -> Result<(), Error> {
+/// // Create a new box.
+/// let pwbox = RustCrypto::build_box(&mut thread_rng())
+/// #   .kdf(Scrypt(ScryptParams::custom(2, 1)))
+///     .seal(b"correct horse", b"battery staple")
+///     .unwrap();
+///
+/// // Read from existing box.
+/// let mut eraser = Eraser::new();
+/// eraser.add_suite::<RustCrypto>().unwrap();
+/// let erased: ErasedPwBox = // deserialized from some format
+/// #   eraser.erase(&pwbox).unwrap();
+/// let plaintext = eraser.restore(&erased)?.open(b"correct horse")?;
+/// # assert_eq!(&*plaintext, b"battery staple");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct RustCrypto(());
+
+impl Suite for RustCrypto {
+    type Cipher = CipherWithMac<Aes128Ctr, Keccak256>;
+    type DeriveKey = Scrypt;
+
+    fn add_ciphers_and_kdfs(eraser: &mut Eraser) -> Result<(), crate::Error> {
+        // `aes-128-ctr` is the name used in Ethereum keystores. A more appropriate name
+        // would be something like `aes-128-ctr/keccak256`, but the shorter one is used here
+        // for compatibility.
+        eraser
+            .add_cipher::<Self::Cipher>(names::AES_128_CTR)?
+            .add_cipher::<CipherWithMac<Aes256Ctr, Keccak256>>(names::AES_256_CTR)?
+            .add_cipher::<Aes128Gcm>(names::AES_128_GCM)?
+            .add_cipher::<Aes256Gcm>(names::AES_256_GCM)?
+            .add_cipher::<Aes128CtrHmacSha512>(names::AES_128_CTR_HMAC_SHA512)?
+            .add_cipher::<Aes256CtrHmacSha512>(names::AES_256_CTR_HMAC_SHA512)?
+            .add_cipher::<XSalsa20Poly1305>(names::XSALSA20_POLY1305)?
+            .add_cipher::<ChaCha20Poly1305>(names::CHACHA20_POLY1305)?
+            .add_phc_kdf::<Scrypt>(names::SCRYPT)?
+            .add_phc_kdf::<ScryptNacl>(names::SCRYPT_NACL)?
+            .add_phc_kdf::<Argon2>(names::ARGON2)?
+            .add_kdf::<Pbkdf2>(names::PBKDF2)?
+            .add_kdf::<NoKdf>(names::NONE)?
+            .add_alias(names::RAW, names::NONE)?
+            .add_kdf::<ExternalKdf>(names::EXTERNAL)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        erased::{
+            test_kdf_and_cipher_corruption, test_kdf_and_cipher_message_sizes,
+            test_kdf_and_cipher_with_aad, test_kdf_presets, ErasedPwBox,
+        },
+        test_kdf_and_cipher, ConcatSplit, EthereumSplit, KeySplit,
+    };
+
+    #[test]
+    fn kdf_presets() {
+        test_kdf_presets::<Scrypt>();
+        test_kdf_presets::<Argon2>();
+    }
+
+    #[test]
+    fn aes_with_keccak_mac() {
+        use rand::{thread_rng, RngCore};
+
+        const MESSAGE: &[u8] = b"battery staple";
+        type Ci = CipherWithMac<Aes128Ctr, Keccak256>;
+
+        let mut rng = thread_rng();
+        let mut key = vec![0; Ci::KEY_LEN];
+        rng.fill_bytes(&mut key);
+        let mut nonce = vec![0; Ci::NONCE_LEN];
+        rng.fill_bytes(&mut nonce);
+
+        let mut sealed = Ci::seal(MESSAGE, &[], &nonce, &key);
+        let mut plaintext = vec![0; MESSAGE.len()];
+        Ci::open(&mut plaintext, &sealed, &[], &nonce, &key).unwrap();
+        assert_eq!(&*plaintext, MESSAGE);
+
+        // Corrupt MAC.
+        sealed.mac[0] ^= 1;
+        let mut plaintext = vec![0; MESSAGE.len()];
+        assert!(Ci::open(&mut plaintext, &sealed, &[], &nonce, &key).is_err());
+
+        // Use associated data.
+        let sealed = Ci::seal(MESSAGE, b"context", &nonce, &key);
+        assert_ne!(sealed.mac, Ci::seal(MESSAGE, &[], &nonce, &key).mac);
+        Ci::open(&mut plaintext, &sealed, b"context", &nonce, &key).unwrap();
+        assert_eq!(&*plaintext, MESSAGE);
+        assert!(Ci::open(&mut plaintext, &sealed, b"other", &nonce, &key).is_err());
+        assert!(Ci::open(&mut plaintext, &sealed, &[], &nonce, &key).is_err());
+    }
+
+    #[test]
+    fn keccak256_test_vector() {
+        const EMPTY_HASH: &str = "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470";
+        assert_eq!(hex::encode(Keccak256::digest(&[], &[])), EMPTY_HASH);
+        // The key is simply prepended to the message.
+        assert_eq!(
+            Keccak256::digest(b"correct horse", b" battery staple"),
+            Keccak256::digest(&[], b"correct horse battery staple")
+        );
+    }
+
+    // Test vectors from RFC 4231.
+    #[test]
+    fn hmac_sha512_test_vectors() {
+        const VECTORS: &[(&[u8], &[u8], &str)] = &[
+            (
+                &[0x0b; 20],
+                b"Hi There",
+                "87aa7cdea5ef619d4ff0b4241a1d6cb02379f4e2ce4ec2787ad0b30545e17cde\
+                 daa833b7d6b8a702038b274eaea3f4e4be9d914eeb61f1702e696c203a126854",
+            ),
+            (
+                b"Jefe",
+                b"what do ya want for nothing?",
+                "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea250554\
+                 9758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737",
+            ),
+        ];
+
+        for &(key, message, expected) in VECTORS {
+            let mut mac = hex::decode(expected).unwrap();
+            assert_eq!(hex::encode(HmacSha512::digest(key, message)), expected);
+            HmacSha512::verify(key, message, &mac).unwrap();
+            mac[63] ^= 1;
+            HmacSha512::verify(key, message, &mac).unwrap_err();
+            HmacSha512::verify(key, message, &mac[..32]).unwrap_err();
+        }
+    }
+
+    /// Checks that MAC verification takes the same time regardless of the position
+    /// of the first mismatching byte. Run with `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore = "statistical test; may be flaky on loaded machines"]
+    fn mac_verification_is_constant_time() {
+        use std::time::{Duration, Instant};
+
+        fn batch_duration<M: Mac>(key: &[u8], message: &[u8], mac: &[u8]) -> Duration {
+            const ITERATIONS: usize = 1_000;
+            let start = Instant::now();
+            for _ in 0..ITERATIONS {
+                assert!(M::verify(key, message, mac).is_err());
+            }
+            start.elapsed()
+        }
+
+        fn check_timing<M: Mac>() {
+            const ROUNDS: usize = 101;
+
+            let key = vec![7; M::KEY_LEN];
+            let message = b"correct horse battery staple";
+            let mac = M::digest(&key, message);
+            let (mut first_mismatch, mut last_mismatch) = (mac.clone(), mac);
+            first_mismatch[0] ^= 1;
+            last_mismatch[M::MAC_LEN - 1] ^= 1;
+
+            // Interleave measurements, so that they are equally affected by noise.
+            let (mut first_times, mut last_times) = (vec![], vec![]);
+            for _ in 0..ROUNDS {
+                first_times.push(batch_duration::<M>(&key, message, &first_mismatch));
+                last_times.push(batch_duration::<M>(&key, message, &last_mismatch));
+            }
+            first_times.sort();
+            last_times.sort();
+            let first = first_times[ROUNDS / 2].as_secs_f64();
+            let last = last_times[ROUNDS / 2].as_secs_f64();
+
+            let ratio = first.max(last) / first.min(last);
+            assert!(
+                ratio < 1.1,
+                "{}: mismatch at byte 0 takes {:?}, at last byte {:?}",
+                core::any::type_name::<M>(),
+                first_times[ROUNDS / 2],
+                last_times[ROUNDS / 2]
+            );
+        }
+
+        check_timing::<HmacSha512>();
+        check_timing::<Keccak256>();
+    }
+
+    // `rust-crypto` is quite slow in debug mode, so we use *very* easy parameters here
+    // (much easier than even `Scrypt::light()`) for the sake of testing.
+    fn light_scrypt() -> Scrypt {
+        Scrypt(ScryptParams::custom(6, 16))
+    }
+
+    #[test]
+    fn scrypt_with_invalid_params() {
+        use crate::PwBoxBuilder;
+        use rand::thread_rng;
+
+        let invalid_params = [
+            ScryptParams::custom(0, 1),
+            ScryptParams::custom(6, 0),
+            ScryptParams {
+                log_n: 18,
+                r: 1,
+                p: 8,
+            },
+            ScryptParams {
+                log_n: 63,
+                r: 8,
+                p: 1,
+            },
+            ScryptParams {
+                log_n: 6,
+                r: 1 << 16,
+                p: 1 << 16,
+            },
+        ];
+        for &params in &invalid_params {
+            let err = PwBoxBuilder::<_, Aes128Gcm>::new(&mut thread_rng())
+                .kdf(Scrypt(params))
+                .seal("password", b"message")
+                .unwrap_err();
+            assert!(err.to_string().contains("invalid scrypt params"));
+        }
+    }
+
+    #[test]
+    fn scrypt_and_aes128ctr() {
+        test_kdf_and_cipher::<_, CipherWithMac<Aes128Ctr, Keccak256>>(light_scrypt());
+    }
+
+    #[test]
+    fn scrypt_and_aes128ctr_corruption() {
+        test_kdf_and_cipher_corruption::<_, CipherWithMac<Aes128Ctr, Keccak256>>(light_scrypt());
+    }
+
+    #[test]
+    fn scrypt_and_aes256ctr() {
+        test_kdf_and_cipher::<_, CipherWithMac<Aes256Ctr, Keccak256>>(light_scrypt());
+    }
+
+    #[test]
+    fn scrypt_and_aes256ctr_corruption() {
+        test_kdf_and_cipher_corruption::<_, CipherWithMac<Aes256Ctr, Keccak256>>(light_scrypt());
+    }
+
+    #[test]
+    fn aes256ctr_erasure() {
+        use crate::{Error, PwBoxBuilder};
+        use assert_matches::assert_matches;
+        use rand::thread_rng;
+
+        const PASSWORD: &str = "correct horse battery staple";
+        const MESSAGE: &[u8] = b"1234567890";
+
+        let mut eraser = Eraser::new();
+        let eraser = eraser.add_suite::<RustCrypto>().unwrap();
+
+        let pwbox = PwBoxBuilder::<_, CipherWithMac<Aes256Ctr, Keccak256>>::new(&mut thread_rng())
+            .kdf(light_scrypt())
+            .seal(PASSWORD, MESSAGE)
+            .unwrap();
+        let erased_box = eraser.erase(&pwbox).unwrap();
+        let json = serde_json::to_value(&erased_box).unwrap();
+        assert_eq!(json["cipher"], "aes-256-ctr");
+
+        let pwbox_copy = eraser.restore(&erased_box).unwrap();
+        assert_eq!(MESSAGE, &*pwbox_copy.open(PASSWORD).unwrap());
+        assert_matches!(
+            pwbox_copy.open("incorrect password").unwrap_err(),
+            Error::MacMismatch
+        );
+    }
+
+    #[test]
+    fn scrypt_and_aes128gcm() {
+        test_kdf_and_cipher::<_, Aes128Gcm>(light_scrypt());
+    }
+
+    #[test]
+    fn scrypt_and_aes128gcm_corruption() {
+        test_kdf_and_cipher_corruption::<_, Aes128Gcm>(light_scrypt());
+    }
+
+    #[test]
+    fn scrypt_and_aes128gcm_with_aad() {
+        test_kdf_and_cipher_with_aad::<_, Aes128Gcm>(light_scrypt());
+    }
+
+    #[test]
+    fn scrypt_and_aes128ctr_with_aad() {
+        test_kdf_and_cipher_with_aad::<_, CipherWithMac<Aes128Ctr, Keccak256>>(light_scrypt());
+    }
+
+    // Minimal parameters accepted by both `rust-argon2` and `libsodium`.
+    fn light_argon2() -> Argon2 {
+        Argon2(Argon2Params::custom(Argon2Variant::Argon2id, 1 << 10, 2, 1))
+    }
+
+    #[test]
+    fn scrypt_and_aes256gcm() {
+        test_kdf_and_cipher::<_, Aes256Gcm>(light_scrypt());
+    }
+
+    #[test]
+    fn scrypt_and_aes256gcm_corruption() {
+        test_kdf_and_cipher_corruption::<_, Aes256Gcm>(light_scrypt());
+    }
+
+    #[test]
+    fn scrypt_and_aes256gcm_with_aad() {
+        test_kdf_and_cipher_with_aad::<_, Aes256Gcm>(light_scrypt());
+    }
+
+    #[test]
+    fn argon2_and_aes256gcm() {
+        test_kdf_and_cipher::<_, Aes256Gcm>(light_argon2());
+    }
+
+    #[test]
+    fn pbkdf2_and_aes256gcm() {
+        test_kdf_and_cipher::<_, Aes256Gcm>(light_pbkdf2(Pbkdf2Prf::HmacSha256));
+    }
+
+    #[test]
+    fn aes256gcm_test_vectors() {
+        // Test cases 13, 14 and 16 from "The Galois/Counter Mode of Operation (GCM)"
+        // by McGrew and Viega, which are included in NIST GCM validation data.
+        const KEY: &str = "feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308";
+        const NONCE: &str = "cafebabefacedbaddecaf888";
+        const PLAINTEXT: &str = "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72\
+            1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39";
+        const AAD: &str = "feedfacedeadbeeffeedfacedeadbeefabaddad2";
+        const CIPHERTEXT: &str = "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa\
+            8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662";
+        const MAC: &str = "76fc6ece0f4e1768cddf8853bb2d551b";
+
+        let zeros = [0_u8; 32];
+        let sealed = Aes256Gcm::seal(&[], &[], &zeros[..12], &zeros);
+        assert!(sealed.ciphertext.is_empty());
+        assert_eq!(hex::encode(&sealed.mac), "530f8afbc74536b9a963b4f1c4cb738b");
+        let sealed = Aes256Gcm::seal(&zeros[..16], &[], &zeros[..12], &zeros);
+        assert_eq!(
+            hex::encode(&sealed.ciphertext),
+            "cea7403d4d606b6e074ec5d3baf39d18"
+        );
+        assert_eq!(hex::encode(&sealed.mac), "d0d1c8a799996bf0265b98b5d48ab919");
+
+        let key = hex::decode(KEY).unwrap();
+        let nonce = hex::decode(NONCE).unwrap();
+        let message = hex::decode(PLAINTEXT).unwrap();
+        let aad = hex::decode(AAD).unwrap();
+        let sealed = Aes256Gcm::seal(&message, &aad, &nonce, &key);
+        assert_eq!(hex::encode(&sealed.ciphertext), CIPHERTEXT);
+        assert_eq!(hex::encode(&sealed.mac), MAC);
+
+        let mut plaintext = vec![0; message.len()];
+        Aes256Gcm::open(&mut plaintext, &sealed, &aad, &nonce, &key).unwrap();
+        assert_eq!(plaintext, message);
+        assert!(Aes256Gcm::open(&mut plaintext, &sealed, &[], &nonce, &key).is_err());
+        // AES-128-GCM with a truncated key produces a different ciphertext.
+        let sealed_128 = Aes128Gcm::seal(&message, &aad, &nonce, &key[..16]);
+        assert_ne!(sealed_128.ciphertext, sealed.ciphertext);
+    }
+
+    #[test]
+    fn argon2_and_aes128gcm() {
+        test_kdf_and_cipher::<_, Aes128Gcm>(light_argon2());
+    }
+
+    #[test]
+    fn argon2_and_aes128gcm_corruption() {
+        test_kdf_and_cipher_corruption::<_, Aes128Gcm>(light_argon2());
+    }
+
+    #[test]
+    fn argon2i_and_aes128ctr() {
+        let argon2 = Argon2(Argon2Params::custom(Argon2Variant::Argon2i, 1 << 10, 3, 1));
+        test_kdf_and_cipher::<_, CipherWithMac<Aes128Ctr, Keccak256>>(argon2);
+    }
+
+    #[test]
+    fn argon2_with_parallelism() {
+        let argon2 = Argon2(Argon2Params::custom(Argon2Variant::Argon2id, 1 << 10, 2, 4));
+        test_kdf_and_cipher::<_, Aes128Gcm>(argon2);
+    }
+
+    #[test]
+    fn argon2_box_json_roundtrip() {
+        use rand::thread_rng;
+
+        const PASSWORD: &str = "correct horse battery staple";
+        const MESSAGE: &[u8] = b"battery staple";
+
+        let pwbox = crate::PwBoxBuilder::<_, Aes128Gcm>::new(&mut thread_rng())
+            .kdf(light_argon2())
+            .seal(PASSWORD, MESSAGE)
+            .unwrap();
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<RustCrypto>().unwrap();
+        let json = serde_json::to_value(eraser.erase(&pwbox).unwrap()).unwrap();
+        assert_eq!(json["kdf"], "argon2");
+        assert_eq!(json["kdfparams"]["variant"], "argon2id");
+        assert_eq!(json["kdfparams"]["mem_cost"], 1 << 10);
+
+        let erased_box: ErasedPwBox = serde_json::from_value(json).unwrap();
+        let restored = eraser.restore(&erased_box).unwrap();
+        assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+    }
+
+    #[test]
+    fn kdf_cost_estimates() {
+        let cost = Pbkdf2::default().cost().unwrap();
+        assert_eq!(cost.memory_bytes, 0);
+        assert_eq!(cost.time_cost, 1 << 25);
+        let pbkdf2 = Pbkdf2 {
+            prf: Pbkdf2Prf::HmacSha512,
+            ..Pbkdf2::default()
+        };
+        assert_eq!(pbkdf2.cost().unwrap().time_cost, 1 << 26);
+        let pbkdf2 = Pbkdf2 {
+            c: 0,
+            ..Pbkdf2::default()
+        };
+        assert!(pbkdf2.validate_params().is_err());
+
+        let cost = Scrypt(crate::ScryptParams::custom(14, 1)).cost().unwrap();
+        assert_eq!(cost.memory_bytes, 1 << 24);
+        assert_eq!(cost.time_cost, 1 << 25);
+        let nacl_scrypt = ScryptNacl {
+            opslimit: 1 << 19,
+            memlimit: 1 << 24,
+        };
+        assert_eq!(nacl_scrypt.cost(), Some(cost));
+
+        let argon2 = Argon2(Argon2Params::custom(Argon2Variant::Argon2id, 1 << 10, 2, 4));
+        let cost = argon2.cost().unwrap();
+        assert_eq!(cost.memory_bytes, 1 << 20);
+        assert_eq!(cost.time_cost, 2 << 20);
+        let argon2 = Argon2(Argon2Params::custom(Argon2Variant::Argon2id, 16, 2, 4));
+        assert!(argon2.validate_params().is_err());
+    }
+
+    #[cfg(feature = "exonum_sodiumoxide")]
+    fn sodium_argon2_eraser() -> Eraser {
+        let mut eraser = Eraser::new();
+        eraser
+            .add_kdf::<crate::sodium::Argon2>("argon2")
+            .unwrap()
+            .add_cipher::<Aes128Gcm>("aes-128-gcm")
+            .unwrap();
+        eraser
+    }
+
+    #[test]
+    #[cfg(feature = "exonum_sodiumoxide")]
+    fn argon2_sodium_to_rcrypto() {
+        use crate::{sodium, PwBoxBuilder};
+        use rand::thread_rng;
+
+        const PASSWORD: &str = "correct horse battery staple";
+        const MESSAGE: &[u8] = b"battery staple";
+
+        let pwbox = PwBoxBuilder::<_, Aes128Gcm>::new(&mut thread_rng())
+            .kdf(sodium::Argon2(light_argon2().0))
+            .seal(PASSWORD, MESSAGE)
+            .unwrap();
+        let erased_box = sodium_argon2_eraser().erase(&pwbox).unwrap();
+
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<RustCrypto>().unwrap();
+        let restored = eraser.restore(&erased_box).unwrap();
+        assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+    }
+
+    #[test]
+    #[cfg(feature = "exonum_sodiumoxide")]
+    fn argon2_rcrypto_to_sodium() {
+        use rand::thread_rng;
+
+        const PASSWORD: &str = "correct horse battery staple";
+        const MESSAGE: &[u8] = b"battery staple";
+
+        let pwbox = crate::PwBoxBuilder::<_, Aes128Gcm>::new(&mut thread_rng())
+            .kdf(light_argon2())
+            .seal(PASSWORD, MESSAGE)
+            .unwrap();
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<RustCrypto>().unwrap();
+        let erased_box = eraser.erase(&pwbox).unwrap();
+
+        let restored = sodium_argon2_eraser().restore(&erased_box).unwrap();
+        assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+    }
+
+    // Minimal parameters: `n = 2^6, r = 8, p = 16`.
+    fn light_scrypt_nacl() -> ScryptNacl {
+        ScryptNacl {
+            opslimit: 1 << 15,
+            memlimit: 1 << 16,
+        }
+    }
+
+    #[test]
+    fn scrypt_nacl_and_xsalsa20() {
+        test_kdf_and_cipher::<_, XSalsa20Poly1305>(light_scrypt_nacl());
+    }
+
+    #[test]
+    fn scrypt_nacl_and_xsalsa20_corruption() {
+        test_kdf_and_cipher_corruption::<_, XSalsa20Poly1305>(light_scrypt_nacl());
+    }
+
+    #[cfg(feature = "exonum_sodiumoxide")]
+    fn cross_backend_erasers() -> (Eraser, Eraser) {
+        let mut sodium_eraser = Eraser::new();
+        sodium_eraser.add_suite::<crate::sodium::Sodium>().unwrap();
+        let mut rcrypto_eraser = Eraser::new();
+        rcrypto_eraser.add_suite::<RustCrypto>().unwrap();
+        (sodium_eraser, rcrypto_eraser)
+    }
+
+    #[test]
+    #[cfg(feature = "exonum_sodiumoxide")]
+    fn secretbox_sodium_to_rcrypto() {
+        use crate::{sodium, PwBoxBuilder};
+        use rand::thread_rng;
+
+        const PASSWORD: &str = "correct horse battery staple";
+        const MESSAGE: &[u8] = b"battery staple";
+
+        let light_scrypt = light_scrypt_nacl();
+        let pwbox = PwBoxBuilder::<_, sodium::XSalsa20Poly1305>::new(&mut thread_rng())
+            .kdf(sodium::Scrypt {
+                opslimit: light_scrypt.opslimit,
+                memlimit: light_scrypt.memlimit,
+            })
+            .seal(PASSWORD, MESSAGE)
+            .unwrap();
+        let (sodium_eraser, rcrypto_eraser) = cross_backend_erasers();
+        let erased_box = sodium_eraser.erase(&pwbox).unwrap();
+        assert_eq!(erased_box.kdf_name(), names::SCRYPT_NACL);
+        assert_eq!(erased_box.cipher_name(), names::XSALSA20_POLY1305);
+
+        let restored = rcrypto_eraser.restore(&erased_box).unwrap();
+        assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+        let pwbox = rcrypto_eraser
+            .restore_typed::<ScryptNacl, XSalsa20Poly1305>(&erased_box)
+            .unwrap();
+        assert_eq!(&*pwbox.open(PASSWORD).unwrap(), MESSAGE);
+    }
+
+    #[test]
+    #[cfg(feature = "exonum_sodiumoxide")]
+    fn secretbox_rcrypto_to_sodium() {
+        use crate::{sodium, PwBoxBuilder};
+        use rand::thread_rng;
+
+        const PASSWORD: &str = "correct horse battery staple";
+
+        let (sodium_eraser, rcrypto_eraser) = cross_backend_erasers();
+        for len in &[0, 1, 31, 32, 33, 64, 1_000] {
+            let message = vec![42_u8; *len];
+            let pwbox = PwBoxBuilder::<_, XSalsa20Poly1305>::new(&mut thread_rng())
+                .kdf(light_scrypt_nacl())
+                .seal(PASSWORD, &message)
+                .unwrap();
+            let erased_box = rcrypto_eraser.erase(&pwbox).unwrap();
+
+            let restored = sodium_eraser.restore(&erased_box).unwrap();
+            assert_eq!(&*restored.open(PASSWORD).unwrap(), &*message);
+            let pwbox = sodium_eraser
+                .restore_typed::<sodium::Scrypt, sodium::XSalsa20Poly1305>(&erased_box)
+                .unwrap();
+            assert_eq!(&*pwbox.open(PASSWORD).unwrap(), &*message);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "exonum_sodiumoxide")]
+    fn secretbox_with_sodium_defaults() {
+        use crate::sodium::Sodium;
+        use rand::thread_rng;
+
+        // Check the "interactive" libsodium params, which are converted non-trivially.
+        let pwbox = Sodium::build_box(&mut thread_rng())
+            .cipher::<crate::sodium::XSalsa20Poly1305>()
+            .seal("password", b"message")
+            .unwrap();
+        let (sodium_eraser, rcrypto_eraser) = cross_backend_erasers();
+        let erased_box = sodium_eraser.erase(&pwbox).unwrap();
+        let restored = rcrypto_eraser.restore(&erased_box).unwrap();
+        assert_eq!(&*restored.open("password").unwrap(), b"message");
+    }
+
+    #[test]
+    fn scrypt_and_chacha20poly1305() {
+        test_kdf_and_cipher::<_, ChaCha20Poly1305>(light_scrypt());
+        test_kdf_and_cipher_corruption::<_, ChaCha20Poly1305>(light_scrypt());
+        test_kdf_and_cipher_with_aad::<_, ChaCha20Poly1305>(light_scrypt());
+    }
+
+    // Test vector from RFC 8439, section 2.8.2.
+    #[test]
+    fn chacha20poly1305_test_vector() {
+        const MESSAGE: &[u8] = b"Ladies and Gentlemen of the class of '99: If I could offer you \
+            only one tip for the future, sunscreen would be it.";
+        const AAD: &str = "50515253c0c1c2c3c4c5c6c7";
+        const NONCE: &str = "070000004041424344454647";
+        const CIPHERTEXT: &str = "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6\
+            3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36\
+            92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc\
+            3ff4def08e4b7a9de576d26586cec64b6116";
+        const MAC: &str = "1ae10b594f09e26a7e902ecbd0600691";
+
+        let key: Vec<u8> = (0x80..0xa0).collect();
+        let aad = hex::decode(AAD).unwrap();
+        let nonce = hex::decode(NONCE).unwrap();
+        let sealed = ChaCha20Poly1305::seal(MESSAGE, &aad, &nonce, &key);
+        assert_eq!(hex::encode(&sealed.ciphertext), CIPHERTEXT);
+        assert_eq!(hex::encode(&sealed.mac), MAC);
+
+        let mut plaintext = vec![0; MESSAGE.len()];
+        ChaCha20Poly1305::open(&mut plaintext, &sealed, &aad, &nonce, &key).unwrap();
+        assert_eq!(plaintext, MESSAGE);
+        assert!(ChaCha20Poly1305::open(&mut plaintext, &sealed, &[], &nonce, &key).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "exonum_sodiumoxide")]
+    fn chacha20poly1305_cross_backend() {
+        use crate::{sodium, PwBoxBuilder};
+        use rand::thread_rng;
+
+        const PASSWORD: &str = "correct horse battery staple";
+
+        let (sodium_eraser, rcrypto_eraser) = cross_backend_erasers();
+        for len in &[0, 1, 63, 64, 65, 1_000] {
+            let message = vec![42_u8; *len];
+            let pwbox = PwBoxBuilder::<_, ChaCha20Poly1305>::new(&mut thread_rng())
+                .kdf(light_scrypt_nacl())
+                .seal(PASSWORD, &message)
+                .unwrap();
+            let erased_box = rcrypto_eraser.erase(&pwbox).unwrap();
+            assert_eq!(erased_box.cipher_name(), names::CHACHA20_POLY1305);
+            let pwbox = sodium_eraser
+                .restore_typed::<sodium::Scrypt, sodium::ChaCha20Poly1305>(&erased_box)
+                .unwrap();
+            assert_eq!(&*pwbox.open(PASSWORD).unwrap(), &*message);
+
+            let pwbox = PwBoxBuilder::<_, sodium::ChaCha20Poly1305>::new(&mut thread_rng())
+                .kdf(sodium::Scrypt::light())
+                .seal(PASSWORD, &message)
+                .unwrap();
+            let erased_box = sodium_eraser.erase(&pwbox).unwrap();
+            let restored = rcrypto_eraser.restore(&erased_box).unwrap();
+            assert_eq!(&*restored.open(PASSWORD).unwrap(), &*message);
+        }
+    }
+
+    #[test]
+    fn ethstore_functionality() {
+        use rand::thread_rng;
+
+        const PASSWORD: &str = "correct horse battery staple";
+        const MESSAGE: &[u8] = b"1234567890";
+
+        let mut eraser = Eraser::new();
+        let eraser = eraser.add_suite::<RustCrypto>().unwrap();
+
+        let mut rng = thread_rng();
+        let pwbox = RustCrypto::build_box(&mut rng)
+            .kdf(light_scrypt())
+            .seal(PASSWORD, MESSAGE)
+            .unwrap();
+
+        let erased_box = eraser.erase(&pwbox).unwrap();
+        let pwbox_copy = eraser.restore(&erased_box).unwrap();
+        assert_eq!(MESSAGE, &*pwbox_copy.open(PASSWORD).unwrap());
+    }
+
+    #[test]
+    fn scrypt_with_short_salt() {
+        use assert_matches::assert_matches;
+        use rand::{thread_rng, RngCore};
+
+        const PASSWORD: &str = "correct horse battery staple";
+        const MESSAGE: &[u8] = b"battery staple";
+
+        // Emulate a box produced by other software, which uses 16-byte salts.
+        let mut rng = thread_rng();
+        let mut salt = [0_u8; 16];
+        rng.fill_bytes(&mut salt);
+        let mut nonce = [0_u8; 16];
+        rng.fill_bytes(&mut nonce);
+        let mut key = [0_u8; 32];
+        light_scrypt()
+            .derive_key(&mut key, PASSWORD.as_bytes(), &salt)
+            .unwrap();
+        let encrypted = CipherWithMac::<Aes128Ctr, Keccak256>::seal(MESSAGE, &[], &nonce, &key);
+
+        let mut kdf_params = serde_json::to_value(light_scrypt()).unwrap();
+        kdf_params["salt"] = hex::encode(&salt).into();
+        let mut json = serde_json::json!({
+            "kdf": "scrypt",
+            "kdfparams": kdf_params,
+            "cipher": "aes-128-ctr",
+            "cipherparams": { "iv": hex::encode(&nonce) },
+            "ciphertext": hex::encode(&encrypted.ciphertext),
+            "mac": hex::encode(&encrypted.mac),
+        });
+        let erased_box: ErasedPwBox = serde_json::from_value(json.clone()).unwrap();
+
+        let mut eraser = Eraser::new();
+        let eraser = eraser.add_suite::<RustCrypto>().unwrap();
+        let pwbox = eraser.restore(&erased_box).unwrap();
+        assert_eq!(&*pwbox.open(PASSWORD).unwrap(), MESSAGE);
+
+        // Salts that are too short are still rejected.
+        json["kdfparams"]["salt"] = hex::encode(&salt[..MIN_SALT_LEN - 1]).into();
+        let erased_box: ErasedPwBox = serde_json::from_value(json).unwrap();
+        assert_matches!(
+            eraser.restore(&erased_box).map(drop).unwrap_err(),
+            crate::Error::SaltLen
+        );
+    }
+
+    // Several iterations are enough for testing.
+    fn light_pbkdf2(prf: Pbkdf2Prf) -> Pbkdf2 {
+        Pbkdf2 {
+            c: 16,
+            prf,
+            dklen: None,
+        }
+    }
+
+    #[test]
+    fn message_sizes() {
+        test_kdf_and_cipher_message_sizes::<_, CipherWithMac<Aes128Ctr, Keccak256>>(light_scrypt());
+        test_kdf_and_cipher_message_sizes::<_, Aes256CtrHmacSha512>(light_scrypt());
+        test_kdf_and_cipher_message_sizes::<_, Aes128Gcm>(light_scrypt());
+        test_kdf_and_cipher_message_sizes::<_, Aes256Gcm>(light_scrypt());
+        test_kdf_and_cipher_message_sizes::<_, XSalsa20Poly1305>(light_scrypt());
+        test_kdf_and_cipher_message_sizes::<_, ChaCha20Poly1305>(light_scrypt());
+    }
+
+    #[test]
+    fn scrypt_and_aes_with_hmac() {
+        test_kdf_and_cipher::<_, Aes128CtrHmacSha512>(light_scrypt());
+        test_kdf_and_cipher::<_, Aes256CtrHmacSha512>(light_scrypt());
+        test_kdf_and_cipher_with_aad::<_, Aes128CtrHmacSha512>(light_scrypt());
+    }
+
+    #[test]
+    fn scrypt_and_aes_with_hmac_corruption() {
+        test_kdf_and_cipher_corruption::<_, Aes128CtrHmacSha512>(light_scrypt());
+        test_kdf_and_cipher_corruption::<_, Aes256CtrHmacSha512>(light_scrypt());
+    }
+
+    /// Split with the MAC key preceding the cipher key and hashed before use.
+    #[derive(Debug)]
+    struct HashedMacKeySplit;
+
+    impl KeySplit for HashedMacKeySplit {
+        fn split_key(
+            key: &[u8],
+            cipher_key_len: usize,
+            mac_key_len: usize,
+        ) -> (SecretVec, SecretVec) {
+            let (mac_key, cipher_key) = key.split_at(mac_key_len);
+            let mut hasher = Sha3::keccak256();
+            hasher.input(mac_key);
+            let mut hashed_mac_key = SecretVec::zeros(32);
+            hasher.result(&mut hashed_mac_key);
+            assert_eq!(cipher_key.len(), cipher_key_len);
+            (
+                SecretVec::from(cipher_key.to_vec()),
+                SecretVec::from(hashed_mac_key[..mac_key_len].to_vec()),
+            )
+        }
+    }
+
+    #[test]
+    fn cipher_with_mac_primitives() {
+        use crate::test_cipher_with_mac;
+        use rand::thread_rng;
+
+        let mut rng = thread_rng();
+        test_cipher_with_mac::<Aes128Ctr, Keccak256, ConcatSplit>(&mut rng);
+        test_cipher_with_mac::<Aes256Ctr, HmacSha512, ConcatSplit>(&mut rng);
+        test_cipher_with_mac::<Aes128Ctr, Keccak256, HashedMacKeySplit>(&mut rng);
+        test_cipher_with_mac::<Aes256Ctr, HmacSha512, HashedMacKeySplit>(&mut rng);
+    }
+
+    #[test]
+    fn custom_key_split() {
+        use crate::{Error, PwBoxBuilder};
+        use assert_matches::assert_matches;
+        use rand::{thread_rng, RngCore};
+
+        const PASSWORD: &str = "correct horse battery staple";
+
+        type Ci = CipherWithMac<Aes128Ctr, Keccak256, HashedMacKeySplit>;
+        type DefaultCi = CipherWithMac<Aes128Ctr, Keccak256>;
+
+        test_kdf_and_cipher::<_, Ci>(light_scrypt());
+        test_kdf_and_cipher_corruption::<_, Ci>(light_scrypt());
+        test_kdf_and_cipher_with_aad::<_, Ci>(light_scrypt());
+
+        let mut rng = thread_rng();
+        let pwbox = PwBoxBuilder::<_, Ci>::new(&mut rng)
+            .kdf(light_scrypt())
+            .seal(PASSWORD, b"message")
+            .unwrap();
+        assert_eq!(&*pwbox.open(PASSWORD).unwrap(), b"message");
+        assert_matches!(pwbox.open("Tr0ub4dor&3").unwrap_err(), Error::MacMismatch);
+
+        // The split changes both the cipher and MAC keys, so boxes are not interchangeable
+        // with the default split.
+        let mut key = vec![0; Ci::KEY_LEN];
+        rng.fill_bytes(&mut key);
+        let mut nonce = vec![0; Ci::NONCE_LEN];
+        rng.fill_bytes(&mut nonce);
+        let enc = Ci::seal(b"message", &[], &nonce, &key);
+        let mut output = vec![0; 7];
+        Ci::open(&mut output, &enc, &[], &nonce, &key).unwrap();
+        assert_eq!(output, b"message");
+        assert!(DefaultCi::open(&mut output, &enc, &[], &nonce, &key).is_err());
+    }
+
+    #[test]
+    fn ethereum_key_split() {
+        use rand::{thread_rng, RngCore};
+
+        type Ci = CipherWithMac<Aes128Ctr, Keccak256, EthereumSplit>;
+
+        let mut rng = thread_rng();
+        let mut key = [0_u8; 32];
+        rng.fill_bytes(&mut key);
+        let mut nonce = [0_u8; 16];
+        rng.fill_bytes(&mut nonce);
+        let enc = Ci::seal(b"message", &[], &nonce, &key);
+
+        // MAC is `keccak256(derived_key[16..32] || ciphertext)`.
+        let mut hasher = Sha3::keccak256();
+        hasher.input(&key[16..]);
+        hasher.input(&enc.ciphertext);
+        let mut mac = [0_u8; 32];
+        hasher.result(&mut mac);
+        assert_eq!(enc.mac, mac);
+        let mut ciphertext = b"message".to_vec();
+        Aes128Ctr::seal_or_open(&mut ciphertext, &nonce, &key[..16]);
+        assert_eq!(enc.ciphertext, ciphertext);
+    }
+
+    #[test]
+    fn pbkdf2_and_aes128ctr() {
+        test_kdf_and_cipher::<_, CipherWithMac<Aes128Ctr, Keccak256>>(light_pbkdf2(
+            Pbkdf2Prf::HmacSha256,
+        ));
+    }
+
+    #[test]
+    fn pbkdf2_and_aes128gcm_corruption() {
+        test_kdf_and_cipher_corruption::<_, Aes128Gcm>(light_pbkdf2(Pbkdf2Prf::HmacSha512));
+    }
+
+    // Test vector from RFC 7914, section 11.
+    #[test]
+    fn pbkdf2_test_vector() {
+        const EXPECTED: &str = "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc\
+                                49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783";
+
+        let kdf = Pbkdf2 {
+            c: 1,
+            ..Pbkdf2::default()
+        };
+        let mut key = [0_u8; 64];
+        kdf.derive_key(&mut key, b"passwd", b"salt").unwrap();
+        assert_eq!(hex::encode(&key[..]), EXPECTED);
+    }
+
+    #[test]
+    fn pbkdf2_invalid_params() {
+        use crate::PwBoxBuilder;
+        use rand::thread_rng;
+
+        let mut rng = thread_rng();
+        let kdf = Pbkdf2 {
+            dklen: Some(16),
+            ..light_pbkdf2(Pbkdf2Prf::HmacSha256)
+        };
+        // `CipherWithMac<Aes128Ctr, _>` requires a 32-byte key.
+        let err = PwBoxBuilder::<_, CipherWithMac<Aes128Ctr, Keccak256>>::new(&mut rng)
+            .kdf(kdf)
+            .seal("password", b"message")
+            .unwrap_err();
+        assert!(err.to_string().contains("key length"));
+        PwBoxBuilder::<_, Aes128Gcm>::new(&mut rng)
+            .kdf(kdf)
+            .seal("password", b"message")
+            .unwrap();
+
+        let kdf = Pbkdf2 {
+            c: 0,
+            ..light_pbkdf2(Pbkdf2Prf::HmacSha256)
+        };
+        let err = PwBoxBuilder::<_, Aes128Gcm>::new(&mut rng)
+            .kdf(kdf)
+            .seal("password", b"message")
+            .unwrap_err();
+        assert!(err.to_string().contains("iterations"));
+
+        let json = serde_json::json!({ "c": 16, "prf": "hmac-md5" });
+        assert!(serde_json::from_value::<Pbkdf2>(json).is_err());
+    }
+
+    #[test]
+    fn ethstore_compatibility() {
+        const PASSWORD: &str = "foo";
+        const MESSAGE_HEX: &str = "fa7b3db73dc7dfdf8c5fbdb796d741e4488628c41fc4febd9160a866ba0f35";
+        const PWBOX: &str = r#"{
+            "cipher" : "aes-128-ctr",
+            "cipherparams" : {
+                "iv" : "e0c41130a323adc1446fc82f724bca2f"
+            },
+            "ciphertext" : "9517cd5bdbe69076f9bf5057248c6c050141e970efa36ce53692d5d59a3984",
+            "kdf" : "scrypt",
+            "kdfparams" : {
+                "dklen" : 32,
+                "n" : 2,
+                "r" : 8,
+                "p" : 1,
+                "salt" : "711f816911c92d649fb4c84b047915679933555030b3552c1212609b38208c63"
+            },
+            "mac" : "d5e116151c6aa71470e67a7d42c9620c75c4d23229847dcc127794f0732b0db5"
+        }"#;
+
+        let mut eraser = Eraser::new();
+        let eraser = eraser.add_suite::<RustCrypto>().unwrap();
+
+        let message = hex::decode(MESSAGE_HEX).unwrap();
+        let erased_box: ErasedPwBox = serde_json::from_str(&PWBOX).unwrap();
+        let pwbox = eraser.restore(&erased_box).unwrap();
+        assert_eq!(message, &*pwbox.open(PASSWORD).unwrap());
+    }
+
+    // Test vector from the Web3 Secret Storage definition.
+    #[test]
+    fn ethstore_pbkdf2_compatibility() {
+        const PASSWORD: &str = "testpassword";
+        const MESSAGE_HEX: &str =
+            "7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d";
+        const PWBOX: &str = r#"{
+            "cipher" : "aes-128-ctr",
+            "cipherparams" : {
+                "iv" : "6087dab2f9fdbbfaddc31a909735c1e6"
+            },
+            "ciphertext" : "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+            "kdf" : "pbkdf2",
+            "kdfparams" : {
+                "c" : 262144,
+                "dklen" : 32,
+                "prf" : "hmac-sha256",
+                "salt" : "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"
+            },
+            "mac" : "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+        }"#;
+
+        let mut eraser = Eraser::new();
+        let eraser = eraser.add_suite::<RustCrypto>().unwrap();
+
+        let message = hex::decode(MESSAGE_HEX).unwrap();
+        let erased_box: ErasedPwBox = serde_json::from_str(&PWBOX).unwrap();
+        let pwbox = eraser.restore(&erased_box).unwrap();
+        assert_eq!(message, &*pwbox.open(PASSWORD).unwrap());
+
+        // Check that KDF params are serialized in the same way.
+        let kdf: Pbkdf2 = erased_box.parse_kdf().unwrap();
+        let expected = serde_json::json!({ "c": 262_144, "dklen": 32, "prf": "hmac-sha256" });
+        assert_eq!(serde_json::to_value(kdf).unwrap(), expected);
     }
 }
@@ -14,18 +14,28 @@
 
 //! Crypto primitives based on `libsodium`.
 
-use anyhow::{anyhow, Error};
+use anyhow::{anyhow, bail, Error};
+use core::convert::TryFrom;
 use exonum_sodiumoxide::crypto::{
-    aead,
+    // `sodiumoxide` re-exports XChaCha20-Poly1305 at the `aead` root, so the IETF
+    // ChaCha20-Poly1305 module is imported explicitly.
+    aead::{chacha20poly1305_ietf as aead, xchacha20poly1305_ietf as xchacha},
     pwhash::{
-        self, derive_key, MemLimit, OpsLimit, Salt, MEMLIMIT_INTERACTIVE, MEMLIMIT_SENSITIVE,
-        OPSLIMIT_INTERACTIVE, OPSLIMIT_SENSITIVE,
+        self, argon2i13, argon2id13, derive_key, MemLimit, OpsLimit, Salt, MEMLIMIT_INTERACTIVE,
+        MEMLIMIT_SENSITIVE, OPSLIMIT_INTERACTIVE, OPSLIMIT_SENSITIVE,
     },
     secretbox::{self, open_detached, seal_detached, Key, Nonce, Tag},
 };
 use serde::{Deserialize, Serialize};
 
-use crate::{Cipher, CipherOutput, DeriveKey, Eraser, MacMismatch, Suite};
+use crate::{
+    alloc::{String, Vec},
+    names, phc,
+    tune::TunableKdf,
+    Argon2Params, Argon2Variant, Cipher, CipherOutput, CipherOutputRef, DeriveKey, Eraser,
+    ExternalKdf, KdfCost, KdfDifficulty, KdfPreset, MacMismatch, NoKdf, PhcString, ScryptParams,
+    Suite,
+};
 
 /// `Scrypt` key derivation function parameterized as per libsodium, i.e., via
 /// `opslimit` (computational hardness) and `memlimit` (RAM consumption).
@@ -70,6 +80,15 @@ impl Scrypt {
         }
     }
 
+    /// Returns the "moderate" `scrypt` parameters. These parameters are not defined
+    /// in libsodium; they correspond to `n = 2^18, r = 8, p = 1` in the original parametrization.
+    pub const fn moderate() -> Self {
+        Scrypt {
+            opslimit: 1 << 23,
+            memlimit: 1 << 28,
+        }
+    }
+
     /// Returns the "sensitive" `scrypt` parameters as defined in libsodium.
     #[allow(clippy::cast_possible_truncation)]
     // ^-- conversion is safe; using `try_from` is impossible because of the const context.
@@ -97,18 +116,58 @@ impl DeriveKey for Scrypt {
         .map(drop)
         .map_err(|()| anyhow!("out of memory"))
     }
+
+    fn cost(&self) -> Option<KdfCost> {
+        Some(ScryptParams::from_nacl_limits(self.opslimit, self.memlimit).cost())
+    }
+}
+
+impl KdfDifficulty for Scrypt {
+    fn preset(preset: KdfPreset) -> Self {
+        match preset {
+            KdfPreset::Interactive => Self::interactive(),
+            KdfPreset::Moderate => Self::moderate(),
+            KdfPreset::Sensitive => Self::sensitive(),
+        }
+    }
+
+    fn memory_cost(&self) -> u64 {
+        u64::from(self.memlimit)
+    }
+
+    fn ops_cost(&self) -> u64 {
+        u64::from(self.opslimit)
+    }
+}
+
+/// The limits are converted to the original parametrization. Only PHC strings with params
+/// obtained by such a conversion (in particular, with `r = 8`) are accepted.
+impl PhcString for Scrypt {
+    fn to_phc_string(&self, salt: &[u8]) -> String {
+        let params = ScryptParams::from_nacl_limits(self.opslimit, self.memlimit);
+        phc::scrypt_to_phc(params, salt)
+    }
+
+    fn from_phc_string(phc: &str) -> Result<(Self, Vec<u8>), crate::Error> {
+        let ((opslimit, memlimit), salt) = phc::nacl_scrypt_from_phc(phc)?;
+        Ok((Scrypt { opslimit, memlimit }, salt))
+    }
 }
 
 /// Sodium wrapper around scrypt. Designed for compatibility with other implementations.
 #[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
 #[serde(transparent)]
-pub struct ScryptCompat(pub crate::utils::ScryptParams);
+pub struct ScryptCompat(pub ScryptParams);
 
+/// Limits saturate at `u32::MAX` if the params are too large.
 impl From<ScryptCompat> for Scrypt {
     fn from(value: ScryptCompat) -> Scrypt {
-        let memlimit = value.0.r << (u32::from(value.0.log_n) + 7);
-        let opslimit = (value.0.r * value.0.p) << (u32::from(value.0.log_n) + 2);
-        Scrypt { opslimit, memlimit }
+        let memlimit = value.0.memory_cost();
+        let opslimit = value.0.ops_cost();
+        Scrypt {
+            opslimit: u32::try_from(opslimit).unwrap_or(u32::MAX),
+            memlimit: u32::try_from(memlimit).unwrap_or(u32::MAX),
+        }
     }
 }
 
@@ -118,8 +177,152 @@ impl DeriveKey for ScryptCompat {
     }
 
     fn derive_key(&self, buf: &mut [u8], password: &[u8], salt: &[u8]) -> anyhow::Result<()> {
+        self.0.validate()?;
+        let max_limit = u64::from(u32::MAX);
+        if self.0.memory_cost() > max_limit || self.0.ops_cost() > max_limit {
+            bail!("scrypt params are too large");
+        }
         Scrypt::from(*self).derive_key(buf, password, salt)
     }
+
+    fn validate_params(&self) -> Result<(), Error> {
+        self.0.validate()
+    }
+
+    fn cost(&self) -> Option<KdfCost> {
+        Some(self.0.cost())
+    }
+}
+
+impl KdfDifficulty for ScryptCompat {
+    fn preset(preset: KdfPreset) -> Self {
+        ScryptCompat(ScryptParams::preset(preset))
+    }
+
+    fn memory_cost(&self) -> u64 {
+        self.0.memory_cost()
+    }
+
+    fn ops_cost(&self) -> u64 {
+        self.0.ops_cost()
+    }
+}
+
+impl TunableKdf for ScryptCompat {
+    fn min_difficulty() -> Self {
+        ScryptCompat(ScryptParams::min_tunable())
+    }
+
+    fn double_memory(&self) -> Option<Self> {
+        self.0.double_memory().map(ScryptCompat)
+    }
+
+    fn multiply_time(&self, multiplier: u32) -> Option<Self> {
+        self.0.multiply_time(multiplier).map(ScryptCompat)
+    }
+}
+
+impl PhcString for ScryptCompat {
+    fn to_phc_string(&self, salt: &[u8]) -> String {
+        phc::scrypt_to_phc(self.0, salt)
+    }
+
+    fn from_phc_string(phc: &str) -> Result<(Self, Vec<u8>), crate::Error> {
+        phc::scrypt_from_phc(phc).map(|(params, salt)| (ScryptCompat(params), salt))
+    }
+}
+
+/// Sodium wrapper around Argon2 (version 0x13).
+///
+/// `libsodium` only supports Argon2 with `parallelism == 1`; deriving a key with
+/// other parallelism values results in an error.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Argon2(pub Argon2Params);
+
+impl DeriveKey for Argon2 {
+    fn salt_len(&self) -> usize {
+        argon2id13::SALTBYTES
+    }
+
+    fn derive_key(&self, buf: &mut [u8], password: &[u8], salt: &[u8]) -> Result<(), Error> {
+        if self.0.parallelism != 1 {
+            bail!("libsodium only supports Argon2 with parallelism = 1");
+        }
+        let opslimit = self.0.time_cost as usize;
+        let memlimit = (self.0.mem_cost as usize)
+            .checked_mul(1_024)
+            .ok_or_else(|| anyhow!("memory cost is too large"))?;
+
+        match self.0.variant {
+            Argon2Variant::Argon2i => argon2i13::derive_key(
+                buf,
+                password,
+                &argon2i13::Salt::from_slice(salt).expect("invalid salt length"),
+                argon2i13::OpsLimit(opslimit),
+                argon2i13::MemLimit(memlimit),
+            ),
+            Argon2Variant::Argon2id => argon2id13::derive_key(
+                buf,
+                password,
+                &argon2id13::Salt::from_slice(salt).expect("invalid salt length"),
+                argon2id13::OpsLimit(opslimit),
+                argon2id13::MemLimit(memlimit),
+            ),
+        }
+        .map(drop)
+        .map_err(|()| anyhow!("invalid parameters or out of memory"))
+    }
+
+    fn validate_params(&self) -> Result<(), Error> {
+        self.0.validate()?;
+        if self.0.parallelism != 1 {
+            bail!("libsodium only supports Argon2 with parallelism = 1");
+        }
+        Ok(())
+    }
+
+    fn cost(&self) -> Option<KdfCost> {
+        Some(self.0.cost())
+    }
+}
+
+impl KdfDifficulty for Argon2 {
+    fn preset(preset: KdfPreset) -> Self {
+        Argon2(Argon2Params::preset(preset))
+    }
+
+    fn memory_cost(&self) -> u64 {
+        self.0.memory_cost()
+    }
+
+    fn ops_cost(&self) -> u64 {
+        self.0.ops_cost()
+    }
+}
+
+impl TunableKdf for Argon2 {
+    fn min_difficulty() -> Self {
+        Argon2(Argon2Params::min_tunable())
+    }
+
+    fn double_memory(&self) -> Option<Self> {
+        self.0.double_memory().map(Argon2)
+    }
+
+    fn multiply_time(&self, multiplier: u32) -> Option<Self> {
+        self.0.multiply_time(multiplier).map(Argon2)
+    }
+}
+
+impl PhcString for Argon2 {
+    fn to_phc_string(&self, salt: &[u8]) -> String {
+        phc::argon2_to_phc(self.0, salt)
+    }
+
+    fn from_phc_string(phc: &str) -> Result<(Self, Vec<u8>), crate::Error> {
+        phc::argon2_from_phc(phc).map(|(params, salt)| (Argon2(params), salt))
+    }
 }
 
 /// `xsalsa20` symmetric cipher with `poly1305` MAC.
@@ -130,31 +333,62 @@ impl Cipher for XSalsa20Poly1305 {
     const KEY_LEN: usize = secretbox::KEYBYTES;
     const NONCE_LEN: usize = secretbox::NONCEBYTES;
     const MAC_LEN: usize = secretbox::MACBYTES;
+    /// `secretbox` construction does not support associated data.
+    const SUPPORTS_ASSOCIATED_DATA: bool = false;
+
+    fn seal(message: &[u8], associated_data: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
+        assert!(
+            associated_data.is_empty(),
+            "XSalsa20Poly1305 does not support associated data"
+        );
+        CipherOutput::seal_with::<Self>(message, associated_data, nonce, key)
+    }
 
-    fn seal(message: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
+    fn seal_into(
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        out: &mut CipherOutputRef<'_>,
+    ) {
+        assert!(
+            associated_data.is_empty(),
+            "XSalsa20Poly1305 does not support associated data"
+        );
         let nonce = Nonce::from_slice(nonce).expect("nonce");
         let key = Key::from_slice(key).expect("key");
-        let mut message = message.to_vec();
 
-        let Tag(mac) = seal_detached(&mut message, &nonce, &key);
-        CipherOutput {
-            ciphertext: message,
-            mac: mac.to_vec(),
-        }
+        out.ciphertext.copy_from_slice(message);
+        let Tag(mac) = seal_detached(out.ciphertext, &nonce, &key);
+        out.mac.copy_from_slice(&mac);
     }
 
     fn open(
         output: &mut [u8],
         enc: &CipherOutput,
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        output.copy_from_slice(&enc.ciphertext);
+        Self::open_in_place(output, &enc.mac, associated_data, nonce, key)
+    }
+
+    fn open_in_place(
+        buffer: &mut [u8],
+        mac: &[u8],
+        associated_data: &[u8],
         nonce: &[u8],
         key: &[u8],
     ) -> Result<(), MacMismatch> {
+        if !associated_data.is_empty() {
+            // The box could not have been sealed with this associated data.
+            return Err(MacMismatch);
+        }
         let nonce = Nonce::from_slice(nonce).expect("invalid nonce length");
         let key = Key::from_slice(key).expect("invalid key length");
-        let mac = Tag::from_slice(&enc.mac).expect("invalid MAC length");
-
-        output.copy_from_slice(&enc.ciphertext);
-        open_detached(output, &mac, &nonce, &key).map_err(|()| MacMismatch)
+        let mac = Tag::from_slice(mac).expect("invalid MAC length");
+        open_detached(buffer, &mac, &nonce, &key).map_err(|()| MacMismatch)
     }
 }
 
@@ -167,30 +401,165 @@ impl Cipher for ChaCha20Poly1305 {
     const NONCE_LEN: usize = aead::NONCEBYTES;
     const MAC_LEN: usize = aead::TAGBYTES;
 
-    fn seal(message: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
+    fn seal(message: &[u8], associated_data: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
+        CipherOutput::seal_with::<Self>(message, associated_data, nonce, key)
+    }
+
+    fn seal_into(
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        out: &mut CipherOutputRef<'_>,
+    ) {
         let nonce = aead::Nonce::from_slice(nonce).expect("nonce");
         let key = aead::Key::from_slice(key).expect("key");
-        let mut message = message.to_vec();
 
-        let aead::Tag(mac) = aead::seal_detached(&mut message, None, &nonce, &key);
-        CipherOutput {
-            ciphertext: message,
-            mac: mac.to_vec(),
-        }
+        out.ciphertext.copy_from_slice(message);
+        let aead::Tag(mac) =
+            aead::seal_detached(out.ciphertext, Some(associated_data), &nonce, &key);
+        out.mac.copy_from_slice(&mac);
     }
 
     fn open(
         output: &mut [u8],
         enc: &CipherOutput,
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        output.copy_from_slice(&enc.ciphertext);
+        Self::open_in_place(output, &enc.mac, associated_data, nonce, key)
+    }
+
+    fn open_in_place(
+        buffer: &mut [u8],
+        mac: &[u8],
+        associated_data: &[u8],
         nonce: &[u8],
         key: &[u8],
     ) -> Result<(), MacMismatch> {
         let nonce = aead::Nonce::from_slice(nonce).expect("invalid nonce length");
         let key = aead::Key::from_slice(key).expect("invalid key length");
-        let mac = aead::Tag::from_slice(&enc.mac).expect("invalid MAC length");
+        let mac = aead::Tag::from_slice(mac).expect("invalid MAC length");
+        aead::open_detached(buffer, Some(associated_data), &mac, &nonce, &key)
+            .map_err(|()| MacMismatch)
+    }
+}
+
+/// ChaCha20-Poly1305 AEAD with 96-bit nonces as per [RFC 8439].
+///
+/// The cipher is functionally equivalent to [`ChaCha20Poly1305`]; it is registered in `Eraser`
+/// under a separate name for interoperability with systems naming the IETF variant explicitly.
+///
+/// [RFC 8439]: https://tools.ietf.org/html/rfc8439
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChaCha20Poly1305Ietf;
+
+impl Cipher for ChaCha20Poly1305Ietf {
+    const KEY_LEN: usize = 32;
+    const NONCE_LEN: usize = 12;
+    const MAC_LEN: usize = 16;
+
+    fn seal(message: &[u8], associated_data: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
+        ChaCha20Poly1305::seal(message, associated_data, nonce, key)
+    }
+
+    fn seal_into(
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        out: &mut CipherOutputRef<'_>,
+    ) {
+        ChaCha20Poly1305::seal_into(message, associated_data, nonce, key, out);
+    }
+
+    fn open(
+        output: &mut [u8],
+        enc: &CipherOutput,
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        ChaCha20Poly1305::open(output, enc, associated_data, nonce, key)
+    }
+
+    fn open_in_place(
+        buffer: &mut [u8],
+        mac: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        ChaCha20Poly1305::open_in_place(buffer, mac, associated_data, nonce, key)
+    }
+}
+
+/// XChaCha20-Poly1305 AEAD, i.e., ChaCha20-Poly1305 with extended 192-bit nonces.
+/// Large nonces make it safe to generate them randomly.
+///
+/// This is the recommended cipher of the [`Sodium`] suite. See [`draft-irtf-cfrg-xchacha`]
+/// for the construction details.
+///
+/// [`draft-irtf-cfrg-xchacha`]: https://tools.ietf.org/html/draft-irtf-cfrg-xchacha-03
+#[derive(Debug, Clone, Copy, Default)]
+pub struct XChaCha20Poly1305;
 
+impl Cipher for XChaCha20Poly1305 {
+    const KEY_LEN: usize = xchacha::KEYBYTES;
+    const NONCE_LEN: usize = xchacha::NONCEBYTES;
+    const MAC_LEN: usize = xchacha::TAGBYTES;
+
+    fn seal(message: &[u8], associated_data: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
+        CipherOutput::seal_with::<Self>(message, associated_data, nonce, key)
+    }
+
+    fn seal_into(
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        out: &mut CipherOutputRef<'_>,
+    ) {
+        let (nonce, key) = match (
+            xchacha::Nonce::from_slice(nonce),
+            xchacha::Key::from_slice(key),
+        ) {
+            (Some(nonce), Some(key)) => (nonce, key),
+            _ => unreachable!("nonce and key lengths are checked before sealing"),
+        };
+
+        out.ciphertext.copy_from_slice(message);
+        let xchacha::Tag(mac) =
+            xchacha::seal_detached(out.ciphertext, Some(associated_data), &nonce, &key);
+        out.mac.copy_from_slice(&mac);
+    }
+
+    fn open(
+        output: &mut [u8],
+        enc: &CipherOutput,
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
         output.copy_from_slice(&enc.ciphertext);
-        aead::open_detached(output, None, &mac, &nonce, &key).map_err(|()| MacMismatch)
+        Self::open_in_place(output, &enc.mac, associated_data, nonce, key)
+    }
+
+    fn open_in_place(
+        buffer: &mut [u8],
+        mac: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        // Nonces and MACs may come from deserialized boxes, so their lengths are not trusted.
+        let nonce = xchacha::Nonce::from_slice(nonce).ok_or(MacMismatch)?;
+        let key = xchacha::Key::from_slice(key).ok_or(MacMismatch)?;
+        let mac = xchacha::Tag::from_slice(mac).ok_or(MacMismatch)?;
+        xchacha::open_detached(buffer, Some(associated_data), &mac, &nonce, &key)
+            .map_err(|()| MacMismatch)
     }
 }
 
@@ -198,14 +567,19 @@ impl Cipher for ChaCha20Poly1305 {
 ///
 /// # Ciphers
 ///
+/// - `xchacha20-poly1305`: XChaCha20 stream cipher with Poly1305 MAC (recommended)
 /// - `xsalsa20-poly1305`: XSalsa20 stream cipher with Poly1305 MAC
 /// - `chacha20-poly1305`: ChaCha20 stream cipher with Poly1305 MAC
 ///   as per [RFC 8439](https://tools.ietf.org/html/rfc8439)
+/// - `chacha20-poly1305-ietf`: the same construction, registered under an explicit name
 ///
 /// # KDFs
 ///
 /// - `scrypt-nacl`: `scrypt` KDF with the `libsodium` parametrization.
 /// - `scrypt`: `scrypt` KDF with the original parametrization.
+/// - `argon2`: Argon2 KDF (`argon2i` or `argon2id` variant) with `parallelism == 1`.
+/// - `none`: [`NoKdf`](crate::NoKdf), i.e., using the password as the key
+///   (`raw` is accepted as an alias).
 ///
 /// # Examples
 ///
@@ -214,22 +588,42 @@ impl Cipher for ChaCha20Poly1305 {
 pub struct Sodium(());
 
 impl Suite for Sodium {
-    type Cipher = XSalsa20Poly1305;
+    type Cipher = XChaCha20Poly1305;
     type DeriveKey = Scrypt;
 
-    fn add_ciphers_and_kdfs(eraser: &mut Eraser) {
+    fn add_ciphers_and_kdfs(eraser: &mut Eraser) -> Result<(), crate::Error> {
         eraser
-            .add_kdf::<Scrypt>("scrypt-nacl")
-            .add_kdf::<ScryptCompat>("scrypt")
-            .add_cipher::<XSalsa20Poly1305>("xsalsa20-poly1305")
-            .add_cipher::<ChaCha20Poly1305>("chacha20-poly1305");
+            .add_phc_kdf::<Scrypt>(names::SCRYPT_NACL)?
+            .add_phc_kdf::<ScryptCompat>(names::SCRYPT)?
+            .add_phc_kdf::<Argon2>(names::ARGON2)?
+            .add_kdf::<NoKdf>(names::NONE)?
+            .add_alias(names::RAW, names::NONE)?
+            .add_kdf::<ExternalKdf>(names::EXTERNAL)?
+            .add_cipher::<XChaCha20Poly1305>(names::XCHACHA20_POLY1305)?
+            .add_cipher::<XSalsa20Poly1305>(names::XSALSA20_POLY1305)?
+            .add_cipher::<ChaCha20Poly1305>(names::CHACHA20_POLY1305)?
+            .add_cipher::<ChaCha20Poly1305Ietf>(names::CHACHA20_POLY1305_IETF)?;
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{erased::test_kdf_and_cipher_corruption, test_kdf_and_cipher};
+    use crate::{
+        erased::{
+            test_kdf_and_cipher_corruption, test_kdf_and_cipher_message_sizes,
+            test_kdf_and_cipher_with_aad, test_kdf_presets,
+        },
+        test_kdf_and_cipher, ErasedPwBox, PwBox, PwBoxBuilder,
+    };
+
+    #[test]
+    fn kdf_presets() {
+        test_kdf_presets::<Scrypt>();
+        test_kdf_presets::<ScryptCompat>();
+        test_kdf_presets::<Argon2>();
+    }
 
     #[test]
     fn scrypt_and_salsa() {
@@ -255,6 +649,247 @@ mod tests {
         test_kdf_and_cipher_corruption::<_, ChaCha20Poly1305>(scrypt);
     }
 
+    #[test]
+    fn scrypt_and_chacha_ietf() {
+        let scrypt = Scrypt::light();
+        test_kdf_and_cipher::<_, ChaCha20Poly1305Ietf>(scrypt);
+    }
+
+    #[test]
+    fn scrypt_and_chacha_ietf_corruption() {
+        let scrypt = Scrypt::light();
+        test_kdf_and_cipher_corruption::<_, ChaCha20Poly1305Ietf>(scrypt);
+    }
+
+    #[test]
+    fn scrypt_and_xchacha() {
+        let scrypt = Scrypt::light();
+        test_kdf_and_cipher::<_, XChaCha20Poly1305>(scrypt);
+    }
+
+    #[test]
+    fn scrypt_and_xchacha_corruption() {
+        let scrypt = Scrypt::light();
+        test_kdf_and_cipher_corruption::<_, XChaCha20Poly1305>(scrypt);
+    }
+
+    #[test]
+    fn scrypt_and_xchacha_with_aad() {
+        let scrypt = Scrypt::light();
+        test_kdf_and_cipher_with_aad::<_, XChaCha20Poly1305>(scrypt);
+    }
+
+    // AEAD test vector from `draft-irtf-cfrg-xchacha-03`, appendix A.3.1 (also used
+    // in the `libsodium` test suite).
+    #[test]
+    fn xchacha_test_vector() {
+        const KEY: &str = "808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f";
+        const NONCE: &str = "404142434445464748494a4b4c4d4e4f5051525354555657";
+        const AAD: &str = "50515253c0c1c2c3c4c5c6c7";
+        const MESSAGE: &[u8] = b"Ladies and Gentlemen of the class of '99: If I could offer \
+            you only one tip for the future, sunscreen would be it.";
+        const CIPHERTEXT: &str = "bd6d179d3e83d43b9576579493c0e939572a1700252bfaccbed2902c21396cbb\
+            731c7f1b0b4aa6440bf3a82f4eda7e39ae64c6708c54c216cb96b72e1213b4522f8c9ba40db5d945b11b69b9\
+            82c1bb9e3f3fac2bc369488f76b2383565d3fff921f9664c97637da9768812f615c68b13b52e";
+        const MAC: &str = "c0875924c1c7987947deafd8780acf49";
+
+        let key = hex::decode(KEY).unwrap();
+        let nonce = hex::decode(NONCE).unwrap();
+        let aad = hex::decode(AAD).unwrap();
+        let encrypted = XChaCha20Poly1305::seal(MESSAGE, &aad, &nonce, &key);
+        assert_eq!(hex::encode(&encrypted.ciphertext), CIPHERTEXT);
+        assert_eq!(hex::encode(&encrypted.mac), MAC);
+
+        let mut output = vec![0_u8; MESSAGE.len()];
+        XChaCha20Poly1305::open(&mut output, &encrypted, &aad, &nonce, &key).unwrap();
+        assert_eq!(output, MESSAGE);
+        assert!(XChaCha20Poly1305::open(&mut output, &encrypted, &[], &nonce, &key).is_err());
+
+        // Malformed nonces and MACs are rejected rather than causing a panic.
+        let short_nonce = &nonce[..12];
+        assert!(XChaCha20Poly1305::open(&mut output, &encrypted, &aad, short_nonce, &key).is_err());
+        let mut truncated = encrypted.clone();
+        truncated.mac.pop();
+        assert!(XChaCha20Poly1305::open(&mut output, &truncated, &aad, &nonce, &key).is_err());
+    }
+
+    #[test]
+    fn message_sizes() {
+        test_kdf_and_cipher_message_sizes::<_, XChaCha20Poly1305>(Scrypt::light());
+        test_kdf_and_cipher_message_sizes::<_, XSalsa20Poly1305>(Scrypt::light());
+        test_kdf_and_cipher_message_sizes::<_, ChaCha20Poly1305>(Scrypt::light());
+        test_kdf_and_cipher_message_sizes::<_, ChaCha20Poly1305Ietf>(Scrypt::light());
+    }
+
+    #[test]
+    fn chacha_ietf_constants() {
+        assert_eq!(ChaCha20Poly1305Ietf::KEY_LEN, ChaCha20Poly1305::KEY_LEN);
+        assert_eq!(ChaCha20Poly1305Ietf::NONCE_LEN, ChaCha20Poly1305::NONCE_LEN);
+        assert_eq!(ChaCha20Poly1305Ietf::MAC_LEN, ChaCha20Poly1305::MAC_LEN);
+    }
+
+    // AEAD test vector from RFC 8439, section 2.8.2 (also used in the `libsodium` test suite).
+    #[test]
+    fn chacha_ietf_test_vector() {
+        const KEY: &str = "808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f";
+        const NONCE: &str = "070000004041424344454647";
+        const AAD: &str = "50515253c0c1c2c3c4c5c6c7";
+        const MESSAGE: &[u8] = b"Ladies and Gentlemen of the class of '99: If I could offer \
+            you only one tip for the future, sunscreen would be it.";
+        const CIPHERTEXT: &str = "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6\
+            3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b3692ddbd7f2d778b8c9803aee3\
+            28091b58fab324e4fad675945585808b4831d7bc3ff4def08e4b7a9de576d26586cec64b6116";
+        const MAC: &str = "1ae10b594f09e26a7e902ecbd0600691";
+
+        let key = hex::decode(KEY).unwrap();
+        let nonce = hex::decode(NONCE).unwrap();
+        let aad = hex::decode(AAD).unwrap();
+        let encrypted = ChaCha20Poly1305Ietf::seal(MESSAGE, &aad, &nonce, &key);
+        assert_eq!(hex::encode(&encrypted.ciphertext), CIPHERTEXT);
+        assert_eq!(hex::encode(&encrypted.mac), MAC);
+
+        let mut output = vec![0_u8; MESSAGE.len()];
+        ChaCha20Poly1305Ietf::open(&mut output, &encrypted, &aad, &nonce, &key).unwrap();
+        assert_eq!(output, MESSAGE);
+    }
+
+    #[test]
+    fn chacha_ietf_erasure() {
+        use rand::thread_rng;
+
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let pwbox = PwBoxBuilder::<_, ChaCha20Poly1305Ietf>::new(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .seal("password", b"message")
+            .unwrap();
+        let erased_box = eraser.erase(&pwbox).unwrap();
+        let json = serde_json::to_value(&erased_box).unwrap();
+        assert_eq!(json["cipher"], "chacha20-poly1305-ietf");
+        assert_eq!(json["cipherparams"]["iv"].as_str().unwrap().len(), 24);
+
+        let restored = eraser.restore(&erased_box).unwrap();
+        assert_eq!(&*restored.open("password").unwrap(), b"message");
+    }
+
+    #[test]
+    fn xchacha_erasure() {
+        use rand::thread_rng;
+
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let pwbox = PwBoxBuilder::<_, XChaCha20Poly1305>::new(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .associated_data(b"context")
+            .seal("password", b"message")
+            .unwrap();
+        let json = serde_json::to_string(&eraser.erase(&pwbox).unwrap()).unwrap();
+        let erased_box: ErasedPwBox = serde_json::from_str(&json).unwrap();
+        assert_eq!(erased_box.cipher_name(), "xchacha20-poly1305");
+        let json = serde_json::to_value(&erased_box).unwrap();
+        assert_eq!(json["cipherparams"]["iv"].as_str().unwrap().len(), 48);
+
+        let restored = eraser.restore(&erased_box).unwrap();
+        assert!(restored.has_associated_data());
+        let message = restored.open_with_aad("password", b"context").unwrap();
+        assert_eq!(&*message, b"message");
+        assert!(restored.open_with_aad("password", b"other").is_err());
+        assert!(restored.open_with_aad("wrong", b"context").is_err());
+
+        let typed: PwBox<Scrypt, XChaCha20Poly1305> =
+            eraser.restore_as::<Sodium>(&erased_box).unwrap();
+        let message = typed.open_with_aad("password", b"context").unwrap();
+        assert_eq!(&*message, b"message");
+    }
+
+    #[test]
+    fn switching_ciphers_in_builder() {
+        use rand::thread_rng;
+
+        let mut rng = thread_rng();
+        let default_box = Sodium::build_box(&mut rng)
+            .kdf(Scrypt::light())
+            .seal("password", b"message")
+            .unwrap();
+        let chacha_box = Sodium::build_box(&mut rng)
+            .cipher::<ChaCha20Poly1305Ietf>()
+            .kdf(Scrypt::light())
+            .seal("password", b"other message")
+            .unwrap();
+
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let default_box = eraser.erase(&default_box).unwrap();
+        let chacha_box = eraser.erase(&chacha_box).unwrap();
+        let json = serde_json::to_value(&default_box).unwrap();
+        assert_eq!(json["cipher"], "xchacha20-poly1305");
+        let json = serde_json::to_value(&chacha_box).unwrap();
+        assert_eq!(json["cipher"], "chacha20-poly1305-ietf");
+
+        let restored = eraser.restore(&default_box).unwrap();
+        assert_eq!(&*restored.open("password").unwrap(), b"message");
+        let restored = eraser.restore(&chacha_box).unwrap();
+        assert_eq!(&*restored.open("password").unwrap(), b"other message");
+    }
+
+    #[test]
+    fn scrypt_and_chacha_with_aad() {
+        let scrypt = Scrypt::light();
+        test_kdf_and_cipher_with_aad::<_, ChaCha20Poly1305>(scrypt);
+    }
+
+    #[test]
+    fn salsa_does_not_support_aad() {
+        let mut rng = rand::thread_rng();
+        let result = PwBoxBuilder::<_, XSalsa20Poly1305>::new(&mut rng)
+            .kdf(Scrypt::light())
+            .associated_data(b"context")
+            .seal("password", b"message");
+        assert!(result.is_err());
+    }
+
+    fn light_argon2(variant: Argon2Variant) -> Argon2 {
+        Argon2(Argon2Params::custom(variant, 1 << 10, 3, 1))
+    }
+
+    #[test]
+    fn argon2_and_salsa() {
+        test_kdf_and_cipher::<_, XSalsa20Poly1305>(light_argon2(Argon2Variant::Argon2id));
+        test_kdf_and_cipher::<_, XSalsa20Poly1305>(light_argon2(Argon2Variant::Argon2i));
+    }
+
+    #[test]
+    fn argon2_and_salsa_corruption() {
+        test_kdf_and_cipher_corruption::<_, XSalsa20Poly1305>(light_argon2(
+            Argon2Variant::Argon2id,
+        ));
+    }
+
+    #[test]
+    fn argon2_with_unsupported_parallelism() {
+        let argon2 = Argon2(Argon2Params::custom(Argon2Variant::Argon2id, 1 << 10, 3, 2));
+        let mut key = [0_u8; 32];
+        let salt = [0_u8; argon2id13::SALTBYTES];
+        assert!(argon2.derive_key(&mut key, b"password", &salt).is_err());
+        assert!(argon2.validate_params().is_err());
+    }
+
+    #[test]
+    fn kdf_cost_estimates() {
+        // Corresponds to `n = 2^14, r = 8, p = 1`.
+        let cost = Scrypt::interactive().cost().unwrap();
+        assert_eq!(cost.memory_bytes, 1 << 24);
+        assert_eq!(cost.time_cost, 1 << 25);
+        let compat = ScryptCompat(crate::ScryptParams::custom(14, 1));
+        assert_eq!(compat.cost(), Some(cost));
+
+        let argon2 = Argon2(Argon2Params::custom(Argon2Variant::Argon2id, 1 << 10, 3, 1));
+        let cost = argon2.cost().unwrap();
+        assert_eq!(cost.memory_bytes, 1 << 20);
+        assert_eq!(cost.time_cost, 3 << 20);
+        assert!(argon2.validate_params().is_ok());
+    }
+
     fn params_are_equal(lhs: Scrypt, rhs: Scrypt) -> bool {
         lhs.opslimit == rhs.opslimit && lhs.memlimit == rhs.memlimit
     }
@@ -0,0 +1,828 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Streaming encryption for large payloads.
+//!
+//! # Construction
+//!
+//! The key is derived from the password once. The plaintext is split into chunks
+//! of a fixed size (the last chunk may be shorter, possibly empty); each chunk is sealed
+//! with the cipher separately and is written as `ciphertext || mac`. The nonce for a chunk
+//! is obtained from the random nonce stored in the box by XORing its last 8 bytes
+//! with the little-endian chunk index, and XORing the preceding byte with 1 for the last chunk
+//! (cf. the [STREAM] construction). Thus, reordering, dropping or truncating chunks
+//! is detected as a MAC mismatch.
+//!
+//! [STREAM]: https://eprint.iacr.org/2015/189.pdf
+
+use rand_core::RngCore;
+use smallvec::SmallVec;
+
+use core::fmt;
+use std::io::{self, Read, Write};
+
+use crate::{
+    alloc::{vec, Arc, Vec},
+    traits::{CipherObject, ObjectSafeCipher},
+    Cipher, CipherOutput, CipherOutputRef, DeriveKey, Error, Progress, SensitiveData,
+};
+
+/// Minimum nonce length of a cipher supported by the streaming construction.
+pub(crate) const MIN_NONCE_LEN: usize = 9;
+
+/// Reader splitting the underlying data into chunks and detecting the last chunk.
+struct ChunkReader<R> {
+    reader: R,
+    lookahead: Option<u8>,
+}
+
+impl<R: Read> ChunkReader<R> {
+    fn new(reader: R) -> Self {
+        ChunkReader {
+            reader,
+            lookahead: None,
+        }
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0_u8];
+        loop {
+            match self.reader.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(byte[0])),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Fills `buffer` with data. Returns the number of read bytes and a flag whether
+    /// the chunk is the last one in the stream.
+    fn read_chunk(&mut self, buffer: &mut [u8]) -> io::Result<(usize, bool)> {
+        debug_assert!(!buffer.is_empty());
+
+        let mut len = 0;
+        if let Some(byte) = self.lookahead.take() {
+            buffer[0] = byte;
+            len = 1;
+        }
+        while len < buffer.len() {
+            match self.reader.read(&mut buffer[len..]) {
+                Ok(0) => return Ok((len, true)),
+                Ok(read) => len += read,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.lookahead = self.read_byte()?;
+        Ok((len, self.lookahead.is_none()))
+    }
+}
+
+/// Derives the nonce for a specific chunk. Nonces of all supported ciphers fit
+/// into the inline buffer, so that deriving a nonce does not allocate.
+fn chunk_nonce(nonce: &[u8], index: u64, is_last: bool) -> SmallVec<[u8; 32]> {
+    let mut chunk_nonce = SmallVec::from_slice(nonce);
+    let len = chunk_nonce.len();
+    for (dest, src) in chunk_nonce[len - 8..].iter_mut().zip(&index.to_le_bytes()) {
+        *dest ^= src;
+    }
+    if is_last {
+        chunk_nonce[len - 9] ^= 1;
+    }
+    chunk_nonce
+}
+
+/// Seals chunks of a stream. The key and output buffers are reused for all chunks.
+struct ChunkSealer {
+    key: SensitiveData,
+    ciphertext: Vec<u8>,
+    mac: Vec<u8>,
+    index: u64,
+}
+
+impl ChunkSealer {
+    /// Seals the next `chunk` of the stream and writes it to `writer`.
+    fn seal_chunk<K, C: ObjectSafeCipher>(
+        &mut self,
+        stream: &StreamInner<K, C>,
+        chunk: &[u8],
+        is_last: bool,
+        writer: &mut impl Write,
+    ) -> io::Result<()> {
+        let chunk_nonce = chunk_nonce(&stream.nonce, self.index, is_last);
+        let mut out = CipherOutputRef {
+            ciphertext: &mut self.ciphertext[..chunk.len()],
+            mac: &mut self.mac,
+        };
+        stream
+            .cipher
+            .seal_into(chunk, &[], &chunk_nonce, &*self.key, &mut out);
+        writer.write_all(out.ciphertext)?;
+        writer.write_all(out.mac)?;
+        self.index += 1;
+        Ok(())
+    }
+}
+
+/// Reads and opens chunks of a stream. The key and buffers are reused for all chunks.
+struct ChunkOpener<R> {
+    reader: ChunkReader<R>,
+    key: SensitiveData,
+    buffer: Vec<u8>,
+    encrypted: CipherOutput,
+    plaintext: SensitiveData,
+    index: u64,
+}
+
+impl<R: Read> ChunkOpener<R> {
+    fn new<K: DeriveKey, C: ObjectSafeCipher>(
+        stream: &StreamInner<K, C>,
+        password: &[u8],
+        reader: R,
+    ) -> Result<Self, Error> {
+        let mut key = SensitiveData::zeros(stream.cipher.key_len());
+        trace_kdf!(
+            salt_len = stream.salt.len(),
+            stream
+                .kdf
+                .derive_key(key.bytes_mut(), password, &stream.salt)
+        )
+        .map_err(Error::DeriveKey)?;
+
+        let chunk_size = stream.chunk_size as usize;
+        let mac_len = stream.cipher.mac_len();
+        Ok(ChunkOpener {
+            reader: ChunkReader::new(reader),
+            key,
+            buffer: vec![0_u8; chunk_size + mac_len],
+            encrypted: CipherOutput {
+                ciphertext: Vec::with_capacity(chunk_size),
+                mac: Vec::with_capacity(mac_len),
+            },
+            plaintext: SensitiveData::zeros(chunk_size),
+            index: 0,
+        })
+    }
+
+    /// Reads and opens the next chunk of the stream. Returns the length of the chunk
+    /// plaintext (which is stored in `self.plaintext`) and a flag whether the chunk
+    /// is the last one.
+    fn open_chunk(
+        &mut self,
+        cipher: &(impl ObjectSafeCipher + ?Sized),
+        nonce: &[u8],
+    ) -> Result<(usize, bool), Error> {
+        let mac_len = cipher.mac_len();
+        let (len, is_last) = self
+            .reader
+            .read_chunk(&mut self.buffer)
+            .map_err(Error::Io)?;
+        if len < mac_len {
+            // The stream is truncated.
+            return Err(Error::MacMismatch);
+        }
+        let (ciphertext, mac) = self.buffer[..len].split_at(len - mac_len);
+        self.encrypted.ciphertext.clear();
+        self.encrypted.ciphertext.extend_from_slice(ciphertext);
+        self.encrypted.mac.clear();
+        self.encrypted.mac.extend_from_slice(mac);
+        let chunk_nonce = chunk_nonce(nonce, self.index, is_last);
+        let output = &mut self.plaintext.bytes_mut()[..ciphertext.len()];
+        cipher.open(output, &self.encrypted, &[], &chunk_nonce, &*self.key)?;
+        self.index += 1;
+        Ok((output.len(), is_last))
+    }
+}
+
+/// Core of `StreamPwBox` and `RestoredStreamPwBox`.
+#[derive(Debug)]
+pub(crate) struct StreamInner<K, C> {
+    pub(crate) salt: Vec<u8>,
+    pub(crate) nonce: Vec<u8>,
+    pub(crate) chunk_size: u32,
+    pub(crate) kdf: K,
+    pub(crate) cipher: C,
+}
+
+impl<K: DeriveKey, C: ObjectSafeCipher> StreamInner<K, C> {
+    /// Generates the salt and nonce for a new stream and derives the key, reporting
+    /// progress to the callback.
+    fn start<R: RngCore + ?Sized>(
+        kdf: K,
+        cipher: C,
+        rng: &mut R,
+        chunk_size: u32,
+        password: &[u8],
+        progress: &mut dyn FnMut(Progress),
+    ) -> Result<(Self, ChunkSealer), Error> {
+        if chunk_size == 0 {
+            return Err(Error::ChunkSize);
+        }
+        if cipher.nonce_len() < MIN_NONCE_LEN {
+            let reason = "cipher nonce is too short to be used for streaming";
+            return Err(Error::SealOptions(reason.into()));
+        }
+
+        let mut salt = vec![0_u8; kdf.salt_len()];
+        rng.fill_bytes(&mut salt);
+        let mut nonce = vec![0_u8; cipher.nonce_len()];
+        rng.fill_bytes(&mut nonce);
+        let mut key = SensitiveData::zeros(cipher.key_len());
+        progress(Progress::DerivingKey);
+        trace_kdf!(
+            salt_len = salt.len(),
+            kdf.derive_key(key.bytes_mut(), password, &salt)
+        )
+        .map_err(Error::DeriveKey)?;
+        progress(Progress::KeyDerived);
+
+        let sealer = ChunkSealer {
+            key,
+            ciphertext: vec![0_u8; chunk_size as usize],
+            mac: vec![0_u8; cipher.mac_len()],
+            index: 0,
+        };
+        let inner = StreamInner {
+            salt,
+            nonce,
+            chunk_size,
+            kdf,
+            cipher,
+        };
+        Ok((inner, sealer))
+    }
+
+    /// Seals the stream from `reader` into `writer`, reporting progress to the callback.
+    pub(crate) fn seal<R: RngCore + ?Sized>(
+        kdf: K,
+        cipher: C,
+        rng: &mut R,
+        chunk_size: u32,
+        password: &[u8],
+        (reader, mut writer): (impl Read, impl Write),
+        progress: &mut dyn FnMut(Progress),
+    ) -> Result<Self, Error> {
+        enter_span!("seal_stream", cipher = %cipher.name(), chunk_size);
+        let (inner, mut sealer) = Self::start(kdf, cipher, rng, chunk_size, password, progress)?;
+
+        let mut reader = ChunkReader::new(reader);
+        let mut chunk = SensitiveData::zeros(chunk_size as usize);
+        let mut bytes_done = 0_u64;
+        loop {
+            let (len, is_last) = reader.read_chunk(chunk.bytes_mut()).map_err(Error::Io)?;
+            sealer
+                .seal_chunk(&inner, &chunk[..len], is_last, &mut writer)
+                .map_err(Error::Io)?;
+            bytes_done += len as u64;
+            progress(Progress::Encrypting {
+                bytes_done,
+                bytes_total: if is_last { Some(bytes_done) } else { None },
+            });
+
+            if is_last {
+                break;
+            }
+        }
+        writer.flush().map_err(Error::Io)?;
+        Ok(inner)
+    }
+
+    fn open(
+        &self,
+        password: &[u8],
+        reader: impl Read,
+        mut writer: impl Write,
+    ) -> Result<(), Error> {
+        enter_span!("open_stream", cipher = %self.cipher.name(), chunk_size = self.chunk_size);
+        let mut opener = ChunkOpener::new(self, password, reader)?;
+        loop {
+            let (len, is_last) = opener.open_chunk(&self.cipher, &self.nonce)?;
+            writer
+                .write_all(&opener.plaintext[..len])
+                .map_err(Error::Io)?;
+            if is_last {
+                break;
+            }
+        }
+        writer.flush().map_err(Error::Io)
+    }
+
+    fn open_reader<R: Read>(
+        &self,
+        password: &[u8],
+        reader: R,
+    ) -> Result<PwBoxReader<'_, R>, Error> {
+        let opener = ChunkOpener::new(self, password, reader)?;
+        Ok(PwBoxReader {
+            cipher: &self.cipher,
+            nonce: &self.nonce,
+            opener,
+            pos: 0,
+            len: 0,
+            state: ReaderState::Reading,
+        })
+    }
+}
+
+impl<K: DeriveKey, C: Cipher> StreamInner<K, CipherObject<C>> {
+    /// Starts sealing a stream written to the returned `PwBoxWriter`.
+    pub(crate) fn seal_to_writer<R: RngCore + ?Sized, W: Write>(
+        kdf: K,
+        rng: &mut R,
+        chunk_size: u32,
+        password: &[u8],
+        writer: W,
+        progress: &mut dyn FnMut(Progress),
+    ) -> Result<PwBoxWriter<K, C, W>, Error> {
+        let cipher = CipherObject::default();
+        let (inner, sealer) = Self::start(kdf, cipher, rng, chunk_size, password, progress)?;
+        Ok(PwBoxWriter {
+            chunk: SensitiveData::zeros(chunk_size as usize),
+            len: 0,
+            inner,
+            sealer,
+            writer,
+        })
+    }
+}
+
+/// Writer sealing the written data into an encrypted stream. The writer is created with
+/// [`PwBoxBuilder::seal_to_writer()`](crate::PwBoxBuilder::seal_to_writer()).
+///
+/// The data is buffered until a full chunk is accumulated; the encrypted chunks are written
+/// to the underlying writer. The stream must be completed with [`Self::finish()`];
+/// if the writer is dropped without finishing, the stream lacks the last chunk and cannot
+/// be opened. Likewise, the stream is unusable if any write fails.
+pub struct PwBoxWriter<K, C, W> {
+    inner: StreamInner<K, CipherObject<C>>,
+    sealer: ChunkSealer,
+    chunk: SensitiveData,
+    /// Number of bytes buffered in `chunk`.
+    len: usize,
+    writer: W,
+}
+
+impl<K, C, W> fmt::Debug for PwBoxWriter<K, C, W> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("PwBoxWriter")
+            .field("chunk_size", &self.inner.chunk_size)
+            .field("buffered_len", &self.len)
+            .finish()
+    }
+}
+
+impl<K: DeriveKey, C: Cipher, W: Write> PwBoxWriter<K, C, W> {
+    /// Seals the buffered data as the last chunk of the stream and flushes the underlying
+    /// writer. Returns the box containing the information necessary to decrypt the stream,
+    /// together with the underlying writer.
+    pub fn finish(mut self) -> io::Result<(StreamPwBox<K, C>, W)> {
+        let last_chunk = &self.chunk[..self.len];
+        self.sealer
+            .seal_chunk(&self.inner, last_chunk, true, &mut self.writer)?;
+        self.writer.flush()?;
+        Ok((StreamPwBox { inner: self.inner }, self.writer))
+    }
+}
+
+impl<K: DeriveKey, C: Cipher, W: Write> Write for PwBoxWriter<K, C, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let chunk_size = self.chunk.len();
+        if self.len == chunk_size {
+            // The buffered chunk is not the last one, since there is more data.
+            self.sealer
+                .seal_chunk(&self.inner, &self.chunk, false, &mut self.writer)?;
+            self.len = 0;
+        }
+        let written = buf.len().min(chunk_size - self.len);
+        self.chunk.bytes_mut()[self.len..(self.len + written)].copy_from_slice(&buf[..written]);
+        self.len += written;
+        Ok(written)
+    }
+
+    /// Flushes the underlying writer. The buffered data is not written, since it can only
+    /// be sealed when it is known whether it forms the last chunk of the stream.
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReaderState {
+    Reading,
+    Finished,
+    Failed,
+}
+
+/// Reader decrypting an encrypted stream. The reader is created with `open_reader()`
+/// method of [`StreamPwBox`] or [`RestoredStreamPwBox`].
+///
+/// Each chunk is authenticated before any of its data is returned. If a chunk cannot
+/// be authenticated (e.g., because the stream is corrupted or truncated), an error
+/// of the [`InvalidData`](io::ErrorKind::InvalidData) kind wrapping [`Error::MacMismatch`]
+/// is returned. The data read before the error should be discarded, and all subsequent
+/// reads fail.
+pub struct PwBoxReader<'a, R> {
+    cipher: &'a dyn ObjectSafeCipher,
+    nonce: &'a [u8],
+    opener: ChunkOpener<R>,
+    /// Position of the next byte to be read from `opener.plaintext`.
+    pos: usize,
+    /// Length of the current chunk plaintext.
+    len: usize,
+    state: ReaderState,
+}
+
+impl<R> fmt::Debug for PwBoxReader<'_, R> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("PwBoxReader")
+            .field("chunk_index", &self.opener.index)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl<R: Read> Read for PwBoxReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.len {
+            match self.state {
+                ReaderState::Reading => {}
+                ReaderState::Finished => return Ok(0),
+                ReaderState::Failed => {
+                    let message = "stream cannot be read after an error";
+                    return Err(io::Error::new(io::ErrorKind::Other, message));
+                }
+            }
+
+            match self.opener.open_chunk(self.cipher, self.nonce) {
+                Ok((len, is_last)) => {
+                    self.pos = 0;
+                    self.len = len;
+                    if is_last {
+                        self.state = ReaderState::Finished;
+                    }
+                }
+                Err(err) => {
+                    self.state = ReaderState::Failed;
+                    return Err(match err {
+                        Error::Io(err) => err,
+                        err => io::Error::new(io::ErrorKind::InvalidData, err),
+                    });
+                }
+            }
+        }
+
+        let read = buf.len().min(self.len - self.pos);
+        buf[..read].copy_from_slice(&self.opener.plaintext[self.pos..(self.pos + read)]);
+        self.pos += read;
+        Ok(read)
+    }
+}
+
+/// Password-encrypted stream of data.
+///
+/// Unlike [`PwBox`](crate::PwBox), the box does not contain the ciphertext; the latter
+/// is written to an [`io::Write`] instance during sealing, and is read from
+/// an [`io::Read`] instance during opening. This allows to encrypt payloads which do not fit
+/// into memory.
+///
+/// Use [`PwBoxBuilder::seal_from_reader()`](crate::PwBoxBuilder::seal_from_reader())
+/// or [`PwBoxBuilder::seal_to_writer()`](crate::PwBoxBuilder::seal_to_writer())
+/// to create boxes, and [`Eraser::erase_stream()`](crate::Eraser::erase_stream())
+/// to serialize them.
+#[derive(Debug)]
+pub struct StreamPwBox<K, C> {
+    pub(crate) inner: StreamInner<K, CipherObject<C>>,
+}
+
+impl<K, C> StreamPwBox<K, C> {
+    /// Default byte size of a plaintext chunk (64 KiB).
+    pub const DEFAULT_CHUNK_SIZE: u32 = 1 << 16;
+}
+
+impl<K: DeriveKey, C: Cipher> StreamPwBox<K, C> {
+    /// Returns the byte size of a plaintext chunk.
+    pub fn chunk_size(&self) -> u32 {
+        self.inner.chunk_size
+    }
+
+    /// Decrypts the stream from `reader` and writes the plaintext to `writer`.
+    ///
+    /// Chunks are written to the `writer` as soon as they are authenticated. Thus,
+    /// if an error occurs (e.g., the stream is truncated), the `writer` may contain
+    /// partial plaintext, which should be discarded.
+    pub fn open_to_writer(
+        &self,
+        password: impl AsRef<[u8]>,
+        reader: impl Read,
+        writer: impl Write,
+    ) -> Result<(), Error> {
+        self.inner.open(password.as_ref(), reader, writer)
+    }
+
+    /// Derives the key from the password and returns a reader decrypting the stream
+    /// from `reader`. See [`PwBoxReader`] for details.
+    pub fn open_reader<R: Read>(
+        &self,
+        password: impl AsRef<[u8]>,
+        reader: R,
+    ) -> Result<PwBoxReader<'_, R>, Error> {
+        self.inner.open_reader(password.as_ref(), reader)
+    }
+}
+
+/// Password-encrypted stream restored after deserialization.
+pub struct RestoredStreamPwBox {
+    pub(crate) inner: StreamInner<Arc<dyn DeriveKey>, Arc<dyn ObjectSafeCipher>>,
+}
+
+impl fmt::Debug for RestoredStreamPwBox {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("RestoredStreamPwBox")
+            .field("chunk_size", &self.inner.chunk_size)
+            .finish()
+    }
+}
+
+impl RestoredStreamPwBox {
+    /// Returns the byte size of a plaintext chunk.
+    pub fn chunk_size(&self) -> u32 {
+        self.inner.chunk_size
+    }
+
+    /// Decrypts the stream from `reader` and writes the plaintext to `writer`.
+    ///
+    /// Chunks are written to the `writer` as soon as they are authenticated. Thus,
+    /// if an error occurs (e.g., the stream is truncated), the `writer` may contain
+    /// partial plaintext, which should be discarded.
+    pub fn open_to_writer(
+        &self,
+        password: impl AsRef<[u8]>,
+        reader: impl Read,
+        writer: impl Write,
+    ) -> Result<(), Error> {
+        self.inner.open(password.as_ref(), reader, writer)
+    }
+
+    /// Derives the key from the password and returns a reader decrypting the stream
+    /// from `reader`. See [`PwBoxReader`] for details.
+    pub fn open_reader<R: Read>(
+        &self,
+        password: impl AsRef<[u8]>,
+        reader: R,
+    ) -> Result<PwBoxReader<'_, R>, Error> {
+        self.inner.open_reader(password.as_ref(), reader)
+    }
+}
+
+#[cfg(all(test, feature = "exonum_sodiumoxide"))]
+mod tests {
+    use super::*;
+    use crate::{
+        sodium::{ChaCha20Poly1305, Scrypt, Sodium, XSalsa20Poly1305},
+        Eraser, PwBoxBuilder, Suite,
+    };
+
+    use assert_matches::assert_matches;
+    use rand::{thread_rng, Rng};
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    fn roundtrip<C: Cipher>(message: &[u8], chunk_size: u32) {
+        let mut rng = thread_rng();
+        let mut encrypted = vec![];
+        let pwbox = PwBoxBuilder::<_, C>::new(&mut rng)
+            .kdf(Scrypt::light())
+            .chunk_size(chunk_size)
+            .seal_from_reader(PASSWORD, message, &mut encrypted)
+            .unwrap();
+        assert_eq!(pwbox.chunk_size(), chunk_size);
+
+        let chunk_size = chunk_size as usize;
+        let chunk_count = ((message.len() + chunk_size - 1) / chunk_size).max(1);
+        assert_eq!(encrypted.len(), message.len() + chunk_count * C::MAC_LEN);
+
+        let mut decrypted = vec![];
+        pwbox
+            .open_to_writer(PASSWORD, &*encrypted, &mut decrypted)
+            .unwrap();
+        assert_eq!(decrypted, message);
+    }
+
+    #[test]
+    fn stream_roundtrip() {
+        let mut message = vec![0_u8; 10_000];
+        thread_rng().fill(&mut message[..]);
+
+        for &len in &[0, 1, 99, 100, 101, 1_000, 10_000] {
+            roundtrip::<XSalsa20Poly1305>(&message[..len], 100);
+            roundtrip::<ChaCha20Poly1305>(&message[..len], 100);
+        }
+        let chunk_size = StreamPwBox::<Scrypt, XSalsa20Poly1305>::DEFAULT_CHUNK_SIZE;
+        roundtrip::<XSalsa20Poly1305>(&message, chunk_size);
+    }
+
+    #[test]
+    fn corrupted_stream() {
+        const CHUNK_SIZE: u32 = 100;
+        const CHUNK_LEN: usize = CHUNK_SIZE as usize + XSalsa20Poly1305::MAC_LEN;
+
+        let mut message = vec![0_u8; 1_000];
+        thread_rng().fill(&mut message[..]);
+        let mut encrypted = vec![];
+        let pwbox = Sodium::build_box(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .chunk_size(CHUNK_SIZE)
+            .seal_from_reader(PASSWORD, &*message, &mut encrypted)
+            .unwrap();
+
+        let open = |encrypted: &[u8], password: &str| {
+            let mut decrypted = vec![];
+            pwbox.open_to_writer(password, encrypted, &mut decrypted)
+        };
+
+        // Truncate the stream at the chunk boundary.
+        let truncated = &encrypted[..CHUNK_LEN * 5];
+        assert_matches!(open(truncated, PASSWORD).unwrap_err(), Error::MacMismatch);
+        // Truncate the stream in the middle of the chunk.
+        let truncated = &encrypted[..encrypted.len() - 1];
+        assert_matches!(open(truncated, PASSWORD).unwrap_err(), Error::MacMismatch);
+        let truncated = &encrypted[..CHUNK_LEN * 3 + 5];
+        assert_matches!(open(truncated, PASSWORD).unwrap_err(), Error::MacMismatch);
+        // Empty stream.
+        assert_matches!(open(&[], PASSWORD).unwrap_err(), Error::MacMismatch);
+
+        // Swap two chunks.
+        let mut swapped = encrypted.clone();
+        swapped[..CHUNK_LEN * 2].rotate_left(CHUNK_LEN);
+        assert_matches!(open(&swapped, PASSWORD).unwrap_err(), Error::MacMismatch);
+
+        // Extend the stream.
+        let mut extended = encrypted.clone();
+        extended.push(0);
+        assert_matches!(open(&extended, PASSWORD).unwrap_err(), Error::MacMismatch);
+
+        // Incorrect password.
+        assert_matches!(
+            open(&encrypted, "incorrect password").unwrap_err(),
+            Error::MacMismatch
+        );
+    }
+
+    #[test]
+    fn stream_erasure() {
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+
+        let mut message = vec![0_u8; 1_000];
+        thread_rng().fill(&mut message[..]);
+        let mut encrypted = vec![];
+        let pwbox = Sodium::build_box(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .chunk_size(256)
+            .seal_from_reader(PASSWORD, &*message, &mut encrypted)
+            .unwrap();
+
+        let erased_box = eraser.erase_stream(&pwbox).unwrap();
+        let json = serde_json::to_value(&erased_box).unwrap();
+        assert_eq!(json["cipherparams"]["chunk_size"], 256);
+        assert_matches!(
+            eraser.restore(&erased_box).map(drop).unwrap_err(),
+            Error::ChunkSize
+        );
+
+        let restored = eraser.restore_stream(&erased_box).unwrap();
+        assert_eq!(restored.chunk_size(), 256);
+        let mut decrypted = vec![];
+        restored
+            .open_to_writer(PASSWORD, &*encrypted, &mut decrypted)
+            .unwrap();
+        assert_eq!(decrypted, message);
+
+        // Ordinary boxes cannot be restored as streams.
+        let pwbox = Sodium::build_box(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .seal(PASSWORD, &message)
+            .unwrap();
+        let erased_box = eraser.erase(&pwbox).unwrap();
+        assert_matches!(
+            eraser.restore_stream(&erased_box).map(drop).unwrap_err(),
+            Error::ChunkSize
+        );
+    }
+
+    #[test]
+    fn writer_and_reader_roundtrip() {
+        const CHUNK_SIZE: u32 = 100;
+
+        let mut message = vec![0_u8; 1_000];
+        thread_rng().fill(&mut message[..]);
+
+        for &len in &[0, 1, 99, 100, 101, 1_000] {
+            let message = &message[..len];
+            let mut writer = Sodium::build_box(&mut thread_rng())
+                .kdf(Scrypt::light())
+                .chunk_size(CHUNK_SIZE)
+                .seal_to_writer(PASSWORD, vec![])
+                .unwrap();
+            // Write the message in pieces not aligned with chunks.
+            for piece in message.chunks(7) {
+                writer.write_all(piece).unwrap();
+            }
+            let (pwbox, encrypted) = writer.finish().unwrap();
+            let chunk_count = ((len + 99) / 100).max(1);
+            assert_eq!(
+                encrypted.len(),
+                len + chunk_count * XSalsa20Poly1305::MAC_LEN
+            );
+
+            // The stream is compatible with `open_to_writer()`.
+            let mut decrypted = vec![];
+            pwbox
+                .open_to_writer(PASSWORD, &*encrypted, &mut decrypted)
+                .unwrap();
+            assert_eq!(decrypted, message);
+
+            let mut reader = pwbox.open_reader(PASSWORD, &*encrypted).unwrap();
+            let mut decrypted = vec![];
+            let mut buffer = [0_u8; 13];
+            loop {
+                let read = reader.read(&mut buffer).unwrap();
+                if read == 0 {
+                    break;
+                }
+                decrypted.extend_from_slice(&buffer[..read]);
+            }
+            assert_eq!(decrypted, message);
+        }
+    }
+
+    #[test]
+    fn reader_with_corrupted_stream() {
+        const CHUNK_SIZE: u32 = 100;
+
+        let mut message = vec![0_u8; 1_000];
+        thread_rng().fill(&mut message[..]);
+        let mut encrypted = vec![];
+        let pwbox = Sodium::build_box(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .chunk_size(CHUNK_SIZE)
+            .seal_from_reader(PASSWORD, &*message, &mut encrypted)
+            .unwrap();
+
+        let mut decrypted = vec![];
+        let mut reader = pwbox.open_reader(PASSWORD, &*encrypted).unwrap();
+        reader.read_to_end(&mut decrypted).unwrap();
+        assert_eq!(decrypted, message);
+
+        // Truncate the stream at the chunk boundary.
+        let truncated = &encrypted[..(CHUNK_SIZE as usize + XSalsa20Poly1305::MAC_LEN) * 5];
+        let mut reader = pwbox.open_reader(PASSWORD, truncated).unwrap();
+        let mut decrypted = vec![];
+        let err = reader.read_to_end(&mut decrypted).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = err.into_inner().unwrap().downcast::<Error>().unwrap();
+        assert_matches!(*err, Error::MacMismatch);
+        // All chunks except for the last one are returned.
+        assert_eq!(decrypted, &message[..400]);
+        // The reader is poisoned.
+        assert!(reader.read(&mut [0; 16]).is_err());
+
+        let mut reader = pwbox
+            .open_reader("incorrect password", &*encrypted)
+            .unwrap();
+        let err = reader.read(&mut [0; 16]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn writer_buffers_last_chunk() {
+        let mut writer = Sodium::build_box(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .chunk_size(100)
+            .seal_to_writer(PASSWORD, vec![])
+            .unwrap();
+        writer.write_all(&[1; 250]).unwrap();
+        writer.flush().unwrap();
+        // The last chunk is buffered; the preceding chunks are written.
+        assert_eq!(writer.writer.len(), 2 * (100 + XSalsa20Poly1305::MAC_LEN));
+    }
+}
@@ -17,10 +17,16 @@
 use anyhow::Error;
 use hex_buffer_serde::{Hex as _, HexForm};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map as JsonMap, Value as JsonValue};
 
-use core::{fmt, marker::PhantomData};
+use core::{any::TypeId, fmt, marker::PhantomData};
 
-use crate::alloc::{Box, Vec};
+#[cfg(feature = "tracing")]
+use crate::alloc::Cow;
+use crate::{
+    alloc::{vec, Arc, Box, String, Vec},
+    SecretVec,
+};
 
 /// Key derivation function (KDF).
 ///
@@ -35,16 +41,56 @@ use crate::alloc::{Box, Vec};
 /// - [`Default`] (should return a KDF instance with reasonable difficulty params)
 /// - [`Clone`]
 /// - [`Serialize`] / [`Deserialize`] from `serde`
-pub trait DeriveKey: 'static {
+///
+/// The KDF must be thread-safe, so that restored boxes can be opened in parallel.
+pub trait DeriveKey: 'static + Send + Sync {
     /// Returns byte size of salt supplied to the KDF.
     fn salt_len(&self) -> usize;
 
+    /// Checks whether the KDF accepts salt of the specified byte size. This is used when
+    /// restoring boxes, which may be produced by other software with a different salt size.
+    ///
+    /// The default implementation only accepts [`salt_len()`](Self::salt_len).
+    fn valid_salt_len(&self, len: usize) -> bool {
+        len == self.salt_len()
+    }
+
     /// Derives a key from the given password and salt.
     ///
     /// # Safety
     ///
-    /// When used within `PwBox`, `salt` is guaranteed to have the correct size.
+    /// When used within `PwBox`, `salt` is guaranteed to have a size accepted by
+    /// [`valid_salt_len()`](Self::valid_salt_len).
     fn derive_key(&self, buf: &mut [u8], password: &[u8], salt: &[u8]) -> Result<(), Error>;
+
+    /// Checks that the KDF params are valid. This is called by [`Eraser`](crate::Eraser)
+    /// after the KDF is parsed from a box, so that invalid params are rejected before
+    /// attempting key derivation.
+    ///
+    /// The default implementation accepts all params.
+    fn validate_params(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Returns the estimated resources required to derive a key, or `None` if the estimate
+    /// is not available. This is used by [`Eraser`](crate::Eraser) to enforce
+    /// [`KdfLimits`](crate::KdfLimits).
+    ///
+    /// The default implementation returns `None`.
+    fn cost(&self) -> Option<KdfCost> {
+        None
+    }
+}
+
+/// Estimated resources required to derive a key with a [KDF](DeriveKey).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfCost {
+    /// Peak memory consumption in bytes.
+    pub memory_bytes: u64,
+    /// Time cost expressed as the number of bytes processed by the underlying primitive
+    /// (e.g., by the `scrypt` mixing function, or by the hash function in PBKDF2).
+    /// Unlike [`KdfDifficulty::ops_cost()`], this cost is comparable among different KDFs.
+    pub time_cost: u64,
 }
 
 impl DeriveKey for Box<dyn DeriveKey> {
@@ -52,9 +98,226 @@ impl DeriveKey for Box<dyn DeriveKey> {
         (**self).salt_len()
     }
 
+    fn valid_salt_len(&self, len: usize) -> bool {
+        (**self).valid_salt_len(len)
+    }
+
     fn derive_key(&self, buf: &mut [u8], password: &[u8], salt: &[u8]) -> Result<(), Error> {
         (**self).derive_key(buf, password, salt)
     }
+    fn validate_params(&self) -> Result<(), Error> {
+        (**self).validate_params()
+    }
+
+    fn cost(&self) -> Option<KdfCost> {
+        (**self).cost()
+    }
+}
+
+impl DeriveKey for Arc<dyn DeriveKey> {
+    fn salt_len(&self) -> usize {
+        (**self).salt_len()
+    }
+
+    fn valid_salt_len(&self, len: usize) -> bool {
+        (**self).valid_salt_len(len)
+    }
+
+    fn derive_key(&self, buf: &mut [u8], password: &[u8], salt: &[u8]) -> Result<(), Error> {
+        (**self).derive_key(buf, password, salt)
+    }
+    fn validate_params(&self) -> Result<(), Error> {
+        (**self).validate_params()
+    }
+
+    fn cost(&self) -> Option<KdfCost> {
+        (**self).cost()
+    }
+}
+
+/// Identity "KDF" using the password as the key. This allows to seal boxes with randomly
+/// generated keys (e.g., stored in key files), for which a password-based KDF is
+/// a needless overhead.
+///
+/// The password must have the same byte size as the cipher key; otherwise, key derivation
+/// fails. No salt is used. The KDF is registered as `none` in all [`Suite`]s,
+/// with `raw` accepted as an alias.
+/// Boxes can be sealed with it via [`PwBoxBuilder::seal_with_key()`].
+///
+/// [`Suite`]: crate::Suite
+/// [`PwBoxBuilder::seal_with_key()`]: crate::PwBoxBuilder::seal_with_key()
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct NoKdf {}
+
+impl DeriveKey for NoKdf {
+    fn salt_len(&self) -> usize {
+        0
+    }
+
+    fn derive_key(&self, buf: &mut [u8], password: &[u8], _salt: &[u8]) -> Result<(), Error> {
+        anyhow::ensure!(
+            password.len() == buf.len(),
+            "invalid key length: expected {} bytes, got {}",
+            buf.len(),
+            password.len()
+        );
+        buf.copy_from_slice(password);
+        Ok(())
+    }
+
+    fn cost(&self) -> Option<KdfCost> {
+        Some(KdfCost {
+            memory_bytes: 0,
+            time_cost: 0,
+        })
+    }
+}
+
+/// Key derivation function requiring mutable access to its state, such as a KDF backed
+/// by a hardware device (e.g., an HMAC challenge-response on a security token,
+/// with the salt used as the challenge). Unlike [`DeriveKey`], implementations
+/// are not required to be `'static`, `Send` or `Sync`, so they may hold a device session.
+///
+/// Such a KDF is not stored in a box. Instead, the box is sealed with
+/// [`PwBoxBuilder::seal_with_external_kdf()`], which records an [`ExternalKdf`] placeholder,
+/// and is opened with the `open_with_kdf()` method of [`PwBox`] or [`RestoredPwBox`],
+/// which takes the KDF instance from the caller.
+///
+/// [`PwBoxBuilder::seal_with_external_kdf()`]: crate::PwBoxBuilder::seal_with_external_kdf()
+/// [`PwBox`]: crate::PwBox
+/// [`RestoredPwBox`]: crate::RestoredPwBox
+pub trait DeriveKeyMut {
+    /// Returns byte size of salt supplied to the KDF.
+    fn salt_len(&self) -> usize;
+
+    /// Checks whether the KDF accepts salt of the specified byte size. The salt length
+    /// is checked before opening a box, so the KDF is not invoked with unexpected salt.
+    ///
+    /// The default implementation only accepts [`salt_len()`](Self::salt_len).
+    fn valid_salt_len(&self, len: usize) -> bool {
+        len == self.salt_len()
+    }
+
+    /// Derives a key from the given password and salt.
+    fn derive_key(&mut self, buf: &mut [u8], password: &[u8], salt: &[u8]) -> Result<(), Error>;
+}
+
+/// Adapter allowing to use a `DeriveKey` implementation as a `DeriveKeyMut` one.
+pub(crate) struct KdfRef<'a, K: ?Sized>(pub &'a K);
+
+impl<K: DeriveKey + ?Sized> DeriveKeyMut for KdfRef<'_, K> {
+    fn salt_len(&self) -> usize {
+        self.0.salt_len()
+    }
+
+    fn valid_salt_len(&self, len: usize) -> bool {
+        self.0.valid_salt_len(len)
+    }
+
+    fn derive_key(&mut self, buf: &mut [u8], password: &[u8], salt: &[u8]) -> Result<(), Error> {
+        self.0.derive_key(buf, password, salt)
+    }
+}
+
+/// Placeholder for a [`DeriveKeyMut`] implementation external to the box, e.g., one backed
+/// by a hardware device. The placeholder records opaque params, such as an identifier
+/// of the device, which are serialized as is. The KDF is registered as `external`
+/// in all [`Suite`]s.
+///
+/// The placeholder cannot derive keys; opening a box with it via `open()` fails
+/// with [`Error::DeriveKey`](crate::Error::DeriveKey). Boxes with this KDF should be opened
+/// with `open_with_kdf()` instead. The placeholder accepts salt of any size;
+/// the salt size is checked by the external KDF.
+///
+/// [`Suite`]: crate::Suite
+///
+/// # Examples
+///
+/// ```
+/// # use rand::thread_rng;
+/// # use pwbox::{sodium::{Sodium, XChaCha20Poly1305}, DeriveKeyMut, ExternalKdf, Eraser, Suite};
+/// # use serde_json::json;
+/// struct Device(u8);
+///
+/// impl DeriveKeyMut for Device {
+///     fn salt_len(&self) -> usize { 16 }
+///
+///     fn derive_key(
+///         &mut self,
+///         buf: &mut [u8],
+///         password: &[u8],
+///         salt: &[u8],
+///     ) -> anyhow::Result<()> {
+///         // A real device would compute, e.g., HMAC of the challenge.
+///         for (i, byte) in buf.iter_mut().enumerate() {
+///             *byte = self.0 ^ password[i % password.len()] ^ salt[i % salt.len()];
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let mut device = Device(42);
+/// let mut params = ExternalKdf::default();
+/// params.0.insert("device".to_owned(), json!("token-1"));
+/// let pwbox = Sodium::build_box(&mut thread_rng())
+///     .seal_with_external_kdf(&mut device, params, "correct horse", b"battery staple")?;
+///
+/// let eraser = Eraser::with_suites::<(Sodium,)>()?;
+/// let erased = eraser.erase(&pwbox)?;
+/// assert_eq!(erased.kdf_name(), "external");
+/// assert_eq!(erased.kdf_params()["device"], "token-1");
+/// let restored = eraser.restore(&erased)?;
+/// let opened = restored.open_with_kdf(&mut device, "correct horse")?;
+/// assert_eq!(&*opened, b"battery staple");
+/// # Ok::<_, anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ExternalKdf(pub JsonMap<String, JsonValue>);
+
+impl DeriveKey for ExternalKdf {
+    /// Returns 0; the salt is generated according to the external KDF.
+    fn salt_len(&self) -> usize {
+        0
+    }
+
+    fn valid_salt_len(&self, _len: usize) -> bool {
+        true
+    }
+
+    fn derive_key(&self, _buf: &mut [u8], _password: &[u8], _salt: &[u8]) -> Result<(), Error> {
+        anyhow::bail!("key must be derived by an external KDF; use `open_with_kdf()`")
+    }
+}
+
+/// Difficulty preset for a key derivation function.
+///
+/// The presets follow the `libsodium` naming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KdfPreset {
+    /// Parameters suitable for interactive use, e.g., to unlock a wallet.
+    Interactive,
+    /// Parameters suitable for infrequent operations. Requires more time and memory
+    /// than `Interactive`.
+    Moderate,
+    /// Parameters suitable for highly sensitive data. Requires significant time and memory.
+    Sensitive,
+}
+
+/// KDF with a tunable difficulty.
+///
+/// Difficulty getters can be used, e.g., to warn the user if a box was sealed
+/// with weak parameters.
+pub trait KdfDifficulty: DeriveKey + Sized {
+    /// Instantiates the KDF with the parameters from the specified preset.
+    fn preset(preset: KdfPreset) -> Self;
+
+    /// Returns the approximate amount of memory in bytes used by the KDF.
+    fn memory_cost(&self) -> u64;
+
+    /// Returns the computational cost of the KDF. The cost is expressed in KDF-specific units,
+    /// so costs are only comparable among instances of the same KDF.
+    fn ops_cost(&self) -> u64;
 }
 
 /// Error corresponding to MAC mismatch in [`Cipher::open()`].
@@ -78,17 +341,46 @@ pub trait Cipher: 'static {
     const NONCE_LEN: usize;
     /// Byte size of a message authentication code (MAC).
     const MAC_LEN: usize;
+    /// Does the cipher support associated data? If set to `false`, the cipher will
+    /// only be supplied with empty associated data within [`PwBox`](crate::PwBox).
+    const SUPPORTS_ASSOCIATED_DATA: bool = true;
 
-    /// Encrypts `message` with the provided `key` and `nonce`.
+    /// Encrypts `message` with the provided `key` and `nonce`. `associated_data` is
+    /// authenticated, but not encrypted; it is not a part of the output.
     ///
     /// # Safety
     ///
     /// When used within [`PwBox`](crate::PwBox), `key` and `nonce` are guaranteed
     /// to have correct sizes.
-    fn seal(message: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput;
+    fn seal(message: &[u8], associated_data: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput;
+
+    /// Encrypts `message` similarly to [`Self::seal()`], but writes the output into
+    /// caller-provided buffers. This allows reusing buffers when sealing multiple messages.
+    ///
+    /// The default implementation calls [`Self::seal()`] and copies its output. Ciphers
+    /// which can encrypt in place should override this method (and may implement `seal()`
+    /// in terms of it), so that sealing does not allocate intermediate buffers.
+    ///
+    /// # Safety
+    ///
+    /// When used within [`PwBox`](crate::PwBox), `key` and `nonce` are guaranteed
+    /// to have correct sizes, `out.ciphertext` has the same size as `message`,
+    /// and `out.mac` has [`Self::MAC_LEN`] bytes.
+    fn seal_into(
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        out: &mut CipherOutputRef<'_>,
+    ) {
+        let output = Self::seal(message, associated_data, nonce, key);
+        out.ciphertext.copy_from_slice(&output.ciphertext);
+        out.mac.copy_from_slice(&output.mac);
+    }
 
     /// Decrypts `encrypted` message with the provided `key` and `nonce` and stores
-    /// the result into `output`. If the MAC does not verify, returns an error.
+    /// the result into `output`. If the MAC does not verify (including the case
+    /// when `associated_data` differs from the one used during sealing), returns an error.
     ///
     /// # Safety
     ///
@@ -97,14 +389,179 @@ pub trait Cipher: 'static {
     fn open(
         output: &mut [u8],
         encrypted: &CipherOutput,
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch>;
+
+    /// Decrypts the message in place: `buffer` contains the ciphertext on input, and
+    /// the plaintext on successful output. If the MAC does not verify, an error is returned,
+    /// and the contents of `buffer` are unspecified.
+    ///
+    /// The default implementation copies the ciphertext and calls [`Self::open()`].
+    /// Ciphers which can decrypt in place should override this method (and may implement
+    /// `open()` in terms of it), so that opening large boxes does not require memory
+    /// for two copies of the data.
+    ///
+    /// # Safety
+    ///
+    /// When used within [`PwBox`](crate::PwBox), `key`, `nonce` and `mac`
+    /// are guaranteed to have correct sizes.
+    fn open_in_place(
+        buffer: &mut [u8],
+        mac: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        let encrypted = CipherOutput {
+            ciphertext: buffer.to_vec(),
+            mac: mac.to_vec(),
+        };
+        Self::open(buffer, &encrypted, associated_data, nonce, key)
+    }
+
+    /// Checks the MAC of the `encrypted` message without returning the decrypted data.
+    ///
+    /// The default implementation decrypts the message into a scratch buffer zeroed on drop.
+    /// Ciphers which can authenticate the message without decrypting it should override
+    /// this method.
+    ///
+    /// # Safety
+    ///
+    /// When used within [`PwBox`](crate::PwBox), `key`, `nonce` and `encrypted.mac`
+    /// are guaranteed to have correct sizes.
+    fn verify(
+        encrypted: &CipherOutput,
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        let mut output = SecretVec::zeros(encrypted.ciphertext.len());
+        Self::open(&mut output, encrypted, associated_data, nonce, key)
+    }
+
+    /// Returns the type IDs of the unauthenticated cipher and the MAC if this cipher
+    /// is a [`CipherWithMac`](crate::CipherWithMac) composition, which can be named
+    /// by an [`Eraser`](crate::Eraser) after its halves.
+    ///
+    /// This method is not a part of the public API and should not be overridden.
+    #[doc(hidden)]
+    fn mac_composition() -> Option<(TypeId, TypeId)> {
+        None
+    }
+}
+
+/// Cipher with sizes of keys, nonces and MACs only known at runtime, e.g., a cipher backed
+/// by a hardware security module or chosen from a configuration file.
+///
+/// This is an object-safe counterpart of [`Cipher`]; the methods have the same semantics
+/// as in `Cipher`. Dynamic ciphers are registered in an [`Eraser`] via
+/// [`Eraser::add_dyn_cipher()`], and boxes can be sealed with them via
+/// [`Eraser::seal_with_cipher()`].
+///
+/// [`Eraser`]: crate::Eraser
+/// [`Eraser::add_dyn_cipher()`]: crate::Eraser::add_dyn_cipher()
+/// [`Eraser::seal_with_cipher()`]: crate::Eraser::seal_with_cipher()
+pub trait DynCipher: 'static + Send + Sync {
+    /// Byte size of a key.
+    fn key_len(&self) -> usize;
+    /// Byte size of a nonce (aka initialization vector, or IV).
+    fn nonce_len(&self) -> usize;
+    /// Byte size of a message authentication code (MAC).
+    fn mac_len(&self) -> usize;
+
+    /// Does the cipher support associated data? The default implementation returns `true`.
+    fn supports_associated_data(&self) -> bool {
+        true
+    }
+
+    /// Encrypts `message` with the provided `key` and `nonce`. See [`Cipher::seal()`].
+    fn seal(
+        &self,
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> CipherOutput;
+
+    /// Encrypts `message` into caller-provided buffers. See [`Cipher::seal_into()`].
+    fn seal_into(
+        &self,
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        out: &mut CipherOutputRef<'_>,
+    ) {
+        let output = self.seal(message, associated_data, nonce, key);
+        out.ciphertext.copy_from_slice(&output.ciphertext);
+        out.mac.copy_from_slice(&output.mac);
+    }
+
+    /// Decrypts `encrypted` message into `output`. See [`Cipher::open()`].
+    fn open(
+        &self,
+        output: &mut [u8],
+        encrypted: &CipherOutput,
+        associated_data: &[u8],
         nonce: &[u8],
         key: &[u8],
     ) -> Result<(), MacMismatch>;
+
+    /// Decrypts the message in place. See [`Cipher::open_in_place()`].
+    fn open_in_place(
+        &self,
+        buffer: &mut [u8],
+        mac: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        let encrypted = CipherOutput {
+            ciphertext: buffer.to_vec(),
+            mac: mac.to_vec(),
+        };
+        self.open(buffer, &encrypted, associated_data, nonce, key)
+    }
+
+    /// Checks the MAC of the `encrypted` message. See [`Cipher::verify()`].
+    fn verify(
+        &self,
+        encrypted: &CipherOutput,
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        let mut output = SecretVec::zeros(encrypted.ciphertext.len());
+        self.open(&mut output, encrypted, associated_data, nonce, key)
+    }
+}
+
+/// Helper for converting `DynCipher`s into `ObjectSafeCipher`s.
+#[derive(Clone)]
+pub(crate) struct DynCipherObject {
+    cipher: Arc<dyn DynCipher>,
+    /// Name of the cipher recorded in tracing events.
+    #[cfg(feature = "tracing")]
+    name: String,
+}
+
+impl DynCipherObject {
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub fn new(cipher: Arc<dyn DynCipher>, name: &str) -> Self {
+        DynCipherObject {
+            cipher,
+            #[cfg(feature = "tracing")]
+            name: name.to_owned(),
+        }
+    }
 }
 
 /// Helper for converting `Cipher`s into `ObjectSafeCipher`s.
+// `fn() -> T` makes the helper thread-safe regardless of `T`; it holds no data anyway.
 #[derive(Debug)]
-pub(crate) struct CipherObject<T>(PhantomData<T>);
+pub(crate) struct CipherObject<T>(PhantomData<fn() -> T>);
 
 impl<T> Default for CipherObject<T> {
     fn default() -> Self {
@@ -112,25 +569,69 @@ impl<T> Default for CipherObject<T> {
     }
 }
 
+impl<T> Clone for CipherObject<T> {
+    fn clone(&self) -> Self {
+        CipherObject(PhantomData)
+    }
+}
+
 /// Object-safe equivalent of a `Cipher`.
-pub(crate) trait ObjectSafeCipher: 'static {
+pub(crate) trait ObjectSafeCipher: 'static + Send + Sync {
     fn key_len(&self) -> usize;
     fn nonce_len(&self) -> usize;
     fn mac_len(&self) -> usize;
+    fn supports_associated_data(&self) -> bool;
+
+    /// Returns the name of the cipher recorded in tracing events.
+    #[cfg(feature = "tracing")]
+    fn name(&self) -> Cow<'static, str>;
+
+    fn seal(
+        &self,
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> CipherOutput;
 
-    fn seal(&self, message: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput;
+    fn seal_into(
+        &self,
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        out: &mut CipherOutputRef<'_>,
+    );
 
     fn open(
         &self,
         output: &mut [u8],
         encrypted: &CipherOutput,
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch>;
+
+    fn open_in_place(
+        &self,
+        buffer: &mut [u8],
+        mac: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch>;
+
+    fn verify(
+        &self,
+        encrypted: &CipherOutput,
+        associated_data: &[u8],
         nonce: &[u8],
         key: &[u8],
     ) -> Result<(), MacMismatch>;
 }
 
 /// Output of a `Cipher`.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CipherOutput {
     /// Encrypted data. Has the same size as the original data.
     #[serde(with = "HexForm")]
@@ -141,6 +642,48 @@ pub struct CipherOutput {
     pub mac: Vec<u8>,
 }
 
+impl CipherOutput {
+    /// Seals `message` with [`Cipher::seal_into()`], allocating the output buffers
+    /// of the exact size. Ciphers overriding `seal_into()` may use this method
+    /// to implement [`Cipher::seal()`].
+    pub fn seal_with<C: Cipher>(
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Self {
+        let mut output = CipherOutput {
+            ciphertext: vec![0; message.len()],
+            mac: vec![0; C::MAC_LEN],
+        };
+        C::seal_into(
+            message,
+            associated_data,
+            nonce,
+            key,
+            &mut output.as_mut_ref(),
+        );
+        output
+    }
+
+    /// Borrows the output as a pair of mutable buffers.
+    pub fn as_mut_ref(&mut self) -> CipherOutputRef<'_> {
+        CipherOutputRef {
+            ciphertext: &mut self.ciphertext,
+            mac: &mut self.mac,
+        }
+    }
+}
+
+/// Mutable buffers for the output of [`Cipher::seal_into()`].
+#[derive(Debug)]
+pub struct CipherOutputRef<'a> {
+    /// Buffer for the encrypted data. Has the same size as the original data.
+    pub ciphertext: &'a mut [u8],
+    /// Buffer for the message authentication code. Has [`Cipher::MAC_LEN`] bytes.
+    pub mac: &'a mut [u8],
+}
+
 impl<T: Cipher> ObjectSafeCipher for CipherObject<T> {
     fn key_len(&self) -> usize {
         T::KEY_LEN
@@ -154,22 +697,173 @@ impl<T: Cipher> ObjectSafeCipher for CipherObject<T> {
         T::MAC_LEN
     }
 
-    fn seal(&self, message: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
-        T::seal(message, nonce, key)
+    fn supports_associated_data(&self) -> bool {
+        T::SUPPORTS_ASSOCIATED_DATA
+    }
+
+    #[cfg(feature = "tracing")]
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed(core::any::type_name::<T>())
+    }
+
+    fn seal(
+        &self,
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> CipherOutput {
+        T::seal(message, associated_data, nonce, key)
+    }
+
+    fn seal_into(
+        &self,
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        out: &mut CipherOutputRef<'_>,
+    ) {
+        T::seal_into(message, associated_data, nonce, key, out);
+    }
+
+    fn open(
+        &self,
+        output: &mut [u8],
+        encrypted: &CipherOutput,
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        trace_cipher!(
+            cipher = self.name(),
+            len = encrypted.ciphertext.len(),
+            T::open(output, encrypted, associated_data, nonce, key)
+        )
+    }
+
+    fn open_in_place(
+        &self,
+        buffer: &mut [u8],
+        mac: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        trace_cipher!(
+            cipher = self.name(),
+            len = buffer.len(),
+            T::open_in_place(buffer, mac, associated_data, nonce, key)
+        )
+    }
+
+    fn verify(
+        &self,
+        encrypted: &CipherOutput,
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        trace_cipher!(
+            cipher = self.name(),
+            len = encrypted.ciphertext.len(),
+            T::verify(encrypted, associated_data, nonce, key)
+        )
+    }
+}
+
+impl ObjectSafeCipher for DynCipherObject {
+    fn key_len(&self) -> usize {
+        self.cipher.key_len()
+    }
+
+    fn nonce_len(&self) -> usize {
+        self.cipher.nonce_len()
+    }
+
+    fn mac_len(&self) -> usize {
+        self.cipher.mac_len()
+    }
+
+    fn supports_associated_data(&self) -> bool {
+        self.cipher.supports_associated_data()
+    }
+
+    #[cfg(feature = "tracing")]
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Owned(self.name.clone())
+    }
+
+    fn seal(
+        &self,
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> CipherOutput {
+        self.cipher.seal(message, associated_data, nonce, key)
+    }
+
+    fn seal_into(
+        &self,
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        out: &mut CipherOutputRef<'_>,
+    ) {
+        self.cipher
+            .seal_into(message, associated_data, nonce, key, out);
     }
 
     fn open(
         &self,
         output: &mut [u8],
         encrypted: &CipherOutput,
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        trace_cipher!(
+            cipher = self.name(),
+            len = encrypted.ciphertext.len(),
+            self.cipher
+                .open(output, encrypted, associated_data, nonce, key)
+        )
+    }
+
+    fn open_in_place(
+        &self,
+        buffer: &mut [u8],
+        mac: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        trace_cipher!(
+            cipher = self.name(),
+            len = buffer.len(),
+            self.cipher
+                .open_in_place(buffer, mac, associated_data, nonce, key)
+        )
+    }
+
+    fn verify(
+        &self,
+        encrypted: &CipherOutput,
+        associated_data: &[u8],
         nonce: &[u8],
         key: &[u8],
     ) -> Result<(), MacMismatch> {
-        T::open(output, encrypted, nonce, key)
+        trace_cipher!(
+            cipher = self.name(),
+            len = encrypted.ciphertext.len(),
+            self.cipher.verify(encrypted, associated_data, nonce, key)
+        )
     }
 }
 
-impl ObjectSafeCipher for Box<dyn ObjectSafeCipher> {
+impl ObjectSafeCipher for Arc<dyn ObjectSafeCipher> {
     fn key_len(&self) -> usize {
         (**self).key_len()
     }
@@ -182,17 +876,65 @@ impl ObjectSafeCipher for Box<dyn ObjectSafeCipher> {
         (**self).mac_len()
     }
 
-    fn seal(&self, message: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
-        (**self).seal(message, nonce, key)
+    fn supports_associated_data(&self) -> bool {
+        (**self).supports_associated_data()
+    }
+
+    #[cfg(feature = "tracing")]
+    fn name(&self) -> Cow<'static, str> {
+        (**self).name()
+    }
+
+    fn seal(
+        &self,
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> CipherOutput {
+        (**self).seal(message, associated_data, nonce, key)
+    }
+
+    fn seal_into(
+        &self,
+        message: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        out: &mut CipherOutputRef<'_>,
+    ) {
+        (**self).seal_into(message, associated_data, nonce, key, out);
     }
 
     fn open(
         &self,
         output: &mut [u8],
         encrypted: &CipherOutput,
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        (**self).open(output, encrypted, associated_data, nonce, key)
+    }
+
+    fn open_in_place(
+        &self,
+        buffer: &mut [u8],
+        mac: &[u8],
+        associated_data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        (**self).open_in_place(buffer, mac, associated_data, nonce, key)
+    }
+
+    fn verify(
+        &self,
+        encrypted: &CipherOutput,
+        associated_data: &[u8],
         nonce: &[u8],
         key: &[u8],
     ) -> Result<(), MacMismatch> {
-        (**self).open(output, encrypted, nonce, key)
+        (**self).verify(encrypted, associated_data, nonce, key)
     }
 }
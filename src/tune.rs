@@ -0,0 +1,339 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Calibration of KDF parameters for the host.
+//!
+//! Choosing KDF difficulty is a trade-off between security and latency. Instead of picking
+//! a [preset](crate::KdfPreset), the parameters can be calibrated with [`calibrate_kdf()`],
+//! which measures key derivation on the host and returns parameters taking approximately
+//! the target time.
+//!
+//! # Examples
+//!
+//! ```
+//! # use pwbox::{sodium::{ScryptCompat, XChaCha20Poly1305}, tune, PwBoxBuilder};
+//! # use rand::thread_rng;
+//! use std::time::Duration;
+//!
+//! # fn main() -> Result<(), pwbox::Error> {
+//! let scrypt: ScryptCompat = tune::calibrate_kdf(Duration::from_millis(20), 4 << 20)?;
+//! assert!(scrypt.0.memory_cost() <= 4 << 20);
+//! let pwbox = PwBoxBuilder::<_, XChaCha20Poly1305>::new(&mut thread_rng())
+//!     .kdf(scrypt)
+//!     .seal("correct horse", b"battery staple")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use core::{convert::TryFrom, time::Duration};
+
+#[cfg(any(
+    test,
+    feature = "exonum_sodiumoxide",
+    feature = "rust-crypto",
+    feature = "pure"
+))]
+use crate::ScryptParams;
+#[cfg(any(test, feature = "exonum_sodiumoxide", feature = "rust-crypto"))]
+use crate::{Argon2Params, Argon2Variant};
+use crate::{Error, KdfCost, KdfDifficulty};
+
+/// KDF which difficulty can be scaled by [`calibrate_kdf()`].
+///
+/// Calibration first increases memory consumption of the KDF (which also increases
+/// the derivation time), and then multiplies the time cost without affecting memory.
+pub trait TunableKdf: KdfDifficulty {
+    /// Returns the KDF with the lowest difficulty considered during calibration.
+    fn min_difficulty() -> Self;
+
+    /// Returns the KDF with memory consumption doubled compared to `self`, or `None`
+    /// if the resulting params would be invalid.
+    fn double_memory(&self) -> Option<Self>;
+
+    /// Returns the KDF with the same memory consumption as `self`, and the time cost
+    /// multiplied by `multiplier`, or `None` if the resulting params would be invalid.
+    fn multiply_time(&self, multiplier: u32) -> Option<Self>;
+}
+
+#[cfg(any(
+    test,
+    feature = "exonum_sodiumoxide",
+    feature = "rust-crypto",
+    feature = "pure"
+))]
+impl ScryptParams {
+    /// `n = 2^10, r = 8, p = 1`, which corresponds to 1 MiB of memory.
+    pub(crate) fn min_tunable() -> Self {
+        Self::custom(10, 1)
+    }
+
+    pub(crate) fn double_memory(self) -> Option<Self> {
+        Self::new(self.log_n.checked_add(1)?, self.r, self.p).ok()
+    }
+
+    pub(crate) fn multiply_time(self, multiplier: u32) -> Option<Self> {
+        Self::new(self.log_n, self.r, self.p.checked_mul(multiplier)?).ok()
+    }
+}
+
+#[cfg(any(test, feature = "exonum_sodiumoxide", feature = "rust-crypto"))]
+impl Argon2Params {
+    /// Argon2id with 1 MiB of memory, a single pass and a single lane.
+    pub(crate) fn min_tunable() -> Self {
+        Self::custom(Argon2Variant::Argon2id, 1_024, 1, 1)
+    }
+
+    pub(crate) fn double_memory(self) -> Option<Self> {
+        let params = Argon2Params {
+            mem_cost: self.mem_cost.checked_mul(2)?,
+            ..self
+        };
+        params.validate().ok().map(|()| params)
+    }
+
+    pub(crate) fn multiply_time(self, multiplier: u32) -> Option<Self> {
+        let params = Argon2Params {
+            time_cost: self.time_cost.checked_mul(multiplier)?,
+            ..self
+        };
+        params.validate().ok().map(|()| params)
+    }
+}
+
+/// Calibrates the KDF so that key derivation on the host takes approximately `target` time.
+/// The memory consumption of the returned KDF does not exceed `max_memory_bytes`, so that
+/// calibration is safe to run in memory-constrained environments.
+///
+/// Key derivation is measured with a throwaway password and salt. See
+/// [`calibrate_kdf_with()`] for details on the calibration procedure.
+///
+/// # Errors
+///
+/// Returns an error if key derivation fails, or if the minimum difficulty of the KDF
+/// exceeds `max_memory_bytes`.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub fn calibrate_kdf<K: TunableKdf>(target: Duration, max_memory_bytes: u64) -> Result<K, Error> {
+    use crate::alloc::vec;
+    use std::time::Instant;
+
+    calibrate_kdf_with(target, max_memory_bytes, |kdf: &K| {
+        let salt = vec![0; kdf.salt_len()];
+        let mut key = [0_u8; 32];
+        let start = Instant::now();
+        kdf.derive_key(&mut key, b"calibration", &salt)
+            .map_err(Error::DeriveKey)?;
+        Ok(start.elapsed())
+    })
+}
+
+/// Calibrates the KDF using `measure` to obtain the key derivation time. This allows
+/// to calibrate with a mocked clock, or to measure derivation in a custom way.
+///
+/// The calibration proceeds as follows:
+///
+/// 1. Starting from [the minimum difficulty](TunableKdf::min_difficulty()), memory consumption
+///   is doubled while the derivation takes at most half of `target` and memory
+///   does not exceed `max_memory_bytes`.
+/// 2. The time cost is [multiplied](TunableKdf::multiply_time()) by the rounded ratio
+///   of `target` to the measured duration.
+///
+/// Provided that the derivation time is proportional to the time cost, the returned KDF
+/// takes `target` time within 25%. The precision is lower if the minimum difficulty takes
+/// more than half of `target`; if it takes longer than `target`, the KDF with the minimum
+/// difficulty is returned.
+///
+/// # Errors
+///
+/// Returns an error if `measure` fails, or if the minimum difficulty of the KDF
+/// exceeds `max_memory_bytes`.
+pub fn calibrate_kdf_with<K, F>(
+    target: Duration,
+    max_memory_bytes: u64,
+    mut measure: F,
+) -> Result<K, Error>
+where
+    K: TunableKdf,
+    F: FnMut(&K) -> Result<Duration, Error>,
+{
+    let mut kdf = K::min_difficulty();
+    if kdf.memory_cost() > max_memory_bytes {
+        let cost = kdf.cost().unwrap_or(KdfCost {
+            memory_bytes: kdf.memory_cost(),
+            time_cost: kdf.ops_cost(),
+        });
+        return Err(Error::KdfParamsTooExpensive(cost));
+    }
+    let mut elapsed = measure(&kdf)?;
+
+    // Doubling memory approximately doubles the derivation time, so we only try the next
+    // difficulty if it is expected to take at most half of the target.
+    while elapsed.checked_mul(4).map_or(false, |time| time <= target) {
+        let next = match kdf.double_memory() {
+            Some(next) if next.memory_cost() <= max_memory_bytes => next,
+            _ => break,
+        };
+        let next_elapsed = measure(&next)?;
+        if next_elapsed
+            .checked_mul(2)
+            .map_or(true, |time| time > target)
+        {
+            break;
+        }
+        kdf = next;
+        elapsed = next_elapsed;
+    }
+
+    // Since `elapsed <= target / 2` (unless the minimum difficulty is too slow),
+    // the rounding error is at most `elapsed / 2 <= target / 4`.
+    let elapsed_nanos = elapsed.as_nanos().max(1);
+    let multiplier = (target.as_nanos() + elapsed_nanos / 2) / elapsed_nanos;
+    let multiplier = u32::try_from(multiplier).unwrap_or(u32::MAX);
+    if multiplier > 1 {
+        if let Some(scaled) = kdf.multiply_time(multiplier) {
+            kdf = scaled;
+        }
+    }
+    Ok(kdf)
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use super::*;
+    use crate::{DeriveKey, KdfPreset};
+
+    /// KDF with derivation time defined by a mocked clock.
+    #[derive(Debug, Clone, Copy)]
+    struct MockKdf(ScryptParams);
+
+    impl DeriveKey for MockKdf {
+        fn salt_len(&self) -> usize {
+            32
+        }
+
+        fn derive_key(&self, _: &mut [u8], _: &[u8], _: &[u8]) -> Result<(), anyhow::Error> {
+            Ok(())
+        }
+    }
+
+    impl KdfDifficulty for MockKdf {
+        fn preset(preset: KdfPreset) -> Self {
+            MockKdf(ScryptParams::preset(preset))
+        }
+
+        fn memory_cost(&self) -> u64 {
+            self.0.memory_cost()
+        }
+
+        fn ops_cost(&self) -> u64 {
+            self.0.ops_cost()
+        }
+    }
+
+    impl TunableKdf for MockKdf {
+        fn min_difficulty() -> Self {
+            MockKdf(ScryptParams::min_tunable())
+        }
+
+        fn double_memory(&self) -> Option<Self> {
+            self.0.double_memory().map(MockKdf)
+        }
+
+        fn multiply_time(&self, multiplier: u32) -> Option<Self> {
+            self.0.multiply_time(multiplier).map(MockKdf)
+        }
+    }
+
+    /// Mocked clock: derivation takes `nanos_per_op` for each op of the KDF.
+    fn mock_clock(nanos_per_op: u64) -> impl FnMut(&MockKdf) -> Result<Duration, Error> {
+        move |kdf| Ok(Duration::from_nanos(kdf.ops_cost() * nanos_per_op))
+    }
+
+    fn assert_approx_duration(actual: Duration, target: Duration) {
+        let (actual, target) = (actual.as_secs_f64(), target.as_secs_f64());
+        assert!(
+            (actual - target).abs() <= target / 4.0,
+            "actual = {}, target = {}",
+            actual,
+            target
+        );
+    }
+
+    #[test]
+    fn calibrating_with_mocked_clock() {
+        let targets = [50, 100, 250, 300, 700, 1_000, 5_000];
+        for &nanos_per_op in &[1, 3, 10] {
+            for &target in &targets {
+                let target = Duration::from_millis(target);
+                let kdf: MockKdf =
+                    calibrate_kdf_with(target, u64::MAX, mock_clock(nanos_per_op)).unwrap();
+                let elapsed = mock_clock(nanos_per_op)(&kdf).unwrap();
+                assert_approx_duration(elapsed, target);
+            }
+        }
+    }
+
+    #[test]
+    fn calibration_respects_memory_limit() {
+        let target = Duration::from_secs(2);
+        let kdf: MockKdf = calibrate_kdf_with(target, 4 << 20, mock_clock(1)).unwrap();
+        assert_eq!(kdf.memory_cost(), 4 << 20);
+        assert_approx_duration(mock_clock(1)(&kdf).unwrap(), target);
+
+        let kdf: MockKdf = calibrate_kdf_with(target, 5 << 20, mock_clock(1)).unwrap();
+        assert_eq!(kdf.memory_cost(), 4 << 20);
+
+        let err = calibrate_kdf_with::<MockKdf, _>(target, 1 << 19, mock_clock(1)).unwrap_err();
+        assert_matches!(err, Error::KdfParamsTooExpensive(cost) if cost.memory_bytes == 1 << 20);
+    }
+
+    #[test]
+    fn calibration_with_slow_host() {
+        // Even the minimum difficulty takes longer than the target.
+        let target = Duration::from_millis(1);
+        let kdf: MockKdf = calibrate_kdf_with(target, u64::MAX, mock_clock(100)).unwrap();
+        let min_kdf = MockKdf::min_difficulty();
+        assert_eq!(kdf.memory_cost(), min_kdf.memory_cost());
+        assert_eq!(kdf.ops_cost(), min_kdf.ops_cost());
+    }
+
+    #[test]
+    fn calibration_propagates_measurement_errors() {
+        let mut calls = 0;
+        let err = calibrate_kdf_with::<MockKdf, _>(Duration::from_secs(1), u64::MAX, |_| {
+            calls += 1;
+            if calls < 3 {
+                Ok(Duration::from_millis(1))
+            } else {
+                Err(Error::DeriveKey(anyhow::anyhow!("out of memory")))
+            }
+        })
+        .unwrap_err();
+        assert_matches!(err, Error::DeriveKey(_));
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn tuning_argon2_params() {
+        let params = Argon2Params::min_tunable();
+        params.validate().unwrap();
+        let doubled = params.double_memory().unwrap();
+        assert_eq!(doubled.memory_cost(), 2 * params.memory_cost());
+        let scaled = params.multiply_time(3).unwrap();
+        assert_eq!((scaled.mem_cost, scaled.time_cost), (1_024, 3));
+        assert!(params.multiply_time(0).is_none());
+    }
+}
@@ -17,7 +17,34 @@ use serde::{Deserialize, Serialize};
 use smallvec::{smallvec, SmallVec};
 use zeroize::Zeroize;
 
-use core::{convert::TryFrom, fmt, ops::Deref};
+use core::{
+    convert::TryFrom,
+    fmt, mem,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{
+    alloc::{vec, Vec},
+    Error, KdfCost, KdfLimits, KdfPreset,
+};
+
+/// Minimum salt byte size accepted by KDFs not tied to a particular salt size.
+#[cfg(any(feature = "pure", feature = "rust-crypto"))]
+pub(crate) const MIN_SALT_LEN: usize = 8;
+/// Maximum salt byte size accepted by KDFs not tied to a particular salt size.
+#[cfg(any(feature = "pure", feature = "rust-crypto"))]
+pub(crate) const MAX_SALT_LEN: usize = 64;
+
+/// Decodes unpadded base64 rejecting non-canonical encodings (e.g., ones with non-zero
+/// trailing bits).
+pub(crate) fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let bytes = base64::decode_config(encoded, base64::STANDARD_NO_PAD).ok()?;
+    if base64::encode_config(&bytes, base64::STANDARD_NO_PAD) == encoded {
+        Some(bytes)
+    } else {
+        None
+    }
+}
 
 /// Expected upper bound on byte buffers created during encryption / decryption.
 const BUFFER_SIZE: usize = 256;
@@ -33,13 +60,50 @@ const BUFFER_SIZE: usize = 256;
 pub struct SensitiveData(SmallVec<[u8; BUFFER_SIZE]>);
 
 impl SensitiveData {
-    pub(crate) fn zeros(len: usize) -> Self {
+    /// Creates a container with `len` zero bytes. The contents can be then filled
+    /// via `DerefMut` / `AsMut` implementations.
+    pub fn zeros(len: usize) -> Self {
         SensitiveData(smallvec![0; len])
     }
 
+    /// Copies bytes from the `source` into a new container and zeroes the `source`.
+    pub fn from_slice(source: &mut [u8]) -> Self {
+        let data = SensitiveData(SmallVec::from_slice(source));
+        source.zeroize();
+        data
+    }
+
+    /// Takes ownership of the bytes. If the bytes are stored on heap in the resulting container,
+    /// the allocation is reused; otherwise, the bytes are copied and the source is zeroed.
+    /// Equivalent to the `From<Vec<u8>>` conversion.
+    pub fn from_vec(mut bytes: Vec<u8>) -> Self {
+        if bytes.capacity() > BUFFER_SIZE {
+            SensitiveData(SmallVec::from_vec(bytes))
+        } else {
+            Self::from_slice(&mut bytes)
+        }
+    }
+
     pub(crate) fn bytes_mut(&mut self) -> &mut [u8] {
         &mut *self.0
     }
+
+    /// Extracts the underlying buffer.
+    ///
+    /// # Safety
+    ///
+    /// The returned buffer is **not** zeroed on drop; the responsibility for zeroing it
+    /// is moved to the caller.
+    pub fn into_inner(mut self) -> SmallVec<[u8; BUFFER_SIZE]> {
+        mem::take(&mut self.0)
+    }
+}
+
+impl From<Vec<u8>> for SensitiveData {
+    /// Takes ownership of the bytes; see [`SensitiveData::from_vec()`].
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::from_vec(bytes)
+    }
 }
 
 impl fmt::Debug for SensitiveData {
@@ -56,12 +120,95 @@ impl Deref for SensitiveData {
     }
 }
 
+impl DerefMut for SensitiveData {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut *self.0
+    }
+}
+
+impl AsRef<[u8]> for SensitiveData {
+    fn as_ref(&self) -> &[u8] {
+        &*self.0
+    }
+}
+
+impl AsMut<[u8]> for SensitiveData {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut *self.0
+    }
+}
+
 impl Drop for SensitiveData {
     fn drop(&mut self) {
         Zeroize::zeroize(self.0.as_mut_slice());
     }
 }
 
+/// Heap-allocated byte buffer zeroed on drop.
+///
+/// The buffer is intended for scratch data in [`Cipher`] and [`DeriveKey`] implementations,
+/// such as intermediate plaintexts or derived keys. Unlike [`SensitiveData`], the buffer
+/// is always stored on heap, and its length is fixed after creation, so that its contents
+/// are never moved to another allocation without zeroing.
+///
+/// [`Cipher`]: crate::Cipher
+/// [`DeriveKey`]: crate::DeriveKey
+#[derive(Clone, Default)]
+pub struct SecretVec(Vec<u8>);
+
+impl SecretVec {
+    /// Creates a buffer with `len` zero bytes.
+    pub fn zeros(len: usize) -> Self {
+        SecretVec(vec![0; len])
+    }
+}
+
+impl From<Vec<u8>> for SecretVec {
+    /// Takes ownership of the bytes without copying.
+    fn from(bytes: Vec<u8>) -> Self {
+        SecretVec(bytes)
+    }
+}
+
+impl fmt::Debug for SecretVec {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.debug_tuple("SecretVec").field(&"_").finish()
+    }
+}
+
+impl Deref for SecretVec {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+impl DerefMut for SecretVec {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut *self.0
+    }
+}
+
+impl AsRef<[u8]> for SecretVec {
+    fn as_ref(&self) -> &[u8] {
+        &*self.0
+    }
+}
+
+impl AsMut<[u8]> for SecretVec {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut *self.0
+    }
+}
+
+impl Drop for SecretVec {
+    fn drop(&mut self) {
+        // Zeroes the entire capacity of the buffer.
+        self.0.zeroize();
+    }
+}
+
 struct LogNTransform;
 
 impl LogNTransform {
@@ -90,6 +237,10 @@ impl LogNTransform {
                 if !value.is_power_of_two() {
                     return Err(E::custom("not a power of two"));
                 }
+                // Larger values cannot be serialized back, and are unusable in practice anyway.
+                if value >= 1 << 32 {
+                    return Err(E::custom("too large value"));
+                }
                 // `try_from` is infallible: the number of leading zeros is <= 63
                 Ok(63 - u8::try_from(value.leading_zeros()).unwrap())
             }
@@ -155,13 +306,373 @@ impl ScryptParams {
         }
     }
 
+    /// Returns the "interactive" `scrypt` parameters as defined in libsodium.
+    ///
+    /// ```text
+    /// n = 2^14, r = 8, p = 1.
+    /// ```
+    pub const fn interactive() -> Self {
+        Self::custom(14, 1)
+    }
+
+    /// Returns the "moderate" `scrypt` parameters.
+    ///
+    /// ```text
+    /// n = 2^18, r = 8, p = 1.
+    /// ```
+    pub const fn moderate() -> Self {
+        Self::custom(18, 1)
+    }
+
+    /// Returns the "sensitive" `scrypt` parameters as defined in libsodium.
+    ///
+    /// ```text
+    /// n = 2^20, r = 8, p = 1.
+    /// ```
+    pub const fn sensitive() -> Self {
+        Self::custom(20, 1)
+    }
+
     /// Creates custom parameters for scrypt KDF.
     ///
     /// The `r` parameter is always set to 8 as per libsodium conversion
     /// from `opslimit` / `memlimit` and per Ethereum keystore implementations.
+    /// The params are not validated; use [`Self::new()`] to construct arbitrary
+    /// validated params.
     pub const fn custom(log_n: u8, p: u32) -> Self {
         ScryptParams { log_n, p, r: 8 }
     }
+
+    /// Creates validated params with `n = 2^log_n` and the specified `r` and `p`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DeriveKey`] describing the violated requirement if the params
+    /// are invalid (see [`Self::validate()`]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pwbox::ScryptParams;
+    /// let params = ScryptParams::new(15, 8, 2)?;
+    /// assert_eq!(params.memory_cost(), 32 << 20);
+    /// assert!(ScryptParams::new(0, 8, 1).is_err());
+    /// # Ok::<_, pwbox::Error>(())
+    /// ```
+    pub fn new(log_n: u8, r: u32, p: u32) -> Result<Self, Error> {
+        Self::with_limits(log_n, r, p, KdfLimits::default())
+    }
+
+    /// Creates validated params with `n = 2^log_n` and the specified `r` and `p`,
+    /// additionally checking that the KDF [cost](Self::cost()) does not exceed `limits`.
+    /// This allows tuning the params to the hardware without risking running out of memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DeriveKey`] if the params are invalid, and
+    /// [`Error::KdfParamsTooExpensive`] if they exceed `limits`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use assert_matches::assert_matches;
+    /// # use pwbox::{Error, KdfLimits, ScryptParams};
+    /// let limits = KdfLimits {
+    ///     max_memory_bytes: 64 << 20,
+    ///     ..KdfLimits::default()
+    /// };
+    /// let params = ScryptParams::with_limits(16, 8, 1, limits)?;
+    /// let err = ScryptParams::with_limits(17, 8, 1, limits).unwrap_err();
+    /// assert_matches!(err, Error::KdfParamsTooExpensive(_));
+    /// # Ok::<_, Error>(())
+    /// ```
+    pub fn with_limits(log_n: u8, r: u32, p: u32, limits: KdfLimits) -> Result<Self, Error> {
+        let params = ScryptParams { log_n, r, p };
+        params.validate().map_err(Error::DeriveKey)?;
+        let cost = params.cost();
+        if limits.allows(cost) {
+            Ok(params)
+        } else {
+            Err(Error::KdfParamsTooExpensive(cost))
+        }
+    }
+
+    /// Returns parameters for the specified preset. The parameters correspond to `libsodium`
+    /// `opslimit` / `memlimit` presets:
+    ///
+    /// ```text
+    /// Interactive: n = 2^14, r = 8, p = 1.
+    /// Moderate:    n = 2^18, r = 8, p = 1.
+    /// Sensitive:   n = 2^20, r = 8, p = 1.
+    /// ```
+    pub fn preset(preset: KdfPreset) -> Self {
+        match preset {
+            KdfPreset::Interactive => Self::interactive(),
+            KdfPreset::Moderate => Self::moderate(),
+            KdfPreset::Sensitive => Self::sensitive(),
+        }
+    }
+
+    /// Converts `libsodium` `opslimit` / `memlimit` into the original parametrization.
+    /// The conversion follows `libsodium`, so the KDF derives the same keys
+    /// as `crypto_pwhash_scryptsalsa208sha256` with the given limits.
+    ///
+    /// ```
+    /// # use pwbox::ScryptParams;
+    /// // "interactive" limits of libsodium.
+    /// let params = ScryptParams::from_nacl_limits(1 << 19, 1 << 24);
+    /// // Corresponds to `n = 2^14, r = 8, p = 1`.
+    /// assert_eq!(params.memory_cost(), 1 << 24);
+    /// assert_eq!(params.ops_cost(), 1 << 19);
+    /// ```
+    pub fn from_nacl_limits(opslimit: u32, memlimit: u32) -> Self {
+        /// Returns the smallest `log_n >= 1` such that `2^log_n > max_n / 2`, capped at 63.
+        fn pick_log_n(max_n: u64) -> u8 {
+            (1..63)
+                .find(|&log_n| 1_u64 << log_n > max_n / 2)
+                .unwrap_or(63)
+        }
+
+        const R: u32 = 8;
+        let opslimit = u64::from(opslimit.max(32_768));
+        let memlimit = u64::from(memlimit);
+        if opslimit < memlimit / 32 {
+            let log_n = pick_log_n(opslimit / (u64::from(R) * 4));
+            ScryptParams { log_n, r: R, p: 1 }
+        } else {
+            let log_n = pick_log_n(memlimit / (u64::from(R) * 128));
+            let max_rp = ((opslimit / 4) >> log_n).min(0x3fff_ffff);
+            #[allow(clippy::cast_possible_truncation)]
+            // ^-- `max_rp` is capped above, so the conversion is lossless.
+            let p = max_rp as u32 / R;
+            ScryptParams { log_n, r: R, p }
+        }
+    }
+
+    /// Returns memory consumption in bytes (`128 * r * n`). The value saturates
+    /// at `u64::MAX`.
+    pub fn memory_cost(self) -> u64 {
+        saturating_shl(u64::from(self.r), u32::from(self.log_n) + 7)
+    }
+
+    /// Returns computational cost as per `libsodium` `opslimit` (`4 * r * p * n`).
+    /// The value saturates at `u64::MAX`.
+    pub fn ops_cost(self) -> u64 {
+        let rp = u64::from(self.r) * u64::from(self.p);
+        saturating_shl(rp, u32::from(self.log_n) + 2)
+    }
+
+    /// Returns the estimated resources required to derive a key. The time cost is equal
+    /// to `256 * r * p * n`, i.e., the number of bytes processed by the mixing function.
+    pub fn cost(self) -> KdfCost {
+        KdfCost {
+            memory_bytes: self.memory_cost(),
+            time_cost: self.ops_cost().saturating_mul(64),
+        }
+    }
+
+    /// Checks that the params are valid: `n > 1`, `r > 0`, `p > 0`, `r * p < 2^30`,
+    /// `n < 2^(16 * r)`, and the consumed memory is addressable on the target platform.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the params are invalid.
+    pub fn validate(self) -> Result<(), anyhow::Error> {
+        let (log_n, r, p) = (self.log_n, self.r, self.p);
+        anyhow::ensure!(
+            log_n > 0,
+            "invalid scrypt params: `n` must be greater than 1"
+        );
+        anyhow::ensure!(
+            r > 0 && p > 0,
+            "invalid scrypt params: `r` and `p` must be positive"
+        );
+        anyhow::ensure!(
+            u32::from(log_n) < r.saturating_mul(16),
+            "invalid scrypt params: `n` must be less than 2^(16 * r)"
+        );
+        anyhow::ensure!(
+            u64::from(r) * u64::from(p) < 1 << 30,
+            "invalid scrypt params: `r * p` must be less than 2^30"
+        );
+        let is_addressable = usize::try_from(r)
+            .ok()
+            .and_then(|r| r.checked_mul(128))
+            .and_then(|r| r.checked_mul(1_usize.checked_shl(u32::from(log_n))?))
+            .is_some();
+        anyhow::ensure!(
+            is_addressable,
+            "invalid scrypt params: required memory is not addressable"
+        );
+        Ok(())
+    }
+}
+
+/// Shifts `value` left, saturating at `u64::MAX` on overflow.
+fn saturating_shl(value: u64, shift: u32) -> u64 {
+    if value == 0 {
+        0
+    } else if shift >= value.leading_zeros() {
+        u64::MAX
+    } else {
+        value << shift
+    }
+}
+
+/// Variant of the Argon2 key derivation function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Argon2Variant {
+    /// Argon2i: data-independent memory access, resistant to side-channel attacks.
+    Argon2i,
+    /// Argon2id: hybrid of Argon2i and Argon2d. This is the recommended variant.
+    Argon2id,
+}
+
+/// `Argon2` key derivation function parameters (version 0x13).
+///
+/// Parameters are shared among all backends, so a box sealed with one Argon2 implementation
+/// can be opened with another one.
+///
+/// # Serialization
+///
+/// The parameters are serialized as four fields: `variant`, `mem_cost` (in KiB),
+/// `time_cost` (number of passes) and `parallelism` (number of lanes).
+///
+/// ```
+/// use serde_json::json;
+/// # use pwbox::Argon2Params;
+///
+/// let argon2 = Argon2Params::default();
+/// assert_eq!(
+///     serde_json::to_value(argon2).unwrap(),
+///     json!({ "variant": "argon2id", "mem_cost": 65536, "time_cost": 2, "parallelism": 1 })
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Argon2Params {
+    /// Argon2 variant.
+    pub variant: Argon2Variant,
+    /// Memory cost in KiB.
+    pub mem_cost: u32,
+    /// Time cost, i.e., the number of passes over the memory.
+    pub time_cost: u32,
+    /// Degree of parallelism (the number of lanes). Note that `libsodium`
+    /// only supports `parallelism == 1`.
+    pub parallelism: u32,
+}
+
+impl Default for Argon2Params {
+    /// Returns the "interactive" Argon2id parameters as defined in libsodium.
+    fn default() -> Self {
+        Self::interactive()
+    }
+}
+
+impl Argon2Params {
+    /// Returns the "interactive" Argon2id parameters as defined in libsodium.
+    ///
+    /// ```text
+    /// mem_cost = 64 MiB, time_cost = 2, parallelism = 1.
+    /// ```
+    pub const fn interactive() -> Self {
+        Argon2Params {
+            variant: Argon2Variant::Argon2id,
+            mem_cost: 1 << 16,
+            time_cost: 2,
+            parallelism: 1,
+        }
+    }
+
+    /// Returns the "moderate" Argon2id parameters as defined in libsodium.
+    ///
+    /// ```text
+    /// mem_cost = 256 MiB, time_cost = 3, parallelism = 1.
+    /// ```
+    pub const fn moderate() -> Self {
+        Argon2Params {
+            variant: Argon2Variant::Argon2id,
+            mem_cost: 1 << 18,
+            time_cost: 3,
+            parallelism: 1,
+        }
+    }
+
+    /// Returns the "sensitive" Argon2id parameters as defined in libsodium.
+    ///
+    /// ```text
+    /// mem_cost = 1 GiB, time_cost = 4, parallelism = 1.
+    /// ```
+    pub const fn sensitive() -> Self {
+        Argon2Params {
+            variant: Argon2Variant::Argon2id,
+            mem_cost: 1 << 20,
+            time_cost: 4,
+            parallelism: 1,
+        }
+    }
+
+    /// Returns parameters for the specified preset.
+    pub fn preset(preset: KdfPreset) -> Self {
+        match preset {
+            KdfPreset::Interactive => Self::interactive(),
+            KdfPreset::Moderate => Self::moderate(),
+            KdfPreset::Sensitive => Self::sensitive(),
+        }
+    }
+
+    /// Returns memory consumption in bytes.
+    pub fn memory_cost(self) -> u64 {
+        u64::from(self.mem_cost) * 1_024
+    }
+
+    /// Returns computational cost (the number of passes over the memory).
+    pub fn ops_cost(self) -> u64 {
+        u64::from(self.time_cost)
+    }
+
+    /// Returns the estimated resources required to derive a key. The time cost is equal
+    /// to the memory consumption multiplied by the number of passes over the memory.
+    pub fn cost(self) -> KdfCost {
+        KdfCost {
+            memory_bytes: self.memory_cost(),
+            time_cost: self.memory_cost().saturating_mul(self.ops_cost()),
+        }
+    }
+
+    /// Checks that the params are valid as per the Argon2 specification: `time_cost > 0`,
+    /// `0 < parallelism < 2^24`, and `mem_cost` is at least `8 * parallelism` KiB.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the params are invalid.
+    pub fn validate(self) -> Result<(), anyhow::Error> {
+        anyhow::ensure!(self.time_cost > 0, "Argon2 time cost must be positive");
+        anyhow::ensure!(
+            self.parallelism > 0 && self.parallelism < 1 << 24,
+            "Argon2 parallelism must be between 1 and 2^24 - 1"
+        );
+        anyhow::ensure!(
+            u64::from(self.mem_cost) >= 8 * u64::from(self.parallelism),
+            "Argon2 memory cost must be at least 8 KiB per lane"
+        );
+        Ok(())
+    }
+
+    /// Creates custom Argon2 parameters.
+    pub const fn custom(
+        variant: Argon2Variant,
+        mem_cost: u32,
+        time_cost: u32,
+        parallelism: u32,
+    ) -> Self {
+        Argon2Params {
+            variant,
+            mem_cost,
+            time_cost,
+            parallelism,
+        }
+    }
 }
 
 #[test]
@@ -183,3 +694,123 @@ fn log2_transform() {
         serde_json::from_str::<Value>(json).unwrap(),
     );
 }
+
+#[test]
+fn scrypt_params_with_extreme_values() {
+    let json = serde_json::json!({ "n": 1_u64 << 40, "r": 8, "p": 1 });
+    let err = serde_json::from_value::<ScryptParams>(json).unwrap_err();
+    assert!(err.to_string().contains("too large"));
+
+    let json = serde_json::json!({ "n": 1_u64 << 31, "r": u32::MAX, "p": u32::MAX });
+    let params: ScryptParams = serde_json::from_value(json).unwrap();
+    assert_eq!(params.memory_cost(), u64::MAX);
+    assert_eq!(params.ops_cost(), u64::MAX);
+    assert_eq!(ScryptParams::default().memory_cost(), 16 << 20);
+}
+
+#[test]
+fn scrypt_params_from_nacl_limits() {
+    let assert_params = |params: ScryptParams, (log_n, r, p): (u8, u32, u32)| {
+        assert_eq!((params.log_n, params.r, params.p), (log_n, r, p));
+    };
+    // "interactive" and "sensitive" limits of libsodium.
+    assert_params(ScryptParams::from_nacl_limits(1 << 19, 1 << 24), (14, 8, 1));
+    assert_params(ScryptParams::from_nacl_limits(1 << 25, 1 << 30), (20, 8, 1));
+    // "light" and "moderate" limits from `sodium::Scrypt`.
+    assert_params(ScryptParams::from_nacl_limits(3 << 18, 1 << 22), (12, 8, 6));
+    assert_params(ScryptParams::from_nacl_limits(1 << 23, 1 << 28), (18, 8, 1));
+    // `opslimit` is the limiting factor.
+    assert_params(ScryptParams::from_nacl_limits(1 << 16, 1 << 30), (11, 8, 1));
+    // Extreme values.
+    assert_params(ScryptParams::from_nacl_limits(0, 0), (1, 8, 512));
+    let params = ScryptParams::from_nacl_limits(u32::MAX, u32::MAX);
+    assert!(params.log_n < 32);
+}
+
+#[test]
+fn validated_scrypt_params() {
+    use assert_matches::assert_matches;
+
+    let params = ScryptParams::new(16, 4, 2).unwrap();
+    assert_eq!((params.log_n, params.r, params.p), (16, 4, 2));
+    let json = serde_json::to_value(params).unwrap();
+    assert_eq!(json, serde_json::json!({ "n": 65_536, "r": 4, "p": 2 }));
+
+    let invalid_params = [
+        ((0, 8, 1), "`n` must be greater than 1"),
+        ((14, 0, 1), "`r` and `p` must be positive"),
+        ((14, 8, 0), "`r` and `p` must be positive"),
+        ((18, 1, 1), "`n` must be less than 2^(16 * r)"),
+        ((14, 1 << 15, 1 << 15), "`r * p` must be less than 2^30"),
+    ];
+    for &((log_n, r, p), message) in &invalid_params {
+        let err = ScryptParams::new(log_n, r, p).unwrap_err();
+        assert_matches!(err, Error::DeriveKey(ref e) if e.to_string().contains(message));
+    }
+
+    let limits = KdfLimits {
+        max_memory_bytes: 16 << 20,
+        ..KdfLimits::default()
+    };
+    let params = ScryptParams::with_limits(14, 8, 1, limits).unwrap();
+    assert_eq!(params.memory_cost(), 16 << 20);
+    let err = ScryptParams::with_limits(14, 16, 1, limits).unwrap_err();
+    assert_matches!(err, Error::KdfParamsTooExpensive(cost) if cost.memory_bytes == 32 << 20);
+    // Validity is checked before the limits.
+    let err = ScryptParams::with_limits(0, 8, 1, limits).unwrap_err();
+    assert_matches!(err, Error::DeriveKey(_));
+
+    let presets = [
+        (ScryptParams::interactive(), KdfPreset::Interactive, 14),
+        (ScryptParams::moderate(), KdfPreset::Moderate, 18),
+        (ScryptParams::sensitive(), KdfPreset::Sensitive, 20),
+    ];
+    for &(params, preset, log_n) in &presets {
+        assert_eq!((params.log_n, params.r, params.p), (log_n, 8, 1));
+        assert_eq!(ScryptParams::preset(preset).log_n, log_n);
+        params.validate().unwrap();
+    }
+}
+
+#[test]
+fn sensitive_data_from_slice() {
+    let mut source = *b"correct horse battery staple";
+    let mut data = SensitiveData::from_slice(&mut source);
+    assert_eq!(&*data, b"correct horse battery staple");
+    assert!(source.iter().all(|&byte| byte == 0));
+
+    data[0] = b'C';
+    assert_eq!(data.as_ref(), b"Correct horse battery staple");
+    data.as_mut()[8] = b'H';
+    assert_eq!(&data[..13], b"Correct Horse");
+
+    let inner = data.into_inner();
+    assert_eq!(&*inner, b"Correct Horse battery staple");
+}
+
+#[test]
+fn sensitive_data_from_vec() {
+    use crate::alloc::vec;
+
+    let data = SensitiveData::from(vec![1, 2, 3]);
+    assert_eq!(&*data, [1, 2, 3]);
+
+    let data = SensitiveData::from(vec![42; 1_000]);
+    assert_eq!(data.len(), 1_000);
+    assert!(data.iter().all(|&byte| byte == 42));
+}
+
+#[test]
+fn sensitive_data_from_vec_constructor() {
+    use crate::alloc::vec;
+
+    let data = SensitiveData::from_vec(b"battery staple".to_vec());
+    assert_eq!(&*data, b"battery staple");
+
+    // The heap allocation is reused for large buffers.
+    let bytes = vec![42; 1_000];
+    let ptr = bytes.as_ptr();
+    let data = SensitiveData::from_vec(bytes);
+    assert_eq!(data.as_ptr(), ptr);
+    assert_eq!(data.len(), 1_000);
+}
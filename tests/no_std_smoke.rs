@@ -0,0 +1,222 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Smoke test for the crate without the `std` feature. The test only uses `core` and `alloc`
+//! APIs, and supplies a custom cipher, KDF and RNG, as would be the case in an embedded
+//! environment.
+//!
+//! Run with `cargo test --no-default-features --test no_std_smoke`. Enable the `pure`
+//! and / or `rust-crypto` features to check the corresponding crypto backends as well.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec;
+use rand_core::{impls, CryptoRng, Error as RngError, RngCore};
+use serde::{Deserialize, Serialize};
+
+use pwbox::{Cipher, CipherOutput, DeriveKey, Eraser, Error, MacMismatch, PwBoxBuilder};
+
+/// Trivial KDF (not secure!) XORing the password with the salt. The struct has braces,
+/// so that it is serialized as a map, as `Eraser` expects of KDF params.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+struct XorKdf {}
+
+impl DeriveKey for XorKdf {
+    fn salt_len(&self) -> usize {
+        16
+    }
+
+    fn derive_key(&self, buf: &mut [u8], password: &[u8], salt: &[u8]) -> anyhow::Result<()> {
+        let key_material = password.iter().cycle().zip(salt.iter().cycle());
+        for (dest, (password_byte, salt_byte)) in buf.iter_mut().zip(key_material) {
+            *dest = password_byte ^ salt_byte;
+        }
+        Ok(())
+    }
+}
+
+/// Toy cipher (not secure!) XORing the message with the key and nonce, with a checksum
+/// of the plaintext as the MAC.
+#[derive(Debug)]
+struct XorCipher;
+
+impl XorCipher {
+    fn checksum(message: &[u8], key: &[u8]) -> u8 {
+        message
+            .iter()
+            .fold(key[0], |acc, &byte| acc.wrapping_mul(31).wrapping_add(byte))
+    }
+
+    fn apply_keystream(message: &mut [u8], nonce: &[u8], key: &[u8]) {
+        let keystream = key.iter().cycle().zip(nonce.iter().cycle());
+        for (byte, (key_byte, nonce_byte)) in message.iter_mut().zip(keystream) {
+            *byte ^= key_byte ^ nonce_byte;
+        }
+    }
+}
+
+impl Cipher for XorCipher {
+    const KEY_LEN: usize = 16;
+    const NONCE_LEN: usize = 8;
+    const MAC_LEN: usize = 1;
+    const SUPPORTS_ASSOCIATED_DATA: bool = false;
+
+    fn seal(message: &[u8], _: &[u8], nonce: &[u8], key: &[u8]) -> CipherOutput {
+        let mut ciphertext = message.to_vec();
+        Self::apply_keystream(&mut ciphertext, nonce, key);
+        CipherOutput {
+            ciphertext,
+            mac: vec![Self::checksum(message, key)],
+        }
+    }
+
+    fn open(
+        output: &mut [u8],
+        encrypted: &CipherOutput,
+        _: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        output.copy_from_slice(&encrypted.ciphertext);
+        Self::apply_keystream(output, nonce, key);
+        if Self::checksum(output, key) == encrypted.mac[0] {
+            Ok(())
+        } else {
+            Err(MacMismatch)
+        }
+    }
+}
+
+/// Deterministic RNG (not secure!) standing in for a hardware RNG.
+#[derive(Debug)]
+struct CounterRng(u64);
+
+impl RngCore for CounterRng {
+    fn next_u32(&mut self) -> u32 {
+        // `next_u32_via_fill()` would recurse infinitely, since `fill_bytes_via_next()` uses
+        // `next_u32()` for the trailing bytes.
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        self.0
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        impls::fill_bytes_via_next(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), RngError> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for CounterRng {}
+
+const PASSWORD: &str = "correct horse battery staple";
+const MESSAGE: &[u8] = b"battery staple";
+
+#[test]
+fn sealing_and_opening_without_std() {
+    let mut rng = CounterRng(0);
+    let pwbox = PwBoxBuilder::<XorKdf, XorCipher>::new(&mut rng)
+        .seal(PASSWORD, MESSAGE)
+        .unwrap();
+    assert_eq!(pwbox.len(), MESSAGE.len());
+    assert_eq!(&*pwbox.open(PASSWORD).unwrap(), MESSAGE);
+    match pwbox.open("Tr0ub4dor&3").unwrap_err() {
+        Error::MacMismatch => {}
+        e => panic!("unexpected error: {:?}", e),
+    }
+
+    let mut buffer = [0_u8; 32];
+    let len = pwbox.open_into(&mut buffer[..], PASSWORD).unwrap();
+    assert_eq!(buffer[..len], *MESSAGE);
+}
+
+#[test]
+fn erasing_without_std() {
+    let mut rng = CounterRng(1);
+    let pwbox = PwBoxBuilder::<XorKdf, XorCipher>::new(&mut rng)
+        .seal(PASSWORD, MESSAGE)
+        .unwrap();
+
+    let mut eraser = Eraser::new();
+    eraser
+        .add_kdf::<XorKdf>("xor")
+        .unwrap()
+        .add_cipher::<XorCipher>("xor")
+        .unwrap();
+    let erased = eraser.erase(&pwbox).unwrap();
+    let json = serde_json::to_string(&erased).unwrap();
+    let erased = serde_json::from_str(&json).unwrap();
+    let restored = eraser.restore(&erased).unwrap();
+    assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+}
+
+/// Seals and opens a box with the recommended cipher of the suite `S` and the specified KDF,
+/// and erases the box.
+#[cfg(any(feature = "pure", feature = "rust-crypto"))]
+fn check_suite<S: pwbox::Suite>(kdf: S::DeriveKey, rng_seed: u64)
+where
+    S::DeriveKey: Serialize,
+{
+    use pwbox::SensitiveData;
+
+    let mut rng = CounterRng(rng_seed);
+    let pwbox = S::build_box(&mut rng)
+        .kdf(kdf)
+        .seal(PASSWORD, SensitiveData::from_vec(MESSAGE.to_vec()))
+        .unwrap();
+    let plaintext: SensitiveData = pwbox.open(PASSWORD).unwrap();
+    assert_eq!(&*plaintext, MESSAGE);
+    match pwbox.open("Tr0ub4dor&3").unwrap_err() {
+        Error::MacMismatch => {}
+        e => panic!("unexpected error: {:?}", e),
+    }
+
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<S>().unwrap();
+    let json = serde_json::to_string(&eraser.erase(&pwbox).unwrap()).unwrap();
+    let restored = eraser
+        .restore(&serde_json::from_str(&json).unwrap())
+        .unwrap();
+    assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+}
+
+#[cfg(feature = "pure")]
+#[test]
+fn pure_suite_without_std() {
+    use pwbox::{
+        pure::{PureCrypto, Scrypt},
+        ScryptParams,
+    };
+
+    check_suite::<PureCrypto>(Scrypt(ScryptParams::custom(6, 1)), 2);
+}
+
+#[cfg(feature = "rust-crypto")]
+#[test]
+fn rust_crypto_suite_without_std() {
+    use pwbox::{
+        rcrypto::{RustCrypto, Scrypt},
+        ScryptParams,
+    };
+
+    check_suite::<RustCrypto>(Scrypt(ScryptParams::custom(6, 1)), 3);
+}
@@ -0,0 +1,117 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checks that opening a box in place requires memory for a single copy of the data.
+//!
+//! The test uses a global allocator, which tracks the peak number of allocated bytes.
+//! The test is slow, so it is ignored by default; run it with `cargo test -- --ignored`.
+
+use rand::thread_rng;
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use pwbox::{
+    sodium::{Scrypt, Sodium},
+    Eraser, Suite,
+};
+
+/// Size of the sealed message.
+const MESSAGE_LEN: usize = 100 << 20;
+
+/// Number of currently allocated bytes.
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+/// Peak number of allocated bytes since the last `peak_allocation()` call.
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+        let mut peak = PEAK.load(Ordering::SeqCst);
+        while allocated > peak {
+            match PEAK.compare_exchange_weak(peak, allocated, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => break,
+                Err(current_peak) => peak = current_peak,
+            }
+        }
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the peak number of allocated bytes during `action`.
+fn peak_allocation(action: impl FnOnce()) -> usize {
+    PEAK.store(ALLOCATED.load(Ordering::SeqCst), Ordering::SeqCst);
+    action();
+    PEAK.load(Ordering::SeqCst)
+}
+
+/// Checks that opening a box in place has roughly half the peak memory usage of opening it
+/// by reference, provided that the box is the only large allocation.
+fn assert_halved_peak(copying_peak: usize, in_place_peak: usize) {
+    assert!(copying_peak > 2 * MESSAGE_LEN);
+    assert!(
+        in_place_peak * 10 < copying_peak * 6,
+        "in-place: {} bytes, copying: {} bytes",
+        in_place_peak,
+        copying_peak
+    );
+}
+
+// All checks are performed in a single test, so that concurrently running tests
+// do not interfere with the allocation counters.
+#[test]
+#[ignore]
+fn opening_in_place_halves_peak_memory() {
+    const PASSWORD: &str = "correct horse";
+
+    let seal = || {
+        let message = vec![42_u8; MESSAGE_LEN];
+        Sodium::build_box(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .seal(PASSWORD, &message)
+            .unwrap()
+    };
+
+    let pwbox = seal();
+    let copying_peak = peak_allocation(|| {
+        assert_eq!(pwbox.open(PASSWORD).unwrap().len(), MESSAGE_LEN);
+    });
+    let in_place_peak = peak_allocation(|| {
+        assert_eq!(pwbox.open_in_place(PASSWORD).unwrap().len(), MESSAGE_LEN);
+    });
+    assert_halved_peak(copying_peak, in_place_peak);
+
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+    let restored = eraser.restore(&eraser.erase(&seal()).unwrap()).unwrap();
+    let copying_peak = peak_allocation(|| {
+        assert_eq!(restored.open(PASSWORD).unwrap().len(), MESSAGE_LEN);
+    });
+    let in_place_peak = peak_allocation(|| {
+        assert_eq!(restored.open_in_place(PASSWORD).unwrap().len(), MESSAGE_LEN);
+    });
+    assert_halved_peak(copying_peak, in_place_peak);
+}
@@ -0,0 +1,301 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Property-based tests for sealing, erasing, serializing, restoring and opening boxes
+//! with all KDF / cipher combinations registered by the enabled suites.
+//!
+//! The `raw` and `external` KDFs are not covered since they do not derive keys from passwords.
+
+use assert_matches::assert_matches;
+use proptest::prelude::*;
+use rand::thread_rng;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+use std::{any, fmt};
+
+use pwbox::{Cipher, DeriveKey, ErasedPwBox, Eraser, Error, FormatVersion, PwBoxBuilder, Suite};
+
+type SealFn = dyn Fn(&Eraser, &[u8], &[u8]) -> ErasedPwBox;
+
+/// KDF / cipher combination.
+struct Combo {
+    kdf: &'static str,
+    cipher: &'static str,
+    /// Eraser with the suite the KDF and cipher belong to.
+    eraser: Eraser,
+    supports_v2: bool,
+    /// Is the nonce authenticated by the cipher itself? This is not the case for `CipherWithMac`.
+    authenticates_nonce: bool,
+    seal: Box<SealFn>,
+}
+
+impl fmt::Debug for Combo {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Combo")
+            .field("kdf", &self.kdf)
+            .field("cipher", &self.cipher)
+            .finish()
+    }
+}
+
+impl Combo {
+    fn new<S, K, C>(kdf: K) -> Self
+    where
+        S: Suite,
+        K: DeriveKey + Clone + Default + Serialize,
+        C: Cipher,
+    {
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<S>().unwrap();
+        Combo {
+            kdf: any::type_name::<K>(),
+            cipher: any::type_name::<C>(),
+            eraser,
+            supports_v2: C::SUPPORTS_ASSOCIATED_DATA,
+            authenticates_nonce: true,
+            seal: Box::new(move |eraser, password, message| {
+                let mut rng = thread_rng();
+                let mut builder = PwBoxBuilder::<K, C>::new(&mut rng);
+                builder.kdf(kdf.clone());
+                eraser.seal(&mut builder, password, message).unwrap()
+            }),
+        }
+    }
+
+    /// Seals a box with the specified format version (if supported by the cipher).
+    fn seal(&self, password: &[u8], message: &[u8], v2: bool) -> ErasedPwBox {
+        let mut eraser = self.eraser.clone();
+        if v2 && self.supports_v2 {
+            eraser.set_format_version(FormatVersion::V2);
+        }
+        (self.seal)(&eraser, password, message)
+    }
+
+    /// Checks whether the nonce is authenticated in boxes sealed with the specified
+    /// format version. In the v2 format, the nonce is a part of the associated data.
+    fn nonce_is_authenticated(&self, v2: bool) -> bool {
+        self.authenticates_nonce || (v2 && self.supports_v2)
+    }
+
+    #[cfg(feature = "rust-crypto")]
+    fn with_unauthenticated_nonce(mut self) -> Self {
+        self.authenticates_nonce = false;
+        self
+    }
+}
+
+#[cfg(feature = "exonum_sodiumoxide")]
+fn add_sodium_combos<C: Cipher>(combos: &mut Vec<Combo>) {
+    use pwbox::{
+        sodium::{Argon2, Scrypt, ScryptCompat, Sodium},
+        Argon2Params, Argon2Variant, ScryptParams,
+    };
+
+    let argon2 = Argon2Params::custom(Argon2Variant::Argon2id, 1 << 10, 3, 1);
+    combos.push(Combo::new::<Sodium, _, C>(Scrypt::light()));
+    combos.push(Combo::new::<Sodium, _, C>(ScryptCompat(
+        ScryptParams::light(),
+    )));
+    combos.push(Combo::new::<Sodium, _, C>(Argon2(argon2)));
+}
+
+#[cfg(feature = "rust-crypto")]
+fn add_rcrypto_combos<C: Cipher>(combos: &mut Vec<Combo>, authenticates_nonce: bool) {
+    use pwbox::{
+        rcrypto::{Argon2, Pbkdf2, RustCrypto, Scrypt, ScryptNacl},
+        Argon2Params, Argon2Variant, ScryptParams,
+    };
+
+    // The pure Rust implementations are slow in debug builds, so the params are minimal.
+    let argon2 = Argon2Params::custom(Argon2Variant::Argon2id, 8, 1, 1);
+    let scrypt_nacl = ScryptNacl {
+        opslimit: 1 << 15,
+        memlimit: 1 << 16,
+    };
+    let pbkdf2 = Pbkdf2 {
+        c: 1_000,
+        ..Pbkdf2::default()
+    };
+    let new_combos = vec![
+        Combo::new::<RustCrypto, _, C>(Scrypt(ScryptParams::custom(6, 16))),
+        Combo::new::<RustCrypto, _, C>(scrypt_nacl),
+        Combo::new::<RustCrypto, _, C>(Argon2(argon2)),
+        Combo::new::<RustCrypto, _, C>(pbkdf2),
+    ];
+    combos.extend(new_combos.into_iter().map(|combo| {
+        if authenticates_nonce {
+            combo
+        } else {
+            combo.with_unauthenticated_nonce()
+        }
+    }));
+}
+
+fn combos() -> Vec<Combo> {
+    let mut combos = vec![];
+
+    #[cfg(feature = "exonum_sodiumoxide")]
+    {
+        use pwbox::sodium::{
+            ChaCha20Poly1305, ChaCha20Poly1305Ietf, XChaCha20Poly1305, XSalsa20Poly1305,
+        };
+        add_sodium_combos::<XChaCha20Poly1305>(&mut combos);
+        add_sodium_combos::<XSalsa20Poly1305>(&mut combos);
+        add_sodium_combos::<ChaCha20Poly1305>(&mut combos);
+        add_sodium_combos::<ChaCha20Poly1305Ietf>(&mut combos);
+    }
+
+    #[cfg(feature = "rust-crypto")]
+    {
+        use pwbox::{
+            rcrypto::{
+                Aes128Ctr, Aes128CtrHmacSha512, Aes128Gcm, Aes256Ctr, Aes256CtrHmacSha512,
+                Aes256Gcm, ChaCha20Poly1305, Keccak256, XSalsa20Poly1305,
+            },
+            CipherWithMac,
+        };
+        add_rcrypto_combos::<CipherWithMac<Aes128Ctr, Keccak256>>(&mut combos, false);
+        add_rcrypto_combos::<CipherWithMac<Aes256Ctr, Keccak256>>(&mut combos, false);
+        add_rcrypto_combos::<Aes128Gcm>(&mut combos, true);
+        add_rcrypto_combos::<Aes256Gcm>(&mut combos, true);
+        add_rcrypto_combos::<Aes128CtrHmacSha512>(&mut combos, false);
+        add_rcrypto_combos::<Aes256CtrHmacSha512>(&mut combos, false);
+        add_rcrypto_combos::<XSalsa20Poly1305>(&mut combos, true);
+        add_rcrypto_combos::<ChaCha20Poly1305>(&mut combos, true);
+    }
+
+    #[cfg(feature = "pure")]
+    {
+        use pwbox::{
+            pure::{PureCrypto, Scrypt},
+            ScryptParams,
+        };
+        type ChaCha20Poly1305 = <PureCrypto as Suite>::Cipher;
+        let scrypt = Scrypt(ScryptParams::custom(6, 16));
+        combos.push(Combo::new::<PureCrypto, _, ChaCha20Poly1305>(scrypt));
+    }
+
+    combos
+}
+
+fn combo_strategy() -> impl Strategy<Value = Combo> {
+    let len = combos().len();
+    (0..len).prop_map(|i| combos().swap_remove(i))
+}
+
+fn password_strategy() -> impl Strategy<Value = Vec<u8>> {
+    // Passwords are not necessarily UTF-8 and may be empty.
+    prop::collection::vec(any::<u8>(), 0..32)
+}
+
+/// Part of a serialized box mutated in tests.
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    Ciphertext,
+    Mac,
+    Salt,
+    Nonce,
+}
+
+impl Field {
+    fn value_mut(self, json: &mut JsonValue) -> &mut JsonValue {
+        match self {
+            Field::Ciphertext => &mut json["ciphertext"],
+            Field::Mac => &mut json["mac"],
+            Field::Salt => &mut json["kdfparams"]["salt"],
+            Field::Nonce => &mut json["cipherparams"]["iv"],
+        }
+    }
+}
+
+fn flip_bit(erased: &ErasedPwBox, field: Field, bit: usize) -> ErasedPwBox {
+    let mut json = serde_json::to_value(erased).unwrap();
+    let value = field.value_mut(&mut json);
+    let mut bytes = hex::decode(value.as_str().unwrap()).unwrap();
+    let bit = bit % (bytes.len() * 8);
+    bytes[bit / 8] ^= 1 << (bit % 8);
+    *value = hex::encode(bytes).into();
+    serde_json::from_value(json).unwrap()
+}
+
+fn config() -> ProptestConfig {
+    // Every case derives keys several times, so the number of cases is reduced.
+    ProptestConfig::with_cases(32)
+}
+
+proptest! {
+    #![proptest_config(config())]
+
+    #[test]
+    fn boxes_roundtrip(
+        combo in combo_strategy(),
+        password in password_strategy(),
+        message in prop::collection::vec(any::<u8>(), 0..4_096),
+        v2 in any::<bool>(),
+    ) {
+        let erased = combo.seal(&password, &message, v2);
+        let json = serde_json::to_string(&erased).unwrap();
+        let erased: ErasedPwBox = serde_json::from_str(&json).unwrap();
+        prop_assert_eq!(erased.len(), message.len());
+
+        let restored = combo.eraser.restore(&erased).unwrap();
+        prop_assert_eq!(&*restored.open(&password).unwrap(), &message[..]);
+        prop_assert_eq!(combo.eraser.erase_restored(&restored).unwrap(), erased);
+
+        // Appending a zero byte is not enough: PBKDF2 pads short passwords with zeros.
+        let mut wrong_password = password;
+        wrong_password.push(b'!');
+        assert_matches!(restored.open(&wrong_password).unwrap_err(), Error::MacMismatch);
+    }
+
+    #[test]
+    fn flipping_bits_in_boxes_is_detected(
+        combo in combo_strategy(),
+        password in password_strategy(),
+        message in prop::collection::vec(any::<u8>(), 1..256),
+        v2 in any::<bool>(),
+        field in prop_oneof![
+            Just(Field::Ciphertext),
+            Just(Field::Mac),
+            Just(Field::Salt),
+            Just(Field::Nonce),
+        ],
+        bit in any::<usize>(),
+    ) {
+        let erased = combo.seal(&password, &message, v2);
+        let mutated = flip_bit(&erased, field, bit);
+        let restored = combo.eraser.restore(&mutated).unwrap();
+        if let (Field::Nonce, false) = (field, combo.nonce_is_authenticated(v2)) {
+            prop_assert_ne!(&*restored.open(&password).unwrap(), &message[..]);
+        } else {
+            assert_matches!(restored.open(&password).unwrap_err(), Error::MacMismatch);
+        }
+    }
+}
+
+#[test]
+fn all_combos_roundtrip() {
+    const PASSWORD: &[u8] = b"correct horse battery staple";
+    const MESSAGE: &[u8] = b"battery staple";
+
+    for combo in combos() {
+        for &v2 in &[false, true] {
+            let erased = combo.seal(PASSWORD, MESSAGE, v2);
+            let restored = combo.eraser.restore(&erased).unwrap();
+            assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE, "{:?}", combo);
+        }
+    }
+}
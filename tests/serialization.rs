@@ -14,10 +14,17 @@
 
 //! Test suite for different supported serialization formats.
 
+use anyhow::Error as AnyError;
+use assert_matches::assert_matches;
 use rand::{thread_rng, Rng};
-use serde::Deserialize;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use pwbox::{rcrypto::RustCrypto, sodium::Sodium, ErasedPwBox, Eraser, Suite};
+use pwbox::{
+    rcrypto::{self, RustCrypto},
+    sodium::{self, Sodium, XChaCha20Poly1305},
+    Argon2Params, Argon2Variant, Cipher, CipherOutput, DeriveKey, ErasedPwBox, Eraser, Error,
+    FormatVersion, PwBoxBuilder, ScryptParams, Suite,
+};
 
 const PASSWORD: &str = "correct horse battery staple";
 
@@ -30,7 +37,7 @@ where
     let secret: [u8; 32] = rng.gen();
 
     let mut eraser = Eraser::new();
-    eraser.add_suite::<Sodium>();
+    eraser.add_suite::<Sodium>().unwrap();
     let encrypted = Sodium::build_box(&mut rng).seal(PASSWORD, &secret).unwrap();
     let encrypted = eraser.erase(&encrypted).unwrap();
 
@@ -68,7 +75,7 @@ fn json_serialization_compatibility() {
     const PASSWORD: &str = "foobar";
 
     let mut eraser = Eraser::new();
-    eraser.add_suite::<RustCrypto>();
+    eraser.add_suite::<RustCrypto>().unwrap();
     let erased: ErasedPwBox = serde_json::from_str(JSON).unwrap();
     assert!(eraser.restore(&erased).unwrap().open(PASSWORD).is_ok());
 }
@@ -106,7 +113,7 @@ fn yaml_serialization_example() {
     }
 
     let mut eraser = Eraser::new();
-    eraser.add_suite::<Sodium>();
+    eraser.add_suite::<Sodium>().unwrap();
 
     let restored: Container = serde_yaml::from_str(YAML).unwrap();
     assert_eq!(restored.secret.len(), 32);
@@ -124,8 +131,6 @@ fn toml_serialization() {
 
 #[test]
 fn toml_deserialization_inner() {
-    use pwbox::Error;
-
     const TOML: &str = r#"
         some_data = 5
         other_data = 'foobar'
@@ -163,12 +168,255 @@ fn toml_deserialization_inner() {
     }
 
     let mut eraser = Eraser::new();
-    eraser.add_suite::<Sodium>();
+    eraser.add_suite::<Sodium>().unwrap();
     let test: Test<ErasedPwBox> = toml::from_str(TOML).unwrap();
     let decrypted_test = test.open(&eraser, PASSWORD).unwrap();
     assert_eq!(decrypted_test.key.len(), 32);
 }
 
+/// Hash params of `NestedKdf`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct HashParams {
+    name: String,
+    rounds: u32,
+}
+
+/// KDF with params not directly representable in TOML: a nested table going before
+/// a plain value, and an optional value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct NestedKdf {
+    hash: HashParams,
+    iterations: u32,
+    pepper_id: Option<u32>,
+}
+
+impl Default for NestedKdf {
+    fn default() -> Self {
+        NestedKdf {
+            hash: HashParams {
+                name: "scrypt".to_owned(),
+                rounds: 1,
+            },
+            iterations: 1,
+            pepper_id: None,
+        }
+    }
+}
+
+impl DeriveKey for NestedKdf {
+    fn salt_len(&self) -> usize {
+        32
+    }
+
+    fn derive_key(&self, buf: &mut [u8], password: &[u8], salt: &[u8]) -> Result<(), AnyError> {
+        sodium::Scrypt::light().derive_key(buf, password, salt)
+    }
+}
+
+/// Seals a box with the specified KDF in all format versions, and checks that it survives
+/// a roundtrip through TOML and YAML.
+fn check_kdf_roundtrip<K, C>(eraser: &mut Eraser, kdf: K)
+where
+    K: DeriveKey + Clone + Default + Serialize + DeserializeOwned,
+    C: Cipher,
+{
+    let mut rng = thread_rng();
+    let secret: [u8; 32] = rng.gen();
+    for &version in &[FormatVersion::V1, FormatVersion::V2] {
+        eraser.set_format_version(version);
+        let mut builder = PwBoxBuilder::<K, C>::new(&mut rng);
+        builder.kdf(kdf.clone());
+        let erased = eraser.seal(&mut builder, PASSWORD, &secret).unwrap();
+
+        let toml_string = toml::to_string(&erased).unwrap();
+        let from_toml: ErasedPwBox = toml::from_str(&toml_string).unwrap();
+        let yaml_string = serde_yaml::to_string(&erased).unwrap();
+        let from_yaml: ErasedPwBox = serde_yaml::from_str(&yaml_string).unwrap();
+        for restored in &[from_toml, from_yaml] {
+            assert_eq!(restored.format_version(), version);
+            assert_eq!(restored.kdf_params(), erased.kdf_params());
+            let restored = eraser.restore(restored).unwrap();
+            assert_eq!(secret, &*restored.open(PASSWORD).unwrap());
+        }
+    }
+}
+
+#[test]
+fn toml_and_yaml_roundtrip_for_builtin_kdfs() {
+    type RcryptoCipher = rcrypto::Aes128Gcm;
+
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<RustCrypto>().unwrap();
+    let scrypt = rcrypto::Scrypt(ScryptParams::light());
+    check_kdf_roundtrip::<_, RcryptoCipher>(&mut eraser, scrypt);
+    let pbkdf2 = rcrypto::Pbkdf2 {
+        c: 1_024,
+        dklen: Some(16),
+        ..rcrypto::Pbkdf2::default()
+    };
+    check_kdf_roundtrip::<_, RcryptoCipher>(&mut eraser, pbkdf2);
+    let pbkdf2 = rcrypto::Pbkdf2 {
+        dklen: None,
+        ..pbkdf2
+    };
+    check_kdf_roundtrip::<_, RcryptoCipher>(&mut eraser, pbkdf2);
+    let argon2 = rcrypto::Argon2(Argon2Params {
+        variant: Argon2Variant::Argon2id,
+        mem_cost: 64,
+        time_cost: 1,
+        parallelism: 1,
+    });
+    check_kdf_roundtrip::<_, RcryptoCipher>(&mut eraser, argon2);
+
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+    check_kdf_roundtrip::<_, XChaCha20Poly1305>(&mut eraser, sodium::Scrypt::light());
+}
+
+#[test]
+fn toml_and_yaml_roundtrip_for_nested_kdf_params() {
+    let mut eraser = Eraser::new();
+    eraser
+        .add_kdf::<NestedKdf>("nested")
+        .unwrap()
+        .add_cipher::<XChaCha20Poly1305>("xchacha20-poly1305")
+        .unwrap();
+    check_kdf_roundtrip::<_, XChaCha20Poly1305>(&mut eraser, NestedKdf::default());
+    let kdf = NestedKdf {
+        pepper_id: Some(3),
+        ..NestedKdf::default()
+    };
+    check_kdf_roundtrip::<_, XChaCha20Poly1305>(&mut eraser, kdf);
+
+    // Nested params are placed after plain ones, and `null` params are skipped.
+    let erased = eraser
+        .seal(
+            &mut PwBoxBuilder::<NestedKdf, XChaCha20Poly1305>::new(&mut thread_rng()),
+            PASSWORD,
+            b"message",
+        )
+        .unwrap();
+    let toml_string = toml::to_string(&erased).unwrap();
+    assert!(toml_string.contains("[kdfparams.hash]"), "{}", toml_string);
+    assert!(!toml_string.contains("pepper_id"), "{}", toml_string);
+}
+
+/// KDF with integer params exceeding the 32-bit range, including a negative one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct WideKdf {
+    memory_bytes: u64,
+    offset: i64,
+}
+
+impl Default for WideKdf {
+    fn default() -> Self {
+        WideKdf {
+            memory_bytes: 1 << 40,
+            offset: -(1 << 40),
+        }
+    }
+}
+
+impl DeriveKey for WideKdf {
+    fn salt_len(&self) -> usize {
+        32
+    }
+
+    fn derive_key(&self, buf: &mut [u8], password: &[u8], salt: &[u8]) -> Result<(), AnyError> {
+        sodium::Scrypt::light().derive_key(buf, password, salt)
+    }
+}
+
+#[test]
+fn roundtrip_for_large_integer_kdf_params() {
+    let mut eraser = Eraser::new();
+    eraser
+        .add_kdf::<WideKdf>("wide")
+        .unwrap()
+        .add_cipher::<XChaCha20Poly1305>("xchacha20-poly1305")
+        .unwrap();
+    check_kdf_roundtrip::<_, XChaCha20Poly1305>(&mut eraser, WideKdf::default());
+    let kdf = WideKdf {
+        memory_bytes: i64::MAX as u64,
+        offset: i64::MIN,
+    };
+    check_kdf_roundtrip::<_, XChaCha20Poly1305>(&mut eraser, kdf);
+
+    // TOML integers are signed 64-bit, so larger integers only roundtrip in JSON and YAML.
+    let kdf = WideKdf {
+        memory_bytes: u64::MAX,
+        offset: 0,
+    };
+    let mut rng = thread_rng();
+    let mut builder = PwBoxBuilder::<WideKdf, XChaCha20Poly1305>::new(&mut rng);
+    builder.kdf(kdf.clone());
+    let erased = eraser.seal(&mut builder, PASSWORD, b"message").unwrap();
+    let from_json: ErasedPwBox =
+        serde_json::from_str(&serde_json::to_string(&erased).unwrap()).unwrap();
+    let from_yaml: ErasedPwBox =
+        serde_yaml::from_str(&serde_yaml::to_string(&erased).unwrap()).unwrap();
+    for restored in &[from_json, from_yaml] {
+        assert_eq!(restored.parse_kdf::<WideKdf>().unwrap(), kdf);
+        let restored = eraser.restore(restored).unwrap();
+        assert_eq!(&*restored.open(PASSWORD).unwrap(), b"message");
+    }
+}
+
+#[test]
+fn kdf_params_without_json_equivalent() {
+    const YAML: &str = r#"
+    kdf: scrypt-nacl
+    cipher: xsalsa20-poly1305
+    ciphertext: 6ebc1234418b494777d6e53f09f1c5a81b82d390ac0bf129c4dbb6a299ca4058
+    mac: 6fc1d3998030960a456436ce2ff3210c
+    kdfparams:
+      salt: d1946ce416f3c6d418a2db97a01e2427da87212bb4103c94ec78bb88103bf81c
+      memlimit: 16777216
+      opslimit: 524288
+      1: .nan
+    cipherparams:
+      iv: 80132c7db2994c3a9229247faac621b944e3e37f39aa4440
+    "#;
+
+    // The integer key and the non-finite float value are converted to strings.
+    let erased: ErasedPwBox = serde_yaml::from_str(YAML).unwrap();
+    assert_eq!(erased.kdf_params()["1"], "NaN");
+
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+    // Unknown params are ignored by the KDF.
+    let restored = eraser.restore(&erased).unwrap();
+    assert!(restored.open(PASSWORD).is_ok());
+
+    let yaml = YAML.replace("memlimit: 16777216", "memlimit: .inf");
+    let erased: ErasedPwBox = serde_yaml::from_str(&yaml).unwrap();
+    assert_matches!(eraser.restore(&erased).unwrap_err(), Error::KdfParams(_));
+}
+
+#[test]
+fn toml_datetime_kdf_params() {
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+    let pwbox = Sodium::build_box(&mut thread_rng())
+        .kdf(sodium::Scrypt::light())
+        .seal(PASSWORD, b"message")
+        .unwrap();
+    let erased = eraser.erase(&pwbox).unwrap();
+    let toml_string = toml::to_string(&erased).unwrap().replace(
+        "[kdfparams]\n",
+        "[kdfparams]\ncreated = 1979-05-27T07:32:00Z\n",
+    );
+
+    // The datetime is converted to a string, which survives further roundtrips.
+    let erased: ErasedPwBox = toml::from_str(&toml_string).unwrap();
+    assert_eq!(erased.kdf_params()["created"], "1979-05-27T07:32:00Z");
+    let toml_string = toml::to_string(&erased).unwrap();
+    let erased: ErasedPwBox = toml::from_str(&toml_string).unwrap();
+    assert_eq!(erased.kdf_params()["created"], "1979-05-27T07:32:00Z");
+    let restored = eraser.restore(&erased).unwrap();
+    assert_eq!(&*restored.open(PASSWORD).unwrap(), b"message");
+}
+
 #[test]
 fn cbor_roundtrip() {
     roundtrip(
@@ -176,3 +424,326 @@ fn cbor_roundtrip() {
         |s| serde_cbor::from_slice(s).expect("deserialize"),
     );
 }
+
+#[test]
+fn bincode_roundtrip() {
+    roundtrip(
+        |pwbox| bincode::serialize(pwbox).expect("serialize"),
+        |s| bincode::deserialize(s).expect("deserialize"),
+    );
+}
+
+#[test]
+fn binary_formats_use_raw_bytes() {
+    let mut rng = thread_rng();
+    let secret: [u8; 32] = rng.gen();
+
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+    let pwbox = Sodium::build_box(&mut rng).seal(PASSWORD, &secret).unwrap();
+    let erased = eraser.erase(&pwbox).unwrap();
+    let json = serde_json::to_value(&erased).unwrap();
+
+    let bincode_bytes = bincode::serialize(&erased).unwrap();
+    let cbor_bytes = serde_cbor::to_vec(&erased).unwrap();
+    // Buffers are not hex-encoded.
+    let hex_ciphertext = json["ciphertext"].as_str().unwrap();
+    assert!(!contains(&bincode_bytes, hex_ciphertext.as_bytes()));
+    assert!(!contains(&cbor_bytes, hex_ciphertext.as_bytes()));
+    let raw_ciphertext = hex::decode(hex_ciphertext).unwrap();
+    assert!(contains(&bincode_bytes, &raw_ciphertext));
+    assert!(contains(&cbor_bytes, &raw_ciphertext));
+
+    // Restored boxes are identical.
+    let from_bincode: ErasedPwBox = bincode::deserialize(&bincode_bytes).unwrap();
+    assert_eq!(serde_json::to_value(&from_bincode).unwrap(), json);
+    let from_cbor: ErasedPwBox = serde_cbor::from_slice(&cbor_bytes).unwrap();
+    assert_eq!(serde_json::to_value(&from_cbor).unwrap(), json);
+}
+
+#[test]
+fn cipher_output_serialization() {
+    let mut rng = thread_rng();
+    let key: [u8; 32] = rng.gen();
+    let nonce: [u8; 24] = rng.gen();
+    let message: [u8; 32] = rng.gen();
+    let encrypted = XChaCha20Poly1305::seal(&message, &[], &nonce, &key);
+
+    // Human-readable formats use hex encoding.
+    let json = serde_json::to_value(&encrypted).unwrap();
+    assert_eq!(json["ciphertext"], hex::encode(&encrypted.ciphertext));
+    assert_eq!(json["mac"], hex::encode(&encrypted.mac));
+    let toml_string = toml::to_string(&encrypted).unwrap();
+    assert_eq!(
+        toml::from_str::<CipherOutput>(&toml_string).unwrap(),
+        encrypted
+    );
+
+    // Binary formats use raw bytes.
+    let bincode_bytes = bincode::serialize(&encrypted).unwrap();
+    let cbor_bytes = serde_cbor::to_vec(&encrypted).unwrap();
+    for bytes in &[&bincode_bytes, &cbor_bytes] {
+        assert!(contains(bytes, &encrypted.ciphertext));
+        assert!(!contains(
+            bytes,
+            json["ciphertext"].as_str().unwrap().as_bytes()
+        ));
+    }
+    assert_eq!(
+        bincode::deserialize::<CipherOutput>(&bincode_bytes).unwrap(),
+        encrypted
+    );
+    assert_eq!(
+        serde_cbor::from_slice::<CipherOutput>(&cbor_bytes).unwrap(),
+        encrypted
+    );
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+#[test]
+fn typed_pwbox_json_roundtrip() {
+    use pwbox::{
+        sodium::{Scrypt, XChaCha20Poly1305},
+        PwBox,
+    };
+
+    let mut rng = thread_rng();
+    let secret: [u8; 32] = rng.gen();
+    let pwbox = Sodium::build_box(&mut rng)
+        .kdf(Scrypt::light())
+        .seal(PASSWORD, &secret)
+        .unwrap();
+
+    let json = serde_json::to_value(&pwbox).unwrap();
+    assert!(json.get("kdf").is_none());
+    assert!(json.get("cipher").is_none());
+    let restored: PwBox<Scrypt, XChaCha20Poly1305> = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(secret, &*restored.open(PASSWORD).unwrap());
+
+    // Deserialize via `Eraser`.
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+    let erased: ErasedPwBox = serde_json::from_value(json).unwrap();
+    let restored = eraser.restore_as::<Sodium>(&erased).unwrap();
+    assert_eq!(secret, &*restored.open(PASSWORD).unwrap());
+    // Dynamic restoration is impossible since KDF and cipher names are unknown.
+    assert!(eraser.restore(&erased).is_err());
+
+    // Vice versa: serialize via `Eraser`, deserialize as a typed box.
+    let json = serde_json::to_string(&eraser.erase(&pwbox).unwrap()).unwrap();
+    let restored: PwBox<Scrypt, XChaCha20Poly1305> = serde_json::from_str(&json).unwrap();
+    assert_eq!(secret, &*restored.open(PASSWORD).unwrap());
+}
+
+#[test]
+fn typed_pwbox_bincode_roundtrip() {
+    use pwbox::{
+        sodium::{Scrypt, XChaCha20Poly1305},
+        PwBox,
+    };
+
+    let mut rng = thread_rng();
+    let secret: [u8; 32] = rng.gen();
+    let pwbox = Sodium::build_box(&mut rng)
+        .kdf(Scrypt::light())
+        .seal(PASSWORD, &secret)
+        .unwrap();
+
+    let bytes = bincode::serialize(&pwbox).unwrap();
+    let restored: PwBox<Scrypt, XChaCha20Poly1305> = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(secret, &*restored.open(PASSWORD).unwrap());
+
+    // The format is compatible with `ErasedPwBox`.
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>().unwrap();
+    let erased = eraser.erase(&pwbox).unwrap();
+    let erased_copy: ErasedPwBox = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(erased_copy.kdf_name(), "");
+    assert_eq!(erased_copy.salt(), erased.salt());
+    assert_eq!(erased_copy.ciphertext(), erased.ciphertext());
+
+    // Serialize via `Eraser`, deserialize as a typed box.
+    let bytes = bincode::serialize(&erased).unwrap();
+    let restored: PwBox<Scrypt, XChaCha20Poly1305> = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(secret, &*restored.open(PASSWORD).unwrap());
+}
+
+#[test]
+fn typed_pwbox_deserialization_errors() {
+    use pwbox::{
+        rcrypto::{Aes128Gcm, Scrypt},
+        PwBox,
+    };
+
+    // The box uses AES-128-CTR, which has another MAC length than AES-128-GCM.
+    const JSON: &str = r#"{
+        "ciphertext": "cb664472deacb41a2e995fa7f96fe29ce744471deb8d146a0e43c7898c9ddd4d",
+        "cipherparams": { "iv": "dfd9ee70812add5f4b8f89d0811c9158" },
+        "kdfparams": {
+            "dklen": 32, "n": 8, "p": 16, "r": 8,
+            "salt": "0d6769bf016d45c479213990d6a08d938469c4adad8a02ce507b4a4e7b7739f1"
+        },
+        "mac": "bac9af994b15a45dd39669fc66f9aa8a3b9dd8c22cb16e4d8d7ea089d0f1a1a9"
+    }"#;
+
+    let err = serde_json::from_str::<PwBox<Scrypt, Aes128Gcm>>(JSON)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("incorrect nonce length"), "{}", err);
+}
+
+/// Returns values replacing a JSON node in malformed documents.
+fn replacements(value: &serde_json::Value) -> Vec<serde_json::Value> {
+    use serde_json::json;
+
+    let mut values = vec![
+        json!(null),
+        json!(true),
+        json!(""),
+        json!("zz"),
+        json!(0),
+        json!(-1),
+        json!(1.5),
+        json!(u64::MAX),
+        json!([]),
+        json!({}),
+    ];
+    if let Some(s) = value.as_str() {
+        values.push(json!(format!("{}00", s)));
+        values.push(json!(format!("{}0", s)));
+        values.push(json!(s.to_uppercase()));
+        if s.len() >= 2 {
+            values.push(json!(s[..s.len() - 2]));
+        }
+    }
+    if let Some(n) = value.as_u64() {
+        values.extend(vec![
+            json!(n / 2),
+            json!(n + 1),
+            json!(n.saturating_mul(1_024)),
+        ]);
+    }
+    values
+}
+
+/// Returns all documents obtained from `value` by replacing or removing a single node.
+fn mutations(value: &serde_json::Value) -> Vec<serde_json::Value> {
+    use serde_json::Value;
+
+    let mut output = replacements(value);
+    if let Value::Object(map) = value {
+        for (key, child) in map {
+            let mut truncated = map.clone();
+            truncated.remove(key);
+            output.push(Value::Object(truncated));
+            for mutated_child in mutations(child) {
+                let mut mutated = map.clone();
+                mutated.insert(key.clone(), mutated_child);
+                output.push(Value::Object(mutated));
+            }
+        }
+    }
+    output
+}
+
+#[test]
+fn malformed_json_boxes_lead_to_typed_errors() {
+    use pwbox::{sodium::Scrypt, KdfLimits};
+
+    // Limit resources, so that mutated KDF params cannot stall the test.
+    let limits = KdfLimits {
+        max_memory_bytes: 4 << 20,
+        max_time_cost: 1 << 26,
+    };
+    let mut sodium_eraser = Eraser::new();
+    sodium_eraser
+        .add_suite::<Sodium>()
+        .unwrap()
+        .set_kdf_limits(limits);
+    let mut v2_eraser = sodium_eraser.clone();
+    v2_eraser.set_format_version(FormatVersion::V2);
+    let mut rcrypto_eraser = Eraser::new();
+    rcrypto_eraser
+        .add_suite::<RustCrypto>()
+        .unwrap()
+        .set_kdf_limits(limits);
+
+    let mut rng = thread_rng();
+    let sodium_box = Sodium::build_box(&mut rng)
+        .kdf(Scrypt::light())
+        .seal(PASSWORD, b"battery staple")
+        .unwrap();
+    let empty_box = Sodium::build_box(&mut rng)
+        .kdf(Scrypt::light())
+        .seal(PASSWORD, b"")
+        .unwrap();
+    let rcrypto_box = RustCrypto::build_box(&mut rng)
+        .kdf(rcrypto::Scrypt(ScryptParams::custom(6, 1)))
+        .seal(PASSWORD, b"battery staple")
+        .unwrap();
+    let boxes = vec![
+        (&sodium_eraser, sodium_eraser.erase(&sodium_box).unwrap()),
+        (&sodium_eraser, sodium_eraser.erase(&empty_box).unwrap()),
+        (&sodium_eraser, v2_eraser.erase(&sodium_box).unwrap()),
+        (&rcrypto_eraser, rcrypto_eraser.erase(&rcrypto_box).unwrap()),
+    ];
+
+    let (mut parse_errors, mut restore_errors, mut open_errors) = (0, 0, 0);
+    let mut len_errors = [0; 3];
+    for (eraser, erased) in &boxes {
+        let json = serde_json::to_value(erased).unwrap();
+        for doc in mutations(&json) {
+            let erased: ErasedPwBox = match serde_json::from_value(doc.clone()) {
+                Ok(erased) => erased,
+                Err(_) => {
+                    parse_errors += 1;
+                    continue;
+                }
+            };
+
+            let restored = match eraser.restore(&erased) {
+                Ok(restored) => restored,
+                Err(err) => {
+                    restore_errors += 1;
+                    match err {
+                        Error::SaltLen => len_errors[0] += 1,
+                        Error::NonceLen => len_errors[1] += 1,
+                        Error::MacLen => len_errors[2] += 1,
+                        Error::NoKdf(_)
+                        | Error::NoCipher(_)
+                        | Error::KdfParams(_)
+                        | Error::KdfParamsTooExpensive(_)
+                        | Error::MalformedBox(_)
+                        | Error::ChunkSize
+                        | Error::DeriveKey(_) => { /* expected */ }
+                        other => panic!("unexpected error {:?} for {}", other, doc),
+                    }
+                    continue;
+                }
+            };
+            match restored.open(PASSWORD) {
+                Ok(plaintext) => assert!(
+                    plaintext.is_empty() || &*plaintext == b"battery staple",
+                    "{}",
+                    doc
+                ),
+                Err(Error::MacMismatch) | Err(Error::DeriveKey(_)) => open_errors += 1,
+                Err(other) => panic!("unexpected error {:?} for {}", other, doc),
+            }
+        }
+    }
+
+    assert!(parse_errors > 0);
+    assert!(restore_errors > 0);
+    assert!(open_errors > 0);
+    assert!(
+        len_errors.iter().all(|&count| count > 0),
+        "{:?}",
+        len_errors
+    );
+}
@@ -0,0 +1,186 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checks instrumentation enabled by the `tracing` feature.
+
+use assert_matches::assert_matches;
+use rand::thread_rng;
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Metadata, Subscriber,
+};
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use pwbox::{
+    sodium::{Scrypt, Sodium},
+    Eraser, Error, Suite,
+};
+
+const PASSWORD: &str = "correct horse battery staple";
+const WRONG_PASSWORD: &str = "Tr0ub4dor&3";
+const MESSAGE: &[u8] = b"battery staple";
+
+/// Field values of a span or event, formatted as strings.
+type Fields = HashMap<String, String>;
+
+struct FieldVisitor<'a>(&'a mut Fields);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_owned(), format!("{:?}", value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_owned(), value.to_owned());
+    }
+}
+
+/// Subscriber recording all spans and events.
+#[derive(Debug, Clone, Default)]
+struct Recorder {
+    spans: Arc<Mutex<Vec<Fields>>>,
+    events: Arc<Mutex<Vec<Fields>>>,
+}
+
+impl Recorder {
+    fn events(&self) -> Vec<Fields> {
+        self.events.lock().unwrap().clone()
+    }
+
+    fn spans(&self) -> Vec<Fields> {
+        self.spans.lock().unwrap().clone()
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+        let mut fields = Fields::new();
+        fields.insert("span".to_owned(), span.metadata().name().to_owned());
+        span.record(&mut FieldVisitor(&mut fields));
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(fields);
+        span::Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, span: &span::Id, values: &span::Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        let fields = &mut spans[span.into_u64() as usize - 1];
+        values.record(&mut FieldVisitor(fields));
+    }
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::new();
+        event.record(&mut FieldVisitor(&mut fields));
+        self.events.lock().unwrap().push(fields);
+    }
+
+    fn enter(&self, _span: &span::Id) {}
+
+    fn exit(&self, _span: &span::Id) {}
+}
+
+fn assert_no_secrets(fields: &Fields) {
+    let secrets = [PASSWORD.as_bytes(), WRONG_PASSWORD.as_bytes(), MESSAGE];
+    for value in fields.values() {
+        for secret in &secrets {
+            let debug_repr = format!("{:?}", secret);
+            let debug_repr = debug_repr.trim_start_matches('[').trim_end_matches(']');
+            assert!(
+                !value.contains(&*String::from_utf8_lossy(secret)),
+                "{:?}",
+                fields
+            );
+            assert!(!value.contains(debug_repr), "{:?}", fields);
+            assert!(!value.contains(&hex::encode(secret)), "{:?}", fields);
+        }
+    }
+}
+
+#[test]
+fn failed_open_is_traced_without_secrets() {
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let pwbox = Sodium::build_box(&mut thread_rng())
+            .kdf(Scrypt::light())
+            .seal(PASSWORD, MESSAGE)
+            .unwrap();
+        let mut eraser = Eraser::new();
+        eraser.add_suite::<Sodium>().unwrap();
+        let erased = eraser.erase(&pwbox).unwrap();
+        let restored = eraser.restore(&erased).unwrap();
+
+        assert_matches!(pwbox.open(WRONG_PASSWORD).unwrap_err(), Error::MacMismatch);
+        assert_matches!(
+            restored.open(WRONG_PASSWORD).unwrap_err(),
+            Error::MacMismatch
+        );
+        assert_eq!(&*restored.open(PASSWORD).unwrap(), MESSAGE);
+
+        let mut output = [0_u8; 4];
+        let err = restored.open_into(&mut output, PASSWORD).unwrap_err();
+        assert_matches!(err, Error::OutputLen { .. });
+        let err = Eraser::new().restore(&erased).unwrap_err();
+        assert_matches!(err, Error::NoKdf(_));
+    });
+
+    let events = recorder.events();
+    let spans = recorder.spans();
+    for fields in events.iter().chain(&spans) {
+        assert_no_secrets(fields);
+    }
+
+    let mismatches: Vec<_> = events
+        .iter()
+        .filter(|event| event.get("error").map(String::as_str) == Some("MacMismatch"))
+        .collect();
+    assert_eq!(mismatches.len(), 2, "{:?}", events);
+    for event in mismatches {
+        assert!(
+            event["cipher"].ends_with("XChaCha20Poly1305"),
+            "{:?}",
+            event
+        );
+        assert_eq!(event["len"], MESSAGE.len().to_string());
+    }
+
+    let kdf_events = events
+        .iter()
+        .filter(|event| event["message"] == "derived key");
+    // One key derivation for sealing, and 3 for opening.
+    assert_eq!(kdf_events.count(), 4);
+    assert!(events.iter().any(|event| {
+        event.get("error").map(String::as_str) == Some("OutputLen") && event["actual"] == "4"
+    }));
+    assert!(events.iter().any(|event| {
+        event["message"] == "restored box" && event["cipher"] == "xchacha20-poly1305"
+    }));
+    assert!(events
+        .iter()
+        .any(|event| event["message"] == "cannot restore box" && event["error"] == "NoKdf"));
+
+    let span_names: Vec<_> = spans.iter().map(|span| span["span"].as_str()).collect();
+    assert_eq!(span_names, ["seal", "open", "open", "open", "open"]);
+}
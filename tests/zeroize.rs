@@ -0,0 +1,208 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checks that secret data does not remain in freed memory after sealing and opening boxes.
+//!
+//! The test uses a global allocator, which scans each deallocated block for a canary
+//! byte pattern before freeing it.
+
+use rand::thread_rng;
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    slice,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use pwbox::{Cipher, CipherOutput, DeriveKey, MacMismatch, PwBoxBuilder, SecretVec};
+
+const CANARY: &[u8; 16] = b"~pwbox-canary-1~";
+
+/// Number of deallocated blocks containing `CANARY`.
+static LEAKED_BLOCKS: AtomicUsize = AtomicUsize::new(0);
+
+struct CanaryAllocator;
+
+unsafe impl GlobalAlloc for CanaryAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let block = slice::from_raw_parts(ptr, layout.size());
+        if block.windows(CANARY.len()).any(|window| window == CANARY) {
+            LEAKED_BLOCKS.fetch_add(1, Ordering::SeqCst);
+        }
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CanaryAllocator = CanaryAllocator;
+
+/// Trivial KDF (not secure!) copying the password into the key.
+#[derive(Debug, Clone, Copy, Default)]
+struct CopyPassword;
+
+impl DeriveKey for CopyPassword {
+    fn salt_len(&self) -> usize {
+        16
+    }
+
+    fn derive_key(&self, buf: &mut [u8], password: &[u8], _salt: &[u8]) -> anyhow::Result<()> {
+        for (dest, src) in buf.iter_mut().zip(password.iter().cycle()) {
+            *dest = *src;
+        }
+        Ok(())
+    }
+}
+
+/// Toy cipher (not secure!) XORing the message with the key, and using scratch buffers
+/// for intermediate plaintexts.
+#[derive(Debug)]
+struct XorCipher;
+
+impl XorCipher {
+    fn xor(message: &[u8], key: &[u8], checksum_key: u8) -> (SecretVec, u8) {
+        let mut scratch = SecretVec::zeros(message.len());
+        scratch.copy_from_slice(message);
+        let mut checksum = checksum_key;
+        for (byte, key_byte) in scratch.iter_mut().zip(key.iter().cycle()) {
+            checksum = checksum.wrapping_mul(31).wrapping_add(*byte);
+            *byte ^= key_byte;
+        }
+        (scratch, checksum)
+    }
+}
+
+impl Cipher for XorCipher {
+    const KEY_LEN: usize = 16;
+    const NONCE_LEN: usize = 16;
+    const MAC_LEN: usize = 1;
+    const SUPPORTS_ASSOCIATED_DATA: bool = false;
+
+    fn seal(message: &[u8], _: &[u8], _nonce: &[u8], key: &[u8]) -> CipherOutput {
+        let (ciphertext, checksum) = Self::xor(message, key, key[0]);
+        CipherOutput {
+            ciphertext: ciphertext.to_vec(),
+            mac: vec![checksum],
+        }
+    }
+
+    fn open(
+        output: &mut [u8],
+        encrypted: &CipherOutput,
+        _: &[u8],
+        _nonce: &[u8],
+        key: &[u8],
+    ) -> Result<(), MacMismatch> {
+        let mut scratch = SecretVec::zeros(encrypted.ciphertext.len());
+        for ((dest, src), key_byte) in scratch
+            .iter_mut()
+            .zip(&encrypted.ciphertext)
+            .zip(key.iter().cycle())
+        {
+            *dest = src ^ key_byte;
+        }
+        let (_, checksum) = Self::xor(&scratch, &[0], key[0]);
+        if checksum != encrypted.mac[0] {
+            return Err(MacMismatch);
+        }
+        output.copy_from_slice(&scratch);
+        Ok(())
+    }
+}
+
+fn leaked_blocks() -> usize {
+    LEAKED_BLOCKS.load(Ordering::SeqCst)
+}
+
+// All checks are performed in a single test, so that concurrently running tests
+// do not interfere with the allocation counter.
+#[test]
+fn secret_data_is_zeroed_after_sealing_and_opening() {
+    // Message larger than `SensitiveData` stack buffer, so that opened data is stored on heap.
+    let mut message = [0_u8; 512];
+    for chunk in message.chunks_mut(CANARY.len()) {
+        chunk.copy_from_slice(CANARY);
+    }
+
+    // Check that the leak detection works.
+    let leaked = leaked_blocks();
+    drop(message.to_vec());
+    assert_eq!(leaked_blocks(), leaked + 1);
+
+    let leaked = leaked_blocks();
+    let pwbox = PwBoxBuilder::<_, XorCipher>::new(&mut thread_rng())
+        .kdf(CopyPassword)
+        .seal("password", &message[..])
+        .unwrap();
+    let opened = pwbox.open("password").unwrap();
+    assert_eq!(*opened, message[..]);
+    drop(opened);
+    // Verification decrypts the box into a scratch buffer, which should be zeroed.
+    pwbox.verify("password").unwrap();
+    assert!(!pwbox.check_password("wrong password").unwrap());
+    // Opening in place consumes the box; the plaintext reuses the ciphertext buffer.
+    let opened = pwbox.open_in_place("password").unwrap();
+    assert_eq!(*opened, message[..]);
+    drop(opened);
+    assert_eq!(leaked_blocks(), leaked);
+
+    // The password is copied into the derived key, which should be zeroed as well.
+    let leaked = leaked_blocks();
+    let password = CANARY;
+    let pwbox = PwBoxBuilder::<_, XorCipher>::new(&mut thread_rng())
+        .kdf(CopyPassword)
+        .seal(password, b"public data")
+        .unwrap();
+    assert_eq!(&*pwbox.open(password).unwrap(), b"public data");
+    drop(pwbox);
+    assert_eq!(leaked_blocks(), leaked);
+
+    #[cfg(feature = "pure")]
+    {
+        use pwbox::{pure::Scrypt, ScryptParams};
+
+        let leaked = leaked_blocks();
+        let pwbox = PwBoxBuilder::<_, chacha20poly1305::ChaCha20Poly1305>::new(&mut thread_rng())
+            .kdf(Scrypt(ScryptParams::custom(6, 1)))
+            .seal("password", &message[..])
+            .unwrap();
+        let opened = pwbox.open("password").unwrap();
+        assert_eq!(*opened, message[..]);
+        drop(opened);
+        drop(pwbox);
+        assert_eq!(leaked_blocks(), leaked);
+    }
+
+    #[cfg(feature = "rust-crypto")]
+    {
+        use pwbox::{
+            rcrypto::{Aes128Ctr, Keccak256, Scrypt},
+            CipherWithMac, ScryptParams,
+        };
+
+        let leaked = leaked_blocks();
+        let pwbox = PwBoxBuilder::<_, CipherWithMac<Aes128Ctr, Keccak256>>::new(&mut thread_rng())
+            .kdf(Scrypt(ScryptParams::custom(6, 1)))
+            .seal("password", &message[..])
+            .unwrap();
+        let opened = pwbox.open("password").unwrap();
+        assert_eq!(*opened, message[..]);
+        drop(opened);
+        drop(pwbox);
+        assert_eq!(leaked_blocks(), leaked);
+    }
+}
@@ -78,7 +78,7 @@ impl Pwbox {
             .seal(passphrase, data)
             .unwrap();
         let mut eraser = Eraser::new();
-        eraser.add_suite::<PureCrypto>();
+        eraser.add_suite::<PureCrypto>().unwrap();
         let pwbox = eraser.erase(&pwbox).unwrap();
         JsValue::from_serde(&pwbox).unwrap()
     }
@@ -87,7 +87,7 @@ impl Pwbox {
     pub fn decrypt(&self, passphrase: &str, encryption: &JsValue) -> Result<Box<[u8]>, JsValue> {
         let encryption: ErasedPwBox = encryption.into_serde().map_err(convert_err)?;
         let mut eraser = Eraser::new();
-        eraser.add_suite::<PureCrypto>();
+        eraser.add_suite::<PureCrypto>().map_err(convert_err)?;
         let plaintext = eraser
             .restore(&encryption)
             .map_err(convert_err)?